@@ -0,0 +1,133 @@
+//! Additional functions of the pins.
+//!
+//! CubeMX distinguishes the alternate functions, which are selected by the
+//! AFR registers, from the additional functions, which a peripheral connects
+//! to the pin regardless of its alternate function (e.g. the ADC inputs, the
+//! wakeup pins "SYS_WKUP1" or the RTC tamper inputs "RTC_TAMP1"). The
+//! additional functions are listed as signals of the pins in the MCU files,
+//! and some GPIO IP files list them without an AF define. `additional_functions`
+//! collects them, so that they can be added to an `AfTree` (see
+//! `AfTreeBuilder::additional_functions`).
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::analog;
+use crate::mcu::Mcu;
+
+lazy_static! {
+    /// Wakeup pins, e.g. "SYS_WKUP1", "PWR_WKUP3" or "SYS_WKUP".
+    static ref WAKEUP_SIGNAL: Regex = Regex::new(r"^(?:SYS|PWR)_WKUP\d*$").unwrap();
+    /// RTC tamper and timestamp inputs, e.g. "RTC_TAMP1", "TAMP_IN2" or
+    /// "RTC_TS".
+    static ref TAMPER_SIGNAL: Regex =
+        Regex::new(r"^(?:RTC_TAMP\d*|RTC_TS|TAMP_IN\d+)$").unwrap();
+}
+
+/// The kind of an additional function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AdditionalKind {
+    /// An analog function (e.g. "ADC_IN0"), see `analog::is_analog`.
+    Analog,
+    /// A wakeup pin (e.g. "SYS_WKUP1").
+    Wakeup,
+    /// An RTC tamper or timestamp input (e.g. "RTC_TAMP1").
+    Tamper,
+    /// Another function listed without an AF define in the GPIO IP file.
+    Other,
+}
+
+impl AdditionalKind {
+    /// The name of the kind (e.g. "WAKEUP"), which the queries list instead
+    /// of an alternate function. The analog functions use `af_tree::ANALOG`.
+    pub fn name(self) -> &'static str {
+        match self {
+            AdditionalKind::Analog => crate::af_tree::ANALOG,
+            AdditionalKind::Wakeup => "WAKEUP",
+            AdditionalKind::Tamper => "TAMPER",
+            AdditionalKind::Other => "ADDITIONAL",
+        }
+    }
+}
+
+/// An additional function of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AdditionalFunction {
+    /// The signal name (e.g. "SYS_WKUP1").
+    pub signal: String,
+    pub kind: AdditionalKind,
+}
+
+impl AdditionalFunction {
+    pub fn new(signal: &str, kind: AdditionalKind) -> Self {
+        Self {
+            signal: signal.to_string(),
+            kind,
+        }
+    }
+
+    /// The peripheral instance, i.e. the part of the name before the first
+    /// underscore (e.g. "SYS").
+    pub fn device(&self) -> &str {
+        self.signal.split('_').next().unwrap()
+    }
+}
+
+/// Return the kind of a known additional function (e.g. `Wakeup` for
+/// "SYS_WKUP1"). Other signals return `None`.
+pub fn additional_kind(signal: &str) -> Option<AdditionalKind> {
+    if analog::is_analog(signal) {
+        Some(AdditionalKind::Analog)
+    } else if WAKEUP_SIGNAL.is_match(signal) {
+        Some(AdditionalKind::Wakeup)
+    } else if TAMPER_SIGNAL.is_match(signal) {
+        Some(AdditionalKind::Tamper)
+    } else {
+        None
+    }
+}
+
+/// Return the wakeup and tamper functions of all GPIO pins of a MCU, as pin
+/// and signal names (e.g. `("PA0", "SYS_WKUP1")`). The analog functions are
+/// collected by `analog::analog_signals`.
+pub fn additional_functions(mcu: &Mcu) -> Vec<(String, String)> {
+    let mut functions = vec![];
+    for pin in mcu.get_pins() {
+        let name = match pin.get_gpio_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        for signal in pin.get_signals() {
+            match additional_kind(signal) {
+                Some(AdditionalKind::Wakeup) | Some(AdditionalKind::Tamper) => {
+                    functions.push((name.to_string(), signal.to_string()))
+                }
+                _ => {}
+            }
+        }
+    }
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_additional_kind() {
+        assert_eq!(additional_kind("SYS_WKUP1"), Some(AdditionalKind::Wakeup));
+        assert_eq!(additional_kind("PWR_WKUP3"), Some(AdditionalKind::Wakeup));
+        assert_eq!(additional_kind("RTC_TAMP2"), Some(AdditionalKind::Tamper));
+        assert_eq!(additional_kind("TAMP_IN1"), Some(AdditionalKind::Tamper));
+        assert_eq!(additional_kind("RTC_TS"), Some(AdditionalKind::Tamper));
+        assert_eq!(additional_kind("ADC_IN0"), Some(AdditionalKind::Analog));
+        assert_eq!(additional_kind("RTC_REFIN"), None);
+        assert_eq!(additional_kind("USART2_TX"), None);
+        assert_eq!(AdditionalKind::Analog.name(), "ANALOG");
+        assert_eq!(
+            AdditionalFunction::new("SYS_WKUP1", AdditionalKind::Wakeup).device(),
+            "SYS"
+        );
+    }
+}
@@ -0,0 +1,1690 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use alphanumeric_sort::compare_str;
+use log::debug;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    additional::{self, AdditionalFunction, AdditionalKind},
+    family::{self, Families},
+    internal_peripheral::{IpGPIO, SERIAL_HANDSHAKE_TRAITS},
+    mcu::{self, Mcu},
+    oscillator::{self, OscillatorFunction},
+    pin_spec::PinSpec,
+    remap::Remap,
+    rf,
+    stems::StemPattern,
+    swj::{self, SwjFunction},
+};
+
+/// Return the stem of a peripheral instance, i.e. the instance name without
+/// the trailing instance number (e.g. "USART" for "USART2"). The I2S
+/// full-duplex extension instances (e.g. "I2S2ext") have the stem of the I2S
+/// instance they extend.
+pub fn device_stem(device: &str) -> &str {
+    let device = match device.strip_suffix("ext") {
+        Some(instance) if instance.starts_with("I2S") => instance,
+        _ => device,
+    };
+    device.trim_end_matches(|c: char| c.is_ascii_digit())
+}
+
+/// A single alternate function of a pin (e.g. `AF4: TxPin<USART2>`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AfSignal {
+    af: String,
+    io: String,
+    device: String,
+    define: Option<String>,
+    signal: Option<String>,
+}
+
+impl AfSignal {
+    pub fn new(af: &str, io: &str, device: &str) -> Self {
+        Self {
+            af: af.to_string(),
+            io: io.to_string(),
+            device: device.to_string(),
+            define: None,
+            signal: None,
+        }
+    }
+
+    /// Set the C HAL define name of the alternate function.
+    pub fn with_define(mut self, define: &str) -> Self {
+        self.define = Some(define.to_string());
+        self
+    }
+
+    /// Set the name of the signal this alternate function was derived from.
+    pub fn with_signal(mut self, signal: &str) -> Self {
+        self.signal = Some(signal.to_string());
+        self
+    }
+
+    /// The alternate function (e.g. "AF4").
+    pub fn af(&self) -> &str {
+        &self.af
+    }
+
+    /// The IO trait name (e.g. "TxPin").
+    pub fn io(&self) -> &str {
+        &self.io
+    }
+
+    /// The peripheral instance (e.g. "USART2").
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// The C HAL define name of the alternate function (e.g.
+    /// "GPIO_AF4_USART2").
+    pub fn define(&self) -> Option<&str> {
+        self.define.as_deref()
+    }
+
+    /// The name of the signal this alternate function was derived from (e.g.
+    /// "USART2_TX").
+    pub fn signal(&self) -> Option<&str> {
+        self.signal.as_deref()
+    }
+}
+
+impl fmt::Display for AfSignal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}<{}>", self.af, self.io, self.device)
+    }
+}
+
+/// The alternate function of analog signals, which are listed in the MCU files
+/// instead of the GPIO IP files.
+pub const ANALOG: &str = "ANALOG";
+
+/// A signal as listed in the GPIO IP file (e.g. "QUADSPI_CLK" on AF9),
+/// regardless of whether it maps to an IO trait, or an analog signal (e.g.
+/// "DAC_OUT1") as listed in the MCU files.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawSignal {
+    name: String,
+    af: String,
+    define: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remap: Option<Remap>,
+}
+
+impl RawSignal {
+    pub fn new(name: &str, af: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            af: af.to_string(),
+            define: None,
+            remap: None,
+        }
+    }
+
+    /// Create a signal routed to the pin by an AFIO remap (STM32F1). Its
+    /// alternate function is the remap (e.g. "REMAP_FULL").
+    pub fn remapped(name: &str, remap: Remap) -> Self {
+        Self {
+            remap: Some(remap.clone()),
+            ..Self::new(name, &remap.af())
+        }
+    }
+
+    /// Create an analog signal (e.g. "DAC_OUT1"), which is not an alternate
+    /// function. Its alternate function is `ANALOG`.
+    pub fn analog(name: &str) -> Self {
+        Self::new(name, ANALOG)
+    }
+
+    /// Whether this is an analog signal (see `analog`).
+    pub fn is_analog(&self) -> bool {
+        self.af == ANALOG
+    }
+
+    /// Set the C HAL define name of the alternate function.
+    pub fn with_define(mut self, define: &str) -> Self {
+        self.define = Some(define.to_string());
+        self
+    }
+
+    /// The signal name (e.g. "QUADSPI_CLK").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The alternate function (e.g. "AF9").
+    pub fn af(&self) -> &str {
+        &self.af
+    }
+
+    /// The C HAL define name of the alternate function (e.g.
+    /// "GPIO_AF9_QUADSPI").
+    pub fn define(&self) -> Option<&str> {
+        self.define.as_deref()
+    }
+
+    /// The AFIO remap routing the signal to the pin (STM32F1).
+    pub fn remap(&self) -> Option<&Remap> {
+        self.remap.as_ref()
+    }
+
+    /// The peripheral instance, i.e. the part of the name before the first
+    /// underscore (e.g. "QUADSPI"), or `rf::RF` for the RF signals of the
+    /// wireless families (e.g. "DEBUG_RF_DTB1").
+    pub fn device(&self) -> &str {
+        if rf::parse_rf_signal(&self.name).is_some() {
+            return rf::RF;
+        }
+        self.name.split('_').next().unwrap()
+    }
+}
+
+/// A GPIO pin (e.g. "PA2") together with its alternate functions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AfPin {
+    port: String,
+    number: String,
+    original_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    oscillator: Option<OscillatorFunction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    debug: Option<SwjFunction>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    dual_pad: bool,
+    signals: Vec<AfSignal>,
+    raw_signals: Vec<RawSignal>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    additional: Vec<AdditionalFunction>,
+}
+
+impl AfPin {
+    pub fn new(
+        port: &str,
+        number: &str,
+        signals: Vec<AfSignal>,
+        raw_signals: Vec<RawSignal>,
+    ) -> Self {
+        Self {
+            port: port.to_string(),
+            number: number.to_string(),
+            original_name: None,
+            oscillator: None,
+            debug: raw_signals
+                .iter()
+                .find_map(|s| swj::parse_swj_signal(s.name())),
+            dual_pad: false,
+            signals,
+            raw_signals,
+            additional: vec![],
+        }
+    }
+
+    /// Set the additional functions that are not analog signals (e.g.
+    /// "SYS_WKUP1").
+    pub fn with_additional_functions(mut self, additional: Vec<AdditionalFunction>) -> Self {
+        self.additional = additional;
+        self
+    }
+
+    /// Create an analog-only dual pad of the STM32H7 (e.g. "PA0_C" for port
+    /// "PA" and number "0"), which has no alternate functions.
+    pub fn dual_pad(port: &str, number: &str) -> Self {
+        Self {
+            dual_pad: true,
+            ..Self::new(port, number, vec![], vec![])
+        }
+    }
+
+    /// Set the pin name as listed in the GPIO IP file, and the oscillator
+    /// function it names (e.g. "PC14-OSC32_IN").
+    pub fn with_original_name(mut self, original_name: &str) -> Self {
+        self.original_name = Some(original_name.to_string());
+        self.oscillator = oscillator::parse_oscillator_pin(original_name).map(|(_, f)| f);
+        self
+    }
+
+    /// The pin name (e.g. "PA2", or "PA0_C" for a dual pad).
+    pub fn name(&self) -> String {
+        match self.dual_pad {
+            true => format!("{}{}_C", self.port, self.number),
+            false => format!("{}{}", self.port, self.number),
+        }
+    }
+
+    /// The port name (e.g. "PA").
+    pub fn port(&self) -> &str {
+        &self.port
+    }
+
+    /// The pin number within the port (e.g. "2"). A dual pad has the number
+    /// of the GPIO it is paired with (e.g. "0" for "PA0_C").
+    pub fn number(&self) -> &str {
+        &self.number
+    }
+
+    /// The debug function of the pin (e.g. `Swdio` for PA13), which it has
+    /// after reset. Using the pin otherwise disconnects the debugger.
+    pub fn debug(&self) -> Option<SwjFunction> {
+        self.debug
+    }
+
+    /// Whether this is an analog-only dual pad of the STM32H7 (e.g. "PA0_C").
+    pub fn is_dual_pad(&self) -> bool {
+        self.dual_pad
+    }
+
+    /// The pin name as listed in the GPIO IP file (e.g. "PC14-OSC32_IN").
+    ///
+    /// Only available if the tree was built with `keep_original_pin_names`.
+    pub fn original_name(&self) -> Option<&str> {
+        self.original_name.as_deref()
+    }
+
+    /// The oscillator function of the pin (e.g. `Osc32In` for
+    /// "PC14-OSC32_IN"). Such pins may be unavailable as GPIO.
+    pub fn oscillator(&self) -> Option<OscillatorFunction> {
+        self.oscillator
+    }
+
+    /// The alternate functions, sorted alphanumerically.
+    pub fn signals(&self) -> &[AfSignal] {
+        &self.signals
+    }
+
+    /// All signals of this pin, in the order of the GPIO IP file, followed by
+    /// the analog signals.
+    pub fn raw_signals(&self) -> &[RawSignal] {
+        &self.raw_signals
+    }
+
+    /// The additional functions, which are not selected by an alternate
+    /// function: the analog signals followed by the others (e.g.
+    /// "SYS_WKUP1").
+    pub fn additional_functions(&self) -> Vec<AdditionalFunction> {
+        let mut functions = self
+            .raw_signals
+            .iter()
+            .filter(|s| s.is_analog())
+            .map(|s| AdditionalFunction::new(&s.name, AdditionalKind::Analog))
+            .collect::<Vec<_>>();
+        for function in &self.additional {
+            if !functions.contains(function) {
+                functions.push(function.clone());
+            }
+        }
+        functions
+    }
+}
+
+/// All pins described by one GPIO IP version (e.g. "STM32L051_gpio_v1_0"),
+/// together with the MCUs that use it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GpioGroup {
+    version: String,
+    mcus: Vec<String>,
+    pins: Vec<AfPin>,
+}
+
+impl GpioGroup {
+    /// The GPIO IP version (e.g. "STM32L051_gpio_v1_0").
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// The ref names of the MCUs using this GPIO IP version.
+    pub fn mcus(&self) -> &[String] {
+        &self.mcus
+    }
+
+    /// The pins, sorted alphanumerically by name.
+    pub fn pins(&self) -> &[AfPin] {
+        &self.pins
+    }
+
+    /// Return the IO signals (e.g. `TxPin<USART1>`) that are provided by more
+    /// than one pin, together with these pins and their alternate functions
+    /// (e.g. `[("PA9", "AF4"), ("PB6", "AF0")]`).
+    pub fn alternatives(&self) -> Vec<(String, Vec<(String, &str)>)> {
+        let mut map: HashMap<String, Vec<(String, &str)>> = HashMap::new();
+        for pin in &self.pins {
+            for s in &pin.signals {
+                map.entry(format!("{}<{}>", s.io, s.device))
+                    .or_default()
+                    .push((pin.name(), &s.af));
+            }
+        }
+        let mut alternatives = map
+            .into_iter()
+            .filter(|(_, pins)| pins.iter().any(|(name, _)| *name != pins[0].0))
+            .collect::<Vec<_>>();
+        alternatives.sort_by(|a, b| compare_str(&a.0, &b.0));
+        alternatives
+    }
+
+    /// Return the stems of all peripherals with signals on any pin (e.g.
+    /// "USART"), sorted alphanumerically.
+    pub fn stems(&self) -> Vec<&str> {
+        let mut stems = self
+            .pins
+            .iter()
+            .flat_map(|pin| pin.raw_signals.iter().map(|s| device_stem(s.device())))
+            .collect::<Vec<_>>();
+        stems.sort_by(|a, b| compare_str(a, b));
+        stems.dedup();
+        stems
+    }
+
+    /// Return whether any pin provides the signal (e.g. "USART2_TX").
+    pub fn has_signal(&self, name: &str) -> bool {
+        self.pins
+            .iter()
+            .any(|pin| pin.raw_signals.iter().any(|s| s.name == name))
+    }
+}
+
+/// What to do if a pin provides the same signal (e.g. `TxPin<USART2>`) on
+/// multiple alternate functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AfPolicy {
+    /// Keep the signal on all alternate functions.
+    All,
+    /// Only keep the signal on the lowest alternate function.
+    Lowest,
+    /// Fail to build the tree.
+    Error,
+}
+
+impl FromStr for AfPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(AfPolicy::All),
+            "lowest" => Ok(AfPolicy::Lowest),
+            "error" => Ok(AfPolicy::Error),
+            _ => Err(format!("Unknown AF policy {:?}", s)),
+        }
+    }
+}
+
+/// Remove duplicate signals of a pin and apply the AF policy to signals that
+/// are available on multiple alternate functions.
+fn apply_af_policy(pin: &mut AfPin, policy: AfPolicy) -> Result<(), String> {
+    pin.signals
+        .sort_by(|a, b| compare_str(a.to_string(), b.to_string()));
+    pin.signals.dedup();
+
+    let mut afs: HashMap<(&str, &str), Vec<&str>> = HashMap::new();
+    for s in &pin.signals {
+        afs.entry((&s.io, &s.device)).or_default().push(&s.af);
+    }
+    let mut duplicates = afs
+        .into_iter()
+        .filter(|(_, afs)| afs.len() > 1)
+        .map(|((io, device), mut afs)| {
+            afs.sort_by(|a, b| compare_str(a, b));
+            (
+                io.to_string(),
+                device.to_string(),
+                afs.join(", "),
+                afs[0].to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    duplicates.sort();
+
+    match policy {
+        AfPolicy::All => {}
+        AfPolicy::Lowest => {
+            pin.signals.retain(|s| {
+                duplicates.iter().all(|(io, device, _, lowest)| {
+                    s.io != *io || s.device != *device || s.af == *lowest
+                })
+            });
+        }
+        AfPolicy::Error => {
+            if let Some((io, device, afs, _)) = duplicates.first() {
+                return Err(format!(
+                    "Signal {}<{}> of pin {} is available on multiple AFs: {}",
+                    io,
+                    device,
+                    pin.name(),
+                    afs
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The alternate function tree of a MCU family, grouped by GPIO IP version.
+///
+/// The tree can be cached as JSON (see `to_json`), e.g. to compare the trees
+/// of different database versions without keeping these around.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AfTree {
+    groups: Vec<GpioGroup>,
+    #[serde(skip)]
+    filter_steps: Vec<FilterStep>,
+}
+
+/// The number of pins and signals (of all GPIO groups) remaining after a
+/// filter of the `AfTreeBuilder` was applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterStep {
+    /// The filter (e.g. "stems"), or "all" before any filter is applied.
+    pub filter: &'static str,
+    pub pins: usize,
+    pub signals: usize,
+}
+
+impl FilterStep {
+    fn new(filter: &'static str) -> Self {
+        Self {
+            filter,
+            pins: 0,
+            signals: 0,
+        }
+    }
+}
+
+impl AfTree {
+    /// Return a builder for an AF tree, loading data from the specified
+    /// CubeMX database directory.
+    pub fn builder<P: AsRef<Path>>(db_dir: P) -> AfTreeBuilder {
+        AfTreeBuilder {
+            db_dir: db_dir.as_ref().to_path_buf(),
+            family: None,
+            mcu_gpio_map: None,
+            stems: None,
+            pins: None,
+            reserved_pins: vec![],
+            afs: None,
+            signals: None,
+            exclude_signals: vec![],
+            assignments: None,
+            analog_signals: HashMap::new(),
+            additional_functions: HashMap::new(),
+            keep_original_pin_names: false,
+            include_mcus: true,
+            af_policy: AfPolicy::All,
+            strict: false,
+            serial_handshake: false,
+        }
+    }
+
+    /// Load a tree cached with `to_json`.
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| e.to_string())
+    }
+
+    /// Serialize the tree as JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// The GPIO groups, sorted by GPIO IP version.
+    pub fn groups(&self) -> &[GpioGroup] {
+        &self.groups
+    }
+
+    /// The filters that were applied while building the tree, in order, with
+    /// the number of pins and signals that remained. Empty for trees loaded
+    /// from JSON.
+    pub fn filter_steps(&self) -> &[FilterStep] {
+        &self.filter_steps
+    }
+
+    /// Return the GPIO group of a GPIO IP version (e.g.
+    /// "STM32L051_gpio_v1_0").
+    pub fn group(&self, version: &str) -> Option<&GpioGroup> {
+        self.groups.iter().find(|group| group.version == version)
+    }
+
+    /// Return the GPIO IP version used by a MCU (e.g. "STM32L071KBTx").
+    ///
+    /// Only available if the tree was built with `include_mcus`.
+    pub fn gpio_version_of(&self, mcu: &str) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|group| group.mcus.iter().any(|m| m == mcu))
+            .map(|group| group.version.as_str())
+    }
+
+    /// Map the GPIO IP versions to the ref names of the MCUs using them, i.e.
+    /// the MCU map the tree was built from.
+    ///
+    /// The MCU lists are empty unless the tree was built with `include_mcus`.
+    pub fn mcu_gpio_map(&self) -> HashMap<&str, &[String]> {
+        self.groups
+            .iter()
+            .map(|group| (group.version.as_str(), group.mcus.as_slice()))
+            .collect()
+    }
+
+    /// Iterate over all pin signals matching the filter.
+    pub fn iter_filtered<'a>(
+        &'a self,
+        filter: &'a FilterSpec,
+    ) -> impl Iterator<Item = FilteredSignal<'a>> + 'a {
+        self.groups
+            .iter()
+            .filter(move |group| filter.matches_group(group))
+            .flat_map(move |group| {
+                group
+                    .pins
+                    .iter()
+                    .filter(move |pin| filter.matches_pin(pin))
+                    .flat_map(move |pin| {
+                        pin.raw_signals
+                            .iter()
+                            .filter(move |signal| filter.matches_signal(signal))
+                            .map(move |signal| FilteredSignal { group, pin, signal })
+                    })
+            })
+    }
+
+    /// Iterate over the additional functions matching the filter, except the
+    /// analog signals, which `iter_filtered` includes. The alternate function
+    /// filter matches the kind (e.g. "WAKEUP").
+    pub fn iter_additional<'a>(
+        &'a self,
+        filter: &'a FilterSpec,
+    ) -> impl Iterator<Item = FilteredAdditional<'a>> + 'a {
+        self.groups
+            .iter()
+            .filter(move |group| filter.matches_group(group))
+            .flat_map(move |group| {
+                group
+                    .pins
+                    .iter()
+                    .filter(move |pin| filter.matches_pin(pin))
+                    .flat_map(move |pin| {
+                        pin.additional
+                            .iter()
+                            .filter(move |function| filter.matches_additional(function))
+                            .map(move |function| FilteredAdditional {
+                                group,
+                                pin,
+                                function,
+                            })
+                    })
+            })
+    }
+}
+
+/// A pin signal returned by `AfTree::iter_filtered`, together with the pin and
+/// GPIO group it belongs to.
+#[derive(Debug, Clone, Copy)]
+pub struct FilteredSignal<'a> {
+    pub group: &'a GpioGroup,
+    pub pin: &'a AfPin,
+    pub signal: &'a RawSignal,
+}
+
+/// An additional function returned by `AfTree::iter_additional`, together
+/// with the pin and GPIO group it belongs to.
+#[derive(Debug, Clone, Copy)]
+pub struct FilteredAdditional<'a> {
+    pub group: &'a GpioGroup,
+    pub pin: &'a AfPin,
+    pub function: &'a AdditionalFunction,
+}
+
+/// Filters for `AfTree::iter_filtered`.
+///
+/// Every filter that is not set matches everything. A filter that is set
+/// matches if any of its values matches.
+#[derive(Debug, Clone, Default)]
+pub struct FilterSpec {
+    devices: Option<Vec<String>>,
+    afs: Option<Vec<String>>,
+    ports: Option<Vec<String>>,
+    pins: Option<Vec<String>>,
+    mcus: Option<Vec<String>>,
+    gpio_versions: Option<Vec<String>>,
+}
+
+fn to_strings<I: IntoIterator<Item = S>, S: Into<String>>(values: I) -> Option<Vec<String>> {
+    Some(values.into_iter().map(Into::into).collect())
+}
+
+fn matches(filter: &Option<Vec<String>>, value: &str) -> bool {
+    filter
+        .as_ref()
+        .is_none_or(|values| values.iter().any(|v| v == value))
+}
+
+impl FilterSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only include signals of these peripheral instances (e.g. "TIM2").
+    pub fn devices<I: IntoIterator<Item = S>, S: Into<String>>(mut self, devices: I) -> Self {
+        self.devices = to_strings(devices);
+        self
+    }
+
+    /// Only include these alternate functions (e.g. "AF7").
+    pub fn afs<I: IntoIterator<Item = S>, S: Into<String>>(mut self, afs: I) -> Self {
+        self.afs = to_strings(afs);
+        self
+    }
+
+    /// Only include pins of these ports (e.g. "PA").
+    pub fn ports<I: IntoIterator<Item = S>, S: Into<String>>(mut self, ports: I) -> Self {
+        self.ports = to_strings(ports);
+        self
+    }
+
+    /// Only include these pins (e.g. "PA2").
+    pub fn pins<I: IntoIterator<Item = S>, S: Into<String>>(mut self, pins: I) -> Self {
+        self.pins = to_strings(pins);
+        self
+    }
+
+    /// Only include GPIO groups used by a MCU whose ref name starts with one
+    /// of these values (e.g. "STM32L071").
+    pub fn mcus<I: IntoIterator<Item = S>, S: Into<String>>(mut self, mcus: I) -> Self {
+        self.mcus = to_strings(mcus);
+        self
+    }
+
+    /// Only include these GPIO IP versions (e.g. "STM32L051_gpio_v1_0").
+    pub fn gpio_versions<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        gpio_versions: I,
+    ) -> Self {
+        self.gpio_versions = to_strings(gpio_versions);
+        self
+    }
+
+    pub fn matches_group(&self, group: &GpioGroup) -> bool {
+        let mcu_match = self.mcus.as_ref().is_none_or(|mcus| {
+            group
+                .mcus
+                .iter()
+                .any(|mcu| mcus.iter().any(|m| mcu.starts_with(m.as_str())))
+        });
+        mcu_match && matches(&self.gpio_versions, &group.version)
+    }
+
+    pub fn matches_pin(&self, pin: &AfPin) -> bool {
+        matches(&self.ports, &pin.port) && matches(&self.pins, &pin.name())
+    }
+
+    pub fn matches_signal(&self, signal: &RawSignal) -> bool {
+        matches(&self.devices, signal.device()) && matches(&self.afs, &signal.af)
+    }
+
+    pub fn matches_additional(&self, function: &AdditionalFunction) -> bool {
+        function.kind != AdditionalKind::Analog
+            && matches(&self.devices, function.device())
+            && matches(&self.afs, function.kind.name())
+    }
+}
+
+/// Builder for an `AfTree`.
+///
+/// The MCUs to include are either taken from a MCU family (see `family`) or
+/// from an explicit GPIO version map (see `mcu_gpio_map`).
+#[derive(Debug, Clone)]
+pub struct AfTreeBuilder {
+    db_dir: PathBuf,
+    family: Option<String>,
+    mcu_gpio_map: Option<HashMap<String, Vec<String>>>,
+    stems: Option<Vec<StemPattern>>,
+    pins: Option<Vec<PinSpec>>,
+    reserved_pins: Vec<PinSpec>,
+    afs: Option<Vec<String>>,
+    signals: Option<Vec<String>>,
+    exclude_signals: Vec<String>,
+    assignments: Option<Vec<(String, String)>>,
+    analog_signals: HashMap<String, Vec<(String, String)>>,
+    additional_functions: HashMap<String, Vec<(String, String)>>,
+    keep_original_pin_names: bool,
+    include_mcus: bool,
+    af_policy: AfPolicy,
+    strict: bool,
+    serial_handshake: bool,
+}
+
+impl AfTreeBuilder {
+    /// Include all MCUs of this family (e.g. "STM32L0").
+    pub fn family(mut self, family: &str) -> Self {
+        self.family = Some(family.to_string());
+        self
+    }
+
+    /// Include the MCUs in this map. The keys are GPIO IP versions, the values
+    /// are MCU ref names.
+    pub fn mcu_gpio_map(mut self, mcu_gpio_map: HashMap<String, Vec<String>>) -> Self {
+        self.mcu_gpio_map = Some(mcu_gpio_map);
+        self
+    }
+
+    /// Only keep signals of peripherals with one of these stems (e.g.
+    /// "USART").
+    pub fn stems<I: IntoIterator<Item = S>, S: Into<String>>(mut self, stems: I) -> Self {
+        self.stems = Some(
+            stems
+                .into_iter()
+                .map(|s| StemPattern::Exact(s.into()))
+                .collect(),
+        );
+        self
+    }
+
+    /// Only keep signals of peripherals whose stem matches one of these
+    /// patterns (e.g. "TIM*").
+    pub fn stem_patterns<I: IntoIterator<Item = StemPattern>>(mut self, patterns: I) -> Self {
+        self.stems = Some(patterns.into_iter().collect());
+        self
+    }
+
+    /// Only keep the selected pins (e.g. "PB3-PB7").
+    pub fn pins<I: IntoIterator<Item = PinSpec>>(mut self, pins: I) -> Self {
+        self.pins = Some(pins.into_iter().collect());
+        self
+    }
+
+    /// Omit these pins (e.g. "PA13-PA14"), e.g. because they are reserved by
+    /// the board.
+    pub fn reserved_pins<I: IntoIterator<Item = PinSpec>>(mut self, pins: I) -> Self {
+        self.reserved_pins = pins.into_iter().collect();
+        self
+    }
+
+    /// Only keep signals on these alternate functions (e.g. "AF7").
+    pub fn afs<I: IntoIterator<Item = S>, S: Into<String>>(mut self, afs: I) -> Self {
+        self.afs = Some(afs.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Only keep these signals (e.g. "USART2_TX").
+    pub fn signals<I: IntoIterator<Item = S>, S: Into<String>>(mut self, signals: I) -> Self {
+        self.signals = Some(signals.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Drop these signals (e.g. "USART2_CK").
+    pub fn exclude_signals<I: IntoIterator<Item = S>, S: Into<String>>(
+        mut self,
+        signals: I,
+    ) -> Self {
+        self.exclude_signals = signals.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only keep the signals assigned to each pin, as pin and signal names
+    /// (e.g. `("PA2", "USART2_TX")`), e.g. the assignments of a CubeMX project.
+    pub fn assignments<I: IntoIterator<Item = (String, String)>>(mut self, assignments: I) -> Self {
+        self.assignments = Some(assignments.into_iter().collect());
+        self
+    }
+
+    /// Add analog signals, which are missing from the GPIO IP files, to the
+    /// pins. The keys are GPIO IP versions, the values are pin and signal
+    /// names (e.g. `("PA4", "DAC_OUT1")`), see `analog::analog_signals`.
+    pub fn analog_signals(
+        mut self,
+        analog_signals: HashMap<String, Vec<(String, String)>>,
+    ) -> Self {
+        self.analog_signals = analog_signals;
+        self
+    }
+
+    /// Add the wakeup and tamper functions, which are listed in the MCU files,
+    /// to the pins. The keys are GPIO IP versions, the values are pin and
+    /// signal names (e.g. `("PA0", "SYS_WKUP1")`), see
+    /// `additional::additional_functions`.
+    pub fn additional_functions(
+        mut self,
+        additional_functions: HashMap<String, Vec<(String, String)>>,
+    ) -> Self {
+        self.additional_functions = additional_functions;
+        self
+    }
+
+    /// Keep the pin names as listed in the GPIO IP file (e.g.
+    /// "PC14-OSC32_IN") in addition to the normalized names.
+    pub fn keep_original_pin_names(mut self, keep: bool) -> Self {
+        self.keep_original_pin_names = keep;
+        self
+    }
+
+    /// Store the list of MCUs using each GPIO version in the tree (default:
+    /// true).
+    pub fn include_mcus(mut self, include: bool) -> Self {
+        self.include_mcus = include;
+        self
+    }
+
+    /// What to do if a pin provides the same signal on multiple alternate
+    /// functions (default: `AfPolicy::All`).
+    pub fn af_policy(mut self, policy: AfPolicy) -> Self {
+        self.af_policy = policy;
+        self
+    }
+
+    /// Fail to build the tree if any pin or signal cannot be parsed, instead
+    /// of ignoring it (default: false).
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Keep the serial flow control, clock and driver enable signals (e.g.
+    /// `CtsPin<USART2>`) in addition to TX and RX (default: false).
+    pub fn serial_handshake(mut self, keep: bool) -> Self {
+        self.serial_handshake = keep;
+        self
+    }
+
+    /// Load the GPIO IP file of every GPIO version and build the tree.
+    pub fn build(self) -> Result<AfTree, String> {
+        let mcu_gpio_map = match (self.mcu_gpio_map.as_ref(), self.family.as_ref()) {
+            (Some(map), _) => map.clone(),
+            (None, Some(family)) => self.load_family(family)?,
+            (None, None) => return Err("Either a family or a MCU map is required".into()),
+        };
+
+        let mut gpio_versions = mcu_gpio_map.keys().collect::<Vec<_>>();
+        gpio_versions.sort();
+
+        let mut groups = vec![];
+        let mut filter_steps = self.filter_steps();
+        for gpio in gpio_versions {
+            debug!("Loading GPIO IP version {}", gpio);
+            let gpio_data = IpGPIO::load(&self.db_dir, gpio)
+                .map_err(|e| format!("Could not load IP GPIO file: {}", e))?;
+            let mcus = if self.include_mcus {
+                let mut mcus = mcu_gpio_map[gpio].clone();
+                mcus.sort_by(|a, b| compare_str(a, b));
+                mcus
+            } else {
+                vec![]
+            };
+            groups.push(GpioGroup {
+                version: gpio.clone(),
+                mcus,
+                pins: self
+                    .collect_pins_traced(
+                        &gpio_data,
+                        self.analog_signals.get(gpio).map_or(&[], Vec::as_slice),
+                        self.additional_functions
+                            .get(gpio)
+                            .map_or(&[], Vec::as_slice),
+                        &mut filter_steps,
+                    )
+                    .map_err(|e| format!("{}: {}", gpio, e))?,
+            });
+        }
+        Ok(AfTree {
+            groups,
+            filter_steps,
+        })
+    }
+
+    /// Map the GPIO versions of all MCUs in the family to their ref names.
+    fn load_family(&self, family: &str) -> Result<HashMap<String, Vec<String>>, String> {
+        let families = Families::load(&self.db_dir)
+            .map_err(|e| format!("Could not load families XML: {}", e))?;
+        let family = families.lookup(family)?;
+
+        let mut mcu_gpio_map: HashMap<String, Vec<String>> = HashMap::new();
+        for mcu in family.mcus() {
+            if let (_, Some(gpio_version)) = self.load_mcu(mcu)? {
+                mcu_gpio_map
+                    .entry(gpio_version)
+                    .or_default()
+                    .push(mcu.ref_name.clone());
+            }
+        }
+        Ok(mcu_gpio_map)
+    }
+
+    /// Load the data of a MCU of a family with its GPIO IP version (e.g.
+    /// "STM32L051_gpio_v1_0"), which is `None` if the MCU has no GPIO IP.
+    pub fn load_mcu(&self, mcu: &family::Mcu) -> Result<(Mcu, Option<String>), String> {
+        debug!("Loading MCU {}", mcu.ref_name);
+        let mcu_dat = Mcu::load(&self.db_dir, &mcu.name)
+            .map_err(|e| format!("Could not load MCU data of {}: {}", mcu.ref_name, e))?;
+        let gpio_version = mcu_dat
+            .get_ip("GPIO")
+            .map(|ip| ip.get_version().to_string());
+        Ok((mcu_dat, gpio_version))
+    }
+
+    fn keep_device(&self, device: &str) -> bool {
+        self.stems
+            .as_ref()
+            .is_none_or(|stems| stems.iter().any(|s| s.matches(device_stem(device))))
+    }
+
+    fn keep_af(&self, af: &str) -> bool {
+        self.afs
+            .as_ref()
+            .is_none_or(|afs| afs.iter().any(|a| a == af))
+    }
+
+    fn keep_signal(&self, signal: &str) -> bool {
+        self.signals
+            .as_ref()
+            .is_none_or(|signals| signals.iter().any(|s| s == signal))
+            && !self.exclude_signals.iter().any(|s| s == signal)
+    }
+
+    /// The filters that are set, in the order they are applied.
+    fn filter_steps(&self) -> Vec<FilterStep> {
+        let mut steps = vec![FilterStep::new("all")];
+        if self.pins.is_some() {
+            steps.push(FilterStep::new("pins"));
+        }
+        if !self.reserved_pins.is_empty() {
+            steps.push(FilterStep::new("reserved pins"));
+        }
+        if self.stems.is_some() {
+            steps.push(FilterStep::new("stems"));
+        }
+        if self.afs.is_some() {
+            steps.push(FilterStep::new("alternate functions"));
+        }
+        if self.signals.is_some() || !self.exclude_signals.is_empty() {
+            steps.push(FilterStep::new("signals"));
+        }
+        if self.assignments.is_some() {
+            steps.push(FilterStep::new("assignments"));
+        }
+        steps
+    }
+
+    #[cfg(test)]
+    fn collect_pins(&self, ip: &IpGPIO) -> Result<Vec<AfPin>, String> {
+        self.collect_pins_traced(ip, &[], &[], &mut self.filter_steps())
+    }
+
+    /// Collect the pins of a GPIO IP file with their `analog` signals and
+    /// `additional` functions (pin and signal names), counting the pins and
+    /// signals that remain after each filter in `steps` (see `filter_steps`).
+    fn collect_pins_traced(
+        &self,
+        ip: &IpGPIO,
+        analog: &[(String, String)],
+        additional: &[(String, String)],
+        steps: &mut [FilterStep],
+    ) -> Result<Vec<AfPin>, String> {
+        fn record(steps: &mut [FilterStep], step: &mut usize, pin: &AfPin) {
+            steps[*step].pins += 1;
+            steps[*step].signals += pin.raw_signals.len();
+            *step += 1;
+        }
+
+        let mut pin_map: HashMap<String, AfPin> = HashMap::new();
+
+        let mut candidates = vec![];
+        for p in &ip.gpio_pin {
+            if self.strict {
+                let errors = p.parse_errors();
+                if !errors.is_empty() {
+                    return Err(errors.join(", "));
+                }
+            }
+            candidates.extend(p.get_pin());
+        }
+        // The dual pads (e.g. "PA0_C") only have analog signals, so they are
+        // missing from the GPIO IP files
+        let mut dual_pads = analog
+            .iter()
+            .filter_map(|(name, _)| mcu::parse_dual_pad(name))
+            .collect::<Vec<_>>();
+        dual_pads.sort_unstable();
+        dual_pads.dedup();
+        candidates.extend(
+            dual_pads
+                .into_iter()
+                .map(|(port, number)| AfPin::dual_pad(port, number)),
+        );
+
+        for mut pin in candidates {
+            if !self.serial_handshake {
+                pin.signals
+                    .retain(|s| !SERIAL_HANDSHAKE_TRAITS.contains(&s.io.as_str()));
+            }
+            let name = pin.name();
+            pin.raw_signals.extend(
+                analog
+                    .iter()
+                    .filter(|(p, _)| *p == name)
+                    .map(|(_, signal)| RawSignal::analog(signal)),
+            );
+            for (_, signal) in additional.iter().filter(|(p, _)| *p == name) {
+                let kind = additional::additional_kind(signal).unwrap_or(AdditionalKind::Other);
+                let function = AdditionalFunction::new(signal, kind);
+                if !pin.additional.contains(&function) {
+                    pin.additional.push(function);
+                }
+            }
+            let mut step = 0;
+            record(steps, &mut step, &pin);
+            if let Some(pins) = &self.pins {
+                if !pins.iter().any(|p| p.matches(&pin.port, &pin.number)) {
+                    continue;
+                }
+                record(steps, &mut step, &pin);
+            }
+            if !self.reserved_pins.is_empty() {
+                let reserved = self
+                    .reserved_pins
+                    .iter()
+                    .any(|p| p.matches(&pin.port, &pin.number));
+                if reserved {
+                    continue;
+                }
+                record(steps, &mut step, &pin);
+            }
+            if self.stems.is_some() {
+                pin.signals.retain(|s| self.keep_device(&s.device));
+                pin.raw_signals.retain(|s| self.keep_device(s.device()));
+                pin.additional.retain(|f| self.keep_device(f.device()));
+                record(steps, &mut step, &pin);
+            }
+            if self.afs.is_some() {
+                pin.signals.retain(|s| self.keep_af(&s.af));
+                pin.raw_signals.retain(|s| self.keep_af(&s.af));
+                pin.additional.retain(|f| self.keep_af(f.kind.name()));
+                record(steps, &mut step, &pin);
+            }
+            if self.signals.is_some() || !self.exclude_signals.is_empty() {
+                pin.signals.retain(|s| {
+                    s.signal
+                        .as_deref()
+                        .is_none_or(|name| self.keep_signal(name))
+                });
+                pin.raw_signals.retain(|s| self.keep_signal(&s.name));
+                pin.additional.retain(|f| self.keep_signal(&f.signal));
+                record(steps, &mut step, &pin);
+            }
+            if let Some(assignments) = &self.assignments {
+                let name = pin.name();
+                let assigned =
+                    |signal: &str| assignments.iter().any(|(p, s)| *p == name && s == signal);
+                pin.signals
+                    .retain(|s| s.signal.as_deref().is_some_and(assigned));
+                pin.raw_signals.retain(|s| assigned(&s.name));
+                pin.additional.retain(|f| assigned(&f.signal));
+                record(steps, &mut step, &pin);
+            }
+            if !self.keep_original_pin_names {
+                pin.original_name = None;
+            }
+            // Some pins are listed multiple times (e.g. with different
+            // remap names), merge their signals
+            match pin_map.get_mut(&pin.name()) {
+                Some(existing) => {
+                    existing.oscillator = existing.oscillator.or(pin.oscillator);
+                    existing.debug = existing.debug.or(pin.debug);
+                    existing.signals.extend(pin.signals);
+                    for signal in pin.raw_signals {
+                        // The analog signals were added to both
+                        if !(signal.is_analog() && existing.raw_signals.contains(&signal)) {
+                            existing.raw_signals.push(signal);
+                        }
+                    }
+                    for function in pin.additional {
+                        if !existing.additional.contains(&function) {
+                            existing.additional.push(function);
+                        }
+                    }
+                }
+                None => {
+                    pin_map.insert(pin.name(), pin);
+                }
+            }
+        }
+
+        let mut pins = pin_map.into_values().collect::<Vec<_>>();
+        for pin in &mut pins {
+            apply_af_policy(pin, self.af_policy)?;
+        }
+        // The dual pads (e.g. "PA0_C") follow the GPIO of the same number
+        pins.sort_by(|a, b| {
+            compare_str(
+                format!("{}{}", a.port, a.number),
+                format!("{}{}", b.port, b.number),
+            )
+            .then(a.dual_pad.cmp(&b.dual_pad))
+        });
+        Ok(pins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> AfTree {
+        let pin = |port, number, signals: &[(&str, &str)]| {
+            let raw_signals = signals
+                .iter()
+                .map(|(name, af)| RawSignal::new(name, af))
+                .collect();
+            AfPin::new(port, number, vec![], raw_signals)
+        };
+        AfTree {
+            filter_steps: vec![],
+            groups: vec![
+                GpioGroup {
+                    version: "STM32L031_gpio_v1_0".into(),
+                    mcus: vec!["STM32L031K6Tx".into()],
+                    pins: vec![
+                        pin("PA", "2", &[("USART2_TX", "AF4"), ("TIM2_CH3", "AF2")]),
+                        pin("PB", "6", &[("I2C1_SCL", "AF1")]),
+                    ],
+                },
+                GpioGroup {
+                    version: "STM32L071_gpio_v1_0".into(),
+                    mcus: vec!["STM32L071KBTx".into()],
+                    pins: vec![pin("PA", "2", &[("USART2_TX", "AF4")])],
+                },
+            ],
+        }
+    }
+
+    fn names(tree: &AfTree, filter: &FilterSpec) -> Vec<String> {
+        tree.iter_filtered(filter)
+            .map(|s| format!("{}/{}/{}", s.group.version(), s.pin.name(), s.signal.name()))
+            .collect()
+    }
+
+    #[test]
+    fn test_collect_signals() {
+        let xml = r#"
+            <IP>
+                <GPIO_Pin PortName="PA" Name="PA2">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_2</PossibleValue>
+                    </SpecificParameter>
+                    <PinSignal Name="USART2_TX">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF4_USART2</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                    <PinSignal Name="TIM2_CH3">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF2_TIM2</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                    <PinSignal Name="LPUART1_TX">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF6_LPUART1</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                </GPIO_Pin>
+            </IP>
+        "#;
+        let ip: IpGPIO = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let collect = |builder: AfTreeBuilder| {
+            let pins = builder.collect_pins(&ip).unwrap();
+            let signals = pins[0]
+                .signals()
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>();
+            let raw_signals = pins[0]
+                .raw_signals()
+                .iter()
+                .map(|s| s.name().to_string())
+                .collect::<Vec<_>>();
+            (signals, raw_signals)
+        };
+
+        let (signals, raw_signals) =
+            collect(AfTree::builder("").signals(vec!["USART2_TX", "TIM2_CH3"]));
+        assert_eq!(signals, vec!["AF4: TxPin<USART2>"]);
+        assert_eq!(raw_signals, vec!["USART2_TX", "TIM2_CH3"]);
+
+        let (signals, raw_signals) = collect(AfTree::builder("").exclude_signals(vec!["TIM2_CH3"]));
+        assert_eq!(signals, vec!["AF4: TxPin<USART2>", "AF6: TxPin<LPUART1>"]);
+        assert_eq!(raw_signals, vec!["USART2_TX", "LPUART1_TX"]);
+
+        let (signals, raw_signals) = collect(AfTree::builder("").assignments(vec![
+            ("PA2".to_string(), "LPUART1_TX".to_string()),
+            ("PA3".to_string(), "USART2_TX".to_string()),
+        ]));
+        assert_eq!(signals, vec!["AF6: TxPin<LPUART1>"]);
+        assert_eq!(raw_signals, vec!["LPUART1_TX"]);
+
+        let reserved = AfTree::builder("")
+            .reserved_pins(vec!["PA*".parse().unwrap()])
+            .collect_pins(&ip)
+            .unwrap();
+        assert!(reserved.is_empty());
+
+        let builder = AfTree::builder("")
+            .stems(vec!["USART", "LPUART"])
+            .exclude_signals(vec!["LPUART1_TX"]);
+        let mut steps = builder.filter_steps();
+        builder
+            .collect_pins_traced(&ip, &[], &[], &mut steps)
+            .unwrap();
+        let steps = steps
+            .iter()
+            .map(|s| (s.filter, s.pins, s.signals))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            vec![("all", 1, 3), ("stems", 1, 2), ("signals", 1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_strict() {
+        let xml = r#"
+            <IP>
+                <GPIO_Pin PortName="PA" Name="PA2">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_2</PossibleValue>
+                    </SpecificParameter>
+                    <PinSignal Name="USART2_TX">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_USART2_AF4</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                </GPIO_Pin>
+            </IP>
+        "#;
+        let ip: IpGPIO = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+
+        let pins = AfTree::builder("").collect_pins(&ip).unwrap();
+        assert!(pins[0].raw_signals().is_empty());
+        assert_eq!(
+            AfTree::builder("")
+                .strict(true)
+                .collect_pins(&ip)
+                .unwrap_err(),
+            "Could not parse AF \"GPIO_USART2_AF4\" of signal USART2_TX on pin PA2"
+        );
+    }
+
+    #[test]
+    fn test_serial_handshake() {
+        let xml = r#"
+            <IP>
+                <GPIO_Pin PortName="PA" Name="PA1">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_1</PossibleValue>
+                    </SpecificParameter>
+                    <PinSignal Name="USART2_RTS_DE">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF4_USART2</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                    <PinSignal Name="UART4_RX">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF6_UART4</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                </GPIO_Pin>
+            </IP>
+        "#;
+        let ip: IpGPIO = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let signals = |builder: AfTreeBuilder| {
+            builder.collect_pins(&ip).unwrap()[0]
+                .signals()
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(signals(AfTree::builder("")), vec!["AF6: RxPin<UART4>"]);
+        assert_eq!(
+            signals(AfTree::builder("").serial_handshake(true)),
+            vec![
+                "AF4: DePin<USART2>",
+                "AF4: RtsPin<USART2>",
+                "AF6: RxPin<UART4>"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_remapped_signals() {
+        let xml = r#"
+            <IP>
+                <GPIO_Pin PortName="PB" Name="PB6">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_6</PossibleValue>
+                    </SpecificParameter>
+                    <PinSignal Name="I2C1_SCL">
+                        <RemapBlock Name="I2C1_REMAP1" DefaultRemap="true"/>
+                    </PinSignal>
+                    <PinSignal Name="USART1_TX">
+                        <RemapBlock Name="USART1_REMAP2">
+                            <SpecificParameter Name="GPIO_AF">
+                                <PossibleValue>__HAL_AFIO_REMAP_USART1_ENABLE</PossibleValue>
+                            </SpecificParameter>
+                        </RemapBlock>
+                    </PinSignal>
+                </GPIO_Pin>
+            </IP>
+        "#;
+        let ip: IpGPIO = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let pins = AfTree::builder("").strict(true).collect_pins(&ip).unwrap();
+        assert!(pins[0].signals().is_empty());
+        let raw_signals = pins[0]
+            .raw_signals()
+            .iter()
+            .map(|s| (s.name(), s.af(), s.define()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            raw_signals,
+            vec![
+                ("I2C1_SCL", "REMAP_NONE", None),
+                (
+                    "USART1_TX",
+                    "REMAP_FULL",
+                    Some("__HAL_AFIO_REMAP_USART1_ENABLE")
+                ),
+            ]
+        );
+        assert_eq!(
+            pins[0].raw_signals()[1].remap().unwrap().peripheral,
+            "USART1"
+        );
+    }
+
+    #[test]
+    fn test_collect_dual_pads() {
+        let xml = r#"
+            <IP>
+                <GPIO_Pin PortName="PA" Name="PA0">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_0</PossibleValue>
+                    </SpecificParameter>
+                </GPIO_Pin>
+            </IP>
+        "#;
+        let ip: IpGPIO = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let analog = [
+            ("PA0".to_string(), "ADC1_INP16".to_string()),
+            ("PA0_C".to_string(), "ADC12_INP0".to_string()),
+        ];
+        let builder = AfTree::builder("");
+        let pins = builder
+            .collect_pins_traced(&ip, &analog, &[], &mut builder.filter_steps())
+            .unwrap();
+        let pins = pins
+            .iter()
+            .map(|p| (p.name(), p.is_dual_pad(), p.raw_signals()[0].name()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pins,
+            vec![
+                ("PA0".to_string(), false, "ADC1_INP16"),
+                ("PA0_C".to_string(), true, "ADC12_INP0"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_additional_functions() {
+        let xml = r#"
+            <IP>
+                <GPIO_Pin PortName="PC" Name="PC13">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_13</PossibleValue>
+                    </SpecificParameter>
+                    <PinSignal Name="RTC_TAMP1"/>
+                    <PinSignal Name="RTC_OUT_CALIB"/>
+                    <PinSignal Name="EVENTOUT">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF15_EVENTOUT</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                </GPIO_Pin>
+            </IP>
+        "#;
+        let ip: IpGPIO = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        assert!(ip.gpio_pin[0].parse_errors().is_empty());
+
+        let analog = [("PC13".to_string(), "ADC_IN13".to_string())];
+        let additional = [
+            ("PC13".to_string(), "SYS_WKUP2".to_string()),
+            ("PC13".to_string(), "RTC_TAMP1".to_string()),
+        ];
+        let builder = AfTree::builder("");
+        let pins = builder
+            .collect_pins_traced(&ip, &analog, &additional, &mut builder.filter_steps())
+            .unwrap();
+        let names = |pin: &AfPin| {
+            pin.additional_functions()
+                .iter()
+                .map(|f| format!("{} {}", f.kind.name(), f.signal))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(&pins[0]),
+            vec![
+                "ANALOG ADC_IN13",
+                "TAMPER RTC_TAMP1",
+                "ADDITIONAL RTC_OUT_CALIB",
+                "WAKEUP SYS_WKUP2",
+            ]
+        );
+        assert_eq!(pins[0].raw_signals().len(), 2);
+
+        let builder = AfTree::builder("").afs(vec!["WAKEUP"]);
+        let pins = builder
+            .collect_pins_traced(&ip, &analog, &additional, &mut builder.filter_steps())
+            .unwrap();
+        assert_eq!(names(&pins[0]), vec!["WAKEUP SYS_WKUP2"]);
+    }
+
+    #[test]
+    fn test_builder() {
+        let db_dir = std::env::temp_dir().join("cube-parse-af-tree-builder");
+        std::fs::create_dir_all(db_dir.join("IP")).unwrap();
+        std::fs::write(
+            db_dir.join("families.xml"),
+            r#"<Families>
+                <Family Name="STM32L0">
+                    <SubFamily Name="STM32L0x1">
+                        <Mcu Name="STM32L031K(4-6)Tx" PackageName="LQFP32" RefName="STM32L031K6Tx"/>
+                        <Mcu Name="STM32L031K(4-6)Ux" PackageName="UFQFPN32" RefName="STM32L031K6Ux"/>
+                    </SubFamily>
+                </Family>
+            </Families>"#,
+        )
+        .unwrap();
+        for name in &["STM32L031K(4-6)Tx", "STM32L031K(4-6)Ux"] {
+            std::fs::write(
+                db_dir.join(format!("{}.xml", name)),
+                r#"<Mcu Family="STM32L0" Package="LQFP32" RefName="STM32L031K(4-6)Tx">
+                    <Core>Arm Cortex-M0+</Core>
+                    <IP InstanceName="GPIO" Name="GPIO" Version="STM32L031_gpio_v1_0"/>
+                </Mcu>"#,
+            )
+            .unwrap();
+        }
+        std::fs::write(
+            db_dir.join(IpGPIO::path("STM32L031_gpio_v1_0")),
+            r#"<IP>
+                <GPIO_Pin PortName="PA" Name="PA2">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_2</PossibleValue>
+                    </SpecificParameter>
+                    <PinSignal Name="USART2_TX">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF4_USART2</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                    <PinSignal Name="TIM21_CH1">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF0_TIM21</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                </GPIO_Pin>
+            </IP>"#,
+        )
+        .unwrap();
+
+        let tree = AfTree::builder(&db_dir)
+            .family("STM32L0")
+            .stems(vec!["USART"])
+            .build()
+            .unwrap();
+        assert_eq!(tree.groups().len(), 1);
+        let group = &tree.groups()[0];
+        assert_eq!(group.version(), "STM32L031_gpio_v1_0");
+        assert_eq!(group.mcus(), ["STM32L031K6Tx", "STM32L031K6Ux"]);
+        let signals = group.pins()[0]
+            .signals()
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(signals, vec!["AF4: TxPin<USART2>"]);
+
+        assert_eq!(
+            AfTree::builder(&db_dir).family("STM32F0").build().err(),
+            Some("Could not find family STM32F0 (did you mean STM32L0?)".to_string())
+        );
+        std::fs::remove_dir_all(&db_dir).unwrap();
+    }
+
+    #[test]
+    fn test_mcu_gpio_map() {
+        let tree = tree();
+        let map = tree.mcu_gpio_map();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["STM32L071_gpio_v1_0"], ["STM32L071KBTx"]);
+        assert_eq!(
+            tree.gpio_version_of("STM32L031K6Tx"),
+            Some("STM32L031_gpio_v1_0")
+        );
+        assert_eq!(tree.gpio_version_of("STM32L052C8Tx"), None);
+        assert_eq!(tree.group("STM32L071_gpio_v1_0").unwrap().pins().len(), 1);
+        assert!(tree.group("STM32L051_gpio_v1_0").is_none());
+    }
+
+    #[test]
+    fn test_apply_af_policy() {
+        let pin = || {
+            let signals = vec![
+                AfSignal::new("AF7", "TxPin", "USART1"),
+                AfSignal::new("AF0", "TxPin", "USART1"),
+                AfSignal::new("AF0", "TxPin", "USART1"),
+                AfSignal::new("AF4", "RxPin", "USART2"),
+            ];
+            AfPin::new("PA", "9", signals, vec![])
+        };
+        let afs = |pin: &AfPin| {
+            pin.signals()
+                .iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let mut all = pin();
+        apply_af_policy(&mut all, AfPolicy::All).unwrap();
+        assert_eq!(
+            afs(&all),
+            vec![
+                "AF0: TxPin<USART1>",
+                "AF4: RxPin<USART2>",
+                "AF7: TxPin<USART1>"
+            ]
+        );
+
+        let mut lowest = pin();
+        apply_af_policy(&mut lowest, AfPolicy::Lowest).unwrap();
+        assert_eq!(
+            afs(&lowest),
+            vec!["AF0: TxPin<USART1>", "AF4: RxPin<USART2>"]
+        );
+
+        assert!(apply_af_policy(&mut pin(), AfPolicy::Error).is_err());
+    }
+
+    #[test]
+    fn test_alternatives() {
+        let pin = |port, number, signals| AfPin::new(port, number, signals, vec![]);
+        let group = GpioGroup {
+            version: "STM32L071_gpio_v1_0".into(),
+            mcus: vec![],
+            pins: vec![
+                pin(
+                    "PA",
+                    "9",
+                    vec![
+                        AfSignal::new("AF4", "TxPin", "USART1"),
+                        AfSignal::new("AF1", "SclPin", "I2C1"),
+                    ],
+                ),
+                pin("PB", "6", vec![AfSignal::new("AF0", "TxPin", "USART1")]),
+            ],
+        };
+        assert_eq!(
+            group.alternatives(),
+            vec![(
+                "TxPin<USART1>".to_string(),
+                vec![("PA9".to_string(), "AF4"), ("PB6".to_string(), "AF0")]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_device_stem() {
+        assert_eq!(device_stem("USART2"), "USART");
+        assert_eq!(device_stem("LPUART1"), "LPUART");
+        assert_eq!(device_stem("I2C1"), "I2C");
+        assert_eq!(device_stem("QUADSPI"), "QUADSPI");
+        assert_eq!(device_stem("I2S2"), "I2S");
+        assert_eq!(device_stem("I2S3ext"), "I2S");
+    }
+
+    #[test]
+    fn test_iter_filtered() {
+        let tree = tree();
+
+        // No filter
+        assert_eq!(names(&tree, &FilterSpec::new()).len(), 4);
+
+        // Device and AF
+        assert_eq!(
+            names(&tree, &FilterSpec::new().devices(vec!["TIM2"])),
+            vec!["STM32L031_gpio_v1_0/PA2/TIM2_CH3"]
+        );
+        assert_eq!(names(&tree, &FilterSpec::new().afs(vec!["AF4"])).len(), 2);
+
+        // Port and pin
+        assert_eq!(
+            names(&tree, &FilterSpec::new().ports(vec!["PB"])),
+            vec!["STM32L031_gpio_v1_0/PB6/I2C1_SCL"]
+        );
+        assert_eq!(names(&tree, &FilterSpec::new().pins(vec!["PA2"])).len(), 3);
+
+        // MCU prefix and GPIO version
+        assert_eq!(
+            names(&tree, &FilterSpec::new().mcus(vec!["STM32L071"])),
+            vec!["STM32L071_gpio_v1_0/PA2/USART2_TX"]
+        );
+        assert_eq!(
+            names(
+                &tree,
+                &FilterSpec::new()
+                    .gpio_versions(vec!["STM32L031_gpio_v1_0"])
+                    .afs(vec!["AF4", "AF1"])
+            )
+            .len(),
+            2
+        );
+    }
+}
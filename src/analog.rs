@@ -0,0 +1,260 @@
+//! Analog pin functions.
+//!
+//! Analog functions (e.g. "ADC_IN0", "DAC_OUT1", "COMP1_INP" or
+//! "OPAMP1_VOUT") are not alternate
+//! functions, so they are missing from the GPIO IP files. They are only listed
+//! as signals of the pins in the MCU files. `analog_signals` collects them, so
+//! that they can be added to an `AfTree` (see
+//! `AfTreeBuilder::analog_signals`).
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+use crate::mcu::Mcu;
+
+lazy_static! {
+    /// ADC inputs, e.g. "ADC_IN0", "ADC123_IN10" or "ADC1_INP5".
+    static ref ADC_INPUT: Regex = Regex::new(r"^ADC(\d*)_IN([PN]?)(\d+)$").unwrap();
+    /// DAC outputs, e.g. "DAC_OUT1" or "DAC1_OUT2".
+    static ref DAC_OUTPUT: Regex = Regex::new(r"^DAC(\d*)_OUT(\d+)$").unwrap();
+    /// Comparator inputs and outputs, e.g. "COMP1_INP", "COMP2_INM" or
+    /// "COMP1_OUT".
+    static ref COMP_FUNCTION: Regex = Regex::new(r"^(COMP\d*)_(IN[PM]\w*|OUT)$").unwrap();
+    /// Op-amp inputs and outputs, e.g. "OPAMP1_VINP", "OPAMP1_VINM0" or
+    /// "OPAMP1_VOUT".
+    static ref OPAMP_FUNCTION: Regex = Regex::new(r"^(OPAMP\d*)_(V(?:INP|INM|OUT)\w*)$").unwrap();
+}
+
+/// Return whether a signal is an analog function.
+///
+/// Comparator outputs are alternate functions, so they are not analog.
+pub fn is_analog(signal: &str) -> bool {
+    ADC_INPUT.is_match(signal)
+        || DAC_OUTPUT.is_match(signal)
+        || (COMP_FUNCTION.is_match(signal) && !signal.ends_with("_OUT"))
+        || OPAMP_FUNCTION.is_match(signal)
+}
+
+/// Return the analog signals of all GPIO pins of a MCU, as pin and signal
+/// names (e.g. `("PA4", "DAC_OUT1")`).
+pub fn analog_signals(mcu: &Mcu) -> Vec<(String, String)> {
+    let mut signals = vec![];
+    for pin in mcu.get_pins() {
+        let name = match pin.get_gpio_name() {
+            Some(name) => name,
+            None => continue,
+        };
+        for signal in pin.get_signals().filter(|s| is_analog(s)) {
+            signals.push((name.to_string(), signal.to_string()));
+        }
+    }
+    signals
+}
+
+/// The peripheral instances of a shared analog signal, which lists the
+/// numbers of all instances (e.g. "123" for ADC1, ADC2 and ADC3). Without a
+/// number, the instance is the peripheral itself (e.g. "ADC").
+fn instances(peripheral: &str, numbers: &str) -> Vec<String> {
+    if numbers.is_empty() {
+        vec![peripheral.to_string()]
+    } else {
+        numbers
+            .chars()
+            .map(|n| format!("{}{}", peripheral, n))
+            .collect()
+    }
+}
+
+/// A pin that is connected to an ADC input channel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AdcInput {
+    /// The pin (e.g. "PA0").
+    pub pin: String,
+    /// The ADC instance (e.g. "ADC1"), or "ADC" on MCUs with a single ADC.
+    pub adc: String,
+    pub channel: u32,
+    /// Whether this is the negative input of a differential channel.
+    pub negative: bool,
+}
+
+/// Parse an ADC input signal into the ADC instances, the channel and whether it
+/// is a negative input (e.g. "ADC12_INN3" into `(["ADC1", "ADC2"], 3, true)`).
+pub fn parse_adc_input(signal: &str) -> Option<(Vec<String>, u32, bool)> {
+    let captures = ADC_INPUT.captures(signal)?;
+    let channel = captures[3].parse().ok()?;
+    let negative = &captures[2] == "N";
+    Some((instances("ADC", &captures[1]), channel, negative))
+}
+
+/// Return the ADC inputs of the pins of a GPIO group, sorted by pin, ADC and
+/// channel.
+pub fn adc_inputs(group: &GpioGroup) -> Vec<AdcInput> {
+    let mut inputs = vec![];
+    for pin in group.pins() {
+        for signal in pin.raw_signals().iter().filter(|s| s.is_analog()) {
+            if let Some((adcs, channel, negative)) = parse_adc_input(signal.name()) {
+                for adc in adcs {
+                    inputs.push(AdcInput {
+                        pin: pin.name(),
+                        adc,
+                        channel,
+                        negative,
+                    });
+                }
+            }
+        }
+    }
+    inputs.sort_by(|a, b| {
+        compare_str(&a.pin, &b.pin)
+            .then_with(|| compare_str(&a.adc, &b.adc))
+            .then_with(|| a.channel.cmp(&b.channel))
+            .then_with(|| a.negative.cmp(&b.negative))
+    });
+    inputs.dedup();
+    inputs
+}
+
+/// A pin that is connected to a DAC output channel.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DacOutput {
+    /// The pin (e.g. "PA4").
+    pub pin: String,
+    /// The DAC instance (e.g. "DAC1"), or "DAC" on MCUs with a single DAC.
+    pub dac: String,
+    pub channel: u32,
+}
+
+/// Parse a DAC output signal into the DAC instances and the channel (e.g.
+/// "DAC1_OUT2" into `(["DAC1"], 2)`).
+pub fn parse_dac_output(signal: &str) -> Option<(Vec<String>, u32)> {
+    let captures = DAC_OUTPUT.captures(signal)?;
+    let channel = captures[2].parse().ok()?;
+    Some((instances("DAC", &captures[1]), channel))
+}
+
+/// Return the DAC outputs of the pins of a GPIO group, sorted by pin, DAC and
+/// channel.
+pub fn dac_outputs(group: &GpioGroup) -> Vec<DacOutput> {
+    let mut outputs = vec![];
+    for pin in group.pins() {
+        for signal in pin.raw_signals().iter().filter(|s| s.is_analog()) {
+            if let Some((dacs, channel)) = parse_dac_output(signal.name()) {
+                for dac in dacs {
+                    outputs.push(DacOutput {
+                        pin: pin.name(),
+                        dac,
+                        channel,
+                    });
+                }
+            }
+        }
+    }
+    outputs.sort_by(|a, b| {
+        compare_str(&a.pin, &b.pin)
+            .then_with(|| compare_str(&a.dac, &b.dac))
+            .then_with(|| a.channel.cmp(&b.channel))
+    });
+    outputs.dedup();
+    outputs
+}
+
+/// A comparator or op-amp function of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AnalogFunction {
+    /// The pin (e.g. "PA1").
+    pub pin: String,
+    /// The peripheral instance (e.g. "COMP1").
+    pub instance: String,
+    /// The function (e.g. "INP" or "VOUT").
+    pub function: String,
+}
+
+/// Parse a comparator or op-amp signal into the instance and the function
+/// (e.g. "COMP1_INP" into `("COMP1", "INP")`).
+pub fn parse_analog_function(signal: &str) -> Option<(&str, &str)> {
+    let captures = COMP_FUNCTION
+        .captures(signal)
+        .or_else(|| OPAMP_FUNCTION.captures(signal))?;
+    Some((captures.get(1)?.as_str(), captures.get(2)?.as_str()))
+}
+
+/// Return the functions of a peripheral (e.g. "COMP") on the pins of a GPIO
+/// group, sorted by pin and instance.
+///
+/// Unlike the other analog signals, the comparator outputs are alternate
+/// functions, which are listed as well.
+pub fn analog_functions(group: &GpioGroup, peripheral: &str) -> Vec<AnalogFunction> {
+    let mut functions = vec![];
+    for pin in group.pins() {
+        for signal in pin.raw_signals() {
+            if let Some((instance, function)) = parse_analog_function(signal.name()) {
+                if instance.trim_end_matches(|c: char| c.is_ascii_digit()) == peripheral {
+                    functions.push(AnalogFunction {
+                        pin: pin.name(),
+                        instance: instance.to_string(),
+                        function: function.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    functions.sort_by(|a, b| {
+        compare_str(&a.pin, &b.pin)
+            .then_with(|| compare_str(&a.instance, &b.instance))
+            .then_with(|| a.function.cmp(&b.function))
+    });
+    functions.dedup();
+    functions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_adc_input() {
+        assert_eq!(
+            parse_adc_input("ADC_IN0"),
+            Some((vec!["ADC".into()], 0, false))
+        );
+        assert_eq!(
+            parse_adc_input("ADC123_IN10"),
+            Some((vec!["ADC1".into(), "ADC2".into(), "ADC3".into()], 10, false))
+        );
+        assert_eq!(
+            parse_adc_input("ADC1_INN5"),
+            Some((vec!["ADC1".into()], 5, true))
+        );
+        assert_eq!(parse_adc_input("ADC_EXTI11"), None);
+        assert_eq!(parse_adc_input("USART2_TX"), None);
+    }
+
+    #[test]
+    fn test_parse_dac_output() {
+        assert_eq!(parse_dac_output("DAC_OUT1"), Some((vec!["DAC".into()], 1)));
+        assert_eq!(
+            parse_dac_output("DAC1_OUT2"),
+            Some((vec!["DAC1".into()], 2))
+        );
+        assert_eq!(parse_dac_output("DAC_EXTI9"), None);
+        assert!(is_analog("DAC_OUT1"));
+        assert!(!is_analog("USART2_TX"));
+    }
+
+    #[test]
+    fn test_parse_analog_function() {
+        assert_eq!(parse_analog_function("COMP1_INP"), Some(("COMP1", "INP")));
+        assert_eq!(parse_analog_function("COMP2_OUT"), Some(("COMP2", "OUT")));
+        assert_eq!(
+            parse_analog_function("OPAMP1_VINM0"),
+            Some(("OPAMP1", "VINM0"))
+        );
+        assert_eq!(parse_analog_function("OPAMP_VOUT"), Some(("OPAMP", "VOUT")));
+        assert_eq!(parse_analog_function("COMP1_BLANKING"), None);
+        assert!(is_analog("COMP1_INM"));
+        assert!(is_analog("OPAMP1_VOUT"));
+        assert!(!is_analog("COMP1_OUT"));
+    }
+}
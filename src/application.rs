@@ -0,0 +1,139 @@
+//! Application-centric pin reports.
+//!
+//! Some applications need signals from several peripherals at once (e.g.
+//! motor control needs advanced timer outputs, break inputs and comparator
+//! outputs). The reports in this module group the relevant signals of a GPIO
+//! group into categories, independent of the peripheral stem.
+
+use std::str::FromStr;
+
+use regex::Regex;
+
+use crate::af_tree::{FilteredSignal, GpioGroup};
+
+/// The supported applications.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Application {
+    MotorControl,
+    Audio,
+}
+
+impl FromStr for Application {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "motor" => Ok(Application::MotorControl),
+            "audio" => Ok(Application::Audio),
+            _ => Err(format!("Unknown application {:?}", s)),
+        }
+    }
+}
+
+const MOTOR_CONTROL_CATEGORIES: &[(&str, &str)] = &[
+    ("Advanced timer channels", r"^TIM(1|8|20)_CH[1-6]$"),
+    (
+        "Advanced timer complementary channels",
+        r"^TIM(1|8|20)_CH[1-4]N$",
+    ),
+    (
+        "Advanced timer break inputs",
+        r"^TIM(1|8|20)_BKIN2?(_COMP\d+)?$",
+    ),
+    ("Advanced timer external triggers", r"^TIM(1|8|20)_ETR$"),
+    ("ADC external trigger inputs", r"^ADC\d*_EXTI\d+$"),
+    ("Comparator outputs", r"^COMP\d+_OUT$"),
+];
+
+const AUDIO_CATEGORIES: &[(&str, &str)] = &[
+    ("SAI blocks", r"^SAI\d+_(SD|FS|SCK)_[AB]$"),
+    ("I2S instances", r"^I2S\d+(ext)?_(SD|WS|CK)$"),
+    ("Master clock outputs", r"^(I2S\d+_MCK|SAI\d+_MCLK_[AB])$"),
+    ("External audio clock inputs", r"^(I2S_CKIN|SAI\d+_EXTCLK)$"),
+    ("SPDIF receiver inputs", r"^SPDIFRX\d*_IN\d$"),
+];
+
+impl Application {
+    /// The categories of this application with the pattern matching their
+    /// signal names.
+    fn categories(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Application::MotorControl => MOTOR_CONTROL_CATEGORIES,
+            Application::Audio => AUDIO_CATEGORIES,
+        }
+    }
+}
+
+/// The signals of one category, e.g. "Comparator outputs".
+#[derive(Debug)]
+pub struct Category<'a> {
+    pub name: &'static str,
+    pub signals: Vec<FilteredSignal<'a>>,
+}
+
+/// Collect the signals relevant for `app` in the GPIO group.
+///
+/// Categories without any signal are included with an empty signal list.
+pub fn collect(group: &GpioGroup, app: Application) -> Vec<Category<'_>> {
+    app.categories()
+        .iter()
+        .map(|(name, pattern)| {
+            let re = Regex::new(pattern).unwrap();
+            let signals = group
+                .pins()
+                .iter()
+                .flat_map(|pin| {
+                    pin.raw_signals()
+                        .iter()
+                        .filter(|sig| re.is_match(sig.name()))
+                        .map(move |signal| FilteredSignal { group, pin, signal })
+                })
+                .collect();
+            Category { name, signals }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return the category names of `app` matching the signal.
+    fn categorize(app: Application, signal: &str) -> Vec<&'static str> {
+        app.categories()
+            .iter()
+            .filter(|(_, pattern)| Regex::new(pattern).unwrap().is_match(signal))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    #[test]
+    fn test_categories() {
+        let motor = Application::MotorControl;
+        assert_eq!(
+            categorize(motor, "TIM1_CH1"),
+            vec!["Advanced timer channels"]
+        );
+        assert_eq!(
+            categorize(motor, "TIM8_CH3N"),
+            vec!["Advanced timer complementary channels"]
+        );
+        assert_eq!(
+            categorize(motor, "TIM1_BKIN2"),
+            vec!["Advanced timer break inputs"]
+        );
+        assert!(categorize(motor, "TIM2_CH1").is_empty());
+
+        let audio = Application::Audio;
+        assert_eq!(categorize(audio, "I2S2ext_SD"), vec!["I2S instances"]);
+        assert_eq!(
+            categorize(audio, "SAI1_MCLK_B"),
+            vec!["Master clock outputs"]
+        );
+        assert_eq!(
+            categorize(audio, "SPDIFRX_IN1"),
+            vec!["SPDIF receiver inputs"]
+        );
+        assert!(categorize(audio, "SPI2_MOSI").is_empty());
+    }
+}
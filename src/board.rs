@@ -0,0 +1,116 @@
+//! Board files of the CubeMX board manager (e.g. Nucleo and Discovery kits).
+//!
+//! Every board is described by a CubeMX project file in
+//! `db/plugins/boardmanager/boards`, next to the MCU database. Its pin labels
+//! (e.g. "LD3 [Green]") are the names used in the board user manual.
+//!
+//! Note that the board files do not contain the connector pin numbers (e.g.
+//! "CN7 pin 1" or Arduino "D13"), only the labels of the MCU pins.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::ioc::{self, IocPin};
+
+/// A board with the MCU it carries and its pins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Board {
+    /// The board name (e.g. "NUCLEO-L031K6").
+    pub name: String,
+    /// The ref name of the MCU (e.g. "STM32L031K6Tx").
+    pub mcu: String,
+    /// The pins with a label, in the order of the board file.
+    pub pins: Vec<IocPin>,
+}
+
+impl Board {
+    /// The directory of the board files, relative to the MCU database
+    /// directory.
+    pub fn boards_dir<P: AsRef<Path>>(db_dir: P) -> PathBuf {
+        db_dir.as_ref().join("../plugins/boardmanager/boards")
+    }
+
+    /// Load a board, either from a board file or by its name (e.g.
+    /// "NUCLEO-L031K6", ignoring case) from the boards directory.
+    pub fn load<P: AsRef<Path>>(db_dir: P, board: &str) -> Result<Self, String> {
+        let path = match Path::new(board) {
+            path if path.is_file() => path.to_path_buf(),
+            _ => Self::find(db_dir, board)?,
+        };
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Find the file of a board in the boards directory. The file names start
+    /// with the board name (e.g. "NUCLEO-L031K6_Board_AllConfig.ioc").
+    fn find<P: AsRef<Path>>(db_dir: P, board: &str) -> Result<PathBuf, String> {
+        let dir = Self::boards_dir(db_dir);
+        let entries = fs::read_dir(&dir)
+            .map_err(|e| format!("Could not read boards directory {}: {}", dir.display(), e))?;
+        let lowercase = board.to_lowercase();
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                name.ends_with(".ioc")
+                    && (name == format!("{}.ioc", lowercase)
+                        || name.starts_with(&format!("{}_", lowercase)))
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("Board {} not found in {}", board, dir.display()))
+    }
+
+    /// Parse a board file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let properties = ioc::properties(contents);
+        let mcu = properties
+            .get("Mcu.UserName")
+            .ok_or("The board file does not specify the MCU (Mcu.UserName)")?;
+        Ok(Self {
+            name: properties
+                .get("board")
+                .copied()
+                .unwrap_or_default()
+                .to_string(),
+            mcu: mcu.to_string(),
+            pins: ioc::pins(contents)
+                .into_iter()
+                .filter(|pin| pin.label.is_some())
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let board = Board::parse(
+            "board=NUCLEO-L031K6\n\
+             Mcu.Pin0=PA2\n\
+             Mcu.Pin1=PB3\n\
+             Mcu.UserName=STM32L031K6Tx\n\
+             PA2.Signal=USART2_TX\n\
+             PB3.GPIO_Label=LD3 [Green]\n\
+             PB3.Signal=GPIO_Output\n",
+        )
+        .unwrap();
+        assert_eq!(board.name, "NUCLEO-L031K6");
+        assert_eq!(board.mcu, "STM32L031K6Tx");
+        assert_eq!(board.pins.len(), 1);
+        assert_eq!(board.pins[0].pin, "PB3");
+        assert!(Board::parse("board=NUCLEO-L031K6\n").is_err());
+    }
+}
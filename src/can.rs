@@ -0,0 +1,133 @@
+//! CAN signals.
+//!
+//! The bxCAN instances of the older families are named "CAN", with an
+//! instance number if there is more than one (e.g. "CAN_RX" on the STM32F0,
+//! "CAN1_RX" on the STM32F4). The newer families have FDCAN instances instead
+//! (e.g. "FDCAN1_RX"). HALs that share their CAN driver between both can
+//! rename the stems with an alias map (e.g. `FDCAN = "CAN"`), see
+//! `CanSignal::aliased_device`.
+
+use std::collections::HashMap;
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::{device_stem, GpioGroup};
+
+lazy_static! {
+    /// CAN signals, e.g. "CAN_RX", "CAN1_TX" or "FDCAN2_RX".
+    static ref CAN_SIGNAL: Regex = Regex::new(r"^((?:FD)?CAN\d*)_(RX|TX)$").unwrap();
+}
+
+/// The function of a CAN pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CanFunction {
+    /// The receive line ("RX").
+    Rx,
+    /// The transmit line ("TX").
+    Tx,
+}
+
+/// A CAN signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CanSignal {
+    /// The CAN instance (e.g. "FDCAN1").
+    pub device: String,
+    pub function: CanFunction,
+}
+
+impl CanSignal {
+    /// The name of the IO trait of the pin (e.g. "RxPin").
+    pub fn io_trait(&self) -> &'static str {
+        match self.function {
+            CanFunction::Rx => "RxPin",
+            CanFunction::Tx => "TxPin",
+        }
+    }
+
+    /// The instance with its stem replaced by its alias, if any (e.g.
+    /// "CAN1" for "FDCAN1" with the alias `FDCAN = "CAN"`).
+    pub fn aliased_device(&self, aliases: &HashMap<String, String>) -> String {
+        let stem = device_stem(&self.device);
+        match aliases.get(stem) {
+            Some(alias) => format!("{}{}", alias, &self.device[stem.len()..]),
+            None => self.device.clone(),
+        }
+    }
+}
+
+/// Parse a CAN signal (e.g. "FDCAN1_RX" into `FDCAN1` and `Rx`).
+pub fn parse_can_signal(signal: &str) -> Option<CanSignal> {
+    let captures = CAN_SIGNAL.captures(signal)?;
+    let function = match &captures[2] {
+        "RX" => CanFunction::Rx,
+        _ => CanFunction::Tx,
+    };
+    Some(CanSignal {
+        device: captures[1].to_string(),
+        function,
+    })
+}
+
+/// A CAN signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CanPin {
+    /// The pin (e.g. "PA11").
+    pub pin: String,
+    /// The alternate function (e.g. "AF9").
+    pub af: String,
+    pub signal: CanSignal,
+}
+
+/// Return the CAN signals of the pins of a GPIO group, sorted by instance,
+/// function and pin.
+pub fn can_pins(group: &GpioGroup) -> Vec<CanPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            if let Some(signal) = parse_can_signal(raw.name()) {
+                pins.push(CanPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.sort_by(|a, b| {
+        compare_str(&a.signal.device, &b.signal.device)
+            .then_with(|| a.signal.function.cmp(&b.signal.function))
+            .then_with(|| compare_str(&a.pin, &b.pin))
+    });
+    pins.dedup();
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_can_signal() {
+        let signal = parse_can_signal("CAN1_RX").unwrap();
+        assert_eq!(signal.device, "CAN1");
+        assert_eq!(signal.function, CanFunction::Rx);
+        assert_eq!(signal.io_trait(), "RxPin");
+
+        assert_eq!(
+            parse_can_signal("CAN_TX").map(|s| s.device),
+            Some("CAN".into())
+        );
+
+        let signal = parse_can_signal("FDCAN2_TX").unwrap();
+        assert_eq!(signal.io_trait(), "TxPin");
+        let mut aliases = HashMap::new();
+        assert_eq!(signal.aliased_device(&aliases), "FDCAN2");
+        aliases.insert("FDCAN".to_string(), "CAN".to_string());
+        assert_eq!(signal.aliased_device(&aliases), "CAN2");
+
+        assert!(parse_can_signal("FDCAN1_RXFD_MODE").is_none());
+        assert!(parse_can_signal("USART2_TX").is_none());
+    }
+}
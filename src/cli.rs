@@ -0,0 +1,818 @@
+//! The command line interface: one subcommand per generated target.
+
+use clap::{App, AppSettings, Arg, SubCommand};
+
+/// The command line of cube-parse. Every generated target has its own
+/// subcommand builder, the global options apply to all of them.
+pub fn app<'a, 'b>(authors: &'b str) -> App<'a, 'b> {
+    App::new("cube-parse")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Extract AF modes on MCU pins from the database files provided with STM32CubeMX")
+        .author(authors)
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .subcommand(features_subcommand())
+        .subcommand(build_cfgs_subcommand())
+        .subcommand(generate_all_subcommand())
+        .subcommand(pins_subcommand())
+        .subcommand(memory_pins_subcommand())
+        .subcommand(adc_pins_subcommand())
+        .subcommand(dac_pins_subcommand())
+        .subcommand(i2s_pins_subcommand())
+        .subcommand(usb_pins_subcommand())
+        .subcommand(tsc_pins_subcommand())
+        .subcommand(pwm_pins_subcommand())
+        .subcommand(i2c_pins_subcommand())
+        .subcommand(spi_pins_subcommand())
+        .subcommand(can_pins_subcommand())
+        .subcommand(qspi_pins_subcommand())
+        .subcommand(fmc_pins_subcommand())
+        .subcommand(peripheral_pins_subcommand())
+        .subcommand(remap_pins_subcommand())
+        .subcommand(comp_pins_subcommand())
+        .subcommand(opamp_pins_subcommand())
+        .subcommand(dma_requests_subcommand())
+        .subcommand(clocks_subcommand())
+        .subcommand(interrupts_subcommand())
+        .subcommand(app_pins_subcommand())
+        .subcommand(portability_subcommand())
+        .subcommand(board_pins_subcommand())
+        .subcommand(ioc_pins_subcommand())
+        .subcommand(query_subcommand())
+        .subcommand(fragmentation_subcommand())
+        .subcommand(list_stems_subcommand())
+        .subcommand(list_mcus_subcommand())
+        .subcommand(mcu_info_subcommand())
+        .subcommand(pinout_subcommand())
+        .subcommand(list_packages_subcommand())
+        .subcommand(ips_subcommand())
+        .subcommand(gpio_groups_subcommand())
+        .subcommand(families_subcommand())
+        .subcommand(db_info_subcommand())
+        .arg(
+            Arg::with_name("summary_json")
+                .long("summary-json")
+                .help("Write a machine-readable summary of the run to this JSON file")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .global(true)
+                .help("Print more log messages (repeat for even more)"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .global(true)
+                .help("Fail instead of ignoring data that cannot be parsed or loaded"),
+        )
+        .arg(
+            Arg::with_name("explain_filters")
+                .long("explain-filters")
+                .global(true)
+                .help("Print how many MCUs, pins and signals remain after each applied filter"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .multiple(true)
+                .global(true)
+                .conflicts_with("verbose")
+                .help("Print fewer log messages (-qq silences errors too)"),
+        )
+}
+
+/// A subcommand generating a target of the selected MCUs of some MCU families.
+fn family_subcommand<'a, 'b>(name: &str, about: &'b str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .about(about)
+        .arg(config_arg())
+        .arg(db_dir_arg())
+        .arg(mcu_family_arg())
+        .arg(mcu_arg())
+        .arg(mcu_list_arg())
+        .arg(package_arg())
+        .arg(output_dir_arg())
+}
+
+fn features_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand("features", "Generate the Cargo features of a MCU family")
+        .arg(
+            Arg::with_name("legacy_package_features")
+                .long("legacy-package-features")
+                .help("Also define the lowercase package names (e.g. lqfp32) as aliases for the package features"),
+        )
+        .arg(
+            Arg::with_name("core_features")
+                .long("core-features")
+                .help("Also define core features (e.g. cortex-m4, dual-core) enabled by the MCU features"),
+        )
+        .arg(
+            Arg::with_name("peripheral_features")
+                .long("peripheral-features")
+                .help("Also define peripheral features (e.g. has-usart3) enabled by the MCU features"),
+        )
+        .arg(die_features_arg())
+        .arg(layout_arg())
+        .arg(guard_arg())
+}
+
+fn build_cfgs_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand("build-cfgs", "Generate the part of a build script that sets the MCU, sub-family and GPIO version cfgs of the enabled MCU feature")
+        .arg(layout_arg())
+        .arg(
+            Arg::with_name("cfg_prefix")
+                .long("cfg-prefix")
+                .help("The prefix of the cfgs (e.g. stm32_)")
+                .takes_value(true),
+        )
+}
+
+fn generate_all_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("generate-all")
+        .about("Generate the features, pin mappings, docs and a manifest configured in a config file into a HAL directory layout")
+        .arg(config_arg().required(true))
+        .arg(db_dir_arg())
+        .arg(guard_arg())
+        .arg(output_dir_arg())
+}
+
+fn pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand("pins", "Generate the pin mappings of a MCU family")
+        .args(&stem_args())
+        .args(&signal_list_args())
+        .args(&reserved_pin_args())
+        .arg(pins_arg())
+        .arg(af_arg())
+        .arg(
+            Arg::with_name("af_defines")
+                .long("af-defines")
+                .help("Annotate the pin mappings with the C HAL AF define names, e.g. GPIO_AF4_USART2"),
+        )
+        .arg(
+            Arg::with_name("alternatives")
+                .long("alternatives")
+                .help("Document the other pins providing the signals of a pin"),
+        )
+        .arg(
+            Arg::with_name("debug_pin_docs")
+                .long("debug-pin-docs")
+                .help("Warn in doc comments on the debug pins (e.g. PA13 SWDIO), which disconnect the debugger when used otherwise"),
+        )
+        .arg(
+            Arg::with_name("sealed_traits")
+                .long("sealed-traits")
+                .help("Define the IO traits (e.g. TxPin<P>) with a sealed supertrait implemented for the listed pins only"),
+        )
+        .arg(
+            Arg::with_name("emit_tests")
+                .long("emit-tests")
+                .help("Also generate tests/pin_mappings.rs, which checks one pin mapping per peripheral stem of every GPIO version"),
+        )
+        .arg(
+            Arg::with_name("test_prelude")
+                .long("test-prelude")
+                .help("The path imported by the tests of --emit-tests, which must provide the pin types, IO traits and peripherals (e.g. stm32l0xx_hal::prelude::*)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("test_pin_type")
+                .long("test-pin-type")
+                .help("The pin type of the tests of --emit-tests, {pin} and {port} are replaced by the pin name and port letter (default: {pin})")
+                .takes_value(true),
+        )
+        .arg(serial_handshake_arg())
+        .arg(
+            Arg::with_name("merge_serial_stems")
+                .long("merge-serial-stems")
+                .help("Treat the UART and LPUART stems as USART, e.g. in the tests of --emit-tests"),
+        )
+        .arg(
+            Arg::with_name("no_mcu_docs")
+                .long("no-mcu-docs")
+                .help("Do not list the MCUs of each GPIO version in comments on the generated code"),
+        )
+        .arg(
+            Arg::with_name("cfg_style")
+                .long("cfg-style")
+                .help("Gate the pin mappings by the io-* features of the GPIO versions, by the mcu-* features, or by bare cfgs of the MCUs (e.g. stm32l031k6tx) set by a build script")
+                .takes_value(true)
+                .possible_values(&["io", "mcu", "bare"]),
+        )
+        .arg(
+            Arg::with_name("cfg_prefix")
+                .long("cfg-prefix")
+                .help("The prefix of the bare cfgs of --cfg-style bare (e.g. stm32_)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("package_gating")
+                .long("package-gating")
+                .help("Gate the pins that are not bonded out in all packages by the package (or MCU) features of the MCUs having them"),
+        )
+        .arg(
+            Arg::with_name("style")
+                .long("style")
+                .help("The macro style of the pin mappings, f4xx generates the pin! blocks of the stm32f4xx-hal alt modules, l0xx the fixed pins.rs format of stm32l0xx-hal, embassy the pin_trait_impl! calls of embassy-stm32, table a PIN_FUNCTIONS table without traits")
+                .takes_value(true)
+                .possible_values(&["default", "f4xx", "l0xx", "embassy", "table"]),
+        )
+        .arg(
+            Arg::with_name("template_dir")
+                .long("template-dir")
+                .value_name("DIR")
+                .help("Override the built-in code templates with the ones in this directory (e.g. pin_group.rs.hbs)"),
+        )
+        .arg(
+            Arg::with_name("af_policy")
+                .long("af-policy")
+                .help("What to do if a pin provides the same signal on multiple AFs")
+                .takes_value(true)
+                .possible_values(&["all", "lowest", "error"]),
+        )
+        .arg(layout_arg())
+        .arg(
+            Arg::with_name("sub_family_override")
+                .long("sub-family-override")
+                .value_name("SUB_FAMILY=FILE")
+                .help("Use the hand-maintained code in FILE as the module of a sub-family (sub-family layout only)")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(guard_arg())
+}
+
+fn memory_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "memory-pins",
+        "List the candidate pins for an external memory",
+    )
+    .arg(
+        Arg::with_name("memory")
+            .long("memory")
+            .help("The external memory type to list candidate pins for")
+            .takes_value(true)
+            .possible_values(&["qspi", "ospi", "sdram", "nand"])
+            .required(true),
+    )
+    .args(&stem_args())
+    .args(&signal_list_args())
+    .args(&reserved_pin_args())
+}
+
+fn adc_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "adc-pins",
+        "Generate the ADC channels of the pins of a MCU family",
+    )
+}
+
+fn dac_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "dac-pins",
+        "Generate the DAC channels of the pins of a MCU family",
+    )
+}
+
+fn i2s_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "i2s-pins",
+        "Generate the I2S functions of the pins of a MCU family",
+    )
+}
+
+fn usb_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "usb-pins",
+        "Generate the USB and ULPI functions of the pins of a MCU family",
+    )
+}
+
+fn tsc_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "tsc-pins",
+        "Generate the touch sensing groups and IOs of the pins of a MCU family",
+    )
+}
+
+fn pwm_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand("pwm-pins", "Generate the PWM channels, including the complementary outputs, of the pins of a MCU family")
+}
+
+fn i2c_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "i2c-pins",
+        "Generate the open-drain I2C pins, including the SMBus alert pins, of a MCU family",
+    )
+}
+
+fn spi_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand("spi-pins", "Generate the SPI pins and the pin combinations of 4-wire and 3-wire buses of a MCU family")
+        .arg(
+            Arg::with_name("no_miso_type")
+                .long("no-miso-type")
+                .help("A placeholder type of the HAL (e.g. NoMiso or NoPin) implementing MisoPin for buses without MISO pin")
+                .takes_value(true),
+        )
+}
+
+fn can_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "can-pins",
+        "Generate the CAN and FDCAN pins of a MCU family",
+    )
+    .arg(
+        Arg::with_name("stem_alias")
+            .long("stem-alias")
+            .value_name("STEM=ALIAS")
+            .help("Rename the instances of a peripheral stem, e.g. FDCAN=CAN for FDCAN1 as CAN1")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+    )
+}
+
+fn qspi_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "qspi-pins",
+        "Generate the QUADSPI and OCTOSPI pins and the bus structs of their banks of a MCU family",
+    )
+}
+
+fn fmc_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "fmc-pins",
+        "Generate the FMC pins and the pin bundles of the external memories of a MCU family",
+    )
+}
+
+fn peripheral_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "peripheral-pins",
+        "Generate the Pins tuple impls of the serial, SPI and I2C peripherals of a MCU family",
+    )
+    .args(&stem_args())
+    .arg(serial_handshake_arg())
+}
+
+fn remap_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "remap-pins",
+        "Generate the AFIO remaps of the pins of the STM32F1 family",
+    )
+    .args(&stem_args())
+}
+
+fn comp_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "comp-pins",
+        "Generate the comparator inputs and outputs of the pins of a MCU family",
+    )
+}
+
+fn opamp_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "opamp-pins",
+        "Generate the op-amp inputs and outputs of the pins of a MCU family",
+    )
+}
+
+fn dma_requests_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "dma-requests",
+        "Generate the DMA request mappings of a MCU family",
+    )
+    .arg(die_features_arg())
+}
+
+fn clocks_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "clocks",
+        "Generate the clock metadata (maximum frequencies and prescalers) of a MCU family",
+    )
+    .arg(die_features_arg())
+}
+
+fn interrupts_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand("interrupts", "Generate the interrupts of a MCU family")
+        .arg(die_features_arg())
+}
+
+fn app_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand("app-pins", "Report the pins relevant for an application")
+        .arg(
+            Arg::with_name("app")
+                .long("app")
+                .help("The application to report the relevant pins for")
+                .takes_value(true)
+                .possible_values(&["motor", "audio"])
+                .required(true),
+        )
+        .args(&stem_args())
+        .args(&signal_list_args())
+        .args(&reserved_pin_args())
+}
+
+fn portability_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("portability")
+        .about("List the MCUs of all families that provide a set of signals")
+        .arg(config_arg())
+        .arg(db_dir_arg())
+        .arg(
+            Arg::with_name("signals")
+                .long("signals")
+                .help("Comma separated signals used by a design, e.g. \"USART2_TX,I2C1_SCL\"")
+                .takes_value(true)
+                .use_delimiter(true)
+                .required(true),
+        )
+        .arg(output_dir_arg())
+}
+
+fn board_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("board-pins")
+        .about("List the labelled pins of a board (e.g. a Nucleo) with their alternate functions")
+        .arg(config_arg())
+        .arg(db_dir_arg())
+        .arg(
+            Arg::with_name("board")
+                .long("board")
+                .help("The board name, e.g. \"NUCLEO-L031K6\", or the path of a board file")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(output_dir_arg())
+}
+
+fn ioc_pins_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("ioc-pins")
+        .about("Generate the pin mappings of the signals assigned in a CubeMX project")
+        .arg(config_arg())
+        .arg(db_dir_arg())
+        .arg(
+            Arg::with_name("ioc")
+                .long("ioc")
+                .help("The CubeMX project file (.ioc)")
+                .takes_value(true)
+                .required(true),
+        )
+        .arg(output_dir_arg())
+}
+
+fn query_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("query")
+        .about("List the pin signals of a MCU family matching some filters")
+        .arg(config_arg())
+        .arg(db_dir_arg().required_unless("ir"))
+        .arg(mcu_family_arg().required_unless("ir"))
+        .arg(mcu_arg())
+        .arg(mcu_list_arg())
+        .arg(package_arg())
+        .args(&stem_args())
+        .args(&signal_list_args())
+        .arg(filter_arg(
+            "device",
+            "Peripheral instances, e.g. \"USART2,TIM1\"",
+        ))
+        .arg(af_arg())
+        .arg(pins_arg())
+        .arg(filter_arg("port", "GPIO ports, e.g. \"PA,PB\""))
+        .arg(filter_arg(
+            "gpio-version",
+            "GPIO IP versions, e.g. \"STM32L051_gpio_v1_0\"",
+        ))
+        .arg(
+            Arg::with_name("dump_ir")
+                .long("dump-ir")
+                .help("Also write the AF tree to this JSON file, to be queried later with --ir")
+                .takes_value(true)
+                .conflicts_with("ir"),
+        )
+        .arg(
+            Arg::with_name("ir")
+                .long("ir")
+                .help("Query an AF tree written with --dump-ir instead of the database")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .conflicts_with_all(&[
+                    "mcu_family",
+                    "mcu",
+                    "mcu_list",
+                    "package",
+                    "stems",
+                    "composite_stem",
+                    "pins",
+                    "signals_from",
+                    "exclude_signals_from",
+                ]),
+        )
+        .arg(
+            Arg::with_name("compare")
+                .long("compare")
+                .help("Compare two --ir files, listing removed (-) and added (+) signals")
+                .requires("ir"),
+        )
+        .arg(
+            Arg::with_name("details")
+                .short("V")
+                .long("details")
+                .help("List each signal once with the GPIO versions and MCUs providing it")
+                .conflicts_with("compare"),
+        )
+        .arg(output_dir_arg())
+}
+
+fn fragmentation_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "fragmentation",
+        "Report the GPIO versions of a MCU family and how much they differ",
+    )
+    .args(&stem_args())
+}
+
+fn list_stems_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "list-stems",
+        "List the peripheral stems of a MCU family, usable with -f",
+    )
+}
+
+fn list_mcus_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "list-mcus",
+        "List the MCUs of a MCU family with their package and GPIO version",
+    )
+    .arg(
+        Arg::with_name("regex")
+            .long("regex")
+            .help("Only list the MCUs whose ref name matches this regex, e.g. \"^STM32L0.1K\"")
+            .takes_value(true),
+    )
+}
+
+fn mcu_info_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "mcu-info",
+        "Dump the metadata (memory sizes, cores, maximum frequency) of the MCUs of a MCU family",
+    )
+    .arg(
+        Arg::with_name("regex")
+            .long("regex")
+            .help("Only list the MCUs whose ref name matches this regex, e.g. \"^STM32L0.1K\"")
+            .takes_value(true),
+    )
+    .arg(
+        Arg::with_name("format")
+            .long("format")
+            .help("The output format (default: json)")
+            .takes_value(true)
+            .possible_values(&["json", "toml"]),
+    )
+}
+
+fn pinout_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "pinout",
+        "List the pin numbers or balls of the GPIO pins of the MCUs of a MCU family",
+    )
+}
+
+fn list_packages_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "list-packages",
+        "List the packages of a MCU family and the MCUs in each",
+    )
+}
+
+fn ips_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "ips",
+        "List the versions of every IP of a MCU family with their instances and MCUs",
+    )
+}
+
+fn gpio_groups_subcommand<'a, 'b>() -> App<'a, 'b> {
+    family_subcommand(
+        "gpio-groups",
+        "List which MCUs of a MCU family share which GPIO Modes file",
+    )
+}
+
+fn families_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("families")
+        .about("List the MCU families and sub-families in the database")
+        .arg(config_arg())
+        .arg(db_dir_arg())
+}
+
+fn db_info_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("db-info")
+        .about("Show the release and size of the database")
+        .arg(config_arg())
+        .arg(db_dir_arg())
+}
+
+fn config_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("config")
+        .long("config")
+        .value_name("TOML")
+        .help("Read the settings from this file (e.g. cube-parse.toml), command line options override it")
+        .takes_value(true)
+}
+
+fn db_dir_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("db_dir")
+        .short("d")
+        .help("Path to the CubeMX MCU database directory")
+        .takes_value(true)
+        .required_unless("config")
+}
+
+fn mcu_family_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("mcu_family")
+        .help("The MCU families to extract, e.g. \"STM32L0\" or \"STM32L4 STM32L4+\"")
+        .takes_value(true)
+        .multiple(true)
+        .required_unless("config")
+}
+
+fn mcu_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("mcu")
+        .short("m")
+        .long("mcu")
+        .help("Only include MCUs whose ref name starts with one of these comma separated values, e.g. \"STM32L071\"")
+        .takes_value(true)
+        .use_delimiter(true)
+}
+
+fn mcu_list_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("mcu_list")
+        .long("mcu-list")
+        .value_name("FILE")
+        .help("Only include the MCUs listed in this file, one ref name per line")
+        .takes_value(true)
+}
+
+fn package_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("package")
+        .long("package")
+        .help("Only include MCUs in one of these comma separated packages, e.g. \"LQFP32,UFQFPN*\"")
+        .takes_value(true)
+        .use_delimiter(true)
+}
+
+fn pins_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("pins")
+        .long("pins")
+        .help("Only include these comma separated pins, e.g. \"PA0,PB3-PB7,PC*\"")
+        .takes_value(true)
+        .use_delimiter(true)
+}
+
+fn af_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("af")
+        .long("af")
+        .help("Only include these comma separated alternate functions, e.g. \"AF7,AF8\", \"7,8\" or \"WAKEUP\"")
+        .takes_value(true)
+        .use_delimiter(true)
+}
+
+fn output_dir_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("output_dir")
+        .short("o")
+        .long("output-dir")
+        .help("Write the generated files to this directory instead of stdout")
+        .takes_value(true)
+}
+
+fn die_features_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("die_features")
+        .long("die-features")
+        .help("Also define die features (e.g. die-447) enabled by the MCU features, and gate the generated code by die")
+}
+
+fn guard_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("guard")
+        .long("guard")
+        .value_name("CARGO_TOML")
+        .help("Fail if a MCU with a mcu-* feature in this Cargo.toml is missing from the database")
+        .takes_value(true)
+}
+
+fn layout_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("layout")
+        .long("layout")
+        .help("Generate one block per GPIO version or one module per sub-family")
+        .takes_value(true)
+        .possible_values(&["gpio-version", "sub-family"])
+}
+
+/// The arguments selecting the pins reserved by the board.
+fn reserved_pin_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("reserved_pins")
+            .long("reserved-pins")
+            .help("Omit these pins, e.g. \"PA13,PA14\" or \"PH*\"")
+            .takes_value(true)
+            .use_delimiter(true),
+        Arg::with_name("ioc")
+            .long("ioc")
+            .value_name("FILE")
+            .help("Omit the pins used by this CubeMX project (.ioc)")
+            .takes_value(true),
+    ]
+}
+
+/// The arguments selecting the signals to include by name.
+fn signal_list_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("signals_from")
+            .long("signals-from")
+            .value_name("FILE")
+            .help(
+                "Only include the signals listed in this file, one name (e.g. USART2_TX) per line",
+            )
+            .takes_value(true),
+        Arg::with_name("exclude_signals_from")
+            .long("exclude-signals-from")
+            .value_name("FILE")
+            .help("Exclude the signals listed in this file, one name (e.g. USART2_CK) per line")
+            .takes_value(true),
+    ]
+}
+
+fn serial_handshake_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("serial_handshake")
+        .long("serial-handshake")
+        .help("Also map the CTS, RTS, CK and DE signals of serial ports (e.g. CtsPin<USART2>)")
+}
+
+/// The arguments selecting the peripheral stems to include.
+fn stem_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("stems")
+            .short("f")
+            .long("stems")
+            .help("Comma separated peripheral stems to include, e.g. \"USART,I2C\" (default: all)")
+            .takes_value(true)
+            .use_delimiter(true),
+        Arg::with_name("composite_stem")
+            .long("composite-stem")
+            .help("Define a composite stem usable with -f, e.g. \"SERIAL=USART|UART|LPUART\"")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1),
+    ]
+}
+
+/// A comma separated filter of the `query` subcommand.
+fn filter_arg<'a, 'b>(name: &'a str, help: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(name)
+        .long(name)
+        .help(help)
+        .takes_value(true)
+        .use_delimiter(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> clap::Result<clap::ArgMatches<'static>> {
+        app("").get_matches_from_safe(std::iter::once("cube-parse").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_family_subcommand() {
+        for name in &["features", "pins", "fmc-pins", "mcu-info"] {
+            let args =
+                parse(&[name, "STM32L0", "-d", "db", "-m", "STM32L07", "-o", "out"]).unwrap();
+            let (subcommand, args) = args.subcommand();
+            let args = args.unwrap();
+            assert_eq!(subcommand, *name);
+            assert_eq!(args.value_of("mcu_family"), Some("STM32L0"));
+            assert_eq!(args.value_of("db_dir"), Some("db"));
+            assert_eq!(args.value_of("mcu"), Some("STM32L07"));
+            assert_eq!(args.value_of("output_dir"), Some("out"));
+        }
+        // The family and database can come from the config file
+        assert!(parse(&["adc-pins", "--config", "cube-parse.toml"]).is_ok());
+        assert!(parse(&["adc-pins"]).is_err());
+        // The options of a target are not accepted by the others
+        assert!(parse(&["pins", "STM32L0", "-d", "db", "--cfg-style", "io"]).is_ok());
+        assert!(parse(&["adc-pins", "STM32L0", "-d", "db", "--cfg-style", "io"]).is_err());
+    }
+
+    #[test]
+    fn test_conflicts() {
+        assert!(parse(&["query", "--ir", "tree.json", "-m", "STM32L07"]).is_err());
+        assert!(parse(&["query", "--ir", "tree.json", "--port", "PA"]).is_ok());
+    }
+}
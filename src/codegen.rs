@@ -0,0 +1,5105 @@
+//! Code and report generators.
+//!
+//! Every generator returns a `GeneratedArtifact`, which the `cube-parse`
+//! binary writes to the output directory (or prints). Other tools can use the
+//! generators directly and route the artifacts as they see fit, e.g. merge the
+//! features into a Cargo.toml.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use log::warn;
+use regex::Regex;
+use serde_derive::Serialize;
+
+use crate::{
+    af_tree, analog, application, board, can, db_info, dma, family,
+    features::FeatureNames,
+    fmc, i2c, i2s, internal_peripheral, mcu, memory, nvic, package, peripheral_pins, qspi, rcc,
+    remap, spi, stems,
+    templates::{self, Templates},
+    timer, tsc, usb,
+};
+
+/// The kind of a generated artifact.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArtifactKind {
+    /// Cargo features (TOML), meant for the `[features]` section of a
+    /// Cargo.toml.
+    Features,
+    /// Rust source with the pin mappings.
+    PinMappings,
+    /// Rust source with the candidate pins of an external memory.
+    MemoryPins,
+    /// Rust source with the DMA request mappings.
+    DmaRequests,
+    /// Rust source with the clock metadata.
+    Clocks,
+    /// Rust source with the interrupts.
+    Interrupts,
+    /// Rust source with the ADC channels of the pins.
+    AdcPins,
+    /// Rust source with the DAC channels of the pins.
+    DacPins,
+    /// Rust source with the comparator or op-amp functions of the pins.
+    AnalogFunctionPins,
+    /// Rust source with the I2S functions of the pins.
+    I2sPins,
+    /// Rust source with the USB and ULPI functions of the pins.
+    UsbPins,
+    /// Rust source with the touch sensing groups and IOs of the pins.
+    TscPins,
+    /// Rust source with the PWM channels of the pins.
+    PwmPins,
+    /// Rust source with the I2C and SMBus pins.
+    I2cPins,
+    /// Rust source with the SPI pins and their combinations.
+    SpiPins,
+    /// Rust source with the CAN and FDCAN pins.
+    CanPins,
+    /// Rust source with the QUADSPI and OCTOSPI pins and buses.
+    QspiPins,
+    /// Rust source with the FMC pins and pin bundles.
+    FmcPins,
+    /// Rust source with the `Pins` tuple impls of the peripherals.
+    PeripheralPins,
+    /// Rust source with the AFIO remaps of the STM32F1.
+    RemapPins,
+    /// The metadata of the MCUs (JSON or TOML).
+    McuInfo,
+    /// Rust source with the compile tests of the pin mappings.
+    PinTests,
+    /// Rust source to include in a build script.
+    BuildScript,
+    /// A human-readable report.
+    Report,
+    /// A list of the generated files with the database release they were
+    /// generated from (TOML).
+    Manifest,
+}
+
+/// The output of a generator.
+#[derive(Debug, Clone)]
+pub struct GeneratedArtifact {
+    /// The default path, relative to the output directory (e.g.
+    /// "pin_mappings.rs").
+    pub path: PathBuf,
+    pub kind: ArtifactKind,
+    pub content: String,
+}
+
+impl GeneratedArtifact {
+    pub fn new<P: Into<PathBuf>>(path: P, kind: ArtifactKind, content: String) -> Self {
+        Self {
+            path: path.into(),
+            kind,
+            content,
+        }
+    }
+}
+
+/// How the generated code is structured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// One block per GPIO IP version, gated by its IO feature.
+    GpioVersion,
+    /// One module per CubeMX sub-family (e.g. "STM32L0x1"), gated by its
+    /// sub-family feature, containing the blocks of the GPIO IP versions used
+    /// by its MCUs.
+    SubFamily,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gpio-version" => Ok(Layout::GpioVersion),
+            "sub-family" => Ok(Layout::SubFamily),
+            _ => Err(format!("Unknown layout {:?}", s)),
+        }
+    }
+}
+
+/// The macro style of the generated pin mappings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// `pins!` blocks with the alternate functions of each pin (e.g.
+    /// `PA2 => {AF4: TxPin<USART2>}`), rendered with the templates.
+    Default,
+    /// One module per peripheral instance with the `pin!` blocks of the
+    /// stm32f4xx-hal `gpio::alt` modules (e.g. `<Tx> for [PA2<7>]`).
+    F4xx,
+    /// The `pins!` blocks as expected by stm32l0xx-hal, which stay the same
+    /// when the default style changes.
+    L0xx,
+    /// The `pin_trait_impl!` calls of the embassy-stm32 build script (e.g.
+    /// `pin_trait_impl!(crate::usart::TxPin, USART2, PA2, 4u8)`).
+    Embassy,
+    /// A `PIN_FUNCTIONS` table with all pin signals for runtime lookup,
+    /// without traits.
+    Table,
+}
+
+impl Style {
+    /// The name of the style (e.g. "f4xx").
+    pub fn name(self) -> &'static str {
+        match self {
+            Style::Default => "default",
+            Style::F4xx => "f4xx",
+            Style::L0xx => "l0xx",
+            Style::Embassy => "embassy",
+            Style::Table => "table",
+        }
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Style::Default),
+            "f4xx" => Ok(Style::F4xx),
+            "l0xx" => Ok(Style::L0xx),
+            "embassy" => Ok(Style::Embassy),
+            "table" => Ok(Style::Table),
+            _ => Err(format!("Unknown style {:?}", s)),
+        }
+    }
+}
+
+/// How the pin mappings of the default style are gated.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CfgStyle {
+    /// By the IO feature of the GPIO version (e.g. `feature =
+    /// "io-STM32L031"`).
+    #[default]
+    Io,
+    /// By the features of the MCUs (e.g. `feature = "mcu-STM32L031K6Tx"`).
+    Mcu,
+    /// By bare cfg identifiers of the MCUs, i.e. the lowercase ref name after
+    /// a prefix (e.g. `stm32l031k6tx`), which a build script has to set.
+    Bare,
+}
+
+impl FromStr for CfgStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "io" => Ok(CfgStyle::Io),
+            "mcu" => Ok(CfgStyle::Mcu),
+            "bare" => Ok(CfgStyle::Bare),
+            _ => Err(format!("Unknown cfg style {:?}", s)),
+        }
+    }
+}
+
+/// The format of the data dumps (e.g. the MCU metadata).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DumpFormat {
+    Json,
+    Toml,
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(DumpFormat::Json),
+            "toml" => Ok(DumpFormat::Toml),
+            _ => Err(format!("Unknown format {:?}", s)),
+        }
+    }
+}
+
+lazy_static! {
+    static ref FEATURE_DEPENDENCIES: HashMap<&'static str, HashMap<&'static str, &'static str>> = {
+        let mut m = HashMap::new();
+
+        // STM32L0
+        let mut l0 = HashMap::new();
+        l0.insert("^STM32L0.1", "stm32l0x1");
+        l0.insert("^STM32L0.2", "stm32l0x2");
+        l0.insert("^STM32L0.3", "stm32l0x3");
+        m.insert("STM32L0", l0);
+
+        m
+    };
+    /// A `cfg` attribute selecting any of several features.
+    static ref ANY_FEATURE_CFG: Regex =
+        Regex::new(r#"#\[cfg\(any\(((?:feature = "[^"]+", )+feature = "[^"]+")\)\)\]"#).unwrap();
+    /// A feature definition of the features artifact (e.g.
+    /// `mcu-STM32L071KBTx = ["io-STM32L071"]`).
+    static ref FEATURE_LINE: Regex = Regex::new(r"^([^#\s]+) = \[(.*)\]$").unwrap();
+}
+
+/// Print the IO features, the package and sub-family features they are
+/// combined with, followed by MCU features that act purely as aliases for
+/// these features.
+///
+/// The sub-family features of MCUs in `mcu_sub_family_map` (e.g. for the
+/// sub-family layout) take precedence over the built-in ones. If
+/// `names.cores` is set, the MCU features also enable the features of their
+/// cores in `mcu_core_map`, and "dual-core" on dual-core MCUs. If
+/// `names.peripherals` is set, they also enable the features of their
+/// peripheral instances in `mcu_peripheral_map` (e.g. "has-usart3"), except
+/// those every MCU has (e.g. "GPIO" or "NVIC"). If `names.dies` is set, they also enable the feature of their die in
+/// `mcu_die_map` (e.g. "die-447").
+///
+/// Every section is sorted alphanumerically, so that the output can be
+/// committed verbatim and only changes if the database does.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_features(
+    mcu_gpio_map: &HashMap<String, Vec<String>>,
+    mcu_package_map: &HashMap<String, String>,
+    mcu_sub_family_map: &HashMap<String, String>,
+    mcu_core_map: &HashMap<String, Vec<mcu::Core>>,
+    mcu_peripheral_map: &HashMap<String, Vec<String>>,
+    mcu_die_map: &HashMap<String, String>,
+    mcu_families: &[&str],
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut io_features = vec![];
+    let mut sub_family_features = vec![];
+    let mut core_features = vec![];
+    let mut target_core_features = vec![];
+    let mut peripheral_features = vec![];
+    let mut die_features = vec![];
+    // Package features, mapped to the package names
+    let mut package_features: HashMap<String, String> = HashMap::new();
+    let mut mcu_aliases = vec![];
+    // The peripheral instances of every MCU don't tell them apart
+    let mut all_mcus = mcu_gpio_map.values().flatten();
+    let mut common_peripherals: HashSet<&str> = all_mcus
+        .next()
+        .and_then(|mcu| mcu_peripheral_map.get(mcu))
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    for mcu in all_mcus {
+        let instances = mcu_peripheral_map.get(mcu).map_or(&[][..], Vec::as_slice);
+        common_peripherals.retain(|common| instances.iter().any(|i| i == common));
+    }
+    for (gpio, mcu_list) in mcu_gpio_map {
+        let gpio_version_feature = names.io_feature(gpio)?;
+        for mcu in mcu_list {
+            let mut dependencies = vec![];
+
+            // GPIO version feature
+            dependencies.push(gpio_version_feature.clone());
+
+            // Additional dependencies
+            if let Some(feature) = sub_family_feature(mcu, mcu_sub_family_map, mcu_families) {
+                dependencies.push(feature.clone());
+                sub_family_features.push(feature);
+            }
+
+            // Package based feature
+            if let Some(package) = mcu_package_map.get(mcu) {
+                let feature = names.package_feature(package, mcu);
+                match package_features.get(&feature) {
+                    Some(other) if other != package => {
+                        return Err(format!(
+                            "Packages {} and {} both map to feature {}",
+                            other, package, feature
+                        ));
+                    }
+                    _ => {
+                        package_features.insert(feature.clone(), package.clone());
+                    }
+                }
+                dependencies.push(feature);
+            }
+
+            // Core features
+            if names.cores {
+                let cores = mcu_core_map.get(mcu).map_or(&[][..], Vec::as_slice);
+                let mut features = cores.iter().map(|c| c.feature_name()).collect::<Vec<_>>();
+                if cores.len() > 1 {
+                    features.push("dual-core");
+                    // Per-core aliases (e.g. "mcu-STM32H745ZITx-cm4"), which
+                    // also select the target core
+                    for core in cores {
+                        mcu_aliases.push(format!(
+                            "{}-{} = [\"{}\", \"{}\"]",
+                            names.mcu_feature(mcu),
+                            core.short_name(),
+                            names.mcu_feature(mcu),
+                            core.short_name()
+                        ));
+                        target_core_features.push(core.short_name().to_string());
+                    }
+                }
+                for feature in features {
+                    dependencies.push(feature.to_string());
+                    core_features.push(feature.to_string());
+                }
+            }
+
+            // Peripheral features
+            if names.peripherals {
+                let mut features = mcu_peripheral_map
+                    .get(mcu)
+                    .into_iter()
+                    .flatten()
+                    .filter(|instance| !common_peripherals.contains(instance.as_str()))
+                    .map(|instance| names.peripheral_feature(instance))
+                    .collect::<Vec<_>>();
+                features.sort_by(|a, b| compare_str(a, b));
+                features.dedup();
+                dependencies.extend(features.iter().cloned());
+                peripheral_features.extend(features);
+            }
+
+            // Die feature
+            if let Some(die) = mcu_die_map.get(mcu).filter(|_| names.dies) {
+                let feature = names.die_feature(die);
+                dependencies.push(feature.clone());
+                die_features.push(feature);
+            }
+
+            let dependencies = dependencies
+                .iter()
+                .map(|val| format!("\"{}\"", val))
+                .collect::<Vec<_>>();
+            mcu_aliases.push(format!(
+                "{} = [{}]",
+                names.mcu_feature(mcu),
+                dependencies.join(", ")
+            ));
+        }
+        io_features.push(gpio_version_feature);
+    }
+    let mut package_table = package_features
+        .iter()
+        .map(|(feature, package)| format!("{}: {}", package, feature))
+        .collect::<Vec<_>>();
+    package_table.sort_by(|a, b| compare_str(a, b));
+    let legacy_packages = package_features
+        .iter()
+        .filter(|_| names.legacy_packages)
+        .map(|(feature, package)| format!("{} = [\"{}\"]", package.to_lowercase(), feature))
+        .collect::<Vec<_>>();
+
+    let mut out = String::new();
+    let mut section = |comment: &[String], mut lines: Vec<String>, empty_features: bool| {
+        if lines.is_empty() {
+            return;
+        }
+        lines.sort_by(|a, b| compare_str(a, b));
+        lines.dedup();
+        if !out.is_empty() {
+            writeln!(out).unwrap();
+        }
+        for line in comment {
+            writeln!(out, "# {}", line).unwrap();
+        }
+        for line in lines {
+            if empty_features {
+                writeln!(out, "{} = []", line).unwrap();
+            } else {
+                writeln!(out, "{}", line).unwrap();
+            }
+        }
+    };
+    let comment = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+    section(
+        &comment(&[
+            "Features based on the GPIO peripheral version",
+            "This determines the pin function mapping of the MCU",
+        ]),
+        io_features,
+        true,
+    );
+    let mut packages_comment = comment(&["Physical packages"]);
+    packages_comment.extend(package_table);
+    section(
+        &packages_comment,
+        package_features.into_keys().collect(),
+        true,
+    );
+    section(
+        &comment(&["Previous package feature names"]),
+        legacy_packages,
+        false,
+    );
+    section(&comment(&["Sub-families"]), sub_family_features, true);
+    section(&comment(&["Cores"]), core_features, true);
+    section(
+        &comment(&["Target cores of the dual-core MCUs"]),
+        target_core_features,
+        true,
+    );
+    section(&comment(&["Peripherals"]), peripheral_features, true);
+    section(&comment(&["Dies"]), die_features, true);
+    section(&comment(&["MCUs"]), mcu_aliases, false);
+
+    Ok(GeneratedArtifact::new(
+        "features.toml",
+        ArtifactKind::Features,
+        out,
+    ))
+}
+
+/// Return the sub-family feature of a MCU: its entry in `mcu_sub_family_map`,
+/// or the built-in feature of its family (e.g. "stm32l0x1").
+fn sub_family_feature(
+    mcu: &str,
+    mcu_sub_family_map: &HashMap<String, String>,
+    mcu_families: &[&str],
+) -> Option<String> {
+    mcu_sub_family_map.get(mcu).cloned().or_else(|| {
+        mcu_families
+            .iter()
+            .filter_map(|family| FEATURE_DEPENDENCIES.get(family))
+            .flatten()
+            .find(|(pattern, _)| Regex::new(pattern).unwrap().is_match(mcu))
+            .map(|(_, feature)| feature.to_string())
+    })
+}
+
+/// Generate the part of a build script that sets cfgs for the enabled MCU
+/// feature, for HALs gating their code with cfgs (see `CfgStyle::Bare`).
+///
+/// The generated `emit_mcu_cfgs` function maps the `CARGO_FEATURE_*`
+/// variable of each MCU feature to the cfgs of the MCU (e.g. `stm32l031k6tx`),
+/// its sub-family (e.g. `stm32l0x1`) and its GPIO version (e.g.
+/// `io_stm32l031`), after `prefix`, and declares all of them as expected
+/// cfgs.
+pub fn generate_build_cfgs(
+    mcu_gpio_map: &HashMap<String, Vec<String>>,
+    mcu_sub_family_map: &HashMap<String, String>,
+    mcu_families: &[&str],
+    names: &FeatureNames,
+    prefix: &str,
+) -> Result<GeneratedArtifact, String> {
+    let cfg = |name: &str| {
+        format!(
+            "{}{}",
+            prefix,
+            name.to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+    };
+    let variable =
+        |feature: &str| format!("CARGO_FEATURE_{}", feature.to_uppercase().replace('-', "_"));
+
+    let mut mcus = vec![];
+    for (gpio, mcu_list) in mcu_gpio_map {
+        let io_cfg = cfg(&names.io_feature(gpio)?);
+        for mcu in mcu_list {
+            let mut cfgs = vec![cfg(mcu)];
+            if let Some(feature) = sub_family_feature(mcu, mcu_sub_family_map, mcu_families) {
+                cfgs.push(cfg(&feature));
+            }
+            cfgs.push(io_cfg.clone());
+            let cfgs = cfgs
+                .iter()
+                .map(|c| format!("\"{}\"", c))
+                .collect::<Vec<_>>();
+            mcus.push((
+                variable(&names.mcu_feature(mcu)),
+                format!("&[{}]", cfgs.join(", ")),
+            ));
+        }
+    }
+    mcus.sort_by(|a, b| compare_str(&a.0, &b.0));
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// Include in build.rs (e.g. `include!(\"build_cfgs.rs\");`) and call\n\
+         // `emit_mcu_cfgs()` from its main function.\n"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "/// The cfgs of each MCU, by the variable of its feature."
+    )
+    .unwrap();
+    writeln!(out, "const MCU_CFGS: &[(&str, &[&str])] = &[").unwrap();
+    for (variable, cfgs) in mcus {
+        writeln!(out, "    (\"{}\", {}),", variable, cfgs).unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+    out.push_str(
+        "/// Set the cfgs of the enabled MCU features, and declare all cfgs as\n\
+         /// expected.\n\
+         fn emit_mcu_cfgs() {\n\
+         \x20   let mut expected = std::collections::BTreeSet::<&str>::new();\n\
+         \x20   for (variable, cfgs) in MCU_CFGS {\n\
+         \x20       expected.extend(cfgs.iter());\n\
+         \x20       if std::env::var_os(variable).is_some() {\n\
+         \x20           for cfg in cfgs.iter() {\n\
+         \x20               println!(\"cargo:rustc-cfg={}\", cfg);\n\
+         \x20           }\n\
+         \x20       }\n\
+         \x20   }\n\
+         \x20   for cfg in expected {\n\
+         \x20       println!(\"cargo:rustc-check-cfg=cfg({})\", cfg);\n\
+         \x20   }\n\
+         }\n",
+    );
+    Ok(GeneratedArtifact::new(
+        "build_cfgs.rs",
+        ArtifactKind::BuildScript,
+        out,
+    ))
+}
+
+/// The options of the pin mappings in the default style.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PinMappingOptions<'a> {
+    /// Annotate every alternate function with the corresponding C HAL define
+    /// name.
+    pub af_defines: bool,
+    /// Document the other pins providing the signals of a pin (e.g.
+    /// "TxPin<USART1> is also available on PB6 (AF0)").
+    pub alternatives: bool,
+    /// Precede the debug pins (e.g. PA13) with a doc comment warning that
+    /// using them disconnects the debugger.
+    pub debug_docs: bool,
+    /// Define the IO traits (e.g. `TxPin<P>`) with a `private::Sealed`
+    /// supertrait, implemented for the listed pins only.
+    pub sealed_traits: bool,
+    /// Gate the pins that some MCUs of a GPIO version lack in their package
+    /// by the package or MCU features of the MCUs having them.
+    pub package_pins: Option<&'a PackagePins>,
+    /// List the MCUs of each GPIO version in a comment in front of its
+    /// mappings.
+    pub mcu_docs: bool,
+    /// How the pin mappings are gated.
+    pub cfg_style: CfgStyle,
+    /// The prefix of the cfg identifiers of `CfgStyle::Bare` (e.g.
+    /// "stm32_").
+    pub cfg_prefix: &'a str,
+    /// Treat the "UART" and "LPUART" stems as "USART", see
+    /// `stems::serial_stem`.
+    pub merge_serial_stems: bool,
+}
+
+impl PinMappingOptions<'_> {
+    /// Return the `cfg` predicate selecting a MCU (e.g. `feature =
+    /// "mcu-STM32L031K6Tx"`, or `stm32l031k6tx` for `CfgStyle::Bare`).
+    fn mcu_predicate(&self, mcu: &str, names: &FeatureNames) -> String {
+        match self.cfg_style {
+            CfgStyle::Bare => format!("{}{}", self.cfg_prefix, mcu.to_lowercase()),
+            CfgStyle::Io | CfgStyle::Mcu => format!("feature = \"{}\"", names.mcu_feature(mcu)),
+        }
+    }
+
+    /// Return the `cfg` predicate selecting the MCUs of a GPIO group.
+    fn group_predicate(
+        &self,
+        group: &af_tree::GpioGroup,
+        names: &FeatureNames,
+    ) -> Result<String, String> {
+        match self.cfg_style {
+            CfgStyle::Io => Ok(format!(
+                "feature = \"{}\"",
+                names.io_feature(group.version())?
+            )),
+            CfgStyle::Mcu | CfgStyle::Bare => {
+                let mut predicates = group
+                    .mcus()
+                    .iter()
+                    .map(|mcu| self.mcu_predicate(mcu, names))
+                    .collect::<Vec<_>>();
+                predicates.sort_by(|a, b| compare_str(a, b));
+                Ok(any_predicate(&predicates))
+            }
+        }
+    }
+}
+
+/// The GPIO pins bonded out by the package of each MCU, for gating the pins
+/// that some packages lack (see `PinMappingOptions::package_pins`).
+#[derive(Debug, Clone, Default)]
+pub struct PackagePins {
+    /// The package feature, if there are package features, and the GPIO
+    /// pins (e.g. "PA2") of each MCU.
+    mcus: HashMap<String, (Option<String>, HashSet<String>)>,
+}
+
+impl PackagePins {
+    /// Collect the GPIO pins of the pinouts. `mcu_package_map` lists the
+    /// MCUs with package features (see `generate_features`).
+    pub fn new(
+        pinouts: &[package::Pinout],
+        mcu_package_map: &HashMap<String, String>,
+        names: &FeatureNames,
+    ) -> Self {
+        let mcus = pinouts
+            .iter()
+            .map(|pinout| {
+                let feature = mcu_package_map
+                    .get(&pinout.mcu)
+                    .map(|package| names.package_feature(package, &pinout.mcu));
+                let pins = pinout.pins.iter().filter_map(|p| p.gpio.clone()).collect();
+                (pinout.mcu.clone(), (feature, pins))
+            })
+            .collect();
+        Self { mcus }
+    }
+
+    /// Return the attribute gating a pin of a GPIO group, or `None` if all
+    /// MCUs of the group have it. The pin is gated by the package features
+    /// of the packages that have it on all MCUs of the group, and by the MCU
+    /// features of the other MCUs having it (or only by the bare cfgs of the
+    /// MCUs with `CfgStyle::Bare`). MCUs without a pinout are assumed to have
+    /// all pins.
+    fn cfg(
+        &self,
+        group: &af_tree::GpioGroup,
+        pin: &str,
+        options: &PinMappingOptions,
+        names: &FeatureNames,
+    ) -> Option<String> {
+        let mcus = group
+            .mcus()
+            .iter()
+            .filter_map(|mcu| self.mcus.get(mcu).map(|data| (mcu, data)))
+            .collect::<Vec<_>>();
+        if mcus.iter().all(|(_, (_, pins))| pins.contains(pin)) {
+            return None;
+        }
+        let mut predicates = vec![];
+        for (mcu, (package, pins)) in &mcus {
+            if !pins.contains(pin) {
+                continue;
+            }
+            let complete_package = package.as_ref().filter(|package| {
+                options.cfg_style != CfgStyle::Bare
+                    && mcus
+                        .iter()
+                        .filter(|(_, (p, _))| p.as_ref() == Some(package))
+                        .all(|(_, (_, pins))| pins.contains(pin))
+            });
+            predicates.push(match complete_package {
+                Some(package) => format!("feature = \"{}\"", package),
+                None => options.mcu_predicate(mcu, names),
+            });
+        }
+        predicates.sort_by(|a, b| compare_str(a, b));
+        predicates.dedup();
+        Some(cfg_attribute(&predicates))
+    }
+}
+
+/// Return the definitions of the sealed IO traits used by the pin mappings,
+/// and the seals of the pins, which implement `private::Sealed` for every
+/// mode of the pin (e.g. `impl<MODE> private::Sealed for PA2<MODE> {}`).
+/// Pins that are missing from some GPIO versions are gated like the versions
+/// providing them.
+fn sealed_traits(
+    af_tree: &af_tree::AfTree,
+    options: &PinMappingOptions,
+    names: &FeatureNames,
+) -> Result<(String, String), String> {
+    let mut traits = HashSet::new();
+    let mut pins: HashMap<String, Vec<String>> = HashMap::new();
+    for group in af_tree.groups() {
+        let predicate = options.group_predicate(group, names)?;
+        for pin in group.pins().iter().filter(|p| !p.signals().is_empty()) {
+            traits.extend(pin.signals().iter().map(|s| s.io()));
+            pins.entry(pin.name()).or_default().push(predicate.clone());
+        }
+    }
+    let mut traits = traits.into_iter().collect::<Vec<_>>();
+    traits.sort_by(|a, b| compare_str(a, b));
+    let mut pins = pins.into_iter().collect::<Vec<_>>();
+    pins.sort_by(|a, b| compare_str(&a.0, &b.0));
+
+    let mut definitions = String::new();
+    writeln!(definitions, "mod private {{").unwrap();
+    writeln!(
+        definitions,
+        "    /// Implemented by the pins below only, so that no other type can implement the pin traits."
+    )
+    .unwrap();
+    writeln!(definitions, "    pub trait Sealed {{}}").unwrap();
+    writeln!(definitions, "}}\n").unwrap();
+    for io in traits {
+        writeln!(definitions, "pub trait {}<P>: private::Sealed {{}}", io).unwrap();
+    }
+    writeln!(definitions).unwrap();
+
+    let mut seals = String::new();
+    for (pin, predicates) in pins {
+        if predicates.len() < af_tree.groups().len() {
+            writeln!(seals, "{}", cfg_attribute(&predicates)).unwrap();
+        }
+        writeln!(seals, "impl<MODE> private::Sealed for {}<MODE> {{}}", pin).unwrap();
+    }
+    Ok((definitions, seals))
+}
+
+/// Generate the pin mappings for the target MCU family. Each GPIO version is
+/// rendered with the `templates::PIN_GROUP` template.
+pub fn generate_pin_mappings(
+    af_tree: &af_tree::AfTree,
+    options: &PinMappingOptions,
+    names: &FeatureNames,
+    templates: &Templates,
+) -> Result<GeneratedArtifact, String> {
+    let (definitions, seals) = match options.sealed_traits {
+        true => sealed_traits(af_tree, options, names)?,
+        false => Default::default(),
+    };
+    let mut out = definitions;
+    for group in af_tree.groups() {
+        out.push_str(&render_pin_group(group, options, names, templates)?);
+        writeln!(out, "\n").unwrap();
+    }
+    out.push_str(&seals);
+    Ok(GeneratedArtifact::new(
+        "pin_mappings.rs",
+        ArtifactKind::PinMappings,
+        out,
+    ))
+}
+
+/// Generate the pin mappings of the GPIO version layout in a macro style. Only
+/// the default style supports the cfg styles other than `CfgStyle::Io`.
+pub fn generate_styled_pin_mappings(
+    af_tree: &af_tree::AfTree,
+    style: Style,
+    options: &PinMappingOptions,
+    names: &FeatureNames,
+    templates: &Templates,
+) -> Result<GeneratedArtifact, String> {
+    if style != Style::Default && options.cfg_style != CfgStyle::Io {
+        return Err(format!(
+            "The {} style only supports the io cfg style",
+            style.name()
+        ));
+    }
+    match style {
+        Style::Default => generate_pin_mappings(af_tree, options, names, templates),
+        Style::F4xx => generate_f4xx_pin_mappings(af_tree, names, options.mcu_docs),
+        Style::L0xx => generate_l0xx_pin_mappings(af_tree, names),
+        Style::Embassy => generate_embassy_pin_traits(af_tree, names, options.mcu_docs),
+        Style::Table => generate_pin_table(af_tree, names, options.mcu_docs),
+    }
+}
+
+/// Generate the pin mappings with the sub-family layout: one module per
+/// sub-family, containing the pin mappings of the GPIO IP versions used by its
+/// MCUs.
+///
+/// `sub_families` lists the selected MCUs (ref names) of each sub-family.
+/// Sub-families that need hand-maintained exceptions can be overridden:
+/// their module contains the code in `overrides` (by sub-family name) instead
+/// of the generated mappings.
+pub fn generate_sub_family_pin_mappings(
+    af_tree: &af_tree::AfTree,
+    sub_families: &[(String, Vec<String>)],
+    overrides: &HashMap<String, String>,
+    options: &PinMappingOptions,
+    names: &FeatureNames,
+    templates: &Templates,
+) -> Result<GeneratedArtifact, String> {
+    for name in overrides.keys() {
+        if sub_families.iter().all(|(sf, _)| sf != name) {
+            warn!(
+                "No MCU of sub-family {} was selected, ignoring its override",
+                name
+            );
+        }
+    }
+
+    let (definitions, seals) = match options.sealed_traits {
+        true => sealed_traits(af_tree, options, names)?,
+        false => Default::default(),
+    };
+    let mut out = definitions;
+    for (sub_family, mcus) in sub_families {
+        let mut module = String::new();
+        match overrides.get(sub_family) {
+            Some(code) => {
+                writeln!(out, "// {} (hand-maintained)", sub_family).unwrap();
+                module.push_str(code);
+            }
+            None => {
+                writeln!(out, "// {}", sub_family).unwrap();
+                let groups = af_tree
+                    .groups()
+                    .iter()
+                    .filter(|group| group.mcus().iter().any(|mcu| mcus.contains(mcu)));
+                for group in groups {
+                    if !module.is_empty() {
+                        writeln!(module).unwrap();
+                    }
+                    module.push_str(&render_pin_group(group, options, names, templates)?);
+                }
+            }
+        }
+        let module_name = sub_family
+            .to_lowercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+        writeln!(
+            out,
+            "#[cfg(feature = \"{}\")]",
+            names.sub_family_feature(sub_family)
+        )
+        .unwrap();
+        writeln!(out, "pub mod {} {{", module_name).unwrap();
+        for line in module.lines() {
+            if line.is_empty() {
+                writeln!(out).unwrap();
+            } else {
+                writeln!(out, "    {}", line).unwrap();
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    out.push_str(&seals);
+    Ok(GeneratedArtifact::new(
+        "pin_mappings.rs",
+        ArtifactKind::PinMappings,
+        out,
+    ))
+}
+
+/// Generate an integration test checking that the pin mappings compile: one
+/// test per GPIO version, gated like its mappings, which requires one pin of
+/// every peripheral stem to implement its IO trait (e.g. `PA2: TxPin<USART2>`).
+///
+/// `prelude` is the path imported by the test (e.g.
+/// "stm32l0xx_hal::prelude::*"), which must bring the pin types, IO traits and
+/// peripherals into scope. `pin_type` is the type of the pins, with `{pin}`
+/// replaced by the pin name (e.g. "PA2") and `{port}` by the lowercase port
+/// letter (e.g. "a"). Pins gated by package are never chosen.
+pub fn generate_pin_tests(
+    af_tree: &af_tree::AfTree,
+    options: &PinMappingOptions,
+    names: &FeatureNames,
+    prelude: &str,
+    pin_type: &str,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "//! Compile tests of the pin mappings: every GPIO version checks one pin\n\
+         //! mapping per peripheral stem.\n"
+    )
+    .unwrap();
+    writeln!(out, "use {};", prelude).unwrap();
+    for group in af_tree.groups() {
+        // The first pin signal of every stem
+        let mut stems: Vec<(&str, &af_tree::AfPin, &af_tree::AfSignal)> = vec![];
+        for pin in group.pins() {
+            let gated = options
+                .package_pins
+                .is_some_and(|p| p.cfg(group, &pin.name(), options, names).is_some());
+            if gated {
+                continue;
+            }
+            for signal in pin.signals() {
+                let mut stem = af_tree::device_stem(signal.device());
+                if options.merge_serial_stems {
+                    stem = stems::serial_stem(stem);
+                }
+                if !stems.iter().any(|(s, _, _)| *s == stem) {
+                    stems.push((stem, pin, signal));
+                }
+            }
+        }
+        if stems.is_empty() {
+            continue;
+        }
+        stems.sort_by(|a, b| compare_str(a.0, b.0));
+
+        writeln!(out).unwrap();
+        if options.mcu_docs {
+            writeln!(out, "// {}", mcu_doc(group)).unwrap();
+        }
+        writeln!(out, "#[cfg({})]", options.group_predicate(group, names)?).unwrap();
+        writeln!(out, "#[test]").unwrap();
+        writeln!(
+            out,
+            "fn {}() {{",
+            names
+                .io_feature(group.version())?
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        for (stem, pin, signal) in stems {
+            let check = stem.to_lowercase();
+            let port = pin.port().trim_start_matches('P').to_lowercase();
+            writeln!(
+                out,
+                "    fn {}<P: {}<{}>>() {{}}",
+                check,
+                signal.io(),
+                signal.device()
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "    {}::<{}>();",
+                check,
+                pin_type
+                    .replace("{pin}", &pin.name())
+                    .replace("{port}", &port)
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "tests/pin_mappings.rs",
+        ArtifactKind::PinTests,
+        out,
+    ))
+}
+
+/// Generate the pin mappings in the format of the stm32l0xx-hal `pins.rs`:
+/// one `pins!` block per GPIO version, gated by its IO feature, with the
+/// alternate functions of each pin (e.g. `PA2 => {AF4: TxPin<USART2>}`).
+///
+/// Unlike `generate_pin_mappings`, the format is fixed, so that regenerating
+/// the HAL needs no reformatting.
+pub fn generate_l0xx_pin_mappings(
+    af_tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in af_tree.groups() {
+        writeln!(
+            out,
+            "#[cfg(feature = \"{}\")]",
+            names.io_feature(group.version())?
+        )
+        .unwrap();
+        writeln!(out, "pins! {{").unwrap();
+        for pin in group.pins() {
+            let af = pin.signals();
+            match af.len() {
+                0 => {}
+                1 => writeln!(out, "    {} => {{{}}},", pin.name(), af[0]).unwrap(),
+                _ => {
+                    writeln!(out, "    {} => {{", pin.name()).unwrap();
+                    for a in af {
+                        writeln!(out, "        {},", a).unwrap();
+                    }
+                    writeln!(out, "    }},").unwrap();
+                }
+            }
+        }
+        writeln!(out, "}}\n\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "pin_mappings.rs",
+        ArtifactKind::PinMappings,
+        out,
+    ))
+}
+
+/// Return the attribute gating code by any of the features (e.g.
+/// `#[cfg(feature = "io-STM32F446")]`).
+fn feature_cfg(features: &[String]) -> String {
+    let predicates = features
+        .iter()
+        .map(|f| format!("feature = \"{}\"", f))
+        .collect::<Vec<_>>();
+    cfg_attribute(&predicates)
+}
+
+/// Return the attribute gating code by any of the `cfg` predicates (e.g.
+/// `#[cfg(any(stm32l031k6tx, stm32l071kbtx))]`).
+fn cfg_attribute(predicates: &[String]) -> String {
+    format!("#[cfg({})]", any_predicate(predicates))
+}
+
+/// Combine `cfg` predicates into a predicate selecting any of them.
+fn any_predicate(predicates: &[String]) -> String {
+    match predicates {
+        [predicate] => predicate.clone(),
+        _ => format!("any({})", predicates.join(", ")),
+    }
+}
+
+/// Return the documentation of the MCUs of a GPIO version (e.g. "MCUs with
+/// STM32L031_gpio_v1_0: STM32L031C6Tx, STM32L031K6Tx").
+fn mcu_doc(group: &af_tree::GpioGroup) -> String {
+    format!("MCUs with {}: {}", group.version(), group.mcus().join(", "))
+}
+
+/// Return the number of the alternate function of a pin signal (e.g. 7 for
+/// "AF7"), or an error for the STM32F1 remaps, which `style` cannot express.
+fn af_number(pin: &af_tree::AfPin, signal: &af_tree::AfSignal, style: Style) -> Result<u8, String> {
+    signal
+        .af()
+        .strip_prefix("AF")
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| {
+            format!(
+                "The {} style needs numbered alternate functions, {} {} has {}",
+                style.name(),
+                pin.name(),
+                signal,
+                signal.af()
+            )
+        })
+}
+
+/// Generate the pin trait implementations in the style of the code generated
+/// by the embassy-stm32 build script: one `pin_trait_impl!` call per pin
+/// signal (e.g. `pin_trait_impl!(crate::usart::TxPin, USART2, PA2, 4u8);`),
+/// in one module per GPIO version, gated by its IO feature. With `mcu_docs`,
+/// the modules document the MCUs of their GPIO version.
+pub fn generate_embassy_pin_traits(
+    af_tree: &af_tree::AfTree,
+    names: &FeatureNames,
+    mcu_docs: bool,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in af_tree.groups() {
+        let feature = names.io_feature(group.version())?;
+        if mcu_docs {
+            writeln!(out, "/// {}", mcu_doc(group)).unwrap();
+        }
+        writeln!(out, "#[cfg(feature = \"{}\")]", feature).unwrap();
+        writeln!(
+            out,
+            "mod {} {{",
+            feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(out, "    use super::*;\n").unwrap();
+        for pin in group.pins() {
+            for signal in pin.signals() {
+                writeln!(
+                    out,
+                    "    pin_trait_impl!(crate::{}::{}, {}, {}, {}u8);",
+                    embassy_module(signal.device()),
+                    signal.io(),
+                    signal.device(),
+                    pin.name(),
+                    af_number(pin, signal, Style::Embassy)?
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "pin_traits.rs",
+        ArtifactKind::PinMappings,
+        out,
+    ))
+}
+
+/// The definition of the entries of the `PIN_FUNCTIONS` tables.
+const PIN_FUNCTION_STRUCT: &str = "\
+/// A signal of a pin and the alternate function selecting it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinFunction {
+    /// The pin (e.g. \"PA2\").
+    pub pin: &'static str,
+    /// The port (e.g. \"PA\").
+    pub port: &'static str,
+    /// The alternate function (e.g. \"AF4\"), or the AFIO remap on the STM32F1.
+    pub af: &'static str,
+    /// The signal (e.g. \"USART2_TX\").
+    pub signal: &'static str,
+    /// The peripheral instance (e.g. \"USART2\").
+    pub device: &'static str,
+}
+";
+
+/// Generate a `PIN_FUNCTIONS` table per GPIO version, gated by its IO
+/// feature, listing all signals of the pins with their alternate function,
+/// for runtime lookup instead of traits. Unlike the pin mappings, the table
+/// includes the signals without an IO trait (e.g. "TIM2_CH1"). With
+/// `mcu_docs`, the tables document the MCUs of their GPIO version.
+pub fn generate_pin_table(
+    af_tree: &af_tree::AfTree,
+    names: &FeatureNames,
+    mcu_docs: bool,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::from(PIN_FUNCTION_STRUCT);
+    for group in af_tree.groups() {
+        writeln!(out).unwrap();
+        if mcu_docs {
+            writeln!(out, "/// {}", mcu_doc(group)).unwrap();
+        }
+        writeln!(
+            out,
+            "#[cfg(feature = \"{}\")]",
+            names.io_feature(group.version())?
+        )
+        .unwrap();
+        writeln!(out, "pub static PIN_FUNCTIONS: &[PinFunction] = &[").unwrap();
+        for pin in group.pins() {
+            for signal in pin.raw_signals() {
+                writeln!(
+                    out,
+                    "    PinFunction {{ pin: \"{}\", port: \"{}\", af: \"{}\", signal: \"{}\", device: \"{}\" }},",
+                    pin.name(),
+                    pin.port(),
+                    signal.af(),
+                    signal.name(),
+                    signal.device()
+                )
+                .unwrap();
+            }
+        }
+        writeln!(out, "];").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "pin_functions.rs",
+        ArtifactKind::PinMappings,
+        out,
+    ))
+}
+
+/// Return the embassy-stm32 driver module of a peripheral instance (e.g.
+/// "usart" for "LPUART1").
+fn embassy_module(device: &str) -> String {
+    stems::serial_stem(af_tree::device_stem(device)).to_lowercase()
+}
+
+/// The IO features of the GPIO versions providing each pin with an AF number
+/// (e.g. `("PA2", 7)`).
+type PinFeatures = HashMap<(String, u8), Vec<String>>;
+
+/// Generate the pin mappings in the style of the stm32f4xx-hal `gpio::alt`
+/// modules: one module per peripheral instance, with a `pin!` block listing
+/// the pins of each IO signal and their AF number (e.g. `<Tx> for [PA2<7>]`).
+/// Pins that are missing from some GPIO versions are gated by the IO features
+/// of the versions providing them. With `mcu_docs`, the modules document the
+/// GPIO versions with their peripheral instance.
+pub fn generate_f4xx_pin_mappings(
+    af_tree: &af_tree::AfTree,
+    names: &FeatureNames,
+    mcu_docs: bool,
+) -> Result<GeneratedArtifact, String> {
+    // The pins by device and IO signal (e.g. "Tx")
+    let mut devices: HashMap<&str, HashMap<&str, PinFeatures>> = HashMap::new();
+    // The GPIO versions of each device
+    let mut versions: HashMap<&str, Vec<&str>> = HashMap::new();
+    for group in af_tree.groups() {
+        let feature = names.io_feature(group.version())?;
+        for pin in group.pins() {
+            for signal in pin.signals() {
+                let device_versions = versions.entry(signal.device()).or_default();
+                if !device_versions.contains(&group.version()) {
+                    device_versions.push(group.version());
+                }
+                let af = af_number(pin, signal, Style::F4xx)?;
+                let io = signal.io().strip_suffix("Pin").unwrap_or(signal.io());
+                devices
+                    .entry(signal.device())
+                    .or_default()
+                    .entry(io)
+                    .or_default()
+                    .entry((pin.name(), af))
+                    .or_default()
+                    .push(feature.clone());
+            }
+        }
+    }
+    let group_count = af_tree.groups().len();
+    let mut devices = devices.into_iter().collect::<Vec<_>>();
+    devices.sort_by(|a, b| compare_str(a.0, b.0));
+
+    let mut out = String::new();
+    for (device, ios) in devices {
+        if mcu_docs {
+            writeln!(
+                out,
+                "/// {} pins of the MCUs with {}",
+                device,
+                versions[device].join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "pub mod {} {{", device.to_lowercase()).unwrap();
+        writeln!(out, "    use super::*;\n").unwrap();
+        writeln!(out, "    pin! {{").unwrap();
+        let mut ios = ios.into_iter().collect::<Vec<_>>();
+        ios.sort_by(|a, b| compare_str(a.0, b.0));
+        for (io, pins) in ios {
+            writeln!(out, "        <{}> for [", io).unwrap();
+            let mut pins = pins.into_iter().collect::<Vec<_>>();
+            pins.sort_by(|a, b| compare_str(&(a.0).0, &(b.0).0).then((a.0).1.cmp(&(b.0).1)));
+            for ((pin, af), mut features) in pins {
+                features.dedup();
+                if features.len() < group_count {
+                    writeln!(out, "            {}", feature_cfg(&features)).unwrap();
+                }
+                writeln!(out, "            {}<{}>,", pin, af).unwrap();
+            }
+            writeln!(out, "        ],").unwrap();
+        }
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "pin_mappings.rs",
+        ArtifactKind::PinMappings,
+        out,
+    ))
+}
+
+/// The data of the `templates::PIN_GROUP` template: the pin mappings of one
+/// GPIO version.
+#[derive(Debug, Serialize)]
+pub struct PinGroupContext<'a> {
+    /// The GPIO IP version (e.g. "STM32L051_gpio_v1_0").
+    pub version: &'a str,
+    /// The feature of the GPIO version (e.g. "io-STM32L051").
+    pub feature: String,
+    /// The `cfg` predicate selecting the MCUs using the GPIO version (e.g.
+    /// `feature = "io-STM32L051"`, see `CfgStyle`).
+    pub cfg: String,
+    /// The ref names of the MCUs using the GPIO version.
+    pub mcus: &'a [String],
+    /// Whether to list the MCUs in a comment.
+    pub mcu_docs: bool,
+    /// The IO signals provided by multiple pins, if requested.
+    pub alternatives: Vec<AlternativeContext>,
+    /// The pins with at least one alternate function.
+    pub pins: Vec<PinContext<'a>>,
+}
+
+/// An IO signal provided by multiple pins (e.g. "TxPin<USART2>").
+#[derive(Debug, Clone, Serialize)]
+pub struct AlternativeContext {
+    pub signal: String,
+    pub pins: Vec<AlternativePinContext>,
+}
+
+/// A pin providing an IO signal, with its alternate function.
+#[derive(Debug, Clone, Serialize)]
+pub struct AlternativePinContext {
+    pub pin: String,
+    pub af: String,
+}
+
+/// A pin with its alternate functions.
+#[derive(Debug, Serialize)]
+pub struct PinContext<'a> {
+    /// The pin name (e.g. "PA2").
+    pub name: String,
+    /// The debug function of the pin after reset (e.g. "SWDIO"), if the
+    /// debug pins are documented.
+    pub debug: Option<&'static str>,
+    /// The attribute gating the pin (e.g. `#[cfg(feature =
+    /// "package-lqfp48")]`), if it is gated by package.
+    pub cfg: Option<String>,
+    /// The signals of the pin that other pins provide too, with these other
+    /// pins, if requested.
+    pub alternatives: Vec<AlternativeContext>,
+    pub signals: Vec<SignalContext<'a>>,
+}
+
+/// An alternate function of a pin (e.g. `AF4: TxPin<USART2>`).
+#[derive(Debug, Serialize)]
+pub struct SignalContext<'a> {
+    pub af: &'a str,
+    pub io: &'a str,
+    pub device: &'a str,
+    /// The signal name (e.g. "USART2_TX").
+    pub signal: Option<&'a str>,
+    /// The C HAL define name (e.g. "GPIO_AF4_USART2"), if the defines are
+    /// annotated.
+    pub define: Option<&'a str>,
+}
+
+/// Return the alternatives of the signals of a pin, without the pin itself.
+fn pin_alternatives(alternatives: &[AlternativeContext], pin: &str) -> Vec<AlternativeContext> {
+    alternatives
+        .iter()
+        .filter(|alternative| alternative.pins.iter().any(|p| p.pin == pin))
+        .map(|alternative| AlternativeContext {
+            signal: alternative.signal.clone(),
+            pins: alternative
+                .pins
+                .iter()
+                .filter(|p| p.pin != pin)
+                .cloned()
+                .collect(),
+        })
+        .collect()
+}
+
+/// Render the pin mappings of a GPIO group with the `templates::PIN_GROUP`
+/// template.
+fn render_pin_group(
+    group: &af_tree::GpioGroup,
+    options: &PinMappingOptions,
+    names: &FeatureNames,
+    templates: &Templates,
+) -> Result<String, String> {
+    let alternatives = match options.alternatives {
+        true => group
+            .alternatives()
+            .into_iter()
+            .map(|(signal, pins)| AlternativeContext {
+                signal,
+                pins: pins
+                    .into_iter()
+                    .map(|(pin, af)| AlternativePinContext {
+                        pin,
+                        af: af.to_string(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        false => vec![],
+    };
+    let pins = group
+        .pins()
+        .iter()
+        .filter(|pin| !pin.signals().is_empty())
+        .map(|pin| PinContext {
+            name: pin.name(),
+            alternatives: pin_alternatives(&alternatives, &pin.name()),
+            debug: pin.debug().filter(|_| options.debug_docs).map(|f| f.name()),
+            cfg: options
+                .package_pins
+                .and_then(|p| p.cfg(group, &pin.name(), options, names)),
+            signals: pin
+                .signals()
+                .iter()
+                .map(|s| SignalContext {
+                    af: s.af(),
+                    io: s.io(),
+                    device: s.device(),
+                    signal: s.signal(),
+                    define: s.define().filter(|_| options.af_defines),
+                })
+                .collect(),
+        })
+        .collect();
+    let context = PinGroupContext {
+        version: group.version(),
+        feature: names.io_feature(group.version())?,
+        cfg: options.group_predicate(group, names)?,
+        mcus: group.mcus(),
+        mcu_docs: options.mcu_docs,
+        alternatives,
+        pins,
+    };
+    templates.render(templates::PIN_GROUP, &context)
+}
+
+/// Generate the candidate pin sets for an external memory.
+///
+/// GPIO groups that cannot provide all signals required by the memory are only
+/// listed in a comment.
+pub fn generate_memory_pins(
+    af_tree: &af_tree::AfTree,
+    memory: memory::MemoryType,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in af_tree.groups() {
+        let gpio_version_feature = names.io_feature(group.version())?;
+        let signals = memory::candidate_pins(group, memory);
+        let missing = signals
+            .iter()
+            .filter(|s| s.candidates.is_empty())
+            .map(|s| s.name())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            writeln!(
+                out,
+                "// {}: incomplete, missing {}\n",
+                gpio_version_feature,
+                missing.join(", ")
+            )
+            .unwrap();
+            continue;
+        }
+
+        writeln!(out, "// MCUs: {}", group.mcus().join(", ")).unwrap();
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(out, "memory_pins! {{").unwrap();
+        for signal in signals {
+            let mut names = signal
+                .candidates
+                .iter()
+                .map(|c| c.signal.as_str())
+                .collect::<Vec<_>>();
+            names.sort_by(|a, b| compare_str(a, b));
+            names.dedup();
+            for name in names {
+                let candidates = signal
+                    .candidates
+                    .iter()
+                    .filter(|c| c.signal == name)
+                    .map(|c| format!("{}: {}", c.pin, c.af))
+                    .collect::<Vec<_>>();
+                if candidates.len() == 1 {
+                    writeln!(out, "    {} => {{{}}},", name, candidates[0]).unwrap();
+                } else {
+                    writeln!(out, "    {} => {{", name).unwrap();
+                    for c in candidates {
+                        writeln!(out, "        {},", c).unwrap();
+                    }
+                    writeln!(out, "    }},").unwrap();
+                }
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "memory_pins.rs",
+        ArtifactKind::MemoryPins,
+        out,
+    ))
+}
+
+/// Generate the ADC channels of the pins of every GPIO version, gated by the
+/// GPIO version feature like the pin mappings.
+///
+/// The ADC inputs are read from the analog signals of the tree. Only the
+/// positive (or single-ended) inputs are listed, GPIO versions without any
+/// are left out.
+pub fn generate_adc_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let inputs = analog::adc_inputs(group)
+            .into_iter()
+            .filter(|i| !i.negative)
+            .collect::<Vec<_>>();
+        if inputs.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(out, "adc_pins! {{").unwrap();
+        for pin in inputs.chunk_by(|a, b| a.pin == b.pin) {
+            let channels = pin
+                .iter()
+                .map(|i| format!("{}: {}", i.adc, i.channel))
+                .collect::<Vec<_>>();
+            writeln!(out, "    {} => {{{}}},", pin[0].pin, channels.join(", ")).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "adc_pins.rs",
+        ArtifactKind::AdcPins,
+        out,
+    ))
+}
+
+/// Generate the DAC channels of the pins of every GPIO version, gated by the
+/// GPIO version feature like the pin mappings.
+pub fn generate_dac_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let outputs = analog::dac_outputs(group);
+        if outputs.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(out, "dac_pins! {{").unwrap();
+        for pin in outputs.chunk_by(|a, b| a.pin == b.pin) {
+            let channels = pin
+                .iter()
+                .map(|o| format!("{}: {}", o.dac, o.channel))
+                .collect::<Vec<_>>();
+            writeln!(out, "    {} => {{{}}},", pin[0].pin, channels.join(", ")).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "dac_pins.rs",
+        ArtifactKind::DacPins,
+        out,
+    ))
+}
+
+/// Generate the functions of a comparator or op-amp peripheral (e.g. "COMP")
+/// on the pins of every GPIO version, gated by the GPIO version feature like
+/// the pin mappings.
+pub fn generate_analog_function_pins(
+    tree: &af_tree::AfTree,
+    peripheral: &str,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let name = format!("{}_pins", peripheral.to_lowercase());
+    let mut out = String::new();
+    for group in tree.groups() {
+        let functions = analog::analog_functions(group, peripheral);
+        if functions.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(out, "{}! {{", name).unwrap();
+        for pin in functions.chunk_by(|a, b| a.pin == b.pin) {
+            let functions = pin
+                .iter()
+                .map(|f| format!("{}: {}", f.instance, f.function))
+                .collect::<Vec<_>>();
+            writeln!(out, "    {} => {{{}}},", pin[0].pin, functions.join(", ")).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        format!("{}.rs", name),
+        ArtifactKind::AnalogFunctionPins,
+        out,
+    ))
+}
+
+/// Generate the I2S pins of every GPIO version (e.g. `AF5: WsPin<I2S2>`),
+/// gated by the GPIO version feature like the pin mappings. The data pins of
+/// the full-duplex extension instances (e.g. "I2S2ext_SD") are listed as
+/// `ExtSdPin` of the instance they extend.
+pub fn generate_i2s_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let pins = i2s::i2s_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        if let Some(p) = pins.iter().find(|p| !p.af.starts_with("AF")) {
+            return Err(format!(
+                "The I2S pins need numbered alternate functions, {} {} has {}",
+                p.pin, p.signal.instance, p.af
+            ));
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(out, "i2s_pins! {{").unwrap();
+        for pin in pins.chunk_by(|a, b| a.pin == b.pin) {
+            let signals = pin
+                .iter()
+                .map(|p| format!("{}: {}<{}>", p.af, p.signal.io_trait(), p.signal.instance))
+                .collect::<Vec<_>>();
+            writeln!(out, "    {} => {{{}}},", pin[0].pin, signals.join(", ")).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "i2s_pins.rs",
+        ArtifactKind::I2sPins,
+        out,
+    ))
+}
+
+/// Generate the USB pins of every GPIO version (e.g. `AF10: DpPin<USB_OTG_FS>`)
+/// in a `usb_pins!` block, and the pins of the ULPI bus of the high speed
+/// instances (e.g. `AF10: UlpiD0Pin<USB_OTG_HS>`) in a separate `ulpi_pins!`
+/// block, gated by the GPIO version feature like the pin mappings.
+pub fn generate_usb_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        for (ulpi, block) in &[(false, "usb_pins"), (true, "ulpi_pins")] {
+            let pins = usb::usb_pins(group, *ulpi);
+            if pins.is_empty() {
+                continue;
+            }
+            if let Some(p) = pins.iter().find(|p| !p.af.starts_with("AF")) {
+                return Err(format!(
+                    "The USB pins need numbered alternate functions, {} {} has {}",
+                    p.pin, p.signal.instance, p.af
+                ));
+            }
+            let gpio_version_feature = names.io_feature(group.version())?;
+            writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+            writeln!(out, "{}! {{", block).unwrap();
+            for pin in pins.chunk_by(|a, b| a.pin == b.pin) {
+                let signals = pin
+                    .iter()
+                    .map(|p| format!("{}: {}<{}>", p.af, p.signal.io_trait(), p.signal.instance))
+                    .collect::<Vec<_>>();
+                writeln!(out, "    {} => {{{}}},", pin[0].pin, signals.join(", ")).unwrap();
+            }
+            writeln!(out, "}}\n").unwrap();
+        }
+    }
+    Ok(GeneratedArtifact::new(
+        "usb_pins.rs",
+        ArtifactKind::UsbPins,
+        out,
+    ))
+}
+
+/// Generate the touch sensing pins of every GPIO version in a `tsc_pins!`
+/// block, gated by the GPIO version feature like the pin mappings. The IOs are
+/// listed with their group and IO number (e.g. `PA1 => {AF3: (1, 2)}` for
+/// "TSC_G1_IO2"), the synchronization input as `SYNC`.
+pub fn generate_tsc_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let pins = tsc::tsc_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        if let Some(p) = pins.iter().find(|p| !p.af.starts_with("AF")) {
+            return Err(format!(
+                "The TSC pins need numbered alternate functions, {} has {}",
+                p.pin, p.af
+            ));
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(out, "tsc_pins! {{").unwrap();
+        for pin in pins.chunk_by(|a, b| a.pin == b.pin) {
+            let signals = pin
+                .iter()
+                .map(|p| match p.signal {
+                    tsc::TscSignal::Io { group, io } => format!("{}: ({}, {})", p.af, group, io),
+                    tsc::TscSignal::Sync => format!("{}: SYNC", p.af),
+                })
+                .collect::<Vec<_>>();
+            writeln!(out, "    {} => {{{}}},", pin[0].pin, signals.join(", ")).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "tsc_pins.rs",
+        ArtifactKind::TscPins,
+        out,
+    ))
+}
+
+/// Generate the PWM channels of every GPIO version in a module gated by the
+/// GPIO version feature like the pin mappings, with one impl per timer channel
+/// of a pin (e.g. `impl PwmChannel<TIM3, C2> for PB5<Alternate<AF2>> {}`).
+/// The complementary outputs implement `PwmComplementaryChannel` instead.
+pub fn generate_pwm_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let pins = timer::timer_pins(group)
+            .into_iter()
+            .filter(|p| p.signal.channel().is_some())
+            .collect::<Vec<_>>();
+        if pins.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(
+            out,
+            "mod {} {{",
+            gpio_version_feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(out, "    use super::*;\n").unwrap();
+        for pin in pins {
+            if !pin.af.starts_with("AF") {
+                return Err(format!(
+                    "The PWM pins need numbered alternate functions, {} {} has {}",
+                    pin.pin, pin.signal.device, pin.af
+                ));
+            }
+            let io = match pin.signal.is_complementary() {
+                true => "PwmComplementaryChannel",
+                false => "PwmChannel",
+            };
+            writeln!(
+                out,
+                "    impl {}<{}, C{}> for {}<Alternate<{}>> {{}}",
+                io,
+                pin.signal.device,
+                pin.signal.channel().unwrap(),
+                pin.pin,
+                pin.af
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "pwm_pins.rs",
+        ArtifactKind::PwmPins,
+        out,
+    ))
+}
+
+/// Generate the I2C pins of every GPIO version in a module gated by the GPIO
+/// version feature like the pin mappings, with one open-drain impl per signal
+/// of a pin (e.g. `impl SdaPin<I2C1> for PB7<Alternate<AF4, OpenDrain>> {}`),
+/// including the SMBus alert pins (`SmbaPin`).
+pub fn generate_i2c_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let pins = i2c::i2c_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(
+            out,
+            "/// The I2C pins are open-drain outputs, SCL and SDA (and SMBA) need\n\
+             /// pull-up resistors, either external or the internal pull-ups."
+        )
+        .unwrap();
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(
+            out,
+            "mod {} {{",
+            gpio_version_feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(out, "    use super::*;\n").unwrap();
+        for pin in pins {
+            if !pin.af.starts_with("AF") {
+                return Err(format!(
+                    "The I2C pins need numbered alternate functions, {} {} has {}",
+                    pin.pin, pin.signal.device, pin.af
+                ));
+            }
+            writeln!(
+                out,
+                "    impl {}<{}> for {}<Alternate<{}, OpenDrain>> {{}}",
+                pin.signal.io_trait(),
+                pin.signal.device,
+                pin.pin,
+                pin.af
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "i2c_pins.rs",
+        ArtifactKind::I2cPins,
+        out,
+    ))
+}
+
+/// Write a generic `Pins<SPI>` impl for a tuple of pins, each given by its type
+/// parameter and IO trait (e.g. `("SCK", "SckPin")`).
+fn write_spi_pins_impl(out: &mut String, pins: &[(&str, &str)]) {
+    let params = pins.iter().map(|(p, _)| *p).collect::<Vec<_>>();
+    writeln!(
+        out,
+        "impl<SPI, {}> Pins<SPI> for ({})\nwhere",
+        params.join(", "),
+        params.join(", ")
+    )
+    .unwrap();
+    for (param, io) in pins {
+        writeln!(out, "    {}: {}<SPI>,", param, io).unwrap();
+    }
+    writeln!(out, "{{\n}}\n").unwrap();
+}
+
+/// Generate the SPI pins of every GPIO version in a module gated by the GPIO
+/// version feature like the pin mappings, with one impl per signal of a pin
+/// (e.g. `impl MosiPin<SPI1> for PA7<Alternate<AF5>> {}`).
+///
+/// In front of the modules, generic `Pins<SPI>` impls accept the pins of a
+/// 4-wire bus as `(SCK, MISO, MOSI)`, or `(SCK, MISO, MOSI, NSS)` with the
+/// hardware slave select. A bus without MISO pin uses the `no_miso`
+/// placeholder type (e.g. "NoMiso"), which implements `MisoPin` for every
+/// instance, or `(SCK, MOSI)` without placeholder type.
+pub fn generate_spi_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+    no_miso: Option<&str>,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "// 4-wire buses, with software or hardware slave select"
+    )
+    .unwrap();
+    write_spi_pins_impl(
+        &mut out,
+        &[("SCK", "SckPin"), ("MISO", "MisoPin"), ("MOSI", "MosiPin")],
+    );
+    write_spi_pins_impl(
+        &mut out,
+        &[
+            ("SCK", "SckPin"),
+            ("MISO", "MisoPin"),
+            ("MOSI", "MosiPin"),
+            ("NSS", "NssPin"),
+        ],
+    );
+    writeln!(out, "// 3-wire buses without MISO pin").unwrap();
+    match no_miso {
+        Some(placeholder) => {
+            writeln!(out, "impl<SPI> MisoPin<SPI> for {} {{}}\n", placeholder).unwrap()
+        }
+        None => write_spi_pins_impl(&mut out, &[("SCK", "SckPin"), ("MOSI", "MosiPin")]),
+    }
+
+    for group in tree.groups() {
+        let pins = spi::spi_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(
+            out,
+            "mod {} {{",
+            gpio_version_feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(out, "    use super::*;\n").unwrap();
+        for pin in pins {
+            if !pin.af.starts_with("AF") {
+                return Err(format!(
+                    "The SPI pins need numbered alternate functions, {} {} has {}",
+                    pin.pin, pin.signal.device, pin.af
+                ));
+            }
+            writeln!(
+                out,
+                "    impl {}<{}> for {}<Alternate<{}>> {{}}",
+                pin.signal.io_trait(),
+                pin.signal.device,
+                pin.pin,
+                pin.af
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "spi_pins.rs",
+        ArtifactKind::SpiPins,
+        out,
+    ))
+}
+
+/// Generate the CAN pins of every GPIO version in a module gated by the GPIO
+/// version feature like the pin mappings, with one impl per signal of a pin
+/// (e.g. `impl RxPin<CAN1> for PA11<Alternate<AF9>> {}`). The instance stems
+/// are renamed by `aliases` (e.g. `FDCAN = "CAN"`), see
+/// `can::CanSignal::aliased_device`.
+pub fn generate_can_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+    aliases: &HashMap<String, String>,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let pins = can::can_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(
+            out,
+            "mod {} {{",
+            gpio_version_feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(out, "    use super::*;\n").unwrap();
+        let mut impls: Vec<String> = vec![];
+        for pin in pins {
+            if !pin.af.starts_with("AF") {
+                return Err(format!(
+                    "The CAN pins need numbered alternate functions, {} {} has {}",
+                    pin.pin, pin.signal.device, pin.af
+                ));
+            }
+            let line = format!(
+                "    impl {}<{}> for {}<Alternate<{}>> {{}}",
+                pin.signal.io_trait(),
+                pin.signal.aliased_device(aliases),
+                pin.pin,
+                pin.af
+            );
+            // Aliases can map two instances to the same name
+            if !impls.contains(&line) {
+                impls.push(line);
+            }
+        }
+        for line in impls {
+            writeln!(out, "{}", line).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "can_pins.rs",
+        ArtifactKind::CanPins,
+        out,
+    ))
+}
+
+/// Write the struct of a QUADSPI or OCTOSPI bus with a constructor requiring
+/// every pin to implement its IO trait, gated by the `features` of the GPIO
+/// versions providing all of them.
+fn write_qspi_bus(out: &mut String, bus: &qspi::QspiBus, features: &[String]) {
+    let params = bus
+        .pins
+        .iter()
+        .map(|(field, _)| field.to_uppercase())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let cfg = feature_cfg(features);
+    writeln!(
+        out,
+        "/// The pins of a quad SPI memory on {} {} {}.",
+        bus.device,
+        match bus.device.as_str() {
+            "OCTOSPIM" => "port",
+            _ => "bank",
+        },
+        bus.bank
+    )
+    .unwrap();
+    writeln!(out, "{}", cfg).unwrap();
+    writeln!(out, "pub struct {}<{}> {{", bus.name, params).unwrap();
+    for (field, _) in &bus.pins {
+        writeln!(out, "    {}: {},", field, field.to_uppercase()).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "{}", cfg).unwrap();
+    writeln!(out, "impl<{}> {}<{}>\nwhere", params, bus.name, params).unwrap();
+    for (field, io) in &bus.pins {
+        writeln!(out, "    {}: {}<{}>,", field.to_uppercase(), io, bus.device).unwrap();
+    }
+    writeln!(out, "{{").unwrap();
+    let args = bus
+        .pins
+        .iter()
+        .map(|(field, _)| format!("{}: {}", field, field.to_uppercase()))
+        .collect::<Vec<_>>();
+    let fields = bus
+        .pins
+        .iter()
+        .map(|(field, _)| field.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(out, "    pub fn new({}) -> Self {{", args.join(", ")).unwrap();
+    writeln!(out, "        Self {{ {} }}", fields).unwrap();
+    writeln!(out, "    }}\n").unwrap();
+    writeln!(out, "    pub fn release(self) -> ({}) {{", params).unwrap();
+    let released = bus
+        .pins
+        .iter()
+        .map(|(field, _)| format!("self.{}", field))
+        .collect::<Vec<_>>();
+    writeln!(out, "        ({})", released.join(", ")).unwrap();
+    writeln!(out, "    }}\n}}\n").unwrap();
+}
+
+/// Generate the QUADSPI and OCTOSPI pins of every GPIO version in a module
+/// gated by the GPIO version feature like the pin mappings, with one impl per
+/// signal of a pin (e.g. `impl Bk1Io0Pin<QUADSPI> for PD11<Alternate<AF9>>
+/// {}`).
+///
+/// In front of the modules, a struct per QUADSPI bank and OCTOSPI port (e.g.
+/// `QspiBank1Pins`) bundles the clock, chip select and four IOs of a memory.
+/// It is only defined for the GPIO versions providing all of them, and its
+/// constructor requires every pin to implement its IO trait.
+pub fn generate_qspi_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut buses: Vec<(qspi::QspiBus, Vec<String>)> = vec![];
+    let mut modules = String::new();
+    for group in tree.groups() {
+        let pins = qspi::qspi_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        for bus in qspi::qspi_buses(&pins) {
+            match buses.iter_mut().find(|(b, _)| *b == bus) {
+                Some((_, features)) => features.push(gpio_version_feature.clone()),
+                None => buses.push((bus, vec![gpio_version_feature.clone()])),
+            }
+        }
+
+        writeln!(modules, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(
+            modules,
+            "mod {} {{",
+            gpio_version_feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(modules, "    use super::*;\n").unwrap();
+        for pin in pins {
+            if !pin.af.starts_with("AF") {
+                return Err(format!(
+                    "The QUADSPI and OCTOSPI pins need numbered alternate functions, {} {} has {}",
+                    pin.pin, pin.signal.device, pin.af
+                ));
+            }
+            writeln!(
+                modules,
+                "    impl {}<{}> for {}<Alternate<{}>> {{}}",
+                pin.signal.io_trait(),
+                pin.signal.device,
+                pin.pin,
+                pin.af
+            )
+            .unwrap();
+        }
+        writeln!(modules, "}}\n").unwrap();
+    }
+
+    let mut out = String::new();
+    buses.sort_by(|a, b| compare_str(&a.0.name, &b.0.name));
+    for (bus, features) in &buses {
+        write_qspi_bus(&mut out, bus, features);
+    }
+    out.push_str(&modules);
+    Ok(GeneratedArtifact::new(
+        "qspi_pins.rs",
+        ArtifactKind::QspiPins,
+        out,
+    ))
+}
+
+/// Generate the FMC pins of every GPIO version in a module gated by the GPIO
+/// version feature like the pin mappings, with one impl of the trait named
+/// after each function of a pin (e.g. `impl A0 for PF0<Alternate<AF12>> {}`).
+///
+/// After the pins, generic impls accept the bundles of `fmc::fmc_bundles` as
+/// tuples (e.g. `impl<P0, ..., P11> AddressPins for (P0, ..., P11)`), if the
+/// GPIO version provides all of their pins.
+pub fn generate_fmc_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let pins = fmc::fmc_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(
+            out,
+            "mod {} {{",
+            gpio_version_feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(out, "    use super::*;\n").unwrap();
+        let mut functions = HashSet::new();
+        for pin in pins {
+            if !pin.af.starts_with("AF") {
+                return Err(format!(
+                    "The FMC pins need numbered alternate functions, {} {} has {}",
+                    pin.pin, pin.signal.device, pin.af
+                ));
+            }
+            writeln!(
+                out,
+                "    impl {} for {}<Alternate<{}>> {{}}",
+                pin.signal.function, pin.pin, pin.af
+            )
+            .unwrap();
+            functions.insert(pin.signal.function);
+        }
+        for bundle in fmc::fmc_bundles() {
+            if bundle.functions.iter().all(|f| functions.contains(f)) {
+                write_fmc_bundle(&mut out, &bundle);
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "fmc_pins.rs",
+        ArtifactKind::FmcPins,
+        out,
+    ))
+}
+
+/// Write the generic impl accepting an FMC bundle as a tuple of pins, in a
+/// GPIO version module.
+fn write_fmc_bundle(out: &mut String, bundle: &fmc::FmcBundle) {
+    let params = (0..bundle.functions.len())
+        .map(|n| format!("P{}", n))
+        .collect::<Vec<_>>();
+    // A tuple of one element needs a trailing comma
+    let tuple = match params.len() {
+        1 => format!("({},)", params[0]),
+        _ => format!("({})", params.join(", ")),
+    };
+    writeln!(
+        out,
+        "\n    impl<{}> {} for {}\n    where",
+        params.join(", "),
+        bundle.name,
+        tuple
+    )
+    .unwrap();
+    for (param, function) in params.iter().zip(&bundle.functions) {
+        writeln!(out, "        {}: {},", param, function).unwrap();
+    }
+    writeln!(out, "    {{\n    }}").unwrap();
+}
+
+/// Write a `Pins` impl of a peripheral instance for a tuple of pins, each given
+/// by its IO trait (e.g. "TxPin" for the type parameter `TX`), in a GPIO
+/// version module.
+fn write_peripheral_pins_impl(out: &mut String, device: &str, traits: &[&str]) {
+    let params = traits
+        .iter()
+        .map(|io| io.trim_end_matches("Pin").to_uppercase())
+        .collect::<Vec<_>>();
+    // A single pin still needs a tuple, `(SCL,)`, not a parenthesized type.
+    let tuple = match params.as_slice() {
+        [param] => format!("({},)", param),
+        _ => format!("({})", params.join(", ")),
+    };
+    writeln!(
+        out,
+        "\n    impl<{}> Pins<{}> for {}\n    where",
+        params.join(", "),
+        device,
+        tuple
+    )
+    .unwrap();
+    for (param, io) in params.iter().zip(traits) {
+        writeln!(out, "        {}: {}<{}>,", param, io, device).unwrap();
+    }
+    writeln!(out, "    {{\n    }}").unwrap();
+}
+
+/// Generate the `Pins` tuple impls of the peripheral instances of every GPIO
+/// version in a module gated by the GPIO version feature like the pin
+/// mappings (e.g. `impl<TX, RX> Pins<USART2> for (TX, RX)`).
+///
+/// Every instance providing all its required pins (see
+/// `peripheral_pins::peripheral_pins`) accepts the tuple of its required pins
+/// and, if it has optional pins, the tuple of all its pins. The RTS and driver
+/// enable pins are alternatives, so such an instance gets a tuple with each
+/// (see `PeripheralPins::optional_sets`). The HAL can implement the optional IO
+/// traits for a placeholder type to leave out some of them. The instances
+/// lacking a required pin are only listed in a comment.
+pub fn generate_peripheral_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let peripherals = peripheral_pins::peripheral_pins(group);
+        if peripherals.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(
+            out,
+            "mod {} {{",
+            gpio_version_feature
+                .to_lowercase()
+                .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        )
+        .unwrap();
+        writeln!(out, "    use super::*;").unwrap();
+        for peripheral in peripherals {
+            if !peripheral.is_complete() {
+                writeln!(
+                    out,
+                    "\n    // {}: incomplete, missing {}",
+                    peripheral.device,
+                    peripheral.missing.join(", ")
+                )
+                .unwrap();
+                continue;
+            }
+            write_peripheral_pins_impl(&mut out, &peripheral.device, &peripheral.required);
+            for optional in peripheral.optional_sets() {
+                let all = peripheral
+                    .required
+                    .iter()
+                    .chain(&optional)
+                    .copied()
+                    .collect::<Vec<_>>();
+                write_peripheral_pins_impl(&mut out, &peripheral.device, &all);
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "peripheral_pins.rs",
+        ArtifactKind::PeripheralPins,
+        out,
+    ))
+}
+
+/// Generate the AFIO remaps of every GPIO version of the STM32F1, gated by the
+/// GPIO version feature like the pin mappings. Every remap of a peripheral is
+/// listed with the pins of its signals (e.g.
+/// `USART1: Full => {PB6: USART1_TX, PB7: USART1_RX}`).
+pub fn generate_remap_pins(
+    tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in tree.groups() {
+        let pins = remap::remap_pins(group);
+        if pins.is_empty() {
+            continue;
+        }
+        let gpio_version_feature = names.io_feature(group.version())?;
+        writeln!(out, "#[cfg(feature = \"{}\")]", gpio_version_feature).unwrap();
+        writeln!(out, "afio_remaps! {{").unwrap();
+        for remap in pins.chunk_by(|a, b| a.remap == b.remap) {
+            let signals = remap
+                .iter()
+                .map(|p| format!("{}: {}", p.pin, p.signal))
+                .collect::<Vec<_>>();
+            writeln!(
+                out,
+                "    {}: {} => {{{}}},",
+                remap[0].remap.peripheral,
+                remap[0].remap.mode,
+                signals.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "afio_remaps.rs",
+        ArtifactKind::RemapPins,
+        out,
+    ))
+}
+
+/// Return the `cfg` predicates selecting the MCUs (e.g.
+/// `feature = "mcu-STM32L071KBTx"`).
+///
+/// If `names.dies` is set, the MCUs are selected by the feature of their die
+/// in `mcu_die_map` (e.g. `feature = "die-447"`) instead, provided that all
+/// MCUs with that die are listed.
+fn cfg_features(
+    mcus: &[String],
+    mcu_die_map: &HashMap<String, String>,
+    names: &FeatureNames,
+) -> Vec<String> {
+    let die_of = |mcu: &String| mcu_die_map.get(mcu).filter(|_| names.dies);
+    let mut features = vec![];
+    for mcu in mcus {
+        let feature = match die_of(mcu) {
+            Some(die)
+                if mcu_die_map
+                    .iter()
+                    .all(|(m, d)| d != die || mcus.contains(m)) =>
+            {
+                names.die_feature(die)
+            }
+            _ => names.mcu_feature(mcu),
+        };
+        if !features.contains(&feature) {
+            features.push(feature);
+        }
+    }
+    features
+        .iter()
+        .map(|feature| format!("feature = \"{}\"", feature))
+        .collect()
+}
+
+/// Generate the DMA requests of every DMA IP version, gated by the features of
+/// the MCUs using it (or of their dies, see `cfg_features`).
+///
+/// Every request is listed with its number (or its C HAL define name on parts
+/// with a DMAMUX) and the channels or streams it is connected to.
+pub fn generate_dma_requests(
+    groups: &[dma::DmaGroup],
+    mcu_die_map: &HashMap<String, String>,
+    names: &FeatureNames,
+) -> GeneratedArtifact {
+    let mut out = String::new();
+    for group in groups {
+        let features = cfg_features(&group.mcus, mcu_die_map, names);
+        writeln!(out, "// {}-{}", group.ip, group.version).unwrap();
+        writeln!(out, "// MCUs: {}", group.mcus.join(", ")).unwrap();
+        writeln!(out, "#[cfg(any({}))]", features.join(", ")).unwrap();
+        writeln!(out, "dma_requests! {{").unwrap();
+        for request in &group.requests {
+            let number = match request.number() {
+                Some(number) => number.to_string(),
+                None => request.request.clone(),
+            };
+            writeln!(
+                out,
+                "    {} => {{{}: [{}]}},",
+                request.signal,
+                number,
+                request.channels.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    GeneratedArtifact::new("dma_requests.rs", ArtifactKind::DmaRequests, out)
+}
+
+/// Generate a `clocks` module with the clock metadata of every RCC IP version,
+/// gated by the features of the MCUs using it (or of their dies).
+pub fn generate_clocks(
+    groups: &[rcc::RccGroup],
+    mcu_die_map: &HashMap<String, String>,
+    names: &FeatureNames,
+) -> GeneratedArtifact {
+    let const_name = |name: &str| {
+        name.chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+                _ => '_',
+            })
+            .collect::<String>()
+    };
+
+    let mut out = String::new();
+    for group in groups {
+        let features = cfg_features(&group.mcus, mcu_die_map, names);
+        writeln!(out, "// RCC-{}", group.version).unwrap();
+        writeln!(out, "// MCUs: {}", group.mcus.join(", ")).unwrap();
+        writeln!(out, "#[cfg(any({}))]", features.join(", ")).unwrap();
+        writeln!(out, "pub mod clocks {{").unwrap();
+        for (clock, max) in &group.max_frequencies {
+            writeln!(out, "    /// Maximum {} frequency in Hz", clock).unwrap();
+            writeln!(
+                out,
+                "    pub const {}_MAX: u32 = {};",
+                const_name(clock),
+                max
+            )
+            .unwrap();
+        }
+        for (prescaler, factors) in &group.prescalers {
+            let factors = factors.iter().map(u32::to_string).collect::<Vec<_>>();
+            writeln!(
+                out,
+                "    /// Division factors of the {} prescaler",
+                prescaler
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "    pub const {}_DIVIDERS: &[u32] = &[{}];",
+                const_name(prescaler),
+                factors.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    GeneratedArtifact::new("clocks.rs", ArtifactKind::Clocks, out)
+}
+
+/// Generate the interrupts of every NVIC IP version in the order of the
+/// interrupt table, gated by the features of the MCUs using it (or of their
+/// dies).
+pub fn generate_interrupts(
+    groups: &[nvic::NvicGroup],
+    mcu_die_map: &HashMap<String, String>,
+    names: &FeatureNames,
+) -> GeneratedArtifact {
+    let mut out = String::new();
+    for group in groups {
+        let features = cfg_features(&group.mcus, mcu_die_map, names);
+        writeln!(out, "// {}-{}", group.ip, group.version).unwrap();
+        writeln!(out, "// MCUs: {}", group.mcus.join(", ")).unwrap();
+        writeln!(out, "#[cfg(any({}))]", features.join(", ")).unwrap();
+        writeln!(out, "interrupts! {{").unwrap();
+        for interrupt in &group.interrupts {
+            if interrupt.description.is_empty() {
+                writeln!(out, "    {},", interrupt.name).unwrap();
+            } else {
+                writeln!(out, "    {}, // {}", interrupt.name, interrupt.description).unwrap();
+            }
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    GeneratedArtifact::new("interrupts.rs", ArtifactKind::Interrupts, out)
+}
+
+/// Generate a report of the pins relevant for an application, grouped by GPIO
+/// version and category.
+pub fn generate_app_pins(
+    af_tree: &af_tree::AfTree,
+    app: application::Application,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let mut out = String::new();
+    for group in af_tree.groups() {
+        writeln!(out, "# {}\n", names.io_feature(group.version())?).unwrap();
+        writeln!(out, "MCUs: {}\n", group.mcus().join(", ")).unwrap();
+        for category in application::collect(group, app) {
+            writeln!(out, "## {}\n", category.name).unwrap();
+            if category.signals.is_empty() {
+                writeln!(out, "(none)\n").unwrap();
+                continue;
+            }
+            let mut names = category
+                .signals
+                .iter()
+                .map(|s| s.signal.name())
+                .collect::<Vec<_>>();
+            names.sort_by(|a, b| compare_str(a, b));
+            names.dedup();
+            for name in names {
+                let pins = category
+                    .signals
+                    .iter()
+                    .filter(|s| s.signal.name() == name)
+                    .map(|s| format!("{} ({})", s.pin.name(), s.signal.af()))
+                    .collect::<Vec<_>>();
+                writeln!(out, "- {}: {}", name, pins.join(", ")).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+    }
+    Ok(GeneratedArtifact::new(
+        "app_pins.md",
+        ArtifactKind::Report,
+        out,
+    ))
+}
+
+/// Report how many GPIO versions the MCUs use and, for every version, the
+/// version with the most similar pin mappings.
+///
+/// Versions used by a single MCU are listed separately, since these force an
+/// extra IO feature and are candidates for merging with their closest version.
+pub fn generate_fragmentation(
+    af_tree: &af_tree::AfTree,
+    names: &FeatureNames,
+) -> Result<GeneratedArtifact, String> {
+    let groups = af_tree.groups();
+    let signals = groups
+        .iter()
+        .map(|group| {
+            group
+                .pins()
+                .iter()
+                .flat_map(|pin| {
+                    pin.signals()
+                        .iter()
+                        .map(move |s| format!("{} {}", pin.name(), s))
+                })
+                .collect::<HashSet<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    // The closest other version of every version and the number of differing
+    // IO signals
+    let mut closest = vec![];
+    for (i, group) in groups.iter().enumerate() {
+        let other = (0..groups.len())
+            .filter(|j| *j != i)
+            .map(|j| (signals[i].symmetric_difference(&signals[j]).count(), j))
+            .min();
+        let other = match other {
+            Some((difference, j)) => Some((names.io_feature(groups[j].version())?, difference)),
+            None => None,
+        };
+        closest.push((names.io_feature(group.version())?, other));
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# GPIO version fragmentation
+"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "{} GPIO versions for {} MCUs.\n",
+        groups.len(),
+        groups.iter().map(|g| g.mcus().len()).sum::<usize>()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "| Version | MCUs | Closest version | Differing signals |"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "|---------|------|-----------------|-------------------|"
+    )
+    .unwrap();
+    for (group, (feature, other)) in groups.iter().zip(&closest) {
+        let (other, difference) = match other {
+            Some((other, difference)) => (other.as_str(), difference.to_string()),
+            None => ("-", "-".to_string()),
+        };
+        writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            feature,
+            group.mcus().len(),
+            other,
+            difference
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\n## Versions used by a single MCU\n").unwrap();
+    let single = groups
+        .iter()
+        .zip(&closest)
+        .filter(|(group, _)| group.mcus().len() == 1)
+        .collect::<Vec<_>>();
+    if single.is_empty() {
+        writeln!(out, "(none)").unwrap();
+    }
+    for (group, (feature, other)) in single {
+        write!(out, "- {}: {}", feature, group.mcus()[0]).unwrap();
+        if let Some((other, difference)) = other {
+            write!(
+                out,
+                " (closest: {}, {} differing signals)",
+                other, difference
+            )
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    Ok(GeneratedArtifact::new(
+        "fragmentation.md",
+        ArtifactKind::Report,
+        out,
+    ))
+}
+
+/// List the peripheral stems of all GPIO groups, together with their
+/// peripheral instances and the number of pins providing any of their signals.
+pub fn generate_stems(af_tree: &af_tree::AfTree) -> GeneratedArtifact {
+    // Peripheral instances and pin names by stem
+    let mut stems: HashMap<&str, (HashSet<&str>, HashSet<String>)> = HashMap::new();
+    for group in af_tree.groups() {
+        for pin in group.pins() {
+            for signal in pin.raw_signals() {
+                let (devices, pins) = stems
+                    .entry(af_tree::device_stem(signal.device()))
+                    .or_default();
+                devices.insert(signal.device());
+                pins.insert(pin.name());
+            }
+        }
+    }
+    let mut stems = stems.into_iter().collect::<Vec<_>>();
+    stems.sort_by(|a, b| compare_str(a.0, b.0));
+
+    let mut out = String::new();
+    for (stem, (devices, pins)) in stems {
+        let mut devices = devices.into_iter().collect::<Vec<_>>();
+        devices.sort_by(|a, b| compare_str(a, b));
+        writeln!(
+            out,
+            "{}: {} devices ({}), {} pins",
+            stem,
+            devices.len(),
+            devices.join(", "),
+            pins.len()
+        )
+        .unwrap();
+    }
+    GeneratedArtifact::new("stems.txt", ArtifactKind::Report, out)
+}
+
+/// List the MCUs (sorted by ref name) with their package and GPIO version,
+/// one per line (e.g. "STM32L071KBTx LQFP32 STM32L071_gpio_v1_0").
+pub fn generate_mcu_list(
+    mcus: &[&family::Mcu],
+    mcu_gpio_map: &HashMap<String, Vec<String>>,
+) -> GeneratedArtifact {
+    let mut mcus = mcus.to_vec();
+    mcus.sort_by(|a, b| compare_str(&a.ref_name, &b.ref_name));
+    let mut out = String::new();
+    for mcu in mcus {
+        let gpio_version = mcu_gpio_map
+            .iter()
+            .find(|(_, mcus)| mcus.contains(&mcu.ref_name))
+            .map_or("-", |(version, _)| version.as_str());
+        let package = match mcu.package_name.as_str() {
+            "" => "-",
+            package => package,
+        };
+        writeln!(out, "{} {} {}", mcu.ref_name, package, gpio_version).unwrap();
+    }
+    GeneratedArtifact::new("mcus.txt", ArtifactKind::Report, out)
+}
+
+/// Dump the metadata of the MCUs (ref names with the data of their MCU file,
+/// sorted by ref name) as JSON, or as TOML with one `[[mcu]]` table per MCU.
+pub fn generate_mcu_info(
+    mcus: &[(String, mcu::Mcu)],
+    format: DumpFormat,
+) -> Result<GeneratedArtifact, String> {
+    /// The metadata of a single MCU (e.g. "STM32L071KBTx"): sizes in KiB
+    /// (EEPROM in bytes), the frequencies in MHz (one per core type).
+    #[derive(Serialize)]
+    struct McuInfo<'a> {
+        ref_name: &'a str,
+        package: &'a str,
+        flash: Option<u32>,
+        ram: Vec<u32>,
+        io_count: Option<u32>,
+        eeprom: Option<u32>,
+        cores: Vec<mcu::Core>,
+        max_frequency: Vec<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        die: Option<&'a str>,
+    }
+
+    #[derive(Serialize)]
+    struct McuTables<'a> {
+        mcu: Vec<McuInfo<'a>>,
+    }
+
+    let mut mcus = mcus
+        .iter()
+        .map(|(ref_name, mcu)| McuInfo {
+            ref_name,
+            package: &mcu.package,
+            flash: mcu.get_variant_flash(ref_name),
+            ram: mcu.get_ram(),
+            io_count: mcu.get_io_count(),
+            eeprom: mcu.get_eeprom(),
+            cores: mcu.get_cores(),
+            max_frequency: mcu.get_frequency(),
+            die: mcu.die.as_deref(),
+        })
+        .collect::<Vec<_>>();
+    mcus.sort_by(|a, b| compare_str(a.ref_name, b.ref_name));
+    let (path, out) = match format {
+        DumpFormat::Json => {
+            let mut out = serde_json::to_string_pretty(&mcus).unwrap();
+            out.push('\n');
+            ("mcus.json", out)
+        }
+        DumpFormat::Toml => {
+            let out = toml::to_string(&McuTables { mcu: mcus })
+                .map_err(|e| format!("Could not serialize the MCU metadata: {}", e))?;
+            ("mcus.toml", out)
+        }
+    };
+    Ok(GeneratedArtifact::new(path, ArtifactKind::McuInfo, out))
+}
+
+/// List the pin placement of every MCU (sorted by ref name), with one pin
+/// per line sorted by position (e.g. "    6 PA0"). The type of the system pins
+/// follows their name (e.g. "    4 NRST (Reset)").
+pub fn generate_pinouts(pinouts: &[package::Pinout]) -> GeneratedArtifact {
+    let mut pinouts = pinouts.iter().collect::<Vec<_>>();
+    pinouts.sort_by(|a, b| compare_str(&a.mcu, &b.mcu));
+    let mut out = String::new();
+    for pinout in pinouts {
+        writeln!(out, "{} ({})", pinout.mcu, pinout.package).unwrap();
+        for pin in &pinout.pins {
+            write!(out, "    {} {}", pin.position, pin.name).unwrap();
+            if pin.is_rf() {
+                write!(out, " (RF)").unwrap();
+            } else if pin.is_system() {
+                write!(out, " ({})", pin.pin_type.as_str()).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+    }
+    GeneratedArtifact::new("pinout.txt", ArtifactKind::Report, out)
+}
+
+/// List the labelled pins of a board (e.g. "LD3 [Green]: PB3") with their
+/// signal in the board file and the alternate functions of the pin in `group`,
+/// the GPIO group of the board MCU.
+pub fn generate_board_pins(
+    board: &board::Board,
+    group: Option<&af_tree::GpioGroup>,
+) -> GeneratedArtifact {
+    let mut out = String::new();
+    writeln!(out, "{} ({})", board.name, board.mcu).unwrap();
+    for pin in &board.pins {
+        write!(
+            out,
+            "    {}: {}",
+            pin.label.as_deref().unwrap_or_default(),
+            pin.pin
+        )
+        .unwrap();
+        match &pin.signal {
+            Some(signal) => writeln!(out, " ({})", signal).unwrap(),
+            None => writeln!(out).unwrap(),
+        }
+        let af_pin = group.and_then(|g| g.pins().iter().find(|p| p.name() == pin.pin));
+        for signal in af_pin.iter().flat_map(|p| p.raw_signals()) {
+            writeln!(out, "        {} {}", signal.af(), signal.name()).unwrap();
+        }
+    }
+    GeneratedArtifact::new("board_pins.txt", ArtifactKind::Report, out)
+}
+
+/// List the GPIO IP versions with their GPIO Modes file and the MCUs sharing
+/// it.
+pub fn generate_gpio_groups(af_tree: &af_tree::AfTree) -> GeneratedArtifact {
+    let mut out = String::new();
+    for group in af_tree.groups() {
+        writeln!(
+            out,
+            "{} ({}): {}",
+            group.version(),
+            internal_peripheral::IpGPIO::path(group.version()),
+            group.mcus().join(", ")
+        )
+        .unwrap();
+    }
+    GeneratedArtifact::new("gpio_groups.txt", ArtifactKind::Report, out)
+}
+
+/// List the versions of every IP used by the MCUs, sorted by IP and version,
+/// with the instances and the MCUs using each version (e.g. "USART" and
+/// "    sci3_v1_1_Cube (USART1, USART2): STM32L071KBTx").
+///
+/// The keys of both maps are IPs and their version, the values are the MCU
+/// ref names in `mcu_ip_map` and the instance names in `ip_instance_map`.
+pub fn generate_ip_list(
+    mcu_ip_map: &HashMap<(String, String), Vec<String>>,
+    ip_instance_map: &HashMap<(String, String), Vec<String>>,
+) -> GeneratedArtifact {
+    let mut versions = mcu_ip_map.iter().collect::<Vec<_>>();
+    versions.sort_by(|((ip_a, a), _), ((ip_b, b), _)| {
+        compare_str(ip_a, ip_b).then_with(|| compare_str(a, b))
+    });
+
+    let mut out = String::new();
+    let mut current_ip = None;
+    for ((ip, version), mcus) in versions {
+        if current_ip != Some(ip) {
+            writeln!(out, "{}", ip).unwrap();
+            current_ip = Some(ip);
+        }
+        let mut instances = ip_instance_map
+            .get(&(ip.clone(), version.clone()))
+            .cloned()
+            .unwrap_or_default();
+        instances.sort_by(|a, b| compare_str(a, b));
+        instances.dedup();
+        let mut mcus = mcus.clone();
+        mcus.sort_by(|a, b| compare_str(a, b));
+        mcus.dedup();
+        writeln!(
+            out,
+            "    {} ({}): {}",
+            version,
+            instances.join(", "),
+            mcus.join(", ")
+        )
+        .unwrap();
+    }
+    GeneratedArtifact::new("ips.txt", ArtifactKind::Report, out)
+}
+
+/// List the packages of the MCUs (sorted by name) with their package feature
+/// and the MCUs in each (e.g. "LQFP32 (package-lqfp32): STM32L071KBTx").
+///
+/// MCUs without a package in the database are listed last, as "(unknown)".
+pub fn generate_package_list(mcus: &[&family::Mcu], names: &FeatureNames) -> GeneratedArtifact {
+    let mut packages: HashMap<&str, Vec<&str>> = HashMap::new();
+    for mcu in mcus {
+        packages
+            .entry(&mcu.package_name)
+            .or_default()
+            .push(&mcu.ref_name);
+    }
+    let mut packages = packages.into_iter().collect::<Vec<_>>();
+    packages
+        .sort_by(|a, b| (a.0.is_empty().cmp(&b.0.is_empty())).then_with(|| compare_str(a.0, b.0)));
+
+    let mut out = String::new();
+    for (package, mut mcus) in packages {
+        mcus.sort_by(|a, b| compare_str(a, b));
+        match package {
+            "" => write!(out, "(unknown)").unwrap(),
+            _ => write!(
+                out,
+                "{} ({})",
+                package,
+                names.package_feature(package, mcus[0])
+            )
+            .unwrap(),
+        }
+        writeln!(out, ": {}", mcus.join(", ")).unwrap();
+    }
+    GeneratedArtifact::new("packages.txt", ArtifactKind::Report, out)
+}
+
+/// List the pin signals matching the filter, one per line. The signals of
+/// oscillator pins, which may be unavailable as GPIO, are flagged with the
+/// oscillator function (e.g. "[OSC32_IN]"), and the signals of debug pins with
+/// their debug function (e.g. "[DEBUG: SWDIO]").
+///
+/// With `details`, the signals are listed once per pin instead, followed by
+/// the GPIO versions providing them and the MCUs of each version.
+pub fn generate_query(
+    af_tree: &af_tree::AfTree,
+    filter: &af_tree::FilterSpec,
+    details: bool,
+) -> GeneratedArtifact {
+    let mut out = String::new();
+    if !details {
+        for line in query_lines(af_tree, filter, true) {
+            writeln!(out, "{}", line).unwrap();
+        }
+        return GeneratedArtifact::new("query.txt", ArtifactKind::Report, out);
+    }
+
+    // The GPIO groups providing each pin signal (e.g. "PA2 AF4 USART2_TX")
+    let mut signal_groups: HashMap<String, Vec<&af_tree::GpioGroup>> = HashMap::new();
+    for s in af_tree.iter_filtered(filter) {
+        let signal = format!(
+            "{} {} {}{}",
+            s.pin.name(),
+            s.signal.af(),
+            s.signal.name(),
+            pin_flags(s.pin)
+        );
+        signal_groups.entry(signal).or_default().push(s.group);
+    }
+    for a in af_tree.iter_additional(filter) {
+        let signal = format!(
+            "{} {} {}{}",
+            a.pin.name(),
+            a.function.kind.name(),
+            a.function.signal,
+            pin_flags(a.pin)
+        );
+        signal_groups.entry(signal).or_default().push(a.group);
+    }
+    let mut signals = signal_groups.into_iter().collect::<Vec<_>>();
+    signals.sort_by(|(a, _), (b, _)| compare_str(a, b));
+    for (signal, groups) in signals {
+        writeln!(out, "{}", signal).unwrap();
+        for group in groups {
+            writeln!(out, "    {}: {}", group.version(), group.mcus().join(", ")).unwrap();
+        }
+    }
+    GeneratedArtifact::new("query.txt", ArtifactKind::Report, out)
+}
+
+/// Compare the pin signals matching the filter in two trees (e.g. cached from
+/// different database versions), listing the signals only found in the old
+/// tree with "-" and the ones only found in the new tree with "+".
+pub fn generate_query_diff(
+    old: &af_tree::AfTree,
+    new: &af_tree::AfTree,
+    filter: &af_tree::FilterSpec,
+) -> GeneratedArtifact {
+    let old_lines = query_lines(old, filter, false);
+    let new_lines = query_lines(new, filter, false);
+    let old_set = old_lines.iter().collect::<HashSet<_>>();
+    let new_set = new_lines.iter().collect::<HashSet<_>>();
+
+    let mut lines = old_lines.iter().chain(&new_lines).collect::<Vec<_>>();
+    lines.sort_by(|a, b| compare_str(a, b));
+    lines.dedup();
+
+    let mut out = String::new();
+    for line in lines {
+        match (old_set.contains(line), new_set.contains(line)) {
+            (true, false) => writeln!(out, "- {}", line).unwrap(),
+            (false, true) => writeln!(out, "+ {}", line).unwrap(),
+            _ => {}
+        }
+    }
+    GeneratedArtifact::new("query.txt", ArtifactKind::Report, out)
+}
+
+/// Return one line per pin signal matching the filter (e.g.
+/// "STM32L051_gpio_v1_0 PA2 AF4 USART2_TX"), followed by the additional
+/// functions with their kind (e.g. "STM32L051_gpio_v1_0 PA0 WAKEUP
+/// SYS_WKUP1"), optionally flagging the oscillator and debug pins.
+fn query_lines(
+    af_tree: &af_tree::AfTree,
+    filter: &af_tree::FilterSpec,
+    flag_pins: bool,
+) -> Vec<String> {
+    let flags = |pin| match flag_pins {
+        true => pin_flags(pin),
+        false => String::new(),
+    };
+    let signals = af_tree.iter_filtered(filter).map(|s| {
+        format!(
+            "{} {} {} {}{}",
+            s.group.version(),
+            s.pin.name(),
+            s.signal.af(),
+            s.signal.name(),
+            flags(s.pin)
+        )
+    });
+    let additional = af_tree.iter_additional(filter).map(|a| {
+        format!(
+            "{} {} {} {}{}",
+            a.group.version(),
+            a.pin.name(),
+            a.function.kind.name(),
+            a.function.signal,
+            flags(a.pin)
+        )
+    });
+    signals.chain(additional).collect()
+}
+
+/// Return the flags of an oscillator pin (e.g. " [OSC32_IN]") or a debug pin
+/// (e.g. " [DEBUG: SWDIO]"), or an empty string for other pins.
+fn pin_flags(pin: &af_tree::AfPin) -> String {
+    let mut flags = String::new();
+    if let Some(function) = pin.oscillator() {
+        write!(flags, " [{}]", function.name()).unwrap();
+    }
+    if let Some(function) = pin.debug() {
+        write!(flags, " [DEBUG: {}]", function.name()).unwrap();
+    }
+    flags
+}
+
+/// Print the metadata of the database.
+pub fn generate_db_info(info: &db_info::DbInfo) -> GeneratedArtifact {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "Release: {}",
+        info.release.as_deref().unwrap_or("unknown")
+    )
+    .unwrap();
+    writeln!(out, "Families: {}", info.families).unwrap();
+    writeln!(out, "MCUs: {}", info.mcus).unwrap();
+    writeln!(
+        out,
+        "IP files: {} ({} GPIO Modes files)",
+        info.ip_files, info.gpio_files
+    )
+    .unwrap();
+    GeneratedArtifact::new("db_info.txt", ArtifactKind::Report, out)
+}
+
+/// Replace the `#[cfg(any(...))]` attributes of the generated code that list
+/// the same set of features more than once by an alias feature (e.g.
+/// `#[cfg(feature = "io-group-1")]`).
+///
+/// The aliases are defined in the features artifact, and enabled by every
+/// feature of their set, so that all listed features must be defined there.
+/// Returns the number of aliases.
+pub fn alias_feature_sets(
+    artifacts: &mut [GeneratedArtifact],
+    names: &FeatureNames,
+) -> Result<usize, String> {
+    let feature_set = |list: &str| {
+        let mut set = list
+            .split(", ")
+            .map(|f| f.trim_start_matches("feature = \"").trim_end_matches('"'))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        set.sort_by(|a, b| compare_str(a, b));
+        set.dedup();
+        set
+    };
+
+    // Count the sets in the order of their first use
+    let mut sets: Vec<(Vec<String>, usize)> = vec![];
+    for artifact in artifacts
+        .iter()
+        .filter(|a| a.kind != ArtifactKind::Features)
+    {
+        for captures in ANY_FEATURE_CFG.captures_iter(&artifact.content) {
+            let set = feature_set(&captures[1]);
+            match sets.iter_mut().find(|(s, _)| *s == set) {
+                Some((_, uses)) => *uses += 1,
+                None => sets.push((set, 1)),
+            }
+        }
+    }
+    let aliases = sets
+        .into_iter()
+        .filter(|(set, uses)| set.len() > 1 && *uses > 1)
+        .enumerate()
+        .map(|(i, (set, _))| (set, names.group_feature(i + 1)))
+        .collect::<Vec<_>>();
+    if aliases.is_empty() {
+        return Ok(0);
+    }
+
+    let features = artifacts
+        .iter_mut()
+        .find(|a| a.kind == ArtifactKind::Features)
+        .ok_or("Feature aliases need the features to be generated as well")?;
+    let mut defined = HashSet::new();
+    let mut content = String::new();
+    for line in features.content.lines() {
+        match FEATURE_LINE.captures(line) {
+            Some(captures) => {
+                let name = &captures[1];
+                let mut dependencies = captures[2]
+                    .split(", ")
+                    .filter(|d| !d.is_empty())
+                    .map(String::from)
+                    .collect::<Vec<_>>();
+                for (set, alias) in &aliases {
+                    if set.iter().any(|f| f == name) {
+                        dependencies.push(format!("\"{}\"", alias));
+                    }
+                }
+                defined.insert(name.to_string());
+                writeln!(content, "{} = [{}]", name, dependencies.join(", ")).unwrap();
+            }
+            None => writeln!(content, "{}", line).unwrap(),
+        }
+    }
+    writeln!(
+        content,
+        "\n# Aliases of recurring feature sets, enabled by their features"
+    )
+    .unwrap();
+    for (set, alias) in &aliases {
+        if let Some(feature) = set.iter().find(|f| !defined.contains(*f)) {
+            return Err(format!(
+                "Feature {} of alias {} is not defined",
+                feature, alias
+            ));
+        }
+        writeln!(content, "{} = []", alias).unwrap();
+    }
+    features.content = content;
+
+    for artifact in artifacts
+        .iter_mut()
+        .filter(|a| a.kind != ArtifactKind::Features)
+    {
+        artifact.content = ANY_FEATURE_CFG
+            .replace_all(&artifact.content, |captures: &regex::Captures| {
+                let set = feature_set(&captures[1]);
+                match aliases.iter().find(|(s, _)| *s == set) {
+                    Some((_, alias)) => format!("#[cfg(feature = \"{}\")]", alias),
+                    None => captures[0].to_string(),
+                }
+            })
+            .into_owned();
+    }
+    Ok(aliases.len())
+}
+
+/// List the generated files of a `generate-all` run, so that a HAL repository
+/// records which families and database release its code was generated from.
+pub fn generate_manifest(
+    families: &[&str],
+    db_release: Option<&str>,
+    mcus: usize,
+    artifacts: &[GeneratedArtifact],
+) -> GeneratedArtifact {
+    let mut out = String::new();
+    writeln!(out, "# Generated by cube-parse, do not edit").unwrap();
+    writeln!(out, "cube_parse_version = {:?}", env!("CARGO_PKG_VERSION")).unwrap();
+    if let Some(release) = db_release {
+        writeln!(out, "db_release = {:?}", release).unwrap();
+    }
+    writeln!(out, "families = {:?}", families).unwrap();
+    writeln!(out, "mcus = {}", mcus).unwrap();
+    writeln!(out, "files = [").unwrap();
+    for artifact in artifacts {
+        writeln!(out, "    {:?},", artifact.path.to_string_lossy()).unwrap();
+    }
+    writeln!(out, "]").unwrap();
+    GeneratedArtifact::new("cube-parse-manifest.toml", ArtifactKind::Manifest, out)
+}
+
+/// List all families with their sub-families and the number of MCUs in each.
+pub fn generate_families(families: &family::Families) -> GeneratedArtifact {
+    let mut out = String::new();
+    for family in families {
+        writeln!(out, "{}", family.name).unwrap();
+        for sf in family.sub_families() {
+            writeln!(out, "    {} ({} MCUs)", sf.name, sf.mcus.len()).unwrap();
+        }
+    }
+    GeneratedArtifact::new("families.txt", ArtifactKind::Report, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_features() {
+        let mut mcu_gpio_map = HashMap::new();
+        mcu_gpio_map.insert(
+            "STM32L071_gpio_v1_0".to_string(),
+            vec!["STM32L071KBTx".to_string()],
+        );
+        let mut mcu_package_map = HashMap::new();
+        mcu_package_map.insert("STM32L071KBTx".to_string(), "LQFP32".to_string());
+
+        let artifact = generate_features(
+            &mcu_gpio_map,
+            &mcu_package_map,
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+            &["STM32L0"],
+            &FeatureNames::default(),
+        )
+        .unwrap();
+        assert_eq!(artifact.kind, ArtifactKind::Features);
+        assert_eq!(artifact.path, PathBuf::from("features.toml"));
+        assert!(artifact.content.contains("io-STM32L071 = []\n"));
+        assert!(artifact.content.contains(
+            "mcu-STM32L071KBTx = [\"io-STM32L071\", \"stm32l0x1\", \"package-lqfp32\"]\n"
+        ));
+
+        mcu_gpio_map.insert(
+            "STM32H745_gpio_v1_0".to_string(),
+            vec!["STM32H745ZITx".to_string()],
+        );
+        let mut mcu_core_map = HashMap::new();
+        mcu_core_map.insert(
+            "STM32H745ZITx".to_string(),
+            vec![mcu::Core::CortexM7, mcu::Core::CortexM4],
+        );
+        let mut mcu_peripheral_map = HashMap::new();
+        mcu_peripheral_map.insert(
+            "STM32H745ZITx".to_string(),
+            vec![
+                "GPIO".to_string(),
+                "NVIC".to_string(),
+                "USART3".to_string(),
+                "DAC1".to_string(),
+            ],
+        );
+        // Every MCU has the GPIO and NVIC, so they get no feature
+        mcu_peripheral_map.insert(
+            "STM32L071KBTx".to_string(),
+            vec!["GPIO".to_string(), "NVIC".to_string()],
+        );
+        let names = FeatureNames {
+            cores: true,
+            peripherals: true,
+            ..FeatureNames::default()
+        };
+        let artifact = generate_features(
+            &mcu_gpio_map,
+            &HashMap::new(),
+            &HashMap::new(),
+            &mcu_core_map,
+            &mcu_peripheral_map,
+            &HashMap::new(),
+            &["STM32H7"],
+            &names,
+        )
+        .unwrap();
+        assert!(artifact
+            .content
+            .contains("# Cores\ncortex-m4 = []\ncortex-m7 = []\ndual-core = []\n"));
+        assert!(artifact
+            .content
+            .contains("# Target cores of the dual-core MCUs\ncm4 = []\ncm7 = []\n"));
+        assert!(artifact
+            .content
+            .contains("mcu-STM32H745ZITx-cm4 = [\"mcu-STM32H745ZITx\", \"cm4\"]\n"));
+        assert!(artifact
+            .content
+            .contains("# Peripherals\nhas-dac1 = []\nhas-usart3 = []\n"));
+        assert!(artifact.content.contains(
+            "mcu-STM32H745ZITx = [\"io-STM32H745\", \"cortex-m7\", \"cortex-m4\", \"dual-core\", \"has-dac1\", \"has-usart3\"]\n"
+        ));
+        assert!(!artifact.content.contains("has-gpio"));
+        assert!(!artifact.content.contains("has-nvic"));
+    }
+
+    #[test]
+    fn test_debug_pin_docs() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "13", "original_name": null, "debug": "Swdio",
+                 "raw_signals": [{"name": "SYS_SWDIO", "af": "AF0", "define": null},
+                                 {"name": "USART2_TX", "af": "AF4", "define": null}],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let names = FeatureNames::default();
+        let templates = Templates::new();
+        let artifact = generate_pin_mappings(
+            &tree,
+            &PinMappingOptions {
+                debug_docs: true,
+                ..Default::default()
+            },
+            &names,
+            &templates,
+        )
+        .unwrap();
+        assert!(artifact.content.contains(
+            "    /// Warning: PA13 is the SWDIO debug pin after reset, using it otherwise disconnects the debugger.\n\
+             \x20   PA13 => {AF4: TxPin<USART2>},\n"
+        ));
+        let artifact =
+            generate_pin_mappings(&tree, &PinMappingOptions::default(), &names, &templates)
+                .unwrap();
+        assert!(!artifact.content.contains("Warning"));
+
+        let artifact = generate_query(&tree, &af_tree::FilterSpec::new(), false);
+        assert!(artifact
+            .content
+            .contains("STM32L031_gpio_v1_0 PA13 AF4 USART2_TX [DEBUG: SWDIO]\n"));
+    }
+
+    #[test]
+    fn test_alternatives_docs() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": [], "pins": [
+                {"port": "PA", "number": "9", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART1", "define": null},
+                             {"af": "AF1", "io": "SclPin", "device": "I2C1", "define": null}]},
+                {"port": "PB", "number": "6", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF0", "io": "TxPin", "device": "USART1", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let artifact = generate_pin_mappings(
+            &tree,
+            &PinMappingOptions {
+                alternatives: true,
+                ..Default::default()
+            },
+            &FeatureNames::default(),
+            &Templates::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L031\")]\n\
+             pins! {\n\
+             \x20   /// TxPin<USART1> is also available on PB6 (AF0).\n\
+             \x20   PA9 => {\n\
+             \x20       AF4: TxPin<USART1>,\n\
+             \x20       AF1: SclPin<I2C1>,\n\
+             \x20   },\n\
+             \x20   /// TxPin<USART1> is also available on PA9 (AF4).\n\
+             \x20   PB6 => {AF0: TxPin<USART1>},\n\
+             }\n\n\n"
+        );
+    }
+
+    #[test]
+    fn test_pin_group_template() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2",
+                              "define": "GPIO_AF4_USART2", "signal": "USART2_TX"}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let names = FeatureNames::default();
+        let mut templates = Templates::new();
+        let artifact = generate_pin_mappings(
+            &tree,
+            &PinMappingOptions {
+                af_defines: true,
+                ..Default::default()
+            },
+            &names,
+            &templates,
+        )
+        .unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L031\")]\n\
+             pins! {\n\
+             \x20   PA2 => {AF4: TxPin<USART2>}, // GPIO_AF4_USART2\n\
+             }\n\n\n"
+        );
+
+        templates
+            .register(
+                templates::PIN_GROUP,
+                "#[cfg(feature = \"{{feature}}\")]\n\
+                 {{#each pins}}{{#each signals}}\
+                 pin!({{../name}}, {{af}}, {{signal}});\n\
+                 {{/each}}{{/each}}",
+            )
+            .unwrap();
+        let artifact = generate_pin_mappings(
+            &tree,
+            &PinMappingOptions {
+                af_defines: true,
+                ..Default::default()
+            },
+            &names,
+            &templates,
+        )
+        .unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L031\")]\npin!(PA2, AF4, USART2_TX);\n\n\n"
+        );
+
+        // Unknown variables are errors
+        templates
+            .register(templates::PIN_GROUP, "{{unknown}}")
+            .unwrap();
+        assert!(generate_pin_mappings(
+            &tree,
+            &PinMappingOptions {
+                af_defines: true,
+                ..Default::default()
+            },
+            &names,
+            &templates
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_generate_l0xx_pin_mappings() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null},
+                             {"af": "AF6", "io": "TxPin", "device": "LPUART1", "define": null}]},
+                {"port": "PA", "number": "3", "original_name": null, "raw_signals": [], "signals": []},
+                {"port": "PB", "number": "6", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF1", "io": "SclPin", "device": "I2C1", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let names = FeatureNames::default();
+        let artifact = generate_l0xx_pin_mappings(&tree, &names).unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L031\")]\n\
+             pins! {\n\
+             \x20   PA2 => {\n\
+             \x20       AF4: TxPin<USART2>,\n\
+             \x20       AF6: TxPin<LPUART1>,\n\
+             \x20   },\n\
+             \x20   PB6 => {AF1: SclPin<I2C1>},\n\
+             }\n\n\n"
+        );
+        // Currently the same as the default style without the MCU docs
+        let default = generate_pin_mappings(
+            &tree,
+            &PinMappingOptions::default(),
+            &names,
+            &Templates::new(),
+        )
+        .unwrap();
+        assert_eq!(artifact.content, default.content);
+    }
+
+    #[test]
+    fn test_generate_f4xx_pin_mappings() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F401_gpio_v1_0", "mcus": ["STM32F401CCUx"], "pins": [
+                    {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF7", "io": "TxPin", "device": "USART2", "define": null}]}
+                ]},
+                {"version": "STM32F446_gpio_v1_0", "mcus": ["STM32F446RETx"], "pins": [
+                    {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF7", "io": "TxPin", "device": "USART2", "define": null}]},
+                    {"port": "PD", "number": "5", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF7", "io": "TxPin", "device": "USART2", "define": null}]}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_f4xx_pin_mappings(&tree, &FeatureNames::default(), false).unwrap();
+        assert_eq!(
+            artifact.content,
+            "pub mod usart2 {\n\
+             \x20   use super::*;\n\n\
+             \x20   pin! {\n\
+             \x20       <Tx> for [\n\
+             \x20           PA2<7>,\n\
+             \x20           #[cfg(feature = \"io-STM32F446\")]\n\
+             \x20           PD5<7>,\n\
+             \x20       ],\n\
+             \x20   }\n\
+             }\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_embassy_pin_traits() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null},
+                             {"af": "AF6", "io": "TxPin", "device": "LPUART1", "define": null}]},
+                {"port": "PB", "number": "6", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF1", "io": "SclPin", "device": "I2C1", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let artifact = generate_embassy_pin_traits(&tree, &FeatureNames::default(), false).unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L031\")]\n\
+             mod io_stm32l031 {\n\
+             \x20   use super::*;\n\n\
+             \x20   pin_trait_impl!(crate::usart::TxPin, USART2, PA2, 4u8);\n\
+             \x20   pin_trait_impl!(crate::usart::TxPin, LPUART1, PA2, 6u8);\n\
+             \x20   pin_trait_impl!(crate::i2c::SclPin, I2C1, PB6, 1u8);\n\
+             }\n\n"
+        );
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32F103x8_gpio_v1_0", "mcus": [], "pins": [
+                {"port": "PB", "number": "6", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "REMAP_FULL", "io": "TxPin", "device": "USART1", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_embassy_pin_traits(&remapped, &FeatureNames::default(), false).unwrap_err(),
+            "The embassy style needs numbered alternate functions, PB6 REMAP_FULL: TxPin<USART1> has REMAP_FULL"
+        );
+    }
+
+    #[test]
+    fn test_generate_pin_table() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null,
+                 "raw_signals": [{"name": "USART2_TX", "af": "AF4", "define": null},
+                                 {"name": "TIM2_CH3", "af": "AF2", "define": null}],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let artifact = generate_pin_table(&tree, &FeatureNames::default(), false).unwrap();
+        assert!(artifact.content.starts_with(PIN_FUNCTION_STRUCT));
+        assert!(artifact.content.ends_with(
+            "#[cfg(feature = \"io-STM32L031\")]\n\
+             pub static PIN_FUNCTIONS: &[PinFunction] = &[\n\
+             \x20   PinFunction { pin: \"PA2\", port: \"PA\", af: \"AF4\", signal: \"USART2_TX\", device: \"USART2\" },\n\
+             \x20   PinFunction { pin: \"PA2\", port: \"PA\", af: \"AF2\", signal: \"TIM2_CH3\", device: \"TIM2\" },\n\
+             ];\n"
+        ));
+    }
+
+    #[test]
+    fn test_sealed_traits() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F401_gpio_v1_0", "mcus": ["STM32F401CCUx"], "pins": [
+                    {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF7", "io": "TxPin", "device": "USART2", "define": null}]}
+                ]},
+                {"version": "STM32F446_gpio_v1_0", "mcus": ["STM32F446RETx"], "pins": [
+                    {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF7", "io": "TxPin", "device": "USART2", "define": null}]},
+                    {"port": "PB", "number": "6", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF4", "io": "SclPin", "device": "I2C1", "define": null}]}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        let options = PinMappingOptions {
+            sealed_traits: true,
+            ..Default::default()
+        };
+        let artifact =
+            generate_pin_mappings(&tree, &options, &FeatureNames::default(), &Templates::new())
+                .unwrap();
+        assert!(artifact.content.starts_with(
+            "mod private {\n\
+             \x20   /// Implemented by the pins below only, so that no other type can implement the pin traits.\n\
+             \x20   pub trait Sealed {}\n\
+             }\n\n\
+             pub trait SclPin<P>: private::Sealed {}\n\
+             pub trait TxPin<P>: private::Sealed {}\n\n\
+             #[cfg(feature = \"io-STM32F401\")]\n"
+        ));
+        assert!(artifact.content.ends_with(
+            "impl<MODE> private::Sealed for PA2<MODE> {}\n\
+             #[cfg(feature = \"io-STM32F446\")]\n\
+             impl<MODE> private::Sealed for PB6<MODE> {}\n"
+        ));
+    }
+
+    #[test]
+    fn test_package_gating() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0",
+                "mcus": ["STM32L031C6Tx", "STM32L031K6Tx", "STM32L031K6Ux"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]},
+                {"port": "PA", "number": "3", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "RxPin", "device": "USART2", "define": null}]},
+                {"port": "PB", "number": "6", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF0", "io": "TxPin", "device": "USART2", "define": null}]},
+                {"port": "PB", "number": "8", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "SclPin", "device": "I2C1", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let pinout = |mcu: &str, package: &str, gpios: &[&str]| package::Pinout {
+            mcu: mcu.to_string(),
+            package: package.to_string(),
+            pins: gpios
+                .iter()
+                .enumerate()
+                .map(|(i, gpio)| package::PackagePin {
+                    position: (i + 1).to_string(),
+                    name: gpio.to_string(),
+                    gpio: Some(gpio.to_string()),
+                    pin_type: package::PinType::parse("I/O"),
+                })
+                .collect(),
+        };
+        let pinouts = vec![
+            pinout("STM32L031C6Tx", "LQFP48", &["PA2", "PB6", "PB8"]),
+            pinout("STM32L031K6Tx", "LQFP32", &["PA2", "PB6"]),
+            pinout("STM32L031K6Ux", "UFQFPN32", &["PA2", "PA3"]),
+        ];
+        let mut mcu_package_map = HashMap::new();
+        mcu_package_map.insert("STM32L031C6Tx".to_string(), "LQFP48".to_string());
+        mcu_package_map.insert("STM32L031K6Tx".to_string(), "LQFP32".to_string());
+        let names = FeatureNames::default();
+        let package_pins = PackagePins::new(&pinouts, &mcu_package_map, &names);
+        let options = PinMappingOptions {
+            package_pins: Some(&package_pins),
+            ..Default::default()
+        };
+        let artifact = generate_pin_mappings(&tree, &options, &names, &Templates::new()).unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L031\")]\n\
+             pins! {\n\
+             \x20   PA2 => {AF4: TxPin<USART2>},\n\
+             \x20   #[cfg(feature = \"mcu-STM32L031K6Ux\")]\n\
+             \x20   PA3 => {AF4: RxPin<USART2>},\n\
+             \x20   #[cfg(any(feature = \"package-lqfp32\", feature = \"package-lqfp48\"))]\n\
+             \x20   PB6 => {AF0: TxPin<USART2>},\n\
+             \x20   #[cfg(feature = \"package-lqfp48\")]\n\
+             \x20   PB8 => {AF4: SclPin<I2C1>},\n\
+             }\n\n\n"
+        );
+    }
+
+    #[test]
+    fn test_cfg_style() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx", "STM32L031C6Tx"], "pins": [
+                    {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]}
+                ]},
+                {"version": "STM32L071_gpio_v1_0", "mcus": ["STM32L071KBTx"], "pins": [
+                    {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]},
+                    {"port": "PB", "number": "6", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF0", "io": "TxPin", "device": "USART1", "define": null}]}
+                ]}
+            ]}"#,
+        )
+        .unwrap();
+        let names = FeatureNames::default();
+        let render = |options: &PinMappingOptions| {
+            generate_pin_mappings(&tree, options, &names, &Templates::new())
+                .unwrap()
+                .content
+        };
+
+        let content = render(&PinMappingOptions {
+            cfg_style: CfgStyle::Mcu,
+            ..Default::default()
+        });
+        assert!(content.starts_with(
+            "#[cfg(any(feature = \"mcu-STM32L031C6Tx\", feature = \"mcu-STM32L031K6Tx\"))]\n\
+             pins! {\n"
+        ));
+        assert!(content.contains("#[cfg(feature = \"mcu-STM32L071KBTx\")]\npins! {\n"));
+
+        let content = render(&PinMappingOptions {
+            sealed_traits: true,
+            cfg_style: CfgStyle::Bare,
+            cfg_prefix: "stm32_",
+            ..Default::default()
+        });
+        assert!(
+            content.contains("#[cfg(any(stm32_stm32l031c6tx, stm32_stm32l031k6tx))]\npins! {\n")
+        );
+        assert!(content.ends_with(
+            "impl<MODE> private::Sealed for PA2<MODE> {}\n\
+             #[cfg(stm32_stm32l071kbtx)]\n\
+             impl<MODE> private::Sealed for PB6<MODE> {}\n"
+        ));
+    }
+
+    #[test]
+    fn test_mcu_docs() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0",
+                "mcus": ["STM32L031C6Tx", "STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let names = FeatureNames::default();
+        let options = PinMappingOptions {
+            mcu_docs: true,
+            ..Default::default()
+        };
+        let artifact = generate_pin_mappings(&tree, &options, &names, &Templates::new()).unwrap();
+        assert!(artifact.content.starts_with(
+            "// MCUs with STM32L031_gpio_v1_0: STM32L031C6Tx, STM32L031K6Tx\n\
+             #[cfg(feature = \"io-STM32L031\")]\n"
+        ));
+        let artifact = generate_embassy_pin_traits(&tree, &names, true).unwrap();
+        assert!(artifact.content.starts_with(
+            "/// MCUs with STM32L031_gpio_v1_0: STM32L031C6Tx, STM32L031K6Tx\n\
+             #[cfg(feature = \"io-STM32L031\")]\n\
+             mod io_stm32l031 {\n"
+        ));
+        let artifact = generate_f4xx_pin_mappings(&tree, &names, true).unwrap();
+        assert!(artifact.content.starts_with(
+            "/// USART2 pins of the MCUs with STM32L031_gpio_v1_0\npub mod usart2 {\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_pin_tests() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]},
+                {"port": "PA", "number": "9", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART1", "define": null},
+                             {"af": "AF1", "io": "SclPin", "device": "I2C1", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let artifact = generate_pin_tests(
+            &tree,
+            &PinMappingOptions::default(),
+            &FeatureNames::default(),
+            "stm32l0xx_hal::prelude::*",
+            "gpio{port}::{pin}<Analog>",
+        )
+        .unwrap();
+        assert_eq!(artifact.path, PathBuf::from("tests/pin_mappings.rs"));
+        assert!(artifact.content.ends_with(
+            "use stm32l0xx_hal::prelude::*;\n\n\
+             #[cfg(feature = \"io-STM32L031\")]\n\
+             #[test]\n\
+             fn io_stm32l031() {\n\
+             \x20   fn i2c<P: SclPin<I2C1>>() {}\n\
+             \x20   i2c::<gpioa::PA9<Analog>>();\n\
+             \x20   fn usart<P: TxPin<USART2>>() {}\n\
+             \x20   usart::<gpioa::PA2<Analog>>();\n\
+             }\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_build_cfgs() {
+        let mut mcu_gpio_map = HashMap::new();
+        mcu_gpio_map.insert(
+            "STM32L071_gpio_v1_0".to_string(),
+            vec!["STM32L071KBTx".to_string()],
+        );
+        let artifact = generate_build_cfgs(
+            &mcu_gpio_map,
+            &HashMap::new(),
+            &["STM32L0"],
+            &FeatureNames::default(),
+            "stm32_",
+        )
+        .unwrap();
+        assert_eq!(artifact.kind, ArtifactKind::BuildScript);
+        assert!(artifact.content.contains(
+            "const MCU_CFGS: &[(&str, &[&str])] = &[\n\
+             \x20   (\"CARGO_FEATURE_MCU_STM32L071KBTX\", \
+             &[\"stm32_stm32l071kbtx\", \"stm32_stm32l0x1\", \"stm32_io_stm32l071\"]),\n\
+             ];\n"
+        ));
+        assert!(artifact.content.contains("fn emit_mcu_cfgs() {\n"));
+    }
+
+    #[test]
+    fn test_generate_sub_family_pin_mappings() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let sub_families = vec![
+            ("STM32L0x1".to_string(), vec!["STM32L031K6Tx".to_string()]),
+            ("STM32L0x3".to_string(), vec!["STM32L073RZTx".to_string()]),
+        ];
+        let mut overrides = HashMap::new();
+        overrides.insert("STM32L0x3".to_string(), "// Custom\n".to_string());
+
+        let artifact = generate_sub_family_pin_mappings(
+            &tree,
+            &sub_families,
+            &overrides,
+            &PinMappingOptions::default(),
+            &FeatureNames::default(),
+            &Templates::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            artifact.content,
+            "// STM32L0x1\n\
+             #[cfg(feature = \"stm32l0x1\")]\n\
+             pub mod stm32l0x1 {\n\
+             \x20   #[cfg(feature = \"io-STM32L031\")]\n\
+             \x20   pins! {\n\
+             \x20       PA2 => {AF4: TxPin<USART2>},\n\
+             \x20   }\n\
+             }\n\n\
+             // STM32L0x3 (hand-maintained)\n\
+             #[cfg(feature = \"stm32l0x3\")]\n\
+             pub mod stm32l0x3 {\n\
+             \x20   // Custom\n\
+             }\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_fragmentation() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"], "pins": [
+                    {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                     "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]}
+                ]},
+                {"version": "STM32L051_gpio_v1_0", "mcus": ["STM32L052C8Tx", "STM32L052K8Tx"], "pins": []}
+            ]}"#,
+        )
+        .unwrap();
+
+        let artifact = generate_fragmentation(&tree, &FeatureNames::default()).unwrap();
+        assert!(artifact.content.contains("2 GPIO versions for 3 MCUs.\n"));
+        assert!(artifact
+            .content
+            .contains("| io-STM32L051 | 2 | io-STM32L031 | 1 |\n"));
+        assert!(artifact.content.contains(
+            "- io-STM32L031: STM32L031K6Tx (closest: io-STM32L051, 1 differing signals)\n"
+        ));
+    }
+
+    #[test]
+    fn test_generate_stems() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L031_gpio_v1_0", "mcus": [], "pins": [
+                {"port": "PA", "number": "2", "original_name": null, "signals": [], "raw_signals": [
+                    {"name": "USART2_TX", "af": "AF4", "define": null},
+                    {"name": "TIM21_CH1", "af": "AF0", "define": null}
+                ]},
+                {"port": "PA", "number": "9", "original_name": null, "signals": [], "raw_signals": [
+                    {"name": "USART1_TX", "af": "AF4", "define": null}
+                ]}
+            ]}]}"#,
+        )
+        .unwrap();
+
+        let artifact = generate_stems(&tree);
+        assert_eq!(
+            artifact.content,
+            "TIM: 1 devices (TIM21), 1 pins\n\
+             USART: 2 devices (USART1, USART2), 2 pins\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_mcu_list() {
+        let mcu = |ref_name: &str, package: &str| family::Mcu {
+            name: ref_name.to_string(),
+            package_name: package.to_string(),
+            ref_name: ref_name.to_string(),
+            rpn: String::new(),
+        };
+        let l071 = mcu("STM32L071KBTx", "LQFP32");
+        let l031 = mcu("STM32L031K6Tx", "");
+        let mut mcu_gpio_map = HashMap::new();
+        mcu_gpio_map.insert(
+            "STM32L031_gpio_v1_0".to_string(),
+            vec!["STM32L031K6Tx".to_string(), "STM32L071KBTx".to_string()],
+        );
+
+        let artifact = generate_mcu_list(&[&l071, &l031], &mcu_gpio_map);
+        assert_eq!(
+            artifact.content,
+            "STM32L031K6Tx - STM32L031_gpio_v1_0\n\
+             STM32L071KBTx LQFP32 STM32L031_gpio_v1_0\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_ip_list() {
+        let key = |ip: &str, version: &str| (ip.to_string(), version.to_string());
+        let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let mut mcu_ip_map = HashMap::new();
+        let mut ip_instance_map = HashMap::new();
+        mcu_ip_map.insert(
+            key("USART", "sci3_v1_1_Cube"),
+            strings(&["STM32L071KBTx", "STM32L031K6Tx", "STM32L071KBTx"]),
+        );
+        ip_instance_map.insert(
+            key("USART", "sci3_v1_1_Cube"),
+            strings(&["USART2", "USART1", "USART2"]),
+        );
+        mcu_ip_map.insert(key("I2C", "i2c2_v1_1_Cube"), strings(&["STM32L071KBTx"]));
+        ip_instance_map.insert(key("I2C", "i2c2_v1_1_Cube"), strings(&["I2C1"]));
+        mcu_ip_map.insert(key("USART", "sci2_v1_1_Cube"), strings(&["STM32L031K6Tx"]));
+        ip_instance_map.insert(key("USART", "sci2_v1_1_Cube"), strings(&["USART2"]));
+
+        let artifact = generate_ip_list(&mcu_ip_map, &ip_instance_map);
+        assert_eq!(artifact.path, PathBuf::from("ips.txt"));
+        assert_eq!(
+            artifact.content,
+            "I2C\n    i2c2_v1_1_Cube (I2C1): STM32L071KBTx\n\
+             USART\n    sci2_v1_1_Cube (USART2): STM32L031K6Tx\n\
+             \x20   sci3_v1_1_Cube (USART1, USART2): STM32L031K6Tx, STM32L071KBTx\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_package_list() {
+        let mcu = |ref_name: &str, package: &str| family::Mcu {
+            name: ref_name.to_string(),
+            package_name: package.to_string(),
+            ref_name: ref_name.to_string(),
+            rpn: String::new(),
+        };
+        let mcus = [
+            mcu("STM32L071KBTx", "LQFP32"),
+            mcu("STM32L052C8Tx", "LQFP48"),
+            mcu("STM32L031K6Tx", "LQFP32"),
+            mcu("STM32L011D4Px", ""),
+        ];
+
+        let artifact =
+            generate_package_list(&mcus.iter().collect::<Vec<_>>(), &FeatureNames::default());
+        assert_eq!(
+            artifact.content,
+            "LQFP32 (package-lqfp32): STM32L031K6Tx, STM32L071KBTx\n\
+             LQFP48 (package-lqfp48): STM32L052C8Tx\n\
+             (unknown): STM32L011D4Px\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_query_diff() {
+        let tree = |signals: &str| {
+            let json = format!(
+                r#"{{"groups": [{{"version": "STM32L051_gpio_v1_0", "mcus": [], "pins": [
+                    {{"port": "PB", "number": "4", "original_name": null, "signals": [],
+                      "raw_signals": [{}]}}
+                ]}}]}}"#,
+                signals
+            );
+            af_tree::AfTree::from_json(&json).unwrap()
+        };
+        let old = tree(r#"{"name": "TIM3_CH1", "af": "AF2", "define": null}"#);
+        let new = tree(
+            r#"{"name": "TIM3_CH1", "af": "AF2", "define": null},
+               {"name": "SPI3_MISO", "af": "AF6", "define": null}"#,
+        );
+        let new = af_tree::AfTree::from_json(&new.to_json()).unwrap();
+
+        let artifact = generate_query_diff(&old, &new, &af_tree::FilterSpec::new());
+        assert_eq!(
+            artifact.content,
+            "+ STM32L051_gpio_v1_0 PB4 AF6 SPI3_MISO\n"
+        );
+        let artifact = generate_query_diff(&new, &old, &af_tree::FilterSpec::new());
+        assert_eq!(
+            artifact.content,
+            "- STM32L051_gpio_v1_0 PB4 AF6 SPI3_MISO\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_query_details() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L051_gpio_v1_0", "mcus": ["STM32L051K8Tx", "STM32L071KBTx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]},
+                {"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]},
+                          {"port": "PC", "number": "14", "oscillator": "Osc32In", "signals": [],
+                           "raw_signals": [{"name": "EVENTOUT", "af": "AF0"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_query(&tree, &af_tree::FilterSpec::new(), true);
+        assert_eq!(
+            artifact.content,
+            "PA2 AF4 USART2_TX\n\
+             \x20   STM32L051_gpio_v1_0: STM32L051K8Tx, STM32L071KBTx\n\
+             \x20   STM32L031_gpio_v1_0: STM32L031K6Tx\n\
+             PC14 AF0 EVENTOUT [OSC32_IN]\n\
+             \x20   STM32L031_gpio_v1_0: STM32L031K6Tx\n"
+        );
+        let artifact = generate_query(&tree, &af_tree::FilterSpec::new(), false);
+        assert!(artifact
+            .content
+            .ends_with("STM32L031_gpio_v1_0 PC14 AF0 EVENTOUT [OSC32_IN]\n"));
+    }
+
+    #[test]
+    fn test_generate_dma_requests() {
+        let groups = vec![dma::DmaGroup {
+            ip: "DMA".into(),
+            version: "STM32L0x_dma_v1_0".into(),
+            mcus: vec!["STM32L071KBTx".into()],
+            requests: vec![
+                dma::DmaRequest {
+                    signal: "USART2_RX".into(),
+                    request: "DMA_REQUEST_4".into(),
+                    channels: vec!["DMA1_Channel5".into(), "DMA1_Channel6".into()],
+                },
+                dma::DmaRequest {
+                    signal: "USART2_TX".into(),
+                    request: "DMA_REQUEST_USART2_TX".into(),
+                    channels: vec!["DMA1_Channel1".into()],
+                },
+            ],
+        }];
+        let artifact = generate_dma_requests(&groups, &HashMap::new(), &FeatureNames::default());
+        assert_eq!(
+            artifact.content,
+            "// DMA-STM32L0x_dma_v1_0\n\
+             // MCUs: STM32L071KBTx\n\
+             #[cfg(any(feature = \"mcu-STM32L071KBTx\"))]\n\
+             dma_requests! {\n\
+             \x20   USART2_RX => {4: [DMA1_Channel5, DMA1_Channel6]},\n\
+             \x20   USART2_TX => {DMA_REQUEST_USART2_TX: [DMA1_Channel1]},\n\
+             }\n\n"
+        );
+    }
+
+    #[test]
+    fn test_cfg_features() {
+        let mut mcu_die_map = HashMap::new();
+        for mcu in &["STM32L071KBTx", "STM32L071CBTx", "STM32L072CZTx"] {
+            mcu_die_map.insert(mcu.to_string(), "DIE447".to_string());
+        }
+        let names = FeatureNames {
+            dies: true,
+            ..FeatureNames::default()
+        };
+        let mcus = |mcus: &[&str]| mcus.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            cfg_features(
+                &mcus(&["STM32L071KBTx", "STM32L071CBTx", "STM32L072CZTx"]),
+                &mcu_die_map,
+                &names
+            ),
+            ["feature = \"die-447\""]
+        );
+        // Not all MCUs of the die, or without a die
+        assert_eq!(
+            cfg_features(
+                &mcus(&["STM32L071KBTx", "STM32L031K6Tx"]),
+                &mcu_die_map,
+                &names
+            ),
+            [
+                "feature = \"mcu-STM32L071KBTx\"",
+                "feature = \"mcu-STM32L031K6Tx\""
+            ]
+        );
+        assert_eq!(
+            cfg_features(
+                &mcus(&["STM32L071KBTx"]),
+                &mcu_die_map,
+                &FeatureNames::default()
+            ),
+            ["feature = \"mcu-STM32L071KBTx\""]
+        );
+    }
+
+    #[test]
+    fn test_generate_clocks() {
+        let groups = vec![rcc::RccGroup {
+            version: "STM32L051_rcc_v1_0".into(),
+            mcus: vec!["STM32L071KBTx".into()],
+            max_frequencies: vec![("SYSCLK".into(), 32_000_000)],
+            prescalers: vec![("AHB".into(), vec![1, 2, 4])],
+        }];
+        let artifact = generate_clocks(&groups, &HashMap::new(), &FeatureNames::default());
+        assert_eq!(
+            artifact.content,
+            "// RCC-STM32L051_rcc_v1_0\n\
+             // MCUs: STM32L071KBTx\n\
+             #[cfg(any(feature = \"mcu-STM32L071KBTx\"))]\n\
+             pub mod clocks {\n\
+             \x20   /// Maximum SYSCLK frequency in Hz\n\
+             \x20   pub const SYSCLK_MAX: u32 = 32000000;\n\
+             \x20   /// Division factors of the AHB prescaler\n\
+             \x20   pub const AHB_DIVIDERS: &[u32] = &[1, 2, 4];\n\
+             }\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_interrupts() {
+        let groups = vec![nvic::NvicGroup {
+            ip: "NVIC".into(),
+            version: "STM32L0xx".into(),
+            mcus: vec!["STM32L071KBTx".into()],
+            interrupts: vec![
+                nvic::Interrupt {
+                    name: "WWDG".into(),
+                    description: "Window watchdog interrupt".into(),
+                },
+                nvic::Interrupt {
+                    name: "USART2".into(),
+                    description: String::new(),
+                },
+            ],
+        }];
+        let artifact = generate_interrupts(&groups, &HashMap::new(), &FeatureNames::default());
+        assert_eq!(
+            artifact.content,
+            "// NVIC-STM32L0xx\n\
+             // MCUs: STM32L071KBTx\n\
+             #[cfg(any(feature = \"mcu-STM32L071KBTx\"))]\n\
+             interrupts! {\n\
+             \x20   WWDG, // Window watchdog interrupt\n\
+             \x20   USART2,\n\
+             }\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_adc_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L071_gpio_v1_0", "mcus": ["STM32L071KBTx"],
+                 "pins": [{"port": "PA", "number": "0", "signals": [],
+                           "raw_signals": [{"name": "ADC12_IN1", "af": "ANALOG"}]},
+                          {"port": "PA", "number": "1", "signals": [],
+                           "raw_signals": [{"name": "ADC1_INN1", "af": "ANALOG"},
+                                           {"name": "ADC1_INP2", "af": "ANALOG"},
+                                           {"name": "USART2_RTS", "af": "AF4"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "1", "signals": [],
+                           "raw_signals": [{"name": "ADC1_INN1", "af": "ANALOG"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        // The STM32L011 only has a negative input, so it gets no block
+        let artifact = generate_adc_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L071\")]\n\
+             adc_pins! {\n\
+             \x20   PA0 => {ADC1: 1, ADC2: 1},\n\
+             \x20   PA1 => {ADC1: 2},\n\
+             }\n\n"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_adc_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32L071\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_dac_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"],
+                 "pins": [{"port": "PA", "number": "4", "signals": [],
+                           "raw_signals": [{"name": "ADC_IN4", "af": "ANALOG"}]}]},
+                {"version": "STM32L071_gpio_v1_0", "mcus": ["STM32L071KBTx"],
+                 "pins": [{"port": "PA", "number": "4", "signals": [],
+                           "raw_signals": [{"name": "ADC_IN4", "af": "ANALOG"},
+                                           {"name": "DAC_OUT1", "af": "ANALOG"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_dac_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L071\")]\n\
+             dac_pins! {\n\
+             \x20   PA4 => {DAC: 1},\n\
+             }\n\n"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_dac_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32L071\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_i2s_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F401_gpio_v1_0", "mcus": ["STM32F401CCUx"],
+                 "pins": [{"port": "PB", "number": "12", "signals": [],
+                           "raw_signals": [{"name": "I2S2_WS", "af": "AF5"},
+                                           {"name": "SPI2_NSS", "af": "AF5"}]},
+                          {"port": "PB", "number": "14", "signals": [],
+                           "raw_signals": [{"name": "I2S2ext_SD", "af": "AF6"}]}]},
+                {"version": "STM32L071_gpio_v1_0", "mcus": ["STM32L071KBTx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_i2s_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("i2s_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32F401\")]\n\
+             i2s_pins! {\n\
+             \x20   PB12 => {AF5: WsPin<I2S2>},\n\
+             \x20   PB14 => {AF6: ExtSdPin<I2S2>},\n\
+             }\n\n"
+        );
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F105_gpio_v1_0", "mcus": ["STM32F105RCTx"],
+                 "pins": [{"port": "PC", "number": "12", "signals": [],
+                           "raw_signals": [{"name": "I2S3_SD", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "SPI3", "mode": "Full"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_i2s_pins(&remapped, &FeatureNames::default()).unwrap_err(),
+            "The I2S pins need numbered alternate functions, PC12 I2S3 has REMAP_FULL"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_i2s_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F401\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_usb_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F407_gpio_v1_0", "mcus": ["STM32F407VGTx"],
+                 "pins": [{"port": "PA", "number": "3", "signals": [],
+                           "raw_signals": [{"name": "USB_OTG_HS_ULPI_D0", "af": "AF10"}]},
+                          {"port": "PA", "number": "12", "signals": [],
+                           "raw_signals": [{"name": "USB_OTG_FS_DP", "af": "AF10"},
+                                           {"name": "USART1_RTS", "af": "AF7"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_usb_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("usb_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32F407\")]\n\
+             usb_pins! {\n\
+             \x20   PA12 => {AF10: DpPin<USB_OTG_FS>},\n\
+             }\n\n\
+             #[cfg(feature = \"io-STM32F407\")]\n\
+             ulpi_pins! {\n\
+             \x20   PA3 => {AF10: UlpiD0Pin<USB_OTG_HS>},\n\
+             }\n\n"
+        );
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F105_gpio_v1_0", "mcus": ["STM32F105RCTx"],
+                 "pins": [{"port": "PA", "number": "12", "signals": [],
+                           "raw_signals": [{"name": "USB_OTG_FS_DP", "af": "REMAP_NONE",
+                                            "remap": {"peripheral": "USB_OTG_FS", "mode": "None"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_usb_pins(&remapped, &FeatureNames::default()).unwrap_err(),
+            "The USB pins need numbered alternate functions, PA12 USB_OTG_FS has REMAP_NONE"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_usb_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F407\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_tsc_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L052_gpio_v1_0", "mcus": ["STM32L052C8Tx"],
+                 "pins": [{"port": "PA", "number": "1", "signals": [],
+                           "raw_signals": [{"name": "TSC_G1_IO2", "af": "AF3"},
+                                           {"name": "TIM2_CH2", "af": "AF2"}]},
+                          {"port": "PB", "number": "8", "signals": [],
+                           "raw_signals": [{"name": "TSC_SYNC", "af": "AF3"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_tsc_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("tsc_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L052\")]\n\
+             tsc_pins! {\n\
+             \x20   PA1 => {AF3: (1, 2)},\n\
+             \x20   PB8 => {AF3: SYNC},\n\
+             }\n\n"
+        );
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PA", "number": "1", "signals": [],
+                           "raw_signals": [{"name": "TSC_G1_IO2", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "TSC", "mode": "Full"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_tsc_pins(&remapped, &FeatureNames::default()).unwrap_err(),
+            "The TSC pins need numbered alternate functions, PA1 has REMAP_FULL"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_tsc_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32L052\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_pwm_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F401_gpio_v1_0", "mcus": ["STM32F401CCUx"],
+                 "pins": [{"port": "PA", "number": "7", "signals": [],
+                           "raw_signals": [{"name": "TIM1_CH1N", "af": "AF1"},
+                                           {"name": "TIM3_CH2", "af": "AF2"}]},
+                          {"port": "PA", "number": "12", "signals": [],
+                           "raw_signals": [{"name": "TIM1_ETR", "af": "AF1"}]},
+                          {"port": "PB", "number": "5", "signals": [],
+                           "raw_signals": [{"name": "TIM3_CH2", "af": "AF2"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_pwm_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("pwm_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32F401\")]\n\
+             mod io_stm32f401 {\n\
+             \x20   use super::*;\n\n\
+             \x20   impl PwmComplementaryChannel<TIM1, C1> for PA7<Alternate<AF1>> {}\n\
+             \x20   impl PwmChannel<TIM3, C2> for PA7<Alternate<AF2>> {}\n\
+             \x20   impl PwmChannel<TIM3, C2> for PB5<Alternate<AF2>> {}\n\
+             }\n\n"
+        );
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PA", "number": "0", "signals": [],
+                           "raw_signals": [{"name": "TIM2_CH1", "af": "REMAP_NONE",
+                                            "remap": {"peripheral": "TIM2", "mode": "None"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_pwm_pins(&remapped, &FeatureNames::default()).unwrap_err(),
+            "The PWM pins need numbered alternate functions, PA0 TIM2 has REMAP_NONE"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_pwm_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F401\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_i2c_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F401_gpio_v1_0", "mcus": ["STM32F401CCUx"],
+                 "pins": [{"port": "PB", "number": "5", "signals": [],
+                           "raw_signals": [{"name": "I2C1_SMBA", "af": "AF4"}]},
+                          {"port": "PB", "number": "7", "signals": [],
+                           "raw_signals": [{"name": "I2C1_SDA", "af": "AF4"},
+                                           {"name": "USART1_RX", "af": "AF7"}]},
+                          {"port": "PB", "number": "8", "signals": [],
+                           "raw_signals": [{"name": "I2C1_SCL", "af": "AF4"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_i2c_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("i2c_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "/// The I2C pins are open-drain outputs, SCL and SDA (and SMBA) need\n\
+             /// pull-up resistors, either external or the internal pull-ups.\n\
+             #[cfg(feature = \"io-STM32F401\")]\n\
+             mod io_stm32f401 {\n\
+             \x20   use super::*;\n\n\
+             \x20   impl SclPin<I2C1> for PB8<Alternate<AF4, OpenDrain>> {}\n\
+             \x20   impl SdaPin<I2C1> for PB7<Alternate<AF4, OpenDrain>> {}\n\
+             \x20   impl SmbaPin<I2C1> for PB5<Alternate<AF4, OpenDrain>> {}\n\
+             }\n\n"
+        );
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PB", "number": "8", "signals": [],
+                           "raw_signals": [{"name": "I2C1_SCL", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "I2C1", "mode": "Full"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_i2c_pins(&remapped, &FeatureNames::default()).unwrap_err(),
+            "The I2C pins need numbered alternate functions, PB8 I2C1 has REMAP_FULL"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_i2c_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F401\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_spi_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F401_gpio_v1_0", "mcus": ["STM32F401CCUx"],
+                 "pins": [{"port": "PA", "number": "4", "signals": [],
+                           "raw_signals": [{"name": "SPI1_NSS", "af": "AF5"}]},
+                          {"port": "PA", "number": "5", "signals": [],
+                           "raw_signals": [{"name": "SPI1_SCK", "af": "AF5"}]},
+                          {"port": "PA", "number": "7", "signals": [],
+                           "raw_signals": [{"name": "SPI1_MOSI", "af": "AF5"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_spi_pins(&tree, &FeatureNames::default(), None).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("spi_pins.rs"));
+        assert!(artifact.content.contains(
+            "impl<SPI, SCK, MISO, MOSI, NSS> Pins<SPI> for (SCK, MISO, MOSI, NSS)\n\
+             where\n\
+             \x20   SCK: SckPin<SPI>,\n"
+        ));
+        assert!(artifact
+            .content
+            .contains("impl<SPI, SCK, MOSI> Pins<SPI> for (SCK, MOSI)\n"));
+        assert!(artifact.content.ends_with(
+            "#[cfg(feature = \"io-STM32F401\")]\n\
+             mod io_stm32f401 {\n\
+             \x20   use super::*;\n\n\
+             \x20   impl SckPin<SPI1> for PA5<Alternate<AF5>> {}\n\
+             \x20   impl MosiPin<SPI1> for PA7<Alternate<AF5>> {}\n\
+             \x20   impl NssPin<SPI1> for PA4<Alternate<AF5>> {}\n\
+             }\n\n"
+        ));
+
+        let artifact = generate_spi_pins(&tree, &FeatureNames::default(), Some("NoMiso")).unwrap();
+        assert!(artifact
+            .content
+            .contains("impl<SPI> MisoPin<SPI> for NoMiso {}\n"));
+        assert!(!artifact.content.contains("for (SCK, MOSI)"));
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PB", "number": "3", "signals": [],
+                           "raw_signals": [{"name": "SPI1_SCK", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "SPI1", "mode": "Full"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_spi_pins(&remapped, &FeatureNames::default(), None).unwrap_err(),
+            "The SPI pins need numbered alternate functions, PB3 SPI1 has REMAP_FULL"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_spi_pins(&tree, &names, None).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F401\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_can_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32G474_gpio_v1_0", "mcus": ["STM32G474RETx"],
+                 "pins": [{"port": "PA", "number": "11", "signals": [],
+                           "raw_signals": [{"name": "FDCAN1_RX", "af": "AF9"}]},
+                          {"port": "PA", "number": "12", "signals": [],
+                           "raw_signals": [{"name": "FDCAN1_TX", "af": "AF9"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_can_pins(&tree, &FeatureNames::default(), &HashMap::new()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("can_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32G474\")]\n\
+             mod io_stm32g474 {\n\
+             \x20   use super::*;\n\n\
+             \x20   impl RxPin<FDCAN1> for PA11<Alternate<AF9>> {}\n\
+             \x20   impl TxPin<FDCAN1> for PA12<Alternate<AF9>> {}\n\
+             }\n\n"
+        );
+
+        let aliases = vec![("FDCAN".to_string(), "CAN".to_string())]
+            .into_iter()
+            .collect();
+        let artifact = generate_can_pins(&tree, &FeatureNames::default(), &aliases).unwrap();
+        assert!(artifact
+            .content
+            .contains("    impl RxPin<CAN1> for PA11<Alternate<AF9>> {}\n"));
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PD", "number": "0", "signals": [],
+                           "raw_signals": [{"name": "CAN_RX", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "CAN", "mode": "Full"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_can_pins(&remapped, &FeatureNames::default(), &HashMap::new()).unwrap_err(),
+            "The CAN pins need numbered alternate functions, PD0 CAN has REMAP_FULL"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_can_pins(&tree, &names, &HashMap::new()).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32G474\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_qspi_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F746_gpio_v1_0", "mcus": ["STM32F746ZGTx"],
+                 "pins": [{"port": "PB", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_CLK", "af": "AF9"}]},
+                          {"port": "PB", "number": "6", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_BK1_NCS", "af": "AF10"}]},
+                          {"port": "PD", "number": "11", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_BK1_IO0", "af": "AF9"}]},
+                          {"port": "PD", "number": "12", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_BK1_IO1", "af": "AF9"}]},
+                          {"port": "PE", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_BK1_IO2", "af": "AF9"}]},
+                          {"port": "PD", "number": "13", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_BK1_IO3", "af": "AF9"}]},
+                          {"port": "PE", "number": "7", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_BK2_IO0", "af": "AF10"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_qspi_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("qspi_pins.rs"));
+        assert!(artifact.content.starts_with(
+            "/// The pins of a quad SPI memory on QUADSPI bank 1.\n\
+             #[cfg(feature = \"io-STM32F746\")]\n\
+             pub struct QspiBank1Pins<CLK, NCS, IO0, IO1, IO2, IO3> {\n"
+        ));
+        assert!(artifact.content.contains(
+            "    pub fn new(clk: CLK, ncs: NCS, io0: IO0, io1: IO1, io2: IO2, io3: IO3) -> Self {\n"
+        ));
+        assert!(artifact.content.contains("    IO3: Bk1Io3Pin<QUADSPI>,\n"));
+        // Bank 2 lacks most of its IOs
+        assert!(!artifact.content.contains("QspiBank2Pins"));
+        assert!(artifact
+            .content
+            .contains("    impl Bk2Io0Pin<QUADSPI> for PE7<Alternate<AF10>> {}\n"));
+        assert!(!artifact.content.contains("STM32L011"));
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PB", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "QUADSPI_CLK", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "QUADSPI", "mode": "Full"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_qspi_pins(&remapped, &FeatureNames::default()).unwrap_err(),
+            "The QUADSPI and OCTOSPI pins need numbered alternate functions, PB2 QUADSPI has REMAP_FULL"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_qspi_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F746\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_fmc_pins() {
+        let pin = |pin: &str, signal: &str| {
+            format!(
+                r#"{{"port": "{}", "number": "{}", "signals": [],
+                     "raw_signals": [{{"name": "{}", "af": "AF12"}}]}}"#,
+                &pin[..2],
+                &pin[2..],
+                signal
+            )
+        };
+        let pins = [
+            pin("PC0", "FMC_SDNWE"),
+            pin("PC2", "FMC_SDNE0"),
+            pin("PC3", "FMC_SDCKE0"),
+            pin("PF11", "FMC_SDNRAS"),
+            pin("PG4", "FMC_BA0"),
+            pin("PG8", "FMC_SDCLK"),
+            pin("PG15", "FMC_SDNCAS"),
+            pin("PD7", "FMC_NE1"),
+        ];
+        // The second group only has the SDRAM clock, so it gets no bundle
+        let tree = af_tree::AfTree::from_json(&format!(
+            r#"{{"groups": [
+                {{"version": "STM32F429_gpio_v1_0", "mcus": ["STM32F429ZITx"],
+                  "pins": [{}]}},
+                {{"version": "STM32F469_gpio_v1_0", "mcus": ["STM32F469ZITx"],
+                  "pins": [{}]}},
+                {{"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"], "pins": []}}
+            ]}}"#,
+            pins.join(", "),
+            pin("PG8", "FMC_SDCLK")
+        ))
+        .unwrap();
+        let artifact = generate_fmc_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("fmc_pins.rs"));
+        // Only the bundles with all pins available, the NOR bank 1 lacks NOE
+        // and NWE
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32F429\")]\n\
+             mod io_stm32f429 {\n\
+             \x20   use super::*;\n\n\
+             \x20   impl BA0 for PG4<Alternate<AF12>> {}\n\
+             \x20   impl NE1 for PD7<Alternate<AF12>> {}\n\
+             \x20   impl SDCKE0 for PC3<Alternate<AF12>> {}\n\
+             \x20   impl SDCLK for PG8<Alternate<AF12>> {}\n\
+             \x20   impl SDNCAS for PG15<Alternate<AF12>> {}\n\
+             \x20   impl SDNE0 for PC2<Alternate<AF12>> {}\n\
+             \x20   impl SDNRAS for PF11<Alternate<AF12>> {}\n\
+             \x20   impl SDNWE for PC0<Alternate<AF12>> {}\n\n\
+             \x20   impl<P0> SdramBankAddressPins for (P0,)\n\
+             \x20   where\n\
+             \x20       P0: BA0,\n\
+             \x20   {\n\
+             \x20   }\n\n\
+             \x20   impl<P0, P1, P2, P3> SdramControlPins for (P0, P1, P2, P3)\n\
+             \x20   where\n\
+             \x20       P0: SDCLK,\n\
+             \x20       P1: SDNWE,\n\
+             \x20       P2: SDNRAS,\n\
+             \x20       P3: SDNCAS,\n\
+             \x20   {\n\
+             \x20   }\n\n\
+             \x20   impl<P0, P1> SdramBank1Pins for (P0, P1)\n\
+             \x20   where\n\
+             \x20       P0: SDCKE0,\n\
+             \x20       P1: SDNE0,\n\
+             \x20   {\n\
+             \x20   }\n\
+             }\n\n\
+             #[cfg(feature = \"io-STM32F469\")]\n\
+             mod io_stm32f469 {\n\
+             \x20   use super::*;\n\n\
+             \x20   impl SDCLK for PG8<Alternate<AF12>> {}\n\
+             }\n\n"
+        );
+
+        let remapped = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PD", "number": "4", "signals": [],
+                           "raw_signals": [{"name": "FSMC_NOE", "af": "REMAP_NONE",
+                                            "remap": {"peripheral": "FSMC", "mode": "None"}}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            generate_fmc_pins(&remapped, &FeatureNames::default()).unwrap_err(),
+            "The FMC pins need numbered alternate functions, PD4 FSMC has REMAP_NONE"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_fmc_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F429\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_remap_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32F103x8_gpio_v1_0", "mcus": ["STM32F103C8Tx"],
+                 "pins": [{"port": "PA", "number": "9", "signals": [],
+                           "raw_signals": [{"name": "USART1_TX", "af": "REMAP_NONE",
+                                            "remap": {"peripheral": "USART1", "mode": "None"}}]},
+                          {"port": "PB", "number": "6", "signals": [],
+                           "raw_signals": [{"name": "USART1_TX", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "USART1", "mode": "Full"}}]},
+                          {"port": "PB", "number": "7", "signals": [],
+                           "raw_signals": [{"name": "USART1_RX", "af": "REMAP_FULL",
+                                            "remap": {"peripheral": "USART1", "mode": "Full"}}]}]},
+                {"version": "STM32L071_gpio_v1_0", "mcus": ["STM32L071KBTx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_remap_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("afio_remaps.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32F103x8\")]\n\
+             afio_remaps! {\n\
+             \x20   USART1: None => {PA9: USART1_TX},\n\
+             \x20   USART1: Full => {PB6: USART1_TX, PB7: USART1_RX},\n\
+             }\n\n"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_remap_pins(&tree, &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32F103x8\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_peripheral_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L071_gpio_v1_0", "mcus": ["STM32L071KBTx"],
+                 "pins": [{"port": "PA", "number": "0", "raw_signals": [],
+                           "signals": [{"af": "AF4", "io": "CtsPin", "device": "USART2", "define": null}]},
+                          {"port": "PA", "number": "2", "raw_signals": [],
+                           "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]},
+                          {"port": "PA", "number": "3", "raw_signals": [],
+                           "signals": [{"af": "AF4", "io": "RxPin", "device": "USART2", "define": null}]},
+                          {"port": "PB", "number": "6", "raw_signals": [],
+                           "signals": [{"af": "AF1", "io": "SclPin", "device": "I2C1", "define": null}]},
+                          {"port": "PB", "number": "10", "raw_signals": [],
+                           "signals": [{"af": "AF6", "io": "SclPin", "device": "I2C2", "define": null}]},
+                          {"port": "PB", "number": "11", "raw_signals": [],
+                           "signals": [{"af": "AF6", "io": "SdaPin", "device": "I2C2", "define": null}]},
+                          {"port": "PA", "number": "9", "raw_signals": [],
+                           "signals": [{"af": "AF4", "io": "TxPin", "device": "USART1", "define": null}]},
+                          {"port": "PA", "number": "10", "raw_signals": [],
+                           "signals": [{"af": "AF4", "io": "RxPin", "device": "USART1", "define": null}]},
+                          {"port": "PA", "number": "12", "raw_signals": [],
+                           "signals": [{"af": "AF4", "io": "RtsPin", "device": "USART1", "define": null},
+                                       {"af": "AF4", "io": "DePin", "device": "USART1", "define": null}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"], "pins": []}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact = generate_peripheral_pins(&tree, &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("peripheral_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L071\")]\n\
+             mod io_stm32l071 {\n\
+             \x20   use super::*;\n\
+             \n\
+             \x20   // I2C1: incomplete, missing SdaPin\n\
+             \n\
+             \x20   impl<SCL, SDA> Pins<I2C2> for (SCL, SDA)\n\
+             \x20   where\n\
+             \x20       SCL: SclPin<I2C2>,\n\
+             \x20       SDA: SdaPin<I2C2>,\n\
+             \x20   {\n\
+             \x20   }\n\
+             \n\
+             \x20   impl<TX, RX> Pins<USART1> for (TX, RX)\n\
+             \x20   where\n\
+             \x20       TX: TxPin<USART1>,\n\
+             \x20       RX: RxPin<USART1>,\n\
+             \x20   {\n\
+             \x20   }\n\
+             \n\
+             \x20   impl<TX, RX, RTS> Pins<USART1> for (TX, RX, RTS)\n\
+             \x20   where\n\
+             \x20       TX: TxPin<USART1>,\n\
+             \x20       RX: RxPin<USART1>,\n\
+             \x20       RTS: RtsPin<USART1>,\n\
+             \x20   {\n\
+             \x20   }\n\
+             \n\
+             \x20   impl<TX, RX, DE> Pins<USART1> for (TX, RX, DE)\n\
+             \x20   where\n\
+             \x20       TX: TxPin<USART1>,\n\
+             \x20       RX: RxPin<USART1>,\n\
+             \x20       DE: DePin<USART1>,\n\
+             \x20   {\n\
+             \x20   }\n\
+             \n\
+             \x20   impl<TX, RX> Pins<USART2> for (TX, RX)\n\
+             \x20   where\n\
+             \x20       TX: TxPin<USART2>,\n\
+             \x20       RX: RxPin<USART2>,\n\
+             \x20   {\n\
+             \x20   }\n\
+             \n\
+             \x20   impl<TX, RX, CTS> Pins<USART2> for (TX, RX, CTS)\n\
+             \x20   where\n\
+             \x20       TX: TxPin<USART2>,\n\
+             \x20       RX: RxPin<USART2>,\n\
+             \x20       CTS: CtsPin<USART2>,\n\
+             \x20   {\n\
+             \x20   }\n\
+             }\n\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_analog_function_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L071_gpio_v1_0", "mcus": ["STM32L071KBTx"],
+                 "pins": [{"port": "PA", "number": "0", "signals": [],
+                           "raw_signals": [{"name": "COMP1_INM", "af": "ANALOG"},
+                                           {"name": "COMP1_OUT", "af": "AF7"}]},
+                          {"port": "PA", "number": "3", "signals": [],
+                           "raw_signals": [{"name": "COMP2_INP", "af": "ANALOG"},
+                                           {"name": "OPAMP1_VOUT", "af": "ANALOG"}]}]},
+                {"version": "STM32L011_gpio_v1_0", "mcus": ["STM32L011K4Tx"],
+                 "pins": [{"port": "PA", "number": "2", "signals": [],
+                           "raw_signals": [{"name": "USART2_TX", "af": "AF4"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let artifact =
+            generate_analog_function_pins(&tree, "COMP", &FeatureNames::default()).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("comp_pins.rs"));
+        assert_eq!(
+            artifact.content,
+            "#[cfg(feature = \"io-STM32L071\")]\n\
+             comp_pins! {\n\
+             \x20   PA0 => {COMP1: INM, COMP1: OUT},\n\
+             \x20   PA3 => {COMP2: INP},\n\
+             }\n\n"
+        );
+
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            ..FeatureNames::default()
+        };
+        let artifact = generate_analog_function_pins(&tree, "COMP", &names).unwrap();
+        assert!(artifact
+            .content
+            .contains("#[cfg(feature = \"gpio-STM32L071\")]\n"));
+        assert!(!artifact.content.contains("\"io-"));
+    }
+
+    #[test]
+    fn test_generate_mcu_info() {
+        let xml = r#"
+            <Mcu Family="STM32L0" Package="LQFP32" RefName="STM32L071K(B-Z)Tx">
+                <Core>Arm Cortex-M0+</Core>
+                <Frequency>32</Frequency>
+                <E2prom>6144</E2prom>
+                <Ram>20</Ram>
+                <Flash>128</Flash>
+                <Flash>192</Flash>
+            </Mcu>
+        "#;
+        let mcus = [(
+            "STM32L071KBTx".to_string(),
+            serde_xml_rs::deserialize(xml.as_bytes()).unwrap(),
+        )];
+        let artifact = generate_mcu_info(&mcus, DumpFormat::Toml).unwrap();
+        assert_eq!(artifact.path, PathBuf::from("mcus.toml"));
+        assert_eq!(
+            artifact.content,
+            "[[mcu]]\n\
+             ref_name = \"STM32L071KBTx\"\n\
+             package = \"LQFP32\"\n\
+             flash = 128\n\
+             ram = [20]\n\
+             eeprom = 6144\n\
+             cores = [\"cortex-m0plus\"]\n\
+             max_frequency = [32]\n"
+        );
+        let artifact = generate_mcu_info(&mcus, DumpFormat::Json).unwrap();
+        assert!(artifact
+            .content
+            .contains("\"max_frequency\": [\n      32\n    ]"));
+    }
+
+    #[test]
+    fn test_generate_pinouts() {
+        let pinout = |mcu: &str, pins: &[(&str, &str)]| package::Pinout {
+            mcu: mcu.into(),
+            package: "LQFP32".into(),
+            pins: pins
+                .iter()
+                .map(|(position, name)| package::PackagePin {
+                    position: position.to_string(),
+                    name: name.to_string(),
+                    gpio: Some(name.to_string()).filter(|name| name.starts_with('P')),
+                    pin_type: match *name {
+                        "NRST" => package::PinType::Reset,
+                        _ => package::PinType::Io,
+                    },
+                })
+                .collect(),
+        };
+        let artifact = generate_pinouts(&[
+            pinout("STM32L071KBTx", &[("6", "PA0")]),
+            pinout(
+                "STM32L031K6Tx",
+                &[("4", "NRST"), ("6", "PA0"), ("8", "PA2")],
+            ),
+        ]);
+        assert_eq!(
+            artifact.content,
+            "STM32L031K6Tx (LQFP32)\n\
+             \x20   4 NRST (Reset)\n\
+             \x20   6 PA0\n\
+             \x20   8 PA2\n\
+             STM32L071KBTx (LQFP32)\n\
+             \x20   6 PA0\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_board_pins() {
+        let tree = af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L031_gpio_v1_0", "mcus": ["STM32L031K6Tx"],
+                 "pins": [{"port": "PB", "number": "3", "signals": [],
+                           "raw_signals": [{"name": "TIM2_CH2", "af": "AF2"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let board = board::Board::parse(
+            "board=NUCLEO-L031K6\n\
+             Mcu.Pin0=PB3\n\
+             Mcu.UserName=STM32L031K6Tx\n\
+             PB3.GPIO_Label=LD3 [Green]\n\
+             PB3.Signal=GPIO_Output\n",
+        )
+        .unwrap();
+        let artifact = generate_board_pins(&board, tree.groups().first());
+        assert_eq!(
+            artifact.content,
+            "NUCLEO-L031K6 (STM32L031K6Tx)\n\
+             \x20   LD3 [Green]: PB3 (GPIO_Output)\n\
+             \x20       AF2 TIM2_CH2\n"
+        );
+    }
+
+    #[test]
+    fn test_generate_manifest() {
+        let artifacts = vec![
+            GeneratedArtifact::new("features.toml", ArtifactKind::Features, String::new()),
+            GeneratedArtifact::new(
+                "src/pin_mappings.rs",
+                ArtifactKind::PinMappings,
+                String::new(),
+            ),
+        ];
+        let manifest = generate_manifest(&["STM32L0"], Some("6.0.0"), 3, &artifacts);
+        assert_eq!(manifest.kind, ArtifactKind::Manifest);
+        let content = manifest.content.split_once('\n').unwrap().1;
+        let content = content.replace(env!("CARGO_PKG_VERSION"), "x");
+        assert_eq!(
+            content,
+            "cube_parse_version = \"x\"\n\
+             db_release = \"6.0.0\"\n\
+             families = [\"STM32L0\"]\n\
+             mcus = 3\n\
+             files = [\n    \"features.toml\",\n    \"src/pin_mappings.rs\",\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_alias_feature_sets() {
+        let any = "#[cfg(any(feature = \"mcu-STM32L031K6Tx\", feature = \"mcu-STM32L071KBTx\"))]";
+        let mut artifacts = vec![
+            GeneratedArtifact::new(
+                "features.toml",
+                ArtifactKind::Features,
+                "# MCUs\n\
+                 mcu-STM32L031K6Tx = [\"io-STM32L031\"]\n\
+                 mcu-STM32L052C8Tx = [\"io-STM32L051\"]\n\
+                 mcu-STM32L071KBTx = [\"io-STM32L071\"]\n"
+                    .to_string(),
+            ),
+            GeneratedArtifact::new(
+                "pin_mappings.rs",
+                ArtifactKind::PinMappings,
+                format!(
+                    "{}\nA\n{}\nB\n\
+                     #[cfg(any(feature = \"mcu-STM32L052C8Tx\", feature = \"mcu-STM32L071KBTx\"))]\nC\n",
+                    any, any
+                ),
+            ),
+        ];
+        assert_eq!(
+            alias_feature_sets(&mut artifacts, &FeatureNames::default()),
+            Ok(1)
+        );
+        assert_eq!(
+            artifacts[0].content,
+            "# MCUs\n\
+             mcu-STM32L031K6Tx = [\"io-STM32L031\", \"io-group-1\"]\n\
+             mcu-STM32L052C8Tx = [\"io-STM32L051\"]\n\
+             mcu-STM32L071KBTx = [\"io-STM32L071\", \"io-group-1\"]\n\n\
+             # Aliases of recurring feature sets, enabled by their features\n\
+             io-group-1 = []\n"
+        );
+        assert_eq!(
+            artifacts[1].content,
+            "#[cfg(feature = \"io-group-1\")]\nA\n\
+             #[cfg(feature = \"io-group-1\")]\nB\n\
+             #[cfg(any(feature = \"mcu-STM32L052C8Tx\", feature = \"mcu-STM32L071KBTx\"))]\nC\n"
+        );
+    }
+}
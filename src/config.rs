@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_derive::Deserialize;
+
+use cube_parse::features::FeatureNames;
+
+/// Settings read from a configuration file (e.g. `cube-parse.toml`), so that
+/// a generation run can be committed to a repository and reproduced.
+///
+/// Every setting can be overridden on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Path to the CubeMX MCU database directory.
+    pub db_dir: Option<PathBuf>,
+    /// The MCU family to extract (e.g. "STM32L0").
+    pub family: Option<String>,
+    /// Additional MCU families to extract together with `family`.
+    pub families: Vec<String>,
+    /// Only include MCUs whose ref name starts with one of these values (e.g.
+    /// `["STM32L071"]`).
+    pub mcus: Option<Vec<String>>,
+    /// Only include the MCUs listed in this file, one ref name per line.
+    pub mcu_list: Option<PathBuf>,
+    /// Only include MCUs in one of these packages (e.g. `["LQFP32"]`).
+    pub packages: Option<Vec<String>>,
+    /// The peripheral stems to include (e.g. `["USART", "I2C"]`).
+    pub stems: Option<Vec<String>>,
+    /// Pins reserved by the board, which are omitted (e.g. `["PA13", "PA14"]`).
+    pub reserved_pins: Vec<String>,
+    /// Omit the pins used by this CubeMX project file (.ioc).
+    pub ioc: Option<PathBuf>,
+    /// Only include the signals listed in this file, one name per line.
+    pub signals_from: Option<PathBuf>,
+    /// Exclude the signals listed in this file, one name per line.
+    pub exclude_signals_from: Option<PathBuf>,
+    /// Composite stem definitions (e.g. `["SERIAL=USART|UART|LPUART"]`).
+    pub composite_stems: Vec<String>,
+    /// The directory to write the generated files to.
+    pub output_dir: Option<PathBuf>,
+    pub features: FeatureNames,
+    pub codegen: Codegen,
+}
+
+impl Config {
+    /// Load a configuration file. Relative paths in the file are relative to
+    /// the directory containing it.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let mut config: Config = toml::from_str(&contents)
+            .map_err(|e| format!("Could not parse {}: {}", path.display(), e))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        config.db_dir = config.db_dir.map(|dir| base.join(dir));
+        config.mcu_list = config.mcu_list.map(|file| base.join(file));
+        config.output_dir = config.output_dir.map(|dir| base.join(dir));
+        config.ioc = config.ioc.map(|file| base.join(file));
+        config.signals_from = config.signals_from.map(|file| base.join(file));
+        config.exclude_signals_from = config.exclude_signals_from.map(|file| base.join(file));
+        for file in config.codegen.sub_family_overrides.values_mut() {
+            *file = base.join(&file);
+        }
+        Ok(config)
+    }
+}
+
+/// Settings of the generated code.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Codegen {
+    /// Annotate the pin mappings with the C HAL AF define names.
+    pub af_defines: bool,
+    /// Document the other pins providing the signals of a pin.
+    pub alternatives: bool,
+    /// Warn in doc comments on the pins with a debug function after reset.
+    pub debug_pin_docs: bool,
+    /// Define the IO traits with a sealed supertrait.
+    pub sealed_traits: bool,
+    /// Gate the pins missing in some packages by package features.
+    pub package_gating: bool,
+    /// Also generate a compile test of the pin mappings.
+    pub emit_tests: bool,
+    /// The path imported by the compile test (e.g.
+    /// "stm32l0xx_hal::prelude::*").
+    pub test_prelude: Option<String>,
+    /// The pin type of the compile test, `{pin}` and `{port}` are replaced by
+    /// the pin name and port letter (default: "{pin}").
+    pub test_pin_type: Option<String>,
+    /// Also map the serial CTS, RTS, CK and DE signals.
+    pub serial_handshake: bool,
+    /// Treat the UART and LPUART stems as USART.
+    pub merge_serial_stems: bool,
+    /// Do not list the MCUs of each GPIO version in comments.
+    pub no_mcu_docs: bool,
+    /// How the pin mappings are gated ("io", "mcu" or "bare").
+    pub cfg_style: Option<String>,
+    /// The prefix of the bare cfgs of the "bare" cfg style.
+    pub cfg_prefix: Option<String>,
+    /// Replace recurring `cfg(any(...))` feature sets by alias features in
+    /// the output of `generate-all`.
+    pub alias_feature_sets: bool,
+    /// What to do if a pin provides the same signal on multiple AFs ("all",
+    /// "lowest" or "error").
+    pub af_policy: Option<String>,
+    /// The layout of the generated code ("gpio-version" or "sub-family").
+    pub layout: Option<String>,
+    /// The macro style of the pin mappings ("default", "f4xx", "l0xx",
+    /// "embassy" or "table").
+    pub style: Option<String>,
+    /// Files with the hand-maintained module of a sub-family (e.g.
+    /// `{ "STM32L0x3" = "overrides/stm32l0x3.rs" }`), for the sub-family
+    /// layout.
+    pub sub_family_overrides: HashMap<String, PathBuf>,
+    /// Rename the instances of peripheral stems in the generated CAN pins
+    /// (e.g. `{ FDCAN = "CAN" }`).
+    pub stem_aliases: HashMap<String, String>,
+    /// A directory with templates overriding the built-in ones (e.g.
+    /// `pin_group.rs.hbs`).
+    pub template_dir: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config: Config = toml::from_str(
+            r#"
+            family = "STM32L4"
+            families = ["STM32L4+"]
+            stems = ["USART", "SERIAL"]
+            composite_stems = ["SERIAL=USART|LPUART"]
+
+            [codegen]
+            af_defines = true
+            layout = "sub-family"
+
+            [codegen.sub_family_overrides]
+            STM32L0x3 = "overrides/stm32l0x3.rs"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.family.as_deref(), Some("STM32L4"));
+        assert_eq!(config.families, ["STM32L4+"]);
+        assert_eq!(config.composite_stems.len(), 1);
+        assert_eq!(config.features.io, "io-{}");
+        assert!(config.codegen.af_defines);
+        assert!(config.codegen.af_policy.is_none());
+        assert_eq!(config.codegen.layout.as_deref(), Some("sub-family"));
+        assert_eq!(
+            config.codegen.sub_family_overrides["STM32L0x3"],
+            PathBuf::from("overrides/stm32l0x3.rs")
+        );
+
+        assert!(toml::from_str::<Config>("famliy = \"STM32L0\"").is_err());
+    }
+}
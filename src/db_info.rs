@@ -0,0 +1,92 @@
+//! Metadata of a CubeMX MCU database.
+
+use std::{fs, path::Path};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::family::Families;
+
+lazy_static! {
+    static ref RELEASE: Regex = Regex::new(r#"\bRelease="([^"]+)""#).unwrap();
+    static ref VERSION: Regex = Regex::new(r#"<\w+[^>]*\b[Vv]ersion="([^"]+)""#).unwrap();
+}
+
+/// An overview of a CubeMX MCU database (e.g. `db/mcu`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbInfo {
+    /// The database release (e.g. "DB.6.0.50"), if it could be determined.
+    pub release: Option<String>,
+    pub families: usize,
+    pub mcus: usize,
+    /// The number of IP files.
+    pub ip_files: usize,
+    /// The number of GPIO Modes files, i.e. GPIO IP versions.
+    pub gpio_files: usize,
+}
+
+impl DbInfo {
+    /// Collect the metadata of the database in `db_dir`.
+    pub fn load<P: AsRef<Path>>(db_dir: P, families: &Families) -> Result<Self, String> {
+        let db_dir = db_dir.as_ref();
+        let ip_dir = db_dir.join("IP");
+        let ip_files = fs::read_dir(&ip_dir)
+            .map_err(|e| format!("Could not read {}: {}", ip_dir.display(), e))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".xml"))
+            .collect::<Vec<_>>();
+        Ok(Self {
+            release: release(db_dir),
+            families: families.into_iter().count(),
+            mcus: families.into_iter().map(|f| f.mcus().count()).sum(),
+            gpio_files: ip_files
+                .iter()
+                .filter(|name| name.starts_with("GPIO-") && name.ends_with("_Modes.xml"))
+                .count(),
+            ip_files: ip_files.len(),
+        })
+    }
+}
+
+/// Return the database release, read from the `package.xml` manifest that
+/// CubeMX installs next to the MCU database directory (`db/package.xml` for
+/// `db/mcu`).
+pub fn release<P: AsRef<Path>>(db_dir: P) -> Option<String> {
+    let db_dir = db_dir.as_ref();
+    let manifest = [db_dir.join("package.xml"), db_dir.join("../package.xml")]
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())?;
+    parse_release(&manifest)
+}
+
+/// Parse the release from a package manifest: the `Release` attribute if
+/// present, otherwise the version of the first element that has one.
+fn parse_release(manifest: &str) -> Option<String> {
+    RELEASE
+        .captures(manifest)
+        .or_else(|| VERSION.captures(manifest))
+        .map(|c| c[1].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_release() {
+        assert_eq!(
+            parse_release(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+                <Package version="6.2.1"><PackDescription Release="DB.6.0.21"/></Package>"#
+            )
+            .as_deref(),
+            Some("DB.6.0.21")
+        );
+        assert_eq!(
+            parse_release(r#"<?xml version="1.0"?><Package Version="6.2.1"/>"#).as_deref(),
+            Some("6.2.1")
+        );
+        assert_eq!(parse_release("<Package/>"), None);
+    }
+}
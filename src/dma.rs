@@ -0,0 +1,202 @@
+//! DMA request mappings.
+//!
+//! The DMA IP Modes files (e.g. `IP/DMA-STM32L0x_dma_v1_0_Modes.xml`) list, for
+//! every peripheral signal that can trigger a transfer (e.g. "USART2_RX"), the
+//! DMA channels or streams it is connected to and the request to select. On
+//! parts with a fixed request mapping, the request is numbered (e.g.
+//! `DMA_REQUEST_4`). On parts with a DMAMUX, any channel can serve any request
+//! and the request is given by its C HAL define name (e.g.
+//! `DMA_REQUEST_USART2_RX`), as the database does not contain the request
+//! numbers.
+
+use std::error::Error;
+use std::path::Path;
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::internal_peripheral::PossibleValue;
+use crate::utils::load_file;
+
+/// The names of the DMA controller IPs in the MCU files.
+pub const DMA_IPS: &[&str] = &["DMA", "BDMA"];
+
+lazy_static! {
+    static ref REQUEST_NUMBER: Regex = Regex::new(r"^DMA_REQUEST_(\d+)$").unwrap();
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Parameter {
+    name: String,
+    #[serde(default)]
+    possible_value: Vec<PossibleValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RefMode {
+    name: String,
+    #[serde(default)]
+    base_mode: Option<String>,
+    #[serde(default)]
+    parameter: Vec<Parameter>,
+}
+
+impl RefMode {
+    fn values(&self, name: &str) -> Vec<String> {
+        self.parameter
+            .iter()
+            .filter(|p| p.name == name)
+            .flat_map(|p| p.possible_value.iter().map(|v| v.val.clone()))
+            .collect()
+    }
+}
+
+/// A DMA IP Modes file.
+#[derive(Debug, Deserialize)]
+#[serde(rename = "IP")]
+pub struct IpDma {
+    #[serde(rename = "RefMode", default)]
+    ref_mode: Vec<RefMode>,
+}
+
+impl IpDma {
+    /// Load the Modes file of a DMA IP (e.g. "DMA") version.
+    pub fn load<P: AsRef<Path>>(
+        db_dir: P,
+        ip: &str,
+        version: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        load_file(db_dir, Self::path(ip, version))
+    }
+
+    /// The path of the Modes file of a DMA IP version, relative to the database
+    /// directory (e.g. "IP/DMA-STM32L0x_dma_v1_0_Modes.xml").
+    pub fn path(ip: &str, version: &str) -> String {
+        format!("IP/{}-{}_Modes.xml", ip, version)
+    }
+
+    /// Return the DMA requests, sorted by signal name.
+    pub fn requests(&self) -> Vec<DmaRequest> {
+        let mut requests = self
+            .ref_mode
+            .iter()
+            .filter(|mode| mode.base_mode.as_deref() == Some("DMA_Request"))
+            .filter_map(|mode| {
+                let request = mode.values("Request").into_iter().next()?;
+                Some(DmaRequest {
+                    signal: mode.name.clone(),
+                    request,
+                    channels: mode.values("Instance"),
+                })
+            })
+            .collect::<Vec<_>>();
+        requests.sort_by(|a, b| compare_str(&a.signal, &b.signal));
+        requests
+    }
+}
+
+/// A peripheral signal that can trigger DMA transfers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmaRequest {
+    /// The signal (e.g. "USART2_RX").
+    pub signal: String,
+    /// The C HAL define name of the request (e.g. "DMA_REQUEST_4").
+    pub request: String,
+    /// The channels or streams the request is connected to (e.g.
+    /// "DMA1_Channel5").
+    pub channels: Vec<String>,
+}
+
+impl DmaRequest {
+    /// Return the request number, unless the request is routed through a
+    /// DMAMUX.
+    pub fn number(&self) -> Option<u32> {
+        REQUEST_NUMBER
+            .captures(&self.request)
+            .and_then(|c| c[1].parse().ok())
+    }
+}
+
+/// The DMA requests of a DMA IP version and the MCUs using it.
+#[derive(Debug)]
+pub struct DmaGroup {
+    /// The DMA IP (e.g. "DMA").
+    pub ip: String,
+    /// The IP version (e.g. "STM32L0x_dma_v1_0").
+    pub version: String,
+    /// The ref names of the MCUs using this version.
+    pub mcus: Vec<String>,
+    pub requests: Vec<DmaRequest>,
+}
+
+impl DmaGroup {
+    pub fn load<P: AsRef<Path>>(
+        db_dir: P,
+        ip: &str,
+        version: &str,
+        mcus: Vec<String>,
+    ) -> Result<Self, String> {
+        let requests = IpDma::load(db_dir, ip, version)
+            .map_err(|e| format!("Could not load {}: {}", IpDma::path(ip, version), e))?
+            .requests();
+        Ok(Self {
+            ip: ip.to_string(),
+            version: version.to_string(),
+            mcus,
+            requests,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests() {
+        let xml = r#"
+            <IP DBVersion="V4.0" IPType="service" Name="DMA" Version="STM32L0x_dma_v1_0">
+                <RefParameter Name="Request" Type="list">
+                    <PossibleValue Value="DMA_REQUEST_0"/>
+                </RefParameter>
+                <RefMode BaseMode="DMA_Request" Name="USART2_RX">
+                    <Parameter Name="Instance">
+                        <PossibleValue>DMA1_Channel5</PossibleValue>
+                        <PossibleValue>DMA1_Channel6</PossibleValue>
+                    </Parameter>
+                    <Parameter Name="Request">
+                        <PossibleValue>DMA_REQUEST_4</PossibleValue>
+                    </Parameter>
+                </RefMode>
+                <RefMode BaseMode="DMA_Request" Name="ADC">
+                    <Parameter Name="Instance">
+                        <PossibleValue>DMA1_Channel1</PossibleValue>
+                    </Parameter>
+                    <Parameter Name="Request">
+                        <PossibleValue>DMA_REQUEST_0</PossibleValue>
+                    </Parameter>
+                </RefMode>
+                <RefMode Name="DMA_Request"/>
+            </IP>
+        "#;
+        let ip: IpDma = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let requests = ip.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].signal, "ADC");
+        assert_eq!(requests[0].number(), Some(0));
+        assert_eq!(requests[1].signal, "USART2_RX");
+        assert_eq!(requests[1].number(), Some(4));
+        assert_eq!(requests[1].channels, ["DMA1_Channel5", "DMA1_Channel6"]);
+
+        let dmamux = DmaRequest {
+            signal: "USART2_RX".into(),
+            request: "DMA_REQUEST_USART2_RX".into(),
+            channels: vec![],
+        };
+        assert_eq!(dmamux.number(), None);
+    }
+}
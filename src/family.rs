@@ -1,9 +1,10 @@
 use std::error::Error;
 use std::path::Path;
 
+use regex::Regex;
 use serde_derive::Deserialize;
 
-use crate::utils::load_file;
+use crate::utils::{edit_distance, load_file, wildcard_regex};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -61,14 +62,121 @@ pub struct SubFamily {
 #[serde(rename_all = "PascalCase")]
 pub struct Mcu {
     pub name: String,
+    /// The package (e.g. "LQFP32"), empty if the database does not specify it.
+    #[serde(default)]
     pub package_name: String,
     pub ref_name: String,
+    /// The root part number (e.g. "STM32L071KB").
+    #[serde(rename = "RPN", default)]
+    pub rpn: String,
+}
+
+/// Return whether a package name (e.g. "LQFP64") matches a pattern, ignoring
+/// case. The pattern may contain `*` and `?` wildcards (e.g. "LQFP*").
+pub fn package_matches(package: &str, pattern: &str) -> bool {
+    wildcard_regex(pattern, true).is_match(package)
+}
+
+impl Mcu {
+    /// Return whether the MCU comes in a package matching the pattern (e.g.
+    /// "LQFP*"), see `package_matches`.
+    pub fn in_package(&self, pattern: &str) -> bool {
+        package_matches(&self.package_name, pattern)
+    }
 }
 
 impl Families {
     pub fn load<P: AsRef<Path>>(db_dir: P) -> Result<Self, Box<dyn Error>> {
         load_file(db_dir, "families.xml")
     }
+
+    /// Find the family with the specified name (e.g. "STM32L0").
+    pub fn find(&self, name: &str) -> Option<&Family> {
+        self.families.iter().find(|f| f.name == name)
+    }
+
+    /// Find a family by name, ignoring case and an omitted "STM32" prefix (e.g.
+    /// "stm32l0" or "L0" for "STM32L0").
+    ///
+    /// If no family matches, the error suggests the family with the most
+    /// similar name, if any is close enough.
+    pub fn lookup(&self, name: &str) -> Result<&Family, String> {
+        if let Some(family) = self.find(name) {
+            return Ok(family);
+        }
+        let mut wanted = name.to_uppercase();
+        if !wanted.starts_with("STM32") {
+            wanted.insert_str(0, "STM32");
+        }
+        if let Some(family) = self
+            .families
+            .iter()
+            .find(|f| f.name.to_uppercase() == wanted)
+        {
+            return Ok(family);
+        }
+        let suggestion = self
+            .families
+            .iter()
+            .map(|f| (edit_distance(&wanted, &f.name.to_uppercase()), f))
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance);
+        match suggestion {
+            Some((_, f)) => Err(format!(
+                "Could not find family {} (did you mean {}?)",
+                name, f.name
+            )),
+            None => Err(format!("Could not find family {}", name)),
+        }
+    }
+
+    /// Find all families whose name starts with the specified prefix,
+    /// ignoring case (e.g. "stm32l" matches "STM32L0", "STM32L4" etc).
+    pub fn find_fuzzy(&self, prefix: &str) -> Vec<&Family> {
+        let prefix = prefix.to_lowercase();
+        self.families
+            .iter()
+            .filter(|f| f.name.to_lowercase().starts_with(&prefix))
+            .collect()
+    }
+
+    /// Iterate over the sub-families of all families, together with the
+    /// family they belong to.
+    pub fn sub_families(&self) -> impl Iterator<Item = (&Family, &SubFamily)> {
+        self.families
+            .iter()
+            .flat_map(|f| f.sub_families.iter().map(move |sf| (f, sf)))
+    }
+
+    /// Return the MCUs of all families whose ref name matches the regex.
+    pub fn mcus_matching(&self, re: &Regex) -> Vec<&Mcu> {
+        self.families
+            .iter()
+            .flat_map(|f| f.mcus_matching(re))
+            .collect()
+    }
+}
+
+impl Family {
+    /// The sub-families of this family.
+    pub fn sub_families(&self) -> &[SubFamily] {
+        &self.sub_families
+    }
+
+    /// Find the sub-family with the specified name (e.g. "STM32L0x1").
+    pub fn find_sub_family(&self, name: &str) -> Option<&SubFamily> {
+        self.sub_families.iter().find(|sf| sf.name == name)
+    }
+
+    /// Iterate over the MCUs of all sub-families.
+    pub fn mcus(&self) -> impl Iterator<Item = &Mcu> {
+        self.sub_families.iter().flat_map(|sf| sf.mcus.iter())
+    }
+
+    /// Return the MCUs of this family whose ref name matches the regex.
+    pub fn mcus_matching(&self, re: &Regex) -> Vec<&Mcu> {
+        self.mcus().filter(|m| re.is_match(&m.ref_name)).collect()
+    }
 }
 
 impl<'a> IntoIterator for &'a Families {
@@ -97,3 +205,91 @@ impl<'a> IntoIterator for &'a SubFamily {
         self.mcus.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn families() -> Families {
+        let xml = r#"
+            <Families>
+                <Family Name="STM32L0">
+                    <SubFamily Name="STM32L0x1">
+                        <Mcu Name="STM32L031K(4-6)Tx" PackageName="LQFP32" RefName="STM32L031K6Tx" RPN="STM32L031K6"/>
+                        <Mcu Name="STM32L071KB(B-Z)Tx" PackageName="LQFP32" RefName="STM32L071KBTx" RPN="STM32L071KB"/>
+                    </SubFamily>
+                    <SubFamily Name="STM32L0x2">
+                        <Mcu Name="STM32L052C(6-8)Tx" PackageName="LQFP48" RefName="STM32L052C8Tx" RPN="STM32L052C8"/>
+                    </SubFamily>
+                </Family>
+                <Family Name="STM32L4">
+                    <SubFamily Name="STM32L4x1">
+                        <Mcu Name="STM32L431CBTx" PackageName="LQFP48" RefName="STM32L431CBTx" RPN="STM32L431CB"/>
+                    </SubFamily>
+                </Family>
+            </Families>
+        "#;
+        serde_xml_rs::deserialize(xml.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_package_matches() {
+        assert!(package_matches("LQFP64", "LQFP64"));
+        assert!(package_matches("LQFP64", "lqfp64"));
+        assert!(package_matches("LQFP64", "LQFP*"));
+        assert!(package_matches("UFQFPN48", "*48"));
+        assert!(!package_matches("LQFP64", "LQFP6"));
+        assert!(!package_matches("UFQFPN48", "LQFP*"));
+    }
+
+    #[test]
+    fn test_search() {
+        let families = families();
+
+        assert_eq!(families.find("STM32L0").unwrap().name, "STM32L0");
+        assert!(families.find("stm32l0").is_none());
+        assert_eq!(families.find_fuzzy("stm32l").len(), 2);
+        assert_eq!(families.find_fuzzy("STM32L4")[0].name, "STM32L4");
+
+        assert_eq!(families.lookup("STM32L0").unwrap().name, "STM32L0");
+        assert_eq!(families.lookup("stm32l0").unwrap().name, "STM32L0");
+        assert_eq!(families.lookup("l4").unwrap().name, "STM32L4");
+        assert_eq!(
+            families.lookup("STM32L0X").unwrap_err(),
+            "Could not find family STM32L0X (did you mean STM32L0?)"
+        );
+        assert_eq!(
+            families.lookup("L4+").unwrap_err(),
+            "Could not find family L4+ (did you mean STM32L4?)"
+        );
+        assert_eq!(
+            families.lookup("STM32MP1").unwrap_err(),
+            "Could not find family STM32MP1"
+        );
+
+        let sub_families = families
+            .sub_families()
+            .map(|(f, sf)| format!("{}/{}", f.name, sf.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sub_families,
+            vec![
+                "STM32L0/STM32L0x1",
+                "STM32L0/STM32L0x2",
+                "STM32L4/STM32L4x1"
+            ]
+        );
+
+        let re = Regex::new("^STM32L0.1").unwrap();
+        let mcus = families
+            .mcus_matching(&re)
+            .iter()
+            .map(|m| m.rpn.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(mcus, vec!["STM32L031K6", "STM32L071KB"]);
+
+        let l0 = families.find("STM32L0").unwrap();
+        assert_eq!(l0.mcus().count(), 3);
+        assert_eq!(l0.find_sub_family("STM32L0x2").unwrap().mcus.len(), 1);
+    }
+}
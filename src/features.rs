@@ -0,0 +1,303 @@
+//! The names of the Cargo features generated by cube-parse.
+//!
+//! HAL build scripts and other tooling can use these functions to compute the
+//! exact feature names that cube-parse emits.
+
+use serde_derive::Deserialize;
+
+use crate::gpio_version::GpioVersion;
+
+/// Convert a GPIO IP version (e.g. "STM32L152x8_gpio_v1_0") to a feature name
+/// (e.g. "io-STM32L152x8") using the default naming scheme.
+pub fn gpio_version_to_feature(version: &str) -> Result<String, String> {
+    FeatureNames::default().io_feature(version)
+}
+
+/// Convert a MCU ref name (e.g. "STM32L071KBTx") to a feature name (e.g.
+/// "mcu-STM32L071KBTx") using the default naming scheme.
+pub fn feature_name_for_mcu(ref_name: &str) -> String {
+    FeatureNames::default().mcu_feature(ref_name)
+}
+
+/// Convert the package name of a MCU (e.g. "LQFP32") to a feature name (e.g.
+/// "package-lqfp32") using the default naming scheme.
+pub fn package_feature_name(package: &str, ref_name: &str) -> String {
+    FeatureNames::default().package_feature(package, ref_name)
+}
+
+/// The naming scheme of the generated Cargo features.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureNames {
+    /// The name of the IO features, `{}` is replaced by the GPIO version stem
+    /// (default: "io-{}").
+    pub io: String,
+    /// The name of the MCU features, `{}` is replaced by the MCU ref name
+    /// (default: "mcu-{}").
+    pub mcu: String,
+    /// The name of the package features, `{}` is replaced by the normalized
+    /// package name (default: "package-{}").
+    pub package: String,
+    /// The name of the sub-family features of the sub-family layout, `{}` is
+    /// replaced by the normalized sub-family name (default: "{}").
+    pub sub_family: String,
+    /// The name of the peripheral features, `{}` is replaced by the normalized
+    /// peripheral instance name (default: "has-{}").
+    pub peripheral: String,
+    /// The name of the die features, `{}` is replaced by the die number
+    /// (default: "die-{}").
+    pub die: String,
+    /// The name of the aliases of recurring feature sets (see
+    /// `codegen::alias_feature_sets`), `{}` is replaced by their number
+    /// (default: "io-group-{}").
+    pub group: String,
+    /// Additionally define the lowercase package names (e.g. "lqfp32") as
+    /// aliases for the package features.
+    pub legacy_packages: bool,
+    /// Additionally define core features (e.g. "cortex-m4" and "dual-core"),
+    /// which the MCU features enable.
+    pub cores: bool,
+    /// Additionally define one feature per peripheral instance (e.g.
+    /// "has-usart3"), which the MCU features with that peripheral enable.
+    pub peripherals: bool,
+    /// Additionally define one feature per silicon die (e.g. "die-447"),
+    /// which the MCU features with that die enable. The generated code is
+    /// then gated by the die features where all MCUs of a die share it.
+    pub dies: bool,
+}
+
+impl Default for FeatureNames {
+    fn default() -> Self {
+        Self {
+            io: "io-{}".to_string(),
+            mcu: "mcu-{}".to_string(),
+            package: "package-{}".to_string(),
+            sub_family: "{}".to_string(),
+            peripheral: "has-{}".to_string(),
+            die: "die-{}".to_string(),
+            group: "io-group-{}".to_string(),
+            legacy_packages: false,
+            cores: false,
+            peripherals: false,
+            dies: false,
+        }
+    }
+}
+
+impl FeatureNames {
+    /// Convert a GPIO IP version (e.g. "STM32L152x8_gpio_v1_0") to a feature
+    /// name (e.g. "io-STM32L152x8"). Versions other than 1.0 are part of the
+    /// name (e.g. "io-STM32F333-v1_1").
+    pub fn io_feature(&self, version: &str) -> Result<String, String> {
+        let gpio_version: GpioVersion = version.parse()?;
+        Ok(self.io.replace("{}", &gpio_version.feature_stem()))
+    }
+
+    /// Convert a MCU ref name (e.g. "STM32L071KBTx") to a feature name (e.g.
+    /// "mcu-STM32L071KBTx").
+    pub fn mcu_feature(&self, mcu: &str) -> String {
+        self.mcu.replace("{}", mcu)
+    }
+
+    /// Convert a peripheral instance name (e.g. "USB_OTG_FS") to a feature
+    /// name (e.g. "has-usb-otg-fs").
+    pub fn peripheral_feature(&self, instance: &str) -> String {
+        let name = instance
+            .to_lowercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+        self.peripheral.replace("{}", &name)
+    }
+
+    /// Convert a die (e.g. "DIE447") to a feature name (e.g. "die-447").
+    pub fn die_feature(&self, die: &str) -> String {
+        let number = match die.get(..3) {
+            Some(prefix) if prefix.eq_ignore_ascii_case("DIE") => &die[3..],
+            _ => die,
+        };
+        self.die.replace("{}", &number.to_lowercase())
+    }
+
+    /// Return the name of the alias of a recurring feature set (e.g.
+    /// "io-group-3").
+    pub fn group_feature(&self, number: usize) -> String {
+        self.group.replace("{}", &number.to_string())
+    }
+
+    /// Convert the package name of a MCU (e.g. "LQFP32") to a feature name
+    /// (e.g. "package-lqfp32").
+    ///
+    /// The package names include the pin count (e.g. "LQFP32" or "SO8N"). Only
+    /// if one does not, the pin count encoded in the MCU ref name is appended
+    /// (see `pin_count`).
+    pub fn package_feature(&self, package: &str, mcu: &str) -> String {
+        let mut name = package
+            .to_lowercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+        if !name.contains(|c: char| c.is_ascii_digit()) {
+            if let Some(pins) = pin_count(mcu) {
+                name.push_str(&pins.to_string());
+            }
+        }
+        self.package.replace("{}", &name)
+    }
+
+    /// Convert a sub-family name (e.g. "STM32F0x0 Value Line") to a feature
+    /// name (e.g. "stm32f0x0-value-line").
+    pub fn sub_family_feature(&self, sub_family: &str) -> String {
+        let name = sub_family
+            .to_lowercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+        self.sub_family.replace("{}", &name)
+    }
+}
+
+/// Return the pin count encoded in a MCU ref name (e.g. 32 for the "K" in
+/// "STM32L071KBTx").
+///
+/// Some letters stand for different pin counts in different families (e.g.
+/// "J" for the 8 pins of the STM32G031J6Mx), and the newer ones are missing,
+/// so the pin count of the package name is preferred.
+pub fn pin_count(mcu: &str) -> Option<u32> {
+    let pins = match mcu.chars().nth(9)? {
+        'A' => 169,
+        'B' => 208,
+        'C' => 48,
+        'D' => 14,
+        'E' => 25,
+        'F' => 20,
+        'G' => 28,
+        'H' => 40,
+        'I' => 176,
+        'J' => 72,
+        'K' => 32,
+        'M' => 80,
+        'N' => 216,
+        'O' => 90,
+        'Q' => 132,
+        'R' => 64,
+        'S' => 44,
+        'T' => 36,
+        'U' => 63,
+        'V' => 100,
+        'X' => 256,
+        'Z' => 144,
+        _ => return None,
+    };
+    Some(pins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_feature() {
+        let names = FeatureNames::default();
+
+        // Success
+        assert_eq!(
+            names.io_feature("STM32L152x8_gpio_v1_0").unwrap(),
+            "io-STM32L152x8"
+        );
+        assert_eq!(
+            names.io_feature("STM32F333_gpio_v1_0").unwrap(),
+            "io-STM32F333"
+        );
+
+        // Versions other than 1.0
+        assert_eq!(
+            names.io_feature("STM32F333_gpio_v1_1").unwrap(),
+            "io-STM32F333-v1_1"
+        );
+        assert_eq!(
+            names.io_feature("STM32U5_gpio_v2_0").unwrap(),
+            "io-STM32U5-v2_0"
+        );
+
+        // Error parsing, wrong pattern
+        assert!(names.io_feature("STM32F333_qqio_v1_0").is_err());
+
+        // Error parsing, too many underscores
+        assert!(names.io_feature("STM32_STM32F333_gpio_v1_0").is_err());
+
+        // Custom naming scheme
+        let names = FeatureNames {
+            io: "gpio-{}".to_string(),
+            mcu: "{}".to_string(),
+            ..FeatureNames::default()
+        };
+        assert_eq!(
+            names.io_feature("STM32F333_gpio_v1_0").unwrap(),
+            "gpio-STM32F333"
+        );
+        assert_eq!(names.mcu_feature("STM32L071KBTx"), "STM32L071KBTx");
+    }
+
+    #[test]
+    fn test_package_feature() {
+        let names = FeatureNames::default();
+        assert_eq!(
+            names.package_feature("LQFP32", "STM32L071KBTx"),
+            "package-lqfp32"
+        );
+        assert_eq!(
+            names.package_feature("UFQFPN48", "STM32L071CBUx"),
+            "package-ufqfpn48"
+        );
+        assert_eq!(
+            names.package_feature("WLCSP", "STM32L071CZYx"),
+            "package-wlcsp48"
+        );
+        assert_eq!(names.package_feature("WLCSP", "STM32"), "package-wlcsp");
+        assert_eq!(
+            names.package_feature("SO8N", "STM32G031J6Mx"),
+            "package-so8n"
+        );
+        assert_eq!(
+            names.package_feature("WLCSP18", "STM32G031Y8Yx"),
+            "package-wlcsp18"
+        );
+    }
+
+    #[test]
+    fn test_peripheral_feature() {
+        let names = FeatureNames::default();
+        assert_eq!(names.peripheral_feature("USART3"), "has-usart3");
+        assert_eq!(names.peripheral_feature("USB_OTG_FS"), "has-usb-otg-fs");
+    }
+
+    #[test]
+    fn test_die_feature() {
+        let names = FeatureNames::default();
+        assert_eq!(names.die_feature("DIE447"), "die-447");
+        assert_eq!(names.die_feature("die4A6"), "die-4a6");
+        assert_eq!(names.die_feature("447"), "die-447");
+    }
+
+    #[test]
+    fn test_default_names() {
+        assert_eq!(
+            gpio_version_to_feature("STM32L051_gpio_v1_0").unwrap(),
+            "io-STM32L051"
+        );
+        assert_eq!(feature_name_for_mcu("STM32L071KBTx"), "mcu-STM32L071KBTx");
+        assert_eq!(
+            package_feature_name("LQFP32", "STM32L071KBTx"),
+            "package-lqfp32"
+        );
+        assert_eq!(pin_count("STM32L071KBTx"), Some(32));
+        assert_eq!(pin_count("STM32L071"), None);
+    }
+
+    #[test]
+    fn test_sub_family_feature() {
+        let mut names = FeatureNames::default();
+        assert_eq!(names.sub_family_feature("STM32L0x1"), "stm32l0x1");
+        assert_eq!(
+            names.sub_family_feature("STM32F0x0 Value Line"),
+            "stm32f0x0-value-line"
+        );
+        names.sub_family = "subfamily-{}".to_string();
+        assert_eq!(names.sub_family_feature("STM32L0x1"), "subfamily-stm32l0x1");
+    }
+}
@@ -0,0 +1,176 @@
+//! Flexible memory controller (FMC) signals.
+//!
+//! The FMC (named FSMC on the older families) connects external SRAM, NOR,
+//! NAND and SDRAM memories through dozens of pins: the address lines (e.g.
+//! "FMC_A12"), the data lines ("FMC_D31"), the byte lane enables ("FMC_NBL1"),
+//! the chip selects of the NOR/SRAM banks ("FMC_NE2") and SDRAM banks
+//! ("FMC_SDNE0"), and the control signals (e.g. "FMC_NOE" or "FMC_SDNRAS").
+//! Every signal has its own IO trait named after the signal (e.g. `A12`), and
+//! the memories are configured with bundles of them (see `fmc_bundles`).
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// FMC signals, e.g. "FMC_A0", "FSMC_NE1", "FMC_SDCKE1", or "FMC_A17_ALE"
+    /// for a pin shared by two functions.
+    static ref FMC_SIGNAL: Regex = Regex::new(r"^(FS?MC)_([A-Z]+\d*(?:_[A-Z]+\d*)*)$").unwrap();
+}
+
+/// An FMC signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FmcSignal {
+    /// The peripheral ("FMC" or "FSMC").
+    pub device: String,
+    /// The function, which is also the name of its IO trait (e.g. "A12",
+    /// "NBL0" or "SDNE1").
+    pub function: String,
+}
+
+/// Parse an FMC signal into its functions (e.g. "FMC_A17_ALE" into `A17` and
+/// `ALE`). Other signals have none.
+pub fn parse_fmc_signal(signal: &str) -> Vec<FmcSignal> {
+    let captures = match FMC_SIGNAL.captures(signal) {
+        Some(captures) => captures,
+        None => return vec![],
+    };
+    captures[2]
+        .split('_')
+        .map(|function| FmcSignal {
+            device: captures[1].to_string(),
+            function: function.to_string(),
+        })
+        .collect()
+}
+
+/// An FMC signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FmcPin {
+    /// The pin (e.g. "PF0").
+    pub pin: String,
+    /// The alternate function (e.g. "AF12").
+    pub af: String,
+    pub signal: FmcSignal,
+}
+
+/// Return the FMC signals of the pins of a GPIO group, sorted by function and
+/// pin.
+pub fn fmc_pins(group: &GpioGroup) -> Vec<FmcPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            for signal in parse_fmc_signal(raw.name()) {
+                pins.push(FmcPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.sort_by(|a, b| {
+        compare_str(&a.signal.function, &b.signal.function)
+            .then_with(|| compare_str(&a.pin, &b.pin))
+    });
+    pins.dedup();
+    pins
+}
+
+/// A bundle of FMC pins configured together (e.g. the 12 address lines of an
+/// SDRAM).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FmcBundle {
+    /// The name of the bundle trait (e.g. "AddressPins").
+    pub name: String,
+    /// The functions of the pins, in tuple order (e.g. "A0" to "A11").
+    pub functions: Vec<String>,
+}
+
+impl FmcBundle {
+    fn new<S: Into<String>>(name: &str, functions: Vec<S>) -> Self {
+        Self {
+            name: name.to_string(),
+            functions: functions.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// A bus of `width` numbered lines (e.g. "D0" to "D15").
+    fn bus(name: &str, prefix: &str, width: u8) -> Self {
+        Self::new(
+            name,
+            (0..width).map(|n| format!("{}{}", prefix, n)).collect(),
+        )
+    }
+}
+
+/// Return the pin bundles of the common memory configurations:
+///
+/// - the address buses of 11 to 13 lines (the SDRAM row addresses), the data
+///   buses of 8, 16 and 32 lines, the byte lane enables of 16 and 32 bit
+///   buses and the SDRAM internal bank addresses,
+/// - the SDRAM control signals,
+/// - per bank, the chip select with its control signals (NOR/SRAM banks 1 to
+///   4, SDRAM banks 1 and 2).
+pub fn fmc_bundles() -> Vec<FmcBundle> {
+    let mut bundles = vec![];
+    for width in 11..=13 {
+        bundles.push(FmcBundle::bus("AddressPins", "A", width));
+    }
+    for width in [8, 16, 32] {
+        bundles.push(FmcBundle::bus("DataPins", "D", width));
+    }
+    for width in [2, 4] {
+        bundles.push(FmcBundle::bus("ByteLanePins", "NBL", width));
+    }
+    for width in [1, 2] {
+        bundles.push(FmcBundle::bus("SdramBankAddressPins", "BA", width));
+    }
+    bundles.push(FmcBundle::new(
+        "SdramControlPins",
+        vec!["SDCLK", "SDNWE", "SDNRAS", "SDNCAS"],
+    ));
+    for bank in 1..=4 {
+        bundles.push(FmcBundle::new(
+            &format!("NorBank{}Pins", bank),
+            vec![format!("NE{}", bank), "NOE".into(), "NWE".into()],
+        ));
+    }
+    for bank in 1..=2 {
+        bundles.push(FmcBundle::new(
+            &format!("SdramBank{}Pins", bank),
+            vec![format!("SDCKE{}", bank - 1), format!("SDNE{}", bank - 1)],
+        ));
+    }
+    bundles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fmc_signal() {
+        let functions = |signal| {
+            parse_fmc_signal(signal)
+                .into_iter()
+                .map(|s| s.function)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(functions("FMC_A12"), vec!["A12"]);
+        assert_eq!(functions("FMC_SDNE0"), vec!["SDNE0"]);
+        assert_eq!(functions("FMC_A17_ALE"), vec!["A17", "ALE"]);
+        assert_eq!(parse_fmc_signal("FSMC_NOE")[0].device, "FSMC");
+        assert!(parse_fmc_signal("QUADSPI_CLK").is_empty());
+    }
+
+    #[test]
+    fn test_fmc_bundles() {
+        let bundles = fmc_bundles();
+        assert_eq!(bundles[0].name, "AddressPins");
+        assert_eq!(bundles[0].functions.len(), 11);
+        assert!(bundles.contains(&FmcBundle::new("SdramBank2Pins", vec!["SDCKE1", "SDNE1"])));
+    }
+}
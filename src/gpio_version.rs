@@ -0,0 +1,94 @@
+use std::{fmt, str::FromStr};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref GPIO_VERSION: Regex = Regex::new(r"^([^_]*)_gpio_v(\d+)_(\d+)$").unwrap();
+}
+
+/// A GPIO IP version (e.g. "STM32L152x8_gpio_v1_0").
+///
+/// The GPIO IP version determines the pin function mapping of a MCU. It
+/// consists of a MCU stem (e.g. "STM32L152x8") and a version number (e.g.
+/// 1.0).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GpioVersion {
+    stem: String,
+    major: u32,
+    minor: u32,
+}
+
+impl GpioVersion {
+    /// The MCU stem (e.g. "STM32L152x8").
+    pub fn stem(&self) -> &str {
+        &self.stem
+    }
+
+    /// The version number as `(major, minor)` (e.g. `(1, 0)`).
+    pub fn version(&self) -> (u32, u32) {
+        (self.major, self.minor)
+    }
+
+    /// The stem of the feature name: the MCU stem, followed by the version
+    /// number if it is not 1.0 (e.g. "STM32L152x8" or "STM32F333-v1_1").
+    pub fn feature_stem(&self) -> String {
+        match self.version() {
+            (1, 0) => self.stem.clone(),
+            (major, minor) => format!("{}-v{}_{}", self.stem, major, minor),
+        }
+    }
+
+    /// The feature name (e.g. "io-STM32L152x8").
+    pub fn to_feature(&self) -> String {
+        format!("io-{}", self.feature_stem())
+    }
+}
+
+impl FromStr for GpioVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let captures = GPIO_VERSION
+            .captures(s)
+            .ok_or_else(|| format!("Could not parse version {:?}", s))?;
+        let number = |i: usize| {
+            captures[i]
+                .parse()
+                .map_err(|_| format!("Could not parse version {:?}", s))
+        };
+        Ok(Self {
+            stem: captures[1].to_string(),
+            major: number(2)?,
+            minor: number(3)?,
+        })
+    }
+}
+
+impl fmt::Display for GpioVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}_gpio_v{}_{}", self.stem, self.major, self.minor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let version: GpioVersion = "STM32L152x8_gpio_v1_0".parse().unwrap();
+        assert_eq!(version.stem(), "STM32L152x8");
+        assert_eq!(version.version(), (1, 0));
+        assert_eq!(version.to_feature(), "io-STM32L152x8");
+        assert_eq!(version.to_string(), "STM32L152x8_gpio_v1_0");
+
+        let version: GpioVersion = "STM32F333_gpio_v1_1".parse().unwrap();
+        assert_eq!(version.version(), (1, 1));
+        assert_eq!(version.feature_stem(), "STM32F333-v1_1");
+        assert_eq!(version.to_feature(), "io-STM32F333-v1_1");
+
+        assert!("STM32F333_qqio_v1_0".parse::<GpioVersion>().is_err());
+        assert!("STM32_STM32F333_gpio_v1_0".parse::<GpioVersion>().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use cube_parse::family::Family;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref MCU_FEATURE: Regex = Regex::new(r#"^\s*"?mcu-([A-Za-z0-9_()+-]+)"?\s*="#).unwrap();
+}
+
+/// Return the MCU ref names of all `mcu-*` features defined in a Cargo.toml.
+fn previous_mcus(cargo_toml: &str) -> Vec<String> {
+    cargo_toml
+        .lines()
+        .filter_map(|line| MCU_FEATURE.captures(line))
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// Return the MCUs with a `mcu-*` feature in a Cargo.toml that are not in any
+/// of the families.
+fn dropped_mcus(cargo_toml: &str, families: &[&Family]) -> Vec<String> {
+    let current = families
+        .iter()
+        .flat_map(|family| family.mcus())
+        .map(|mcu| mcu.ref_name.as_str())
+        .collect::<HashSet<_>>();
+    previous_mcus(cargo_toml)
+        .into_iter()
+        .filter(|mcu| !current.contains(mcu.as_str()))
+        .collect()
+}
+
+/// Make sure that every MCU with a `mcu-*` feature in the specified Cargo.toml
+/// is still supported.
+///
+/// This prevents silently dropping support for MCUs when regenerating with a
+/// newer CubeMX database. The MCUs are compared with all MCUs of the families
+/// in the database, so MCUs left out by the MCU filters (e.g. `--package`) or
+/// skipped because their data could not be loaded are not reported.
+pub fn check_no_mcus_dropped<P: AsRef<Path>>(
+    cargo_toml: P,
+    families: &[&Family],
+) -> Result<(), String> {
+    let cargo_toml = cargo_toml.as_ref();
+    let contents = fs::read_to_string(cargo_toml)
+        .map_err(|e| format!("Could not read {}: {}", cargo_toml.display(), e))?;
+    let missing = dropped_mcus(&contents, families);
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} MCU(s) from {} are missing in the database: {}",
+            missing.len(),
+            cargo_toml.display(),
+            missing.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_previous_mcus() {
+        let cargo_toml = r#"
+[features]
+io-STM32L031 = []
+lqfp32 = []
+mcu-STM32L031K6Tx = ["io-STM32L031", "stm32l0x1", "lqfp32"]
+  mcu-STM32L071KBTx = ["io-STM32L071"]
+"mcu-STM32L4S5VITx" = []
+# mcu-STM32L011D3Px = []
+"#;
+        assert_eq!(
+            previous_mcus(cargo_toml),
+            vec!["STM32L031K6Tx", "STM32L071KBTx", "STM32L4S5VITx"]
+        );
+    }
+
+    #[test]
+    fn test_dropped_mcus() {
+        let families: cube_parse::family::Families = serde_xml_rs::deserialize(
+            r#"<Families>
+                <Family Name="STM32L0">
+                    <SubFamily Name="STM32L0x1">
+                        <Mcu Name="STM32L031K(4-6)Tx" PackageName="LQFP32" RefName="STM32L031K6Tx"/>
+                        <Mcu Name="STM32L071KB(B-Z)Tx" PackageName="LQFP32" RefName="STM32L071KBTx"/>
+                    </SubFamily>
+                </Family>
+            </Families>"#
+                .as_bytes(),
+        )
+        .unwrap();
+        let families = [families.find("STM32L0").unwrap()];
+
+        // Both MCUs are in the family, even when generating for one of them
+        // only (e.g. with `--mcu STM32L031`).
+        let cargo_toml = "mcu-STM32L031K6Tx = []\nmcu-STM32L071KBTx = []\n";
+        assert!(dropped_mcus(cargo_toml, &families).is_empty());
+
+        let cargo_toml = "mcu-STM32L031K6Tx = []\nmcu-STM32L011D3Px = []\n";
+        assert_eq!(dropped_mcus(cargo_toml, &families), vec!["STM32L011D3Px"]);
+    }
+}
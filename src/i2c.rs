@@ -0,0 +1,119 @@
+//! I2C and SMBus signals.
+//!
+//! Besides the clock and data lines, most I2C instances have an SMBus alert
+//! line ("I2C1_SMBA"). The fast mode plus instances of some families are
+//! named "FMPI2C" (e.g. "FMPI2C1_SDA"). All of them are open-drain, so HALs
+//! configure their pins differently from the push-pull alternate functions.
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// I2C signals, e.g. "I2C1_SCL", "I2C3_SMBA" or "FMPI2C1_SDA".
+    static ref I2C_SIGNAL: Regex = Regex::new(r"^((?:FMP)?I2C\d+)_(SCL|SDA|SMBA)$").unwrap();
+}
+
+/// The function of an I2C pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum I2cFunction {
+    /// The clock line ("SCL").
+    Scl,
+    /// The data line ("SDA").
+    Sda,
+    /// The SMBus alert line ("SMBA").
+    Smba,
+}
+
+/// An I2C signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct I2cSignal {
+    /// The I2C instance (e.g. "I2C1").
+    pub device: String,
+    pub function: I2cFunction,
+}
+
+impl I2cSignal {
+    /// The name of the IO trait of the pin (e.g. "SdaPin").
+    pub fn io_trait(&self) -> &'static str {
+        match self.function {
+            I2cFunction::Scl => "SclPin",
+            I2cFunction::Sda => "SdaPin",
+            I2cFunction::Smba => "SmbaPin",
+        }
+    }
+}
+
+/// Parse an I2C signal (e.g. "I2C1_SDA" into `I2C1` and `Sda`).
+pub fn parse_i2c_signal(signal: &str) -> Option<I2cSignal> {
+    let captures = I2C_SIGNAL.captures(signal)?;
+    let function = match &captures[2] {
+        "SCL" => I2cFunction::Scl,
+        "SDA" => I2cFunction::Sda,
+        _ => I2cFunction::Smba,
+    };
+    Some(I2cSignal {
+        device: captures[1].to_string(),
+        function,
+    })
+}
+
+/// An I2C signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct I2cPin {
+    /// The pin (e.g. "PB7").
+    pub pin: String,
+    /// The alternate function (e.g. "AF4").
+    pub af: String,
+    pub signal: I2cSignal,
+}
+
+/// Return the I2C signals of the pins of a GPIO group, sorted by instance,
+/// function and pin.
+pub fn i2c_pins(group: &GpioGroup) -> Vec<I2cPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            if let Some(signal) = parse_i2c_signal(raw.name()) {
+                pins.push(I2cPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.sort_by(|a, b| {
+        compare_str(&a.signal.device, &b.signal.device)
+            .then_with(|| a.signal.function.cmp(&b.signal.function))
+            .then_with(|| compare_str(&a.pin, &b.pin))
+    });
+    pins.dedup();
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_i2c_signal() {
+        let signal = parse_i2c_signal("I2C1_SDA").unwrap();
+        assert_eq!(signal.device, "I2C1");
+        assert_eq!(signal.function, I2cFunction::Sda);
+        assert_eq!(signal.io_trait(), "SdaPin");
+
+        let signal = parse_i2c_signal("FMPI2C1_SCL").unwrap();
+        assert_eq!(signal.device, "FMPI2C1");
+        assert_eq!(signal.io_trait(), "SclPin");
+
+        assert_eq!(
+            parse_i2c_signal("I2C3_SMBA").map(|s| s.function),
+            Some(I2cFunction::Smba)
+        );
+        assert!(parse_i2c_signal("I2S2_SD").is_none());
+        assert!(parse_i2c_signal("USART2_TX").is_none());
+    }
+}
@@ -0,0 +1,160 @@
+//! I2S signals.
+//!
+//! The I2S peripherals share the SPI instances of the same number (e.g. I2S2
+//! is SPI2 in I2S mode), but their signals have their own names (e.g.
+//! "I2S2_WS"). Some families add a full-duplex extension instance whose
+//! signals (e.g. "I2S2ext_SD") belong to the I2S instance it extends.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// I2S signals, e.g. "I2S2_WS", "I2S2ext_SD", "I2S1_SDO" or the external
+    /// clock input "I2S_CKIN".
+    static ref I2S_SIGNAL: Regex =
+        Regex::new(r"^I2S(?:(\d+)(ext)?_(CK|WS|SD|SDI|SDO|MCK)|_(CKIN))$").unwrap();
+}
+
+/// The function of an I2S pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum I2sFunction {
+    /// The serial clock ("CK").
+    Ck,
+    /// The word select ("WS").
+    Ws,
+    /// The serial data ("SD").
+    Sd,
+    /// The serial data input of full-duplex instances ("SDI").
+    Sdi,
+    /// The serial data output of full-duplex instances ("SDO").
+    Sdo,
+    /// The master clock output ("MCK").
+    Mck,
+    /// The external clock input ("I2S_CKIN").
+    CkIn,
+}
+
+/// An I2S signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct I2sSignal {
+    /// The I2S instance (e.g. "I2S2", also for "I2S2ext_SD"), or "I2S" for
+    /// the external clock input shared by all instances.
+    pub instance: String,
+    /// Whether this is a signal of the full-duplex extension instance (e.g.
+    /// "I2S2ext").
+    pub ext: bool,
+    pub function: I2sFunction,
+}
+
+impl I2sSignal {
+    /// The SPI instance the I2S instance is part of (e.g. "SPI2" for "I2S2").
+    pub fn spi_instance(&self) -> Option<String> {
+        let number = self.instance.strip_prefix("I2S")?;
+        if number.is_empty() {
+            return None;
+        }
+        Some(format!("SPI{}", number))
+    }
+
+    /// The name of the IO trait of the pin (e.g. "WsPin", or "ExtSdPin" for
+    /// the data pin of the extension instance).
+    pub fn io_trait(&self) -> &'static str {
+        match (self.function, self.ext) {
+            (I2sFunction::Ck, _) => "CkPin",
+            (I2sFunction::Ws, _) => "WsPin",
+            (I2sFunction::Sd, false) => "SdPin",
+            (I2sFunction::Sd, true) => "ExtSdPin",
+            (I2sFunction::Sdi, _) => "SdiPin",
+            (I2sFunction::Sdo, _) => "SdoPin",
+            (I2sFunction::Mck, _) => "MckPin",
+            (I2sFunction::CkIn, _) => "CkInPin",
+        }
+    }
+}
+
+/// Parse an I2S signal (e.g. "I2S2ext_SD" into `I2S2`, `ext` and `Sd`).
+pub fn parse_i2s_signal(signal: &str) -> Option<I2sSignal> {
+    let captures = I2S_SIGNAL.captures(signal)?;
+    if captures.get(4).is_some() {
+        return Some(I2sSignal {
+            instance: "I2S".to_string(),
+            ext: false,
+            function: I2sFunction::CkIn,
+        });
+    }
+    let function = match &captures[3] {
+        "CK" => I2sFunction::Ck,
+        "WS" => I2sFunction::Ws,
+        "SD" => I2sFunction::Sd,
+        "SDI" => I2sFunction::Sdi,
+        "SDO" => I2sFunction::Sdo,
+        _ => I2sFunction::Mck,
+    };
+    Some(I2sSignal {
+        instance: format!("I2S{}", &captures[1]),
+        ext: captures.get(2).is_some(),
+        function,
+    })
+}
+
+/// An I2S signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct I2sPin {
+    /// The pin (e.g. "PB12").
+    pub pin: String,
+    /// The alternate function (e.g. "AF5").
+    pub af: String,
+    pub signal: I2sSignal,
+}
+
+/// Return the I2S signals of the pins of a GPIO group, sorted by pin.
+pub fn i2s_pins(group: &GpioGroup) -> Vec<I2sPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            if let Some(signal) = parse_i2s_signal(raw.name()) {
+                pins.push(I2sPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.dedup();
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_i2s_signal() {
+        let signal = parse_i2s_signal("I2S2_WS").unwrap();
+        assert_eq!(signal.instance, "I2S2");
+        assert!(!signal.ext);
+        assert_eq!(signal.function, I2sFunction::Ws);
+        assert_eq!(signal.spi_instance().as_deref(), Some("SPI2"));
+        assert_eq!(signal.io_trait(), "WsPin");
+
+        let signal = parse_i2s_signal("I2S3ext_SD").unwrap();
+        assert_eq!(signal.instance, "I2S3");
+        assert!(signal.ext);
+        assert_eq!(signal.io_trait(), "ExtSdPin");
+
+        let signal = parse_i2s_signal("I2S_CKIN").unwrap();
+        assert_eq!(signal.instance, "I2S");
+        assert_eq!(signal.function, I2sFunction::CkIn);
+        assert_eq!(signal.spi_instance(), None);
+
+        assert_eq!(
+            parse_i2s_signal("I2S1_SDO").map(|s| s.function),
+            Some(I2sFunction::Sdo)
+        );
+        assert!(parse_i2s_signal("SPI2_MOSI").is_none());
+        assert!(parse_i2s_signal("I2S2_CKIN").is_none());
+    }
+}
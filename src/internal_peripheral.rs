@@ -2,9 +2,14 @@ use std::error::Error;
 use std::path::Path;
 
 use lazy_static::lazy_static;
+use log::warn;
 use regex::Regex;
 use serde_derive::Deserialize;
 
+use crate::additional::{self, AdditionalFunction, AdditionalKind};
+use crate::af_tree::{AfPin, AfSignal, RawSignal};
+use crate::remap::{self, Remap};
+use crate::rf;
 use crate::utils::load_file;
 
 #[derive(Debug, Deserialize)]
@@ -20,20 +25,71 @@ pub struct SpecificParameter {
     possible_value: PossibleValue,
 }
 
+/// An AFIO remap routing a signal to a pin on the STM32F1 (e.g.
+/// `<RemapBlock Name="USART1_REMAP2">` with `__HAL_AFIO_REMAP_USART1_ENABLE`).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RemapBlock {
+    name: String,
+    #[serde(default)]
+    default_remap: Option<String>,
+    specific_parameter: Option<SpecificParameter>,
+}
+
+impl RemapBlock {
+    /// Return the C HAL define name of the remap, if any (e.g.
+    /// "__HAL_AFIO_REMAP_USART1_ENABLE").
+    fn get_define(&self) -> Option<&str> {
+        self.specific_parameter
+            .as_ref()
+            .map(|p| p.possible_value.val.as_str())
+    }
+
+    /// Return the remap, or `None` if it cannot be determined.
+    fn get_remap(&self) -> Option<Remap> {
+        let default = self.default_remap.as_deref() == Some("true");
+        remap::parse_remap_block(&self.name, default, self.get_define())
+    }
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PinSignal {
     name: String,
-    specific_parameter: SpecificParameter,
+    specific_parameter: Option<SpecificParameter>,
+    #[serde(default)]
+    remap_block: Vec<RemapBlock>,
 }
 
 impl PinSignal {
-    fn get_af_value(&self) -> &str {
+    /// Return the C HAL define name of the alternate function (e.g.
+    /// "GPIO_AF4_USART2"). The STM32F1 signals have remap blocks instead.
+    fn get_af_define(&self) -> Option<&str> {
         self.specific_parameter
-            .possible_value
-            .val
-            .split('_')
-            .collect::<Vec<_>>()[1]
+            .as_ref()
+            .map(|p| p.possible_value.val.as_str())
+    }
+
+    /// Whether this is an additional function (e.g. "SYS_WKUP1"), which is
+    /// listed without an AF define or remap block.
+    fn is_additional(&self) -> bool {
+        self.specific_parameter.is_none() && self.remap_block.is_empty()
+    }
+
+    /// Return the alternate function (e.g. "AF4"), if the define name can be
+    /// parsed.
+    fn get_af_value(&self) -> Option<&str> {
+        self.get_af_define().and_then(parse_af_define)
+    }
+
+    /// Return the AFIO remaps routing the signal to the pin (STM32F1), with
+    /// their define names. Remaps that cannot be determined are ignored (see
+    /// `GPIOPin::parse_errors`).
+    fn get_remaps(&self) -> Vec<(Remap, Option<&str>)> {
+        self.remap_block
+            .iter()
+            .filter_map(|block| Some((block.get_remap()?, block.get_define())))
+            .collect()
     }
 }
 
@@ -55,63 +111,235 @@ pub struct IpGPIO {
 
 impl IpGPIO {
     pub fn load<P: AsRef<Path>>(db_dir: P, version: &str) -> Result<Self, Box<dyn Error>> {
-        load_file(db_dir, format!("IP/GPIO-{}_Modes.xml", version))
+        load_file(db_dir, Self::path(version))
+    }
+
+    /// The path of the GPIO Modes file of a GPIO IP version, relative to the
+    /// database directory (e.g. "IP/GPIO-STM32L051_gpio_v1_0_Modes.xml").
+    pub fn path(version: &str) -> String {
+        format!("IP/GPIO-{}_Modes.xml", version)
+    }
+
+    /// Iterate over the signal names and AF define names of all pins (e.g.
+    /// `("USART2_TX", "GPIO_AF4_USART2")`), without any parsing. The STM32F1
+    /// signals are listed with the define names of their remaps instead (e.g.
+    /// `("USART1_TX", "__HAL_AFIO_REMAP_USART1_ENABLE")`).
+    pub fn signals(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.gpio_pin
+            .iter()
+            .flat_map(|p| p.pin_signal.iter().flatten())
+            .flat_map(|sig| {
+                sig.get_af_define()
+                    .into_iter()
+                    .chain(sig.remap_block.iter().filter_map(|b| b.get_define()))
+                    .map(move |define| (sig.name.as_str(), define))
+            })
     }
 }
 
 lazy_static! {
-    static ref USART_RX: Regex = Regex::new("(LP)?US?ART._RX").unwrap();
-    static ref USART_TX: Regex = Regex::new("(LP)?US?ART._TX").unwrap();
-    static ref SPI_MOSI: Regex = Regex::new("SPI._MOSI").unwrap();
-    static ref SPI_MISO: Regex = Regex::new("SPI._MISO").unwrap();
-    static ref SPI_SCK: Regex = Regex::new("SPI._SCK").unwrap();
-    static ref I2C_SCL: Regex = Regex::new("I2C._SCL").unwrap();
-    static ref I2C_SDA: Regex = Regex::new("I2C._SDA").unwrap();
+    static ref AF_DEFINE: Regex = Regex::new(r"^GPIO_(AF\d+)_\w+$").unwrap();
+    static ref PIN_DEFINE: Regex = Regex::new(r"^GPIO_PIN_(\d+)$").unwrap();
+
+    /// Signal patterns and the IO trait implemented by matching signals, in
+    /// the conventional order of the pins of a peripheral (e.g. TX before RX,
+    /// see `io_trait_names`).
+    static ref IO_TRAITS: Vec<(Regex, &'static str)> = vec![
+        (Regex::new("(LP)?US?ART._TX").unwrap(), "TxPin"),
+        (Regex::new("(LP)?US?ART._RX").unwrap(), "RxPin"),
+        (Regex::new(r"^(LP)?US?ART\d+_CTS").unwrap(), "CtsPin"),
+        (Regex::new(r"^(LP)?US?ART\d+_RTS").unwrap(), "RtsPin"),
+        (Regex::new(r"^(LP)?US?ART\d+_CK$").unwrap(), "CkPin"),
+        (Regex::new(r"^(LP)?US?ART\d+_(RTS_)?DE$").unwrap(), "DePin"),
+        (Regex::new("SPI._SCK").unwrap(), "SckPin"),
+        (Regex::new("SPI._MISO").unwrap(), "MisoPin"),
+        (Regex::new("SPI._MOSI").unwrap(), "MosiPin"),
+        (Regex::new("I2C._SCL").unwrap(), "SclPin"),
+        (Regex::new("I2C._SDA").unwrap(), "SdaPin"),
+    ];
+}
+
+/// Parse the alternate function (e.g. "AF7") from an AF define name (e.g.
+/// "GPIO_AF7_USART1").
+pub fn parse_af_define(define: &str) -> Option<&str> {
+    AF_DEFINE
+        .captures(define)
+        .map(|c| c.get(1).unwrap().as_str())
+}
+
+/// Parse the pin number (e.g. "6") from a pin define name (e.g. "GPIO_PIN_6").
+pub fn parse_pin_define(define: &str) -> Option<&str> {
+    PIN_DEFINE
+        .captures(define)
+        .map(|c| c.get(1).unwrap().as_str())
+}
+
+/// The IO traits of the serial hardware flow control, clock and driver enable
+/// signals, which `AfTreeBuilder::serial_handshake` keeps.
+pub const SERIAL_HANDSHAKE_TRAITS: &[&str] = &["CtsPin", "RtsPin", "CkPin", "DePin"];
+
+/// Return the names of all IO traits, in the conventional order of the pins of
+/// a peripheral (e.g. "SckPin", "MisoPin", "MosiPin").
+pub fn io_trait_names() -> Vec<&'static str> {
+    IO_TRAITS.iter().map(|(_, io)| *io).collect()
+}
+
+/// Return the IO traits (e.g. "TxPin") implemented by a signal (e.g.
+/// "USART2_TX"). The RF signals (e.g. "RF_TX_MOD_EXT_PA") implement none.
+/// Combined signals implement all of their traits (e.g. "RtsPin" and "DePin"
+/// for "USART2_RTS_DE").
+pub fn io_traits(signal: &str) -> Vec<&'static str> {
+    if rf::parse_rf_signal(signal).is_some() {
+        return vec![];
+    }
+    IO_TRAITS
+        .iter()
+        .filter(|(re, _)| re.is_match(signal))
+        .map(|(_, io)| *io)
+        .collect()
 }
 
 impl GPIOPin {
-    pub fn get_name(&self) -> Option<String> {
-        let gpio_pin = self
-            .specific_parameter
+    /// Return the pin number (e.g. "6" for `GPIO_PIN_6`).
+    fn get_number(&self) -> Option<&str> {
+        self.specific_parameter
             .iter()
-            .find(|v| v.name == "GPIO_Pin");
-        match gpio_pin {
-            Some(v) => {
-                let num = v.possible_value.val.split('_').collect::<Vec<_>>()[2];
-                Some(format!("{}{}", &self.port_name, num))
+            .find(|v| v.name == "GPIO_Pin")
+            .and_then(|v| parse_pin_define(&v.possible_value.val))
+    }
+
+    /// Describe the data of this pin that cannot be parsed and is ignored
+    /// (e.g. an unknown AF define name).
+    pub fn parse_errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if self.get_number().is_none() {
+            errors.push(format!("Could not parse the number of pin {}", self.name));
+        }
+        for sig in self.pin_signal.iter().flatten() {
+            if !sig.remap_block.is_empty() {
+                for block in sig.remap_block.iter().filter(|b| b.get_remap().is_none()) {
+                    errors.push(format!(
+                        "Could not parse remap {} of signal {} on pin {}",
+                        block.name, sig.name, self.name
+                    ));
+                }
+            } else if !sig.is_additional() && sig.get_af_value().is_none() {
+                errors.push(format!(
+                    "Could not parse AF {:?} of signal {} on pin {}",
+                    sig.get_af_define().unwrap_or_default(),
+                    sig.name,
+                    self.name
+                ));
             }
-            None => None,
         }
+        errors
+    }
+
+    /// Return the pin with all its supported alternate functions. The data
+    /// that cannot be parsed is logged once and ignored (see `parse_errors`).
+    pub fn get_pin(&self) -> Option<AfPin> {
+        for error in self.parse_errors() {
+            warn!("{} (ignoring)", error);
+        }
+        self.get_number().map(|num| {
+            AfPin::new(
+                &self.port_name,
+                num,
+                self.get_af_modes(),
+                self.get_raw_signals(),
+            )
+            .with_additional_functions(self.get_additional_functions())
+            .with_original_name(&self.name)
+        })
     }
 
-    pub fn get_af_modes(&self) -> Vec<String> {
+    /// Return all signals of this pin with their alternate function, or on
+    /// the STM32F1 once per remap routing them to the pin.
+    pub fn get_raw_signals(&self) -> Vec<RawSignal> {
         let mut res = Vec::new();
-        if let Some(ref v) = self.pin_signal {
-            for sig in v {
-                let per = sig.name.split('_').collect::<Vec<_>>()[0];
-                if USART_RX.is_match(&sig.name) {
-                    res.push(format!("{}: RxPin<{}>", sig.get_af_value(), per));
-                }
-                if USART_TX.is_match(&sig.name) {
-                    res.push(format!("{}: TxPin<{}>", sig.get_af_value(), per));
-                }
-                if SPI_MOSI.is_match(&sig.name) {
-                    res.push(format!("{}: MosiPin<{}>", sig.get_af_value(), per));
-                }
-                if SPI_MISO.is_match(&sig.name) {
-                    res.push(format!("{}: MisoPin<{}>", sig.get_af_value(), per));
-                }
-                if SPI_SCK.is_match(&sig.name) {
-                    res.push(format!("{}: SckPin<{}>", sig.get_af_value(), per));
-                }
-                if I2C_SCL.is_match(&sig.name) {
-                    res.push(format!("{}: SclPin<{}>", sig.get_af_value(), per));
-                }
-                if I2C_SDA.is_match(&sig.name) {
-                    res.push(format!("{}: SdaPin<{}>", sig.get_af_value(), per));
-                }
+        for sig in self.pin_signal.iter().flatten() {
+            if let (Some(af), Some(define)) = (sig.get_af_value(), sig.get_af_define()) {
+                res.push(RawSignal::new(&sig.name, af).with_define(define));
+            }
+            for (remap, define) in sig.get_remaps() {
+                let signal = RawSignal::remapped(&sig.name, remap);
+                res.push(match define {
+                    Some(define) => signal.with_define(define),
+                    None => signal,
+                });
             }
         }
         res
     }
+
+    /// Return the additional functions of this pin (e.g. "SYS_WKUP1"), which
+    /// are not selected by an alternate function.
+    pub fn get_additional_functions(&self) -> Vec<AdditionalFunction> {
+        self.pin_signal
+            .iter()
+            .flatten()
+            .filter(|sig| sig.is_additional())
+            .map(|sig| {
+                let kind = additional::additional_kind(&sig.name).unwrap_or(AdditionalKind::Other);
+                AdditionalFunction::new(&sig.name, kind)
+            })
+            .collect()
+    }
+
+    pub fn get_af_modes(&self) -> Vec<AfSignal> {
+        let mut res = Vec::new();
+        for sig in self.pin_signal.iter().flatten() {
+            let (af, define) = match (sig.get_af_value(), sig.get_af_define()) {
+                (Some(af), Some(define)) => (af, define),
+                _ => continue,
+            };
+            let per = sig.name.split('_').next().unwrap();
+            for io in io_traits(&sig.name) {
+                res.push(
+                    AfSignal::new(af, io, per)
+                        .with_define(define)
+                        .with_signal(&sig.name),
+                );
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_errors() {
+        let xml = r#"
+            <IP>
+                <GPIO_Pin PortName="PA" Name="PA2">
+                    <SpecificParameter Name="GPIO_Pin">
+                        <PossibleValue>GPIO_PIN_2</PossibleValue>
+                    </SpecificParameter>
+                    <PinSignal Name="USART2_TX">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_AF4_USART2</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                    <PinSignal Name="TIM2_CH3">
+                        <SpecificParameter Name="GPIO_AF">
+                            <PossibleValue>GPIO_TIM2_CH3</PossibleValue>
+                        </SpecificParameter>
+                    </PinSignal>
+                </GPIO_Pin>
+            </IP>
+        "#;
+        let ip: IpGPIO = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let pin = &ip.gpio_pin[0];
+        assert_eq!(
+            pin.parse_errors(),
+            vec!["Could not parse AF \"GPIO_TIM2_CH3\" of signal TIM2_CH3 on pin PA2"]
+        );
+        // The signal is left out of both the AF signals and the raw signals
+        let pin = pin.get_pin().unwrap();
+        assert_eq!(pin.raw_signals().len(), 1);
+        assert_eq!(pin.signals().len(), 1);
+    }
 }
@@ -0,0 +1,176 @@
+//! Read CubeMX project files (`.ioc`).
+
+use std::{collections::HashMap, fs, path::Path};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    /// A pin assignment (e.g. "Mcu.Pin3=PC14-OSC32_IN"), capturing the
+    /// normalized pin name (e.g. "PC14").
+    static ref MCU_PIN: Regex = Regex::new(r"^Mcu\.Pin\d+=(P[A-Z]\d+)\b").unwrap();
+}
+
+/// Parse the settings of a project file (e.g. "Mcu.UserName=STM32L031K6Tx"),
+/// skipping comments.
+pub fn properties(contents: &str) -> HashMap<&str, &str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .collect()
+}
+
+/// A GPIO pin assigned in a CubeMX project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IocPin {
+    /// The normalized pin name (e.g. "PC14").
+    pub pin: String,
+    /// The signal (e.g. "USART2_TX" or "GPIO_Output").
+    pub signal: Option<String>,
+    /// The user label (e.g. "LD3 [Green]").
+    pub label: Option<String>,
+}
+
+/// Return the GPIO pins assigned in a CubeMX project with their signal and
+/// label, in the order of the project file.
+pub fn pins(contents: &str) -> Vec<IocPin> {
+    let properties = properties(contents);
+    contents
+        .lines()
+        .filter_map(|line| {
+            let captures = MCU_PIN.captures(line.trim())?;
+            // The settings use the full pin name (e.g. "PC14-OSC32_IN")
+            let name = line.trim().split_once('=')?.1;
+            let setting = |key: &str| {
+                properties
+                    .get(format!("{}.{}", name, key).as_str())
+                    .map(|value| value.to_string())
+            };
+            Some(IocPin {
+                pin: captures[1].to_string(),
+                signal: setting("Signal"),
+                label: setting("GPIO_Label"),
+            })
+        })
+        .collect()
+}
+
+/// Return the GPIO pins (e.g. "PA13") used by a CubeMX project, in the order
+/// of the project file.
+///
+/// Virtual pins (e.g. "VP_SYS_VS_Systick") are skipped.
+pub fn used_pins(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| MCU_PIN.captures(line.trim()))
+        .map(|c| c[1].to_string())
+        .collect()
+}
+
+/// The MCU of a CubeMX project and the signals assigned to its pins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Project {
+    /// The ref name of the MCU (e.g. "STM32L031K6Tx").
+    pub mcu: String,
+    /// The GPIO pins with a signal, in the order of the project file.
+    pub pins: Vec<IocPin>,
+}
+
+impl Project {
+    /// Load a CubeMX project file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        Self::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Parse a CubeMX project file.
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        let mcu = properties(contents)
+            .get("Mcu.UserName")
+            .ok_or("The project does not specify the MCU (Mcu.UserName)")?
+            .to_string();
+        Ok(Self {
+            mcu,
+            pins: pins(contents)
+                .into_iter()
+                .filter(|pin| pin.signal.is_some())
+                .collect(),
+        })
+    }
+
+    /// Return the pin and signal names of the assignments (e.g. `("PA2",
+    /// "USART2_TX")`).
+    pub fn assignments(&self) -> Vec<(String, String)> {
+        self.pins
+            .iter()
+            .filter_map(|pin| Some((pin.pin.clone(), pin.signal.clone()?)))
+            .collect()
+    }
+}
+
+/// Load a CubeMX project file and return the GPIO pins it uses, see
+/// `used_pins`.
+pub fn load_used_pins<P: AsRef<Path>>(path: P) -> Result<Vec<String>, String> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    Ok(used_pins(&contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_used_pins() {
+        let ioc = "\
+            #MicroXplorer Configuration settings - do not modify\n\
+            Mcu.IPNb=3\n\
+            Mcu.Pin0=PC14-OSC32_IN\n\
+            Mcu.Pin1=PA13\n\
+            Mcu.Pin2=VP_SYS_VS_Systick\n\
+            Mcu.PinsNb=3\n\
+            PA13.Signal=SYS_SWDIO\n";
+        assert_eq!(used_pins(ioc), vec!["PC14", "PA13"]);
+    }
+
+    #[test]
+    fn test_pins() {
+        let ioc = "\
+            Mcu.Pin0=PC14-OSC32_IN\n\
+            Mcu.Pin1=PB3\n\
+            Mcu.UserName=STM32L031K6Tx\n\
+            PB3.GPIO_Label=LD3 [Green]\n\
+            PB3.Signal=GPIO_Output\n\
+            PC14-OSC32_IN.Signal=RCC_OSC32_IN\n";
+        assert_eq!(properties(ioc)["Mcu.UserName"], "STM32L031K6Tx");
+        let project = Project::parse(ioc).unwrap();
+        assert_eq!(project.mcu, "STM32L031K6Tx");
+        assert_eq!(
+            project.assignments(),
+            vec![
+                ("PC14".to_string(), "RCC_OSC32_IN".to_string()),
+                ("PB3".to_string(), "GPIO_Output".to_string()),
+            ]
+        );
+        assert_eq!(
+            pins(ioc),
+            vec![
+                IocPin {
+                    pin: "PC14".into(),
+                    signal: Some("RCC_OSC32_IN".into()),
+                    label: None,
+                },
+                IocPin {
+                    pin: "PB3".into(),
+                    signal: Some("GPIO_Output".into()),
+                    label: Some("LD3 [Green]".into()),
+                },
+            ]
+        );
+    }
+}
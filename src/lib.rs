@@ -0,0 +1,44 @@
+//! Parse the STM32CubeMX MCU database.
+//!
+//! The `cube-parse` binary uses this library to generate the pin mappings and
+//! Cargo features of a MCU family. The same data is available to other tools
+//! (e.g. HAL build scripts) through the modules below.
+
+pub mod additional;
+pub mod af_tree;
+pub mod analog;
+pub mod application;
+pub mod board;
+pub mod can;
+pub mod codegen;
+pub mod db_info;
+pub mod dma;
+pub mod family;
+pub mod features;
+pub mod fmc;
+pub mod gpio_version;
+pub mod i2c;
+pub mod i2s;
+pub mod internal_peripheral;
+pub mod ioc;
+pub mod mcu;
+pub mod memory;
+pub mod nvic;
+pub mod oscillator;
+pub mod package;
+pub mod peripheral_pins;
+pub mod pin_spec;
+pub mod qspi;
+pub mod rcc;
+pub mod remap;
+pub mod rf;
+pub mod spi;
+pub mod stems;
+pub mod swj;
+pub mod templates;
+pub mod timer;
+pub mod tsc;
+pub mod usb;
+mod utils;
+
+pub use utils::take_decoding_issues;
@@ -1,301 +1,1406 @@
-use std::{collections::HashMap, env, path::Path};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
 
 use alphanumeric_sort::compare_str;
-use clap::{App, Arg};
-use lazy_static::lazy_static;
+use clap::{ArgMatches, ErrorKind};
+use log::{debug, error, info, LevelFilter};
 use regex::Regex;
 
-mod family;
-mod internal_peripheral;
-mod mcu;
-mod utils;
+use cube_parse::{
+    additional, af_tree, analog, application, board, codegen, db_info, dma, family, features,
+    gpio_version, ioc, mcu, memory, nvic, package, pin_spec, rcc, stems, templates,
+};
+
+mod cli;
+mod config;
+mod guard;
+mod summary;
+mod workspace;
 
 #[derive(Debug, PartialEq)]
 enum GenerateTarget {
     PinMappings,
     Features,
+    MemoryPins(memory::MemoryType),
+    AppPins(application::Application),
+    Portability,
+    Query,
+    Fragmentation,
+    ListStems,
+    ListMcus,
+    GpioGroups,
+    ListPackages,
+    Ips,
+    Families,
+    DbInfo,
+    All,
+    DmaRequests,
+    Clocks,
+    Interrupts,
+    AdcPins,
+    DacPins,
+    /// The functions of a comparator or op-amp peripheral (e.g. "COMP")
+    AnalogFunctionPins(&'static str),
+    I2sPins,
+    UsbPins,
+    TscPins,
+    PwmPins,
+    I2cPins,
+    SpiPins,
+    CanPins,
+    QspiPins,
+    FmcPins,
+    PeripheralPins,
+    RemapPins,
+    McuInfo,
+    Pinout,
+    BoardPins,
+    IocPins,
+    BuildCfgs,
 }
 
-lazy_static! {
-    // Note: Version >1.0 is not currently supported
-    static ref GPIO_VERSION: Regex = Regex::new("^([^_]*)_gpio_v1_0$").unwrap();
-}
+impl GenerateTarget {
+    /// Determine the target from the subcommand name and arguments.
+    fn from_subcommand(name: &str, args: &ArgMatches) -> Result<Self, String> {
+        Ok(match name {
+            "pins" => GenerateTarget::PinMappings,
+            "features" => GenerateTarget::Features,
+            "memory-pins" => GenerateTarget::MemoryPins(args.value_of("memory").unwrap().parse()?),
+            "app-pins" => GenerateTarget::AppPins(args.value_of("app").unwrap().parse()?),
+            "portability" => GenerateTarget::Portability,
+            "query" => GenerateTarget::Query,
+            "fragmentation" => GenerateTarget::Fragmentation,
+            "list-stems" => GenerateTarget::ListStems,
+            "list-mcus" => GenerateTarget::ListMcus,
+            "gpio-groups" => GenerateTarget::GpioGroups,
+            "list-packages" => GenerateTarget::ListPackages,
+            "ips" => GenerateTarget::Ips,
+            "families" => GenerateTarget::Families,
+            "db-info" => GenerateTarget::DbInfo,
+            "generate-all" => GenerateTarget::All,
+            "dma-requests" => GenerateTarget::DmaRequests,
+            "clocks" => GenerateTarget::Clocks,
+            "interrupts" => GenerateTarget::Interrupts,
+            "adc-pins" => GenerateTarget::AdcPins,
+            "dac-pins" => GenerateTarget::DacPins,
+            "i2s-pins" => GenerateTarget::I2sPins,
+            "usb-pins" => GenerateTarget::UsbPins,
+            "tsc-pins" => GenerateTarget::TscPins,
+            "pwm-pins" => GenerateTarget::PwmPins,
+            "i2c-pins" => GenerateTarget::I2cPins,
+            "spi-pins" => GenerateTarget::SpiPins,
+            "can-pins" => GenerateTarget::CanPins,
+            "qspi-pins" => GenerateTarget::QspiPins,
+            "fmc-pins" => GenerateTarget::FmcPins,
+            "peripheral-pins" => GenerateTarget::PeripheralPins,
+            "remap-pins" => GenerateTarget::RemapPins,
+            "comp-pins" => GenerateTarget::AnalogFunctionPins("COMP"),
+            "opamp-pins" => GenerateTarget::AnalogFunctionPins("OPAMP"),
+            "mcu-info" => GenerateTarget::McuInfo,
+            "pinout" => GenerateTarget::Pinout,
+            "board-pins" => GenerateTarget::BoardPins,
+            "ioc-pins" => GenerateTarget::IocPins,
+            "build-cfgs" => GenerateTarget::BuildCfgs,
+            _ => unreachable!(),
+        })
+    }
 
-/// Convert a GPIO IP version (e.g. "STM32L152x8_gpio_v1_0") to a feature name
-/// (e.g. "io-STM32L152x8").
-fn gpio_version_to_feature(version: &str) -> Result<String, String> {
-    if let Some(captures) = GPIO_VERSION.captures(version) {
-        Ok(format!("io-{}", captures.get(1).unwrap().as_str()))
-    } else {
-        Err(format!("Could not parse version {:?}", version))
+    /// Whether the target lists the analog signals of the pins, which are not
+    /// alternate functions.
+    fn uses_analog_signals(&self) -> bool {
+        matches!(
+            self,
+            GenerateTarget::Query
+                | GenerateTarget::AdcPins
+                | GenerateTarget::DacPins
+                | GenerateTarget::AnalogFunctionPins(_)
+        )
+    }
+
+    /// Whether the target ignores the stems of the config file. The list of
+    /// stems lists all of them, and the analog, I2S, USB, TSC, PWM, I2C, SPI,
+    /// CAN, QSPI and FMC pin targets always cover all instances of their
+    /// peripherals.
+    fn ignores_configured_stems(&self) -> bool {
+        match self {
+            GenerateTarget::Query => false,
+            _ if self.uses_analog_signals() => true,
+            _ => matches!(
+                self,
+                GenerateTarget::ListStems
+                    | GenerateTarget::I2sPins
+                    | GenerateTarget::UsbPins
+                    | GenerateTarget::TscPins
+                    | GenerateTarget::PwmPins
+                    | GenerateTarget::I2cPins
+                    | GenerateTarget::SpiPins
+                    | GenerateTarget::CanPins
+                    | GenerateTarget::QspiPins
+                    | GenerateTarget::FmcPins
+            ),
+        }
     }
 }
 
-fn main() -> Result<(), String> {
-    let args = App::new("cube-parse")
-        .version(env!("CARGO_PKG_VERSION"))
-        .about("Extract AF modes on MCU pins from the database files provided with STM32CubeMX")
-        .author(&*env!("CARGO_PKG_AUTHORS").replace(":", ", "))
-        .arg(
-            Arg::with_name("db_dir")
-                .short("d")
-                .help("Path to the CubeMX MCU database directory")
-                .takes_value(true)
-                .required(true),
-        )
-        .arg(
-            Arg::with_name("generate")
-                .help("What to generate")
-                .takes_value(true)
-                .possible_values(&["pin_mappings", "features"])
-                .required(true),
-        )
-        .arg(
-            Arg::with_name("mcu_family")
-                .help("The MCU family to extract, e.g. \"STM32L0\"")
-                .takes_value(true)
-                .required(true),
-        )
-        .get_matches();
+/// Exit code if the run failed.
+const EXIT_ERROR: i32 = 1;
+
+/// Exit code if the command line arguments are invalid.
+const EXIT_USAGE: i32 = 2;
+
+/// Exit code if the run completed, but some input had to be skipped because of
+/// errors.
+const EXIT_PARTIAL: i32 = 3;
+
+fn main() {
+    let authors = env!("CARGO_PKG_AUTHORS").replace(":", ", ");
+    let args = match cli::app(&authors).get_matches_safe() {
+        Ok(args) => args,
+        Err(e) => match e.kind {
+            ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed => e.exit(),
+            _ => {
+                eprintln!("{}", e.message);
+                process::exit(EXIT_USAGE);
+            }
+        },
+    };
+
+    // Set up logging. The RUST_LOG environment variable takes precedence.
+    let level = match (args.occurrences_of("verbose"), args.occurrences_of("quiet")) {
+        (0, 0) => LevelFilter::Warn,
+        (1, _) => LevelFilter::Info,
+        (2, _) => LevelFilter::Debug,
+        (_, 0) => LevelFilter::Trace,
+        (_, 1) => LevelFilter::Error,
+        (_, _) => LevelFilter::Off,
+    };
+    // The filter explanation is logged as info
+    let explain = args.is_present("explain_filters")
+        || args
+            .subcommand()
+            .1
+            .is_some_and(|args| args.is_present("explain_filters"));
+    let level = match explain {
+        true => level.max(LevelFilter::Info),
+        false => level,
+    };
+    // Dependencies (e.g. serde-xml-rs) only log warnings and errors, since
+    // their debug output is very noisy.
+    env_logger::Builder::new()
+        .filter_level(level.min(LevelFilter::Warn))
+        .filter_module("cube_parse", level)
+        .format_timestamp(None)
+        .format_module_path(false)
+        .parse_filters(&env::var("RUST_LOG").unwrap_or_default())
+        .init();
+
+    let mut summary = summary::Summary::new();
+    let result = run(&args, &mut summary);
+    // Already logged while loading
+    summary.warnings.extend(cube_parse::take_decoding_issues());
+    let mut exit_code = match result {
+        Ok(()) if summary.errors.is_empty() => 0,
+        Ok(()) => EXIT_PARTIAL,
+        Err(e) => {
+            summary.error(e);
+            EXIT_ERROR
+        }
+    };
+    if let Some(path) = args.value_of("summary_json") {
+        if let Err(e) = summary.write(path) {
+            error!("Could not write summary JSON: {}", e);
+            exit_code = EXIT_ERROR;
+        }
+    }
+    process::exit(exit_code);
+}
 
+fn run(args: &ArgMatches, summary: &mut summary::Summary) -> Result<(), String> {
     // Process args
-    let db_dir = Path::new(args.value_of("db_dir").unwrap());
-    let mcu_family = args.value_of("mcu_family").unwrap();
-    let generate = match args.value_of("generate").unwrap() {
-        "pin_mappings" => GenerateTarget::PinMappings,
-        "features" => GenerateTarget::Features,
-        _ => unreachable!(),
+    let (name, args) = args.subcommand();
+    let args = args.unwrap();
+    let config = match args.value_of("config") {
+        Some(path) => config::Config::load(path)?,
+        None => config::Config::default(),
     };
+    let generate = GenerateTarget::from_subcommand(name, args)?;
+    summary.targets.push(name.to_string());
 
-    // Load families
-    let families = family::Families::load(&db_dir)
-        .map_err(|e| format!("Could not load families XML: {}", e))?;
+    let artifacts = if let Some(irs) = args.values_of("ir") {
+        // Cached trees replace the database
+        vec![query_irs(&irs.collect::<Vec<_>>(), args)?]
+    } else {
+        let db_dir = args
+            .value_of("db_dir")
+            .map(PathBuf::from)
+            .or_else(|| config.db_dir.clone())
+            .ok_or("No CubeMX database directory specified")?;
+        let db_dir = db_dir.as_path();
 
-    // Find target family
-    let family = (&families)
-        .into_iter()
-        .find(|v| v.name == mcu_family)
-        .ok_or_else(|| format!("Could not find family {}", mcu_family))?;
-
-    // MCU map
-    //
-    // The keys of this map are GPIO peripheral version strings (e.g.
-    // "STM32L051_gpio_v1_0"), while the value is a Vec of MCU ref names.
-    let mut mcu_gpio_map: HashMap<String, Vec<String>> = HashMap::new();
-
-    // Package map
-    //
-    // The keys of this map are MCU ref names, the values are package names
-    // (e.g. ).
-    let mut mcu_package_map: HashMap<String, String> = HashMap::new();
-
-    for sf in family {
-        for mcu in sf {
-            let mcu_dat = mcu::Mcu::load(&db_dir, &mcu.name)
-                .map_err(|e| format!("Could not load MCU data: {}", e))?;
-
-            let gpio_version = mcu_dat.get_ip("GPIO").unwrap().get_version().to_string();
-            mcu_gpio_map
-                .entry(gpio_version)
-                .or_insert(vec![])
-                .push(mcu.ref_name.clone());
+        // Load families
+        let families = family::Families::load(db_dir)
+            .map_err(|e| format!("Could not load families XML: {}", e))?;
+        summary.db_release = db_info::release(db_dir);
 
-            if mcu_family == "STM32L0" {
-                // The stm32l0xx-hal has package based features
-                mcu_package_map.insert(mcu.ref_name.clone(), mcu.package_name.clone());
+        match generate {
+            GenerateTarget::Families => vec![codegen::generate_families(&families)],
+            GenerateTarget::DbInfo => vec![codegen::generate_db_info(&db_info::DbInfo::load(
+                db_dir, &families,
+            )?)],
+            GenerateTarget::Portability => {
+                let signals = args.values_of("signals").unwrap().collect::<Vec<_>>();
+                let strict = args.is_present("strict");
+                vec![generate_portability(
+                    &families, db_dir, &signals, strict, summary,
+                )?]
             }
+            GenerateTarget::BoardPins => vec![generate_board_pins(
+                &families,
+                db_dir,
+                args.value_of("board").unwrap(),
+                args.is_present("strict"),
+            )?],
+            GenerateTarget::IocPins => vec![generate_ioc_pins(
+                &families,
+                db_dir,
+                Path::new(args.value_of("ioc").unwrap()),
+                args.is_present("strict"),
+                &config,
+            )?],
+            _ => generate_family(&generate, args, &config, &families, db_dir, summary)?,
         }
-    }
+    };
 
-    match generate {
-        GenerateTarget::Features => {
-            generate_features(&mcu_gpio_map, &mcu_package_map, &mcu_family)?
-        }
-        GenerateTarget::PinMappings => generate_pin_mappings(&mcu_gpio_map, &db_dir)?,
+    let output_dir = match generate {
+        // The list of families and the database info are never written to a
+        // file
+        GenerateTarget::Families | GenerateTarget::DbInfo => None,
+        _ => args
+            .value_of("output_dir")
+            .map(PathBuf::from)
+            .or(config.output_dir),
     };
+    match output_dir {
+        None if generate == GenerateTarget::All => {
+            return Err("generate-all needs an output directory (-o or output_dir)".into());
+        }
+        Some(output_dir) => {
+            let mut workspace = workspace::Workspace::new(output_dir)
+                .map_err(|e| format!("Could not create workspace: {}", e))?;
+            for artifact in &artifacts {
+                workspace
+                    .write(&artifact.path, &artifact.content)
+                    .map_err(|e| format!("Could not write {}: {}", artifact.path.display(), e))?;
+            }
+            let written = workspace
+                .commit()
+                .map_err(|e| format!("Could not move generated files into place: {}", e))?;
+            for path in &written {
+                info!("Wrote {}", path.display());
+            }
+            summary.files_written.extend(written);
+        }
+        None => {
+            for artifact in &artifacts {
+                print!("{}", artifact.content);
+            }
+        }
+    }
 
     Ok(())
 }
 
-lazy_static! {
-    static ref FEATURE_DEPENDENCIES: HashMap<&'static str, HashMap<&'static str, &'static str>> = {
-        let mut m = HashMap::new();
+/// The selected MCUs of some MCU families, with the data the targets are
+/// generated from.
+struct FamilyData<'f> {
+    families: Vec<&'f family::Family>,
+    /// The names of the families (e.g. "STM32L0")
+    family_names: Vec<&'f str>,
+    /// The selected MCUs, in family order
+    mcus: Vec<&'f family::Mcu>,
+    /// The loaded MCUs by ref name, in family order
+    mcu_data: Vec<(String, mcu::Mcu)>,
+    /// The selected MCUs (ref names) of each sub-family
+    sub_family_mcus: Vec<(String, Vec<String>)>,
+    /// GPIO peripheral version strings (e.g. "STM32L051_gpio_v1_0") mapped to
+    /// the ref names of the MCUs using them
+    mcu_gpio_map: HashMap<String, Vec<String>>,
+    /// MCU ref names mapped to their package names, only for the STM32L0,
+    /// since the stm32l0xx-hal has package based features
+    mcu_package_map: HashMap<String, String>,
+    /// IPs and their version (e.g. "DMA" and "STM32L0x_dma_v1_0") mapped to
+    /// the ref names of the MCUs using them
+    mcu_ip_map: HashMap<(String, String), Vec<String>>,
+    /// IPs and their version mapped to their instance names (e.g. "USART1")
+    ip_instance_map: HashMap<(String, String), Vec<String>>,
+    /// GPIO peripheral version strings mapped to the analog signals of the
+    /// pins of all MCUs using them (e.g. "PA4" and "DAC_OUT1")
+    analog_map: HashMap<String, Vec<(String, String)>>,
+    /// GPIO peripheral version strings mapped to the other additional
+    /// functions of the pins of all MCUs using them
+    additional_map: HashMap<String, Vec<(String, String)>>,
+    pinouts: Vec<package::Pinout>,
+    mcu_core_map: HashMap<String, Vec<mcu::Core>>,
+    mcu_peripheral_map: HashMap<String, Vec<String>>,
+    mcu_die_map: HashMap<String, String>,
+}
 
-        // STM32L0
-        let mut l0 = HashMap::new();
-        l0.insert("^STM32L0.1", "stm32l0x1");
-        l0.insert("^STM32L0.2", "stm32l0x2");
-        l0.insert("^STM32L0.3", "stm32l0x3");
-        m.insert("STM32L0", l0);
+/// Generate a target that covers a single MCU family. Only `pins` and
+/// `generate-all` return more than one artifact.
+fn generate_family(
+    generate: &GenerateTarget,
+    args: &ArgMatches,
+    config: &config::Config,
+    families: &family::Families,
+    db_dir: &Path,
+    summary: &mut summary::Summary,
+) -> Result<Vec<codegen::GeneratedArtifact>, String> {
+    let (family, tree_builder) = load_family(args, config, families, db_dir, summary)?;
+    let mut names = config.features.clone();
+    names.legacy_packages |= args.is_present("legacy_package_features");
+    names.cores |= args.is_present("core_features");
+    names.peripherals |= args.is_present("peripheral_features");
+    names.dies |= args.is_present("die_features");
+    let names = &names;
 
-        m
+    let artifact = match *generate {
+        GenerateTarget::Features => generate_features(&family, args, config, names),
+        GenerateTarget::BuildCfgs => generate_build_cfgs(&family, args, config, names),
+        GenerateTarget::DmaRequests => generate_dma_requests(&family, db_dir, names),
+        GenerateTarget::Clocks => generate_clocks(&family, db_dir, names),
+        GenerateTarget::Interrupts => generate_interrupts(&family, db_dir, names),
+        GenerateTarget::ListPackages => Ok(codegen::generate_package_list(&family.mcus, names)),
+        GenerateTarget::Ips => Ok(codegen::generate_ip_list(
+            &family.mcu_ip_map,
+            &family.ip_instance_map,
+        )),
+        GenerateTarget::ListMcus => Ok(codegen::generate_mcu_list(
+            &family.mcus,
+            &family.mcu_gpio_map,
+        )),
+        GenerateTarget::Pinout => Ok(codegen::generate_pinouts(&family.pinouts)),
+        GenerateTarget::McuInfo => codegen::generate_mcu_info(
+            &family.mcu_data,
+            args.value_of("format").unwrap_or("json").parse()?,
+        ),
+        _ => {
+            let (tree_builder, stem_patterns) =
+                configure_tree(generate, args, config, &family, tree_builder)?;
+            let explain = args.is_present("explain_filters");
+            let tree = build_tree(tree_builder, &stem_patterns, explain, summary)?;
+            return generate_from_tree(generate, &tree, args, config, &family, names, summary);
+        }
     };
+    Ok(vec![artifact?])
 }
 
-/// Print the IO features, followed by MCU features that act purely as aliases
-/// for the IO features.
-///
-/// Both lists are sorted alphanumerically.
-fn generate_features(
-    mcu_gpio_map: &HashMap<String, Vec<String>>,
-    mcu_package_map: &HashMap<String, String>,
-    mcu_family: &str,
-) -> Result<(), String> {
-    let mut main_features = mcu_gpio_map
-        .keys()
-        .map(|gpio| gpio_version_to_feature(gpio))
-        .collect::<Result<Vec<String>, String>>()?;
-    main_features.sort();
-
-    let mut mcu_aliases = vec![];
-    for (gpio, mcu_list) in mcu_gpio_map {
-        let gpio_version_feature = gpio_version_to_feature(gpio).unwrap();
-        for mcu in mcu_list {
-            let mut dependencies = vec![];
-
-            // GPIO version feature
-            dependencies.push(gpio_version_feature.clone());
-
-            // Additional dependencies
-            if let Some(family) = FEATURE_DEPENDENCIES.get(mcu_family) {
-                for (pattern, feature) in family {
-                    if Regex::new(pattern).unwrap().is_match(&mcu) {
-                        dependencies.push(feature.to_string());
-                        break;
+/// Generate a target from the AF tree of the selected MCUs.
+fn generate_from_tree(
+    generate: &GenerateTarget,
+    tree: &af_tree::AfTree,
+    args: &ArgMatches,
+    config: &config::Config,
+    family: &FamilyData,
+    names: &features::FeatureNames,
+    summary: &mut summary::Summary,
+) -> Result<Vec<codegen::GeneratedArtifact>, String> {
+    let artifact = match *generate {
+        GenerateTarget::PinMappings => return generate_pins(tree, args, config, family, names),
+        GenerateTarget::All => return generate_all(tree, args, config, family, names, summary),
+        GenerateTarget::Query => generate_query(tree, args, summary),
+        GenerateTarget::MemoryPins(memory) => codegen::generate_memory_pins(tree, memory, names),
+        GenerateTarget::AppPins(app) => codegen::generate_app_pins(tree, app, names),
+        GenerateTarget::Fragmentation => codegen::generate_fragmentation(tree, names),
+        GenerateTarget::ListStems => Ok(codegen::generate_stems(tree)),
+        GenerateTarget::GpioGroups => Ok(codegen::generate_gpio_groups(tree)),
+        GenerateTarget::AdcPins => codegen::generate_adc_pins(tree, names),
+        GenerateTarget::DacPins => codegen::generate_dac_pins(tree, names),
+        GenerateTarget::AnalogFunctionPins(peripheral) => {
+            codegen::generate_analog_function_pins(tree, peripheral, names)
+        }
+        GenerateTarget::I2sPins => codegen::generate_i2s_pins(tree, names),
+        GenerateTarget::UsbPins => codegen::generate_usb_pins(tree, names),
+        GenerateTarget::TscPins => codegen::generate_tsc_pins(tree, names),
+        GenerateTarget::PwmPins => codegen::generate_pwm_pins(tree, names),
+        GenerateTarget::I2cPins => codegen::generate_i2c_pins(tree, names),
+        GenerateTarget::SpiPins => {
+            codegen::generate_spi_pins(tree, names, args.value_of("no_miso_type"))
+        }
+        GenerateTarget::CanPins => {
+            codegen::generate_can_pins(tree, names, &stem_aliases(args, config)?)
+        }
+        GenerateTarget::QspiPins => codegen::generate_qspi_pins(tree, names),
+        GenerateTarget::FmcPins => codegen::generate_fmc_pins(tree, names),
+        GenerateTarget::PeripheralPins => codegen::generate_peripheral_pins(tree, names),
+        GenerateTarget::RemapPins => codegen::generate_remap_pins(tree, names),
+        _ => unreachable!(),
+    };
+    Ok(vec![artifact?])
+}
+
+/// Select the MCUs of the families given on the command line or in the config
+/// file that pass the MCU filters, and load them. The returned AF tree builder
+/// has loaded the GPIO IPs of the MCUs.
+fn load_family<'f>(
+    args: &ArgMatches,
+    config: &config::Config,
+    families: &'f family::Families,
+    db_dir: &Path,
+    summary: &mut summary::Summary,
+) -> Result<(FamilyData<'f>, af_tree::AfTreeBuilder), String> {
+    let mcu_families = match args.values_of("mcu_family") {
+        Some(names) => names.collect::<Vec<_>>(),
+        None => config
+            .family
+            .iter()
+            .chain(&config.families)
+            .map(String::as_str)
+            .collect(),
+    };
+    if mcu_families.is_empty() {
+        return Err("No MCU family specified".into());
+    }
+
+    // Find target families
+    let selected_families = mcu_families
+        .iter()
+        .map(|name| families.lookup(name))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut family = FamilyData {
+        family_names: selected_families
+            .iter()
+            .map(|family| family.name.as_str())
+            .collect(),
+        families: selected_families,
+        mcus: vec![],
+        mcu_data: vec![],
+        sub_family_mcus: vec![],
+        mcu_gpio_map: HashMap::new(),
+        mcu_package_map: HashMap::new(),
+        mcu_ip_map: HashMap::new(),
+        ip_instance_map: HashMap::new(),
+        analog_map: HashMap::new(),
+        additional_map: HashMap::new(),
+        pinouts: vec![],
+        mcu_core_map: HashMap::new(),
+        mcu_peripheral_map: HashMap::new(),
+        mcu_die_map: HashMap::new(),
+    };
+    let mcu_family = family.family_names.join(", ");
+    summary.family = mcu_family.clone();
+
+    // MCU filter (e.g. "STM32L071")
+    let mcu_filter = match args.values_of("mcu") {
+        Some(mcus) => Some(mcus.map(String::from).collect::<Vec<_>>()),
+        None => config.mcus.clone(),
+    };
+    // Explicit MCU list (one ref name per line)
+    let mcu_list = match args
+        .value_of("mcu_list")
+        .map(PathBuf::from)
+        .or_else(|| config.mcu_list.clone())
+    {
+        Some(path) => Some(read_list(&path, "MCU list")?),
+        None => None,
+    };
+    // Ref name regex (e.g. "^STM32L0.1K")
+    let mcu_regex = match args.value_of("regex") {
+        Some(re) => Some(Regex::new(re).map_err(|e| format!("Invalid regex {:?}: {}", re, e))?),
+        None => None,
+    };
+    // Package filter (e.g. "LQFP32")
+    let package_filter = match args.values_of("package") {
+        Some(packages) => Some(packages.map(String::from).collect::<Vec<_>>()),
+        None => config.packages.clone(),
+    };
+    if let Some(packages) = &package_filter {
+        for family in &family.families {
+            check_packages(family, packages, summary);
+        }
+    }
+    let strict = args.is_present("strict");
+    // The number of MCUs remaining after each MCU filter that is set
+    let mut mcu_steps = vec![(format!("family {}", mcu_family), 0)];
+    for (filter, set) in &[
+        ("mcu", mcu_filter.is_some()),
+        ("mcu list", mcu_list.is_some()),
+        ("package", package_filter.is_some()),
+        ("regex", mcu_regex.is_some()),
+    ] {
+        if *set {
+            mcu_steps.push((filter.to_string(), 0));
+        }
+    }
+    let mut found = false;
+    let tree_builder = af_tree::AfTree::builder(db_dir).strict(strict);
+
+    for &selected_family in &family.families {
+        for (sf, mcu) in selected_family
+            .sub_families()
+            .iter()
+            .flat_map(|sf| sf.mcus.iter().map(move |mcu| (sf, mcu)))
+        {
+            let checks = [
+                mcu_filter.as_ref().map(|prefixes| {
+                    prefixes
+                        .iter()
+                        .any(|p| mcu.ref_name.starts_with(p.as_str()))
+                }),
+                mcu_list.as_ref().map(|list| list.contains(&mcu.ref_name)),
+                package_filter
+                    .as_ref()
+                    .map(|packages| packages.iter().any(|p| mcu.in_package(p))),
+                mcu_regex.as_ref().map(|re| re.is_match(&mcu.ref_name)),
+            ];
+            let passed = checks.iter().flatten().take_while(|ok| **ok).count();
+            for (_, count) in &mut mcu_steps[..=passed] {
+                *count += 1;
+            }
+            if passed + 1 < mcu_steps.len() {
+                continue;
+            }
+            found = true;
+            let (mcu_dat, gpio_version) = match tree_builder.load_mcu(mcu) {
+                Ok(loaded) => loaded,
+                Err(msg) => {
+                    if strict {
+                        return Err(msg);
                     }
+                    summary.error(msg);
+                    continue;
                 }
+            };
+            if mcu_dat.is_mpu() {
+                debug!("{} is a MPU, using its Cortex-M side", mcu.ref_name);
             }
 
-            // Package based feature
-            if let Some(package) = mcu_package_map.get(mcu) {
-                dependencies.push(package.to_lowercase());
+            let gpio_version = match gpio_version {
+                Some(gpio_version) => gpio_version,
+                None if strict => return Err(format!("MCU {} has no GPIO IP", mcu.ref_name)),
+                None => {
+                    summary.warn(format!("MCU {} has no GPIO IP (ignoring)", mcu.ref_name));
+                    continue;
+                }
+            };
+            if strict {
+                gpio_version
+                    .parse::<gpio_version::GpioVersion>()
+                    .map_err(|e| format!("MCU {}: {}", mcu.ref_name, e))?;
+            }
+            let analog_signals = family.analog_map.entry(gpio_version.clone()).or_default();
+            for signal in analog::analog_signals(&mcu_dat) {
+                if !analog_signals.contains(&signal) {
+                    analog_signals.push(signal);
+                }
+            }
+            let additional_functions = family
+                .additional_map
+                .entry(gpio_version.clone())
+                .or_default();
+            for function in additional::additional_functions(&mcu_dat) {
+                if !additional_functions.contains(&function) {
+                    additional_functions.push(function);
+                }
+            }
+            family
+                .mcu_gpio_map
+                .entry(gpio_version)
+                .or_insert(vec![])
+                .push(mcu.ref_name.clone());
+            for ip in mcu_dat.get_ips() {
+                let key = (ip.get_name().to_string(), ip.get_version().to_string());
+                family
+                    .ip_instance_map
+                    .entry(key.clone())
+                    .or_insert(vec![])
+                    .push(ip.get_instance_name().to_string());
+                family
+                    .mcu_ip_map
+                    .entry(key)
+                    .or_insert(vec![])
+                    .push(mcu.ref_name.clone());
+            }
+            family
+                .pinouts
+                .push(package::Pinout::new(&mcu.ref_name, &mcu_dat));
+            family
+                .mcu_core_map
+                .insert(mcu.ref_name.clone(), mcu_dat.get_cores());
+            family.mcu_peripheral_map.insert(
+                mcu.ref_name.clone(),
+                mcu_dat
+                    .get_ips()
+                    .iter()
+                    .map(|ip| ip.get_instance_name().to_string())
+                    .collect(),
+            );
+            if let Some(die) = &mcu_dat.die {
+                family.mcu_die_map.insert(mcu.ref_name.clone(), die.clone());
+            }
+            family.mcus.push(mcu);
+            family.mcu_data.push((mcu.ref_name.clone(), mcu_dat));
+            match family.sub_family_mcus.last_mut() {
+                Some((name, mcus)) if *name == sf.name => mcus.push(mcu.ref_name.clone()),
+                _ => family
+                    .sub_family_mcus
+                    .push((sf.name.clone(), vec![mcu.ref_name.clone()])),
             }
 
-            let mcu_feature = format!("mcu-{}", mcu);
-            mcu_aliases.push(format!(
-                "{} = [{}]",
-                mcu_feature,
-                &dependencies.iter().map(|val| format!("\"{}\"", val)).fold(
-                    String::new(),
-                    |mut acc, x| {
-                        if !acc.is_empty() {
-                            acc.push_str(", ");
-                        }
-                        acc.push_str(&x);
-                        acc
-                    }
-                )
-            ));
+            if selected_family.name == "STM32L0" {
+                // The stm32l0xx-hal has package based features
+                family
+                    .mcu_package_map
+                    .insert(mcu.ref_name.clone(), mcu.package_name.clone());
+            }
         }
     }
-    mcu_aliases.sort();
 
-    println!("# Features based on the GPIO peripheral version");
-    println!("# This determines the pin function mapping of the MCU");
-    for feature in main_features {
-        println!("{} = []", feature);
-    }
-    println!();
-    if !mcu_package_map.is_empty() {
-        println!("# Physical packages");
-        let mut packages = mcu_package_map
-            .values()
-            .map(|v| v.to_lowercase())
+    if let Some(list) = &mcu_list {
+        let unknown = list
+            .iter()
+            .filter(|name| {
+                family
+                    .families
+                    .iter()
+                    .flat_map(|family| family.mcus())
+                    .all(|mcu| mcu.ref_name != **name)
+            })
+            .map(String::as_str)
             .collect::<Vec<_>>();
-        packages.sort_by(|a, b| compare_str(a, b));
-        packages.dedup();
-        for pkg in packages {
-            println!("{} = []", pkg);
+        if !unknown.is_empty() {
+            return Err(format!(
+                "MCUs not found in family {}: {}",
+                mcu_family,
+                unknown.join(", ")
+            ));
         }
-        println!();
     }
-    println!("# MCUs");
-    for alias in mcu_aliases {
-        println!("{}", alias);
+    let loaded = family.mcu_gpio_map.values().map(Vec::len).sum::<usize>();
+    if args.is_present("explain_filters") {
+        mcu_steps.push(("loaded".into(), loaded));
+        info!("Filters applied:");
+        for (filter, count) in &mcu_steps {
+            info!("  {}: {} MCUs", filter, count);
+        }
+    }
+    if !found {
+        return Err(format!("No MCU of family {} was selected", mcu_family));
     }
 
-    Ok(())
+    info!(
+        "Loaded {} MCUs with {} GPIO versions",
+        loaded,
+        family.mcu_gpio_map.len()
+    );
+
+    if let Some(cargo_toml) = args.value_of("guard") {
+        guard::check_no_mcus_dropped(cargo_toml, &family.families)?;
+    }
+    Ok((family, tree_builder))
 }
 
-/// Generate the pin mappings for the target MCU family.
-fn generate_pin_mappings(
-    mcu_gpio_map: &HashMap<String, Vec<String>>,
-    db_dir: &Path,
-) -> Result<(), String> {
-    let mut gpio_versions = mcu_gpio_map.keys().collect::<Vec<_>>();
-    gpio_versions.sort();
-    for gpio in gpio_versions {
-        let gpio_version_feature = gpio_version_to_feature(&gpio)?;
-        println!("#[cfg(feature = \"{}\")]", gpio_version_feature);
-        let gpio_data = internal_peripheral::IpGPIO::load(db_dir, &gpio)
-            .map_err(|e| format!("Could not load IP GPIO file: {}", e))?;
-        render_pin_modes(&gpio_data);
-        println!("\n");
+/// Apply the stem, pin, AF and signal filters of the command line and config
+/// file to the AF tree of the selected MCUs. Returns the builder and the
+/// selected stem patterns.
+fn configure_tree(
+    generate: &GenerateTarget,
+    args: &ArgMatches,
+    config: &config::Config,
+    family: &FamilyData,
+    tree_builder: af_tree::AfTreeBuilder,
+) -> Result<(af_tree::AfTreeBuilder, Vec<stems::StemPattern>), String> {
+    // Composite stems (e.g. "SERIAL=USART|UART|LPUART")
+    let mut composite_stems = stems::CompositeStems::new();
+    let cli_composite_stems = args.values_of("composite_stem").into_iter().flatten();
+    for definition in config
+        .composite_stems
+        .iter()
+        .map(String::as_str)
+        .chain(cli_composite_stems)
+    {
+        composite_stems.add_definition(definition)?;
     }
-    Ok(())
+
+    let mut tree_builder = tree_builder
+        .mcu_gpio_map(family.mcu_gpio_map.clone())
+        .serial_handshake(args.is_present("serial_handshake") || config.codegen.serial_handshake);
+    // Analog signals are not alternate functions, only the targets listing
+    // them add them to the tree
+    if generate.uses_analog_signals() {
+        tree_builder = tree_builder.analog_signals(family.analog_map.clone());
+    }
+    if *generate == GenerateTarget::Query {
+        tree_builder = tree_builder.additional_functions(family.additional_map.clone());
+    }
+    let selected = match args.values_of("stems") {
+        Some(selected) => Some(selected.collect::<Vec<_>>()),
+        None if generate.ignores_configured_stems() => None,
+        None => config
+            .stems
+            .as_ref()
+            .map(|stems| stems.iter().map(String::as_str).collect()),
+    };
+    let mut stem_patterns = vec![];
+    if let Some(selected) = selected {
+        let selected = composite_stems.expand(&selected);
+        info!("Selected stems: {}", selected.join(", "));
+        stem_patterns = selected
+            .iter()
+            .map(|s| s.parse())
+            .collect::<Result<Vec<stems::StemPattern>, String>>()?;
+        tree_builder = tree_builder.stem_patterns(stem_patterns.clone());
+    }
+    if let Some(pins) = args.values_of("pins") {
+        let pins = pins
+            .map(str::parse)
+            .collect::<Result<Vec<pin_spec::PinSpec>, String>>()?;
+        tree_builder = tree_builder.pins(pins);
+    }
+    if let Some(afs) = args.values_of("af") {
+        let afs = afs.map(parse_af).collect::<Result<Vec<_>, String>>()?;
+        tree_builder = tree_builder.afs(afs);
+    }
+    // Pins reserved by the board, e.g. those used in a CubeMX project
+    let mut reserved_pins = match args.values_of("reserved_pins") {
+        Some(pins) => pins.map(String::from).collect(),
+        None => config.reserved_pins.clone(),
+    };
+    if let Some(path) = args
+        .value_of("ioc")
+        .map(PathBuf::from)
+        .or_else(|| config.ioc.clone())
+    {
+        reserved_pins.extend(ioc::load_used_pins(path)?);
+    }
+    if !reserved_pins.is_empty() {
+        info!("Reserved pins: {}", reserved_pins.join(", "));
+        let reserved_pins = reserved_pins
+            .iter()
+            .map(|p| p.parse())
+            .collect::<Result<Vec<pin_spec::PinSpec>, String>>()?;
+        tree_builder = tree_builder.reserved_pins(reserved_pins);
+    }
+    if let Some(path) = args
+        .value_of("signals_from")
+        .map(PathBuf::from)
+        .or_else(|| config.signals_from.clone())
+    {
+        tree_builder = tree_builder.signals(read_list(&path, "signal list")?);
+    }
+    if let Some(path) = args
+        .value_of("exclude_signals_from")
+        .map(PathBuf::from)
+        .or_else(|| config.exclude_signals_from.clone())
+    {
+        tree_builder = tree_builder.exclude_signals(read_list(&path, "signal list")?);
+    }
+    if let Some(policy) = args
+        .value_of("af_policy")
+        .or(config.codegen.af_policy.as_deref())
+    {
+        tree_builder = tree_builder.af_policy(policy.parse()?);
+    }
+    Ok((tree_builder, stem_patterns))
+}
+
+/// The layout of the generated code, `Layout::GpioVersion` by default.
+fn layout(args: &ArgMatches, config: &config::Config) -> Result<codegen::Layout, String> {
+    match args.value_of("layout").or(config.codegen.layout.as_deref()) {
+        Some(layout) => layout.parse(),
+        None => Ok(codegen::Layout::GpioVersion),
+    }
+}
+
+/// Map the selected MCUs to the features of their sub-families, if the code is
+/// laid out by sub-family.
+fn mcu_sub_family_map(
+    layout: codegen::Layout,
+    family: &FamilyData,
+    names: &features::FeatureNames,
+) -> HashMap<String, String> {
+    let mut mcu_sub_family_map = HashMap::new();
+    if layout == codegen::Layout::SubFamily {
+        for (sub_family, mcus) in &family.sub_family_mcus {
+            for mcu in mcus {
+                mcu_sub_family_map.insert(mcu.clone(), names.sub_family_feature(sub_family));
+            }
+        }
+    }
+    mcu_sub_family_map
+}
+
+/// The prefix of the bare cfgs (e.g. "stm32_"), empty by default.
+fn cfg_prefix<'a>(args: &'a ArgMatches, config: &'a config::Config) -> &'a str {
+    args.value_of("cfg_prefix")
+        .or(config.codegen.cfg_prefix.as_deref())
+        .unwrap_or_default()
 }
 
-fn render_pin_modes(ip: &internal_peripheral::IpGPIO) {
-    let mut pin_map: HashMap<String, Vec<String>> = HashMap::new();
+/// Generate the Cargo features of the selected MCUs.
+fn generate_features(
+    family: &FamilyData,
+    args: &ArgMatches,
+    config: &config::Config,
+    names: &features::FeatureNames,
+) -> Result<codegen::GeneratedArtifact, String> {
+    codegen::generate_features(
+        &family.mcu_gpio_map,
+        &family.mcu_package_map,
+        &mcu_sub_family_map(layout(args, config)?, family, names),
+        &family.mcu_core_map,
+        &family.mcu_peripheral_map,
+        &family.mcu_die_map,
+        &family.family_names,
+        names,
+    )
+}
 
-    for p in &ip.gpio_pin {
-        let name = p.get_name();
-        if let Some(n) = name {
-            pin_map.insert(n, p.get_af_modes());
+/// Generate the build script part setting the cfgs of the selected MCUs.
+fn generate_build_cfgs(
+    family: &FamilyData,
+    args: &ArgMatches,
+    config: &config::Config,
+    names: &features::FeatureNames,
+) -> Result<codegen::GeneratedArtifact, String> {
+    codegen::generate_build_cfgs(
+        &family.mcu_gpio_map,
+        &mcu_sub_family_map(layout(args, config)?, family, names),
+        &family.family_names,
+        names,
+        cfg_prefix(args, config),
+    )
+}
+
+/// Generate the pin mappings in the configured style and layout, followed by
+/// their tests with `--emit-tests`.
+fn generate_pins(
+    tree: &af_tree::AfTree,
+    args: &ArgMatches,
+    config: &config::Config,
+    family: &FamilyData,
+    names: &features::FeatureNames,
+) -> Result<Vec<codegen::GeneratedArtifact>, String> {
+    let layout = layout(args, config)?;
+    let style = match args.value_of("style").or(config.codegen.style.as_deref()) {
+        Some(style) => style.parse()?,
+        None => codegen::Style::Default,
+    };
+    let package_pins = if args.is_present("package_gating") || config.codegen.package_gating {
+        Some(codegen::PackagePins::new(
+            &family.pinouts,
+            &family.mcu_package_map,
+            names,
+        ))
+    } else {
+        None
+    };
+    let cfg_style = match args
+        .value_of("cfg_style")
+        .or(config.codegen.cfg_style.as_deref())
+    {
+        Some(cfg_style) => cfg_style.parse()?,
+        None => codegen::CfgStyle::Io,
+    };
+    let options = codegen::PinMappingOptions {
+        af_defines: args.is_present("af_defines") || config.codegen.af_defines,
+        alternatives: args.is_present("alternatives") || config.codegen.alternatives,
+        debug_docs: args.is_present("debug_pin_docs") || config.codegen.debug_pin_docs,
+        sealed_traits: args.is_present("sealed_traits") || config.codegen.sealed_traits,
+        package_pins: package_pins.as_ref(),
+        mcu_docs: !(args.is_present("no_mcu_docs") || config.codegen.no_mcu_docs),
+        cfg_style,
+        cfg_prefix: cfg_prefix(args, config),
+        merge_serial_stems: args.is_present("merge_serial_stems")
+            || config.codegen.merge_serial_stems,
+    };
+    let templates = load_templates(
+        args.value_of("template_dir")
+            .map(PathBuf::from)
+            .or_else(|| config.codegen.template_dir.clone()),
+    )?;
+
+    let mappings = match layout {
+        codegen::Layout::GpioVersion => {
+            codegen::generate_styled_pin_mappings(tree, style, &options, names, &templates)?
+        }
+        _ if style != codegen::Style::Default => {
+            return Err(format!(
+                "The {} style only supports the gpio-version layout",
+                style.name()
+            ));
         }
+        codegen::Layout::SubFamily => {
+            let mut overrides = HashMap::new();
+            for (sub_family, path) in &config.codegen.sub_family_overrides {
+                overrides.insert(sub_family.clone(), read_override(path)?);
+            }
+            for definition in args.values_of("sub_family_override").into_iter().flatten() {
+                let (sub_family, path) = definition.split_once('=').ok_or_else(|| {
+                    format!(
+                        "Invalid sub-family override {:?}, expected SUB_FAMILY=FILE",
+                        definition
+                    )
+                })?;
+                overrides.insert(sub_family.to_string(), read_override(Path::new(path))?);
+            }
+            codegen::generate_sub_family_pin_mappings(
+                tree,
+                &family.sub_family_mcus,
+                &overrides,
+                &options,
+                names,
+                &templates,
+            )?
+        }
+    };
+    if !(args.is_present("emit_tests") || config.codegen.emit_tests) {
+        return Ok(vec![mappings]);
+    }
+
+    if style != codegen::Style::Default && style != codegen::Style::L0xx {
+        return Err(format!(
+            "The tests do not support the {} style",
+            style.name()
+        ));
+    }
+    let prelude = args
+        .value_of("test_prelude")
+        .or(config.codegen.test_prelude.as_deref())
+        .ok_or("The tests need a prelude to import (--test-prelude or test_prelude)")?;
+    let pin_type = args
+        .value_of("test_pin_type")
+        .or(config.codegen.test_pin_type.as_deref())
+        .unwrap_or("{pin}");
+    let tests = codegen::generate_pin_tests(tree, &options, names, prelude, pin_type)?;
+    Ok(vec![mappings, tests])
+}
+
+/// Generate the features, pin mappings (and their tests), docs and manifest
+/// in the directory layout of a HAL repository.
+fn generate_all(
+    tree: &af_tree::AfTree,
+    args: &ArgMatches,
+    config: &config::Config,
+    family: &FamilyData,
+    names: &features::FeatureNames,
+    summary: &summary::Summary,
+) -> Result<Vec<codegen::GeneratedArtifact>, String> {
+    let mut artifacts = vec![generate_features(family, args, config, names)?];
+    artifacts.extend(generate_pins(tree, args, config, family, names)?);
+    if config.codegen.alias_feature_sets {
+        let aliases = codegen::alias_feature_sets(&mut artifacts, names)?;
+        info!("Defined {} feature set aliases", aliases);
+    }
+    artifacts[1].path = PathBuf::from("src").join(&artifacts[1].path);
+    let mut docs = codegen::generate_fragmentation(tree, names)?;
+    docs.path = PathBuf::from("docs").join(&docs.path);
+    artifacts.push(docs);
+    artifacts.push(codegen::generate_manifest(
+        &family.family_names,
+        summary.db_release.as_deref(),
+        family.mcus.len(),
+        &artifacts,
+    ));
+    Ok(artifacts)
+}
+
+/// List the pin signals matching the filters of the `query` subcommand, and
+/// write the tree to the `--dump-ir` file.
+fn generate_query(
+    tree: &af_tree::AfTree,
+    args: &ArgMatches,
+    summary: &mut summary::Summary,
+) -> Result<codegen::GeneratedArtifact, String> {
+    if let Some(path) = args.value_of("dump_ir") {
+        fs::write(path, tree.to_json())
+            .map_err(|e| format!("Could not write IR {}: {}", path, e))?;
+        summary.files_written.push(PathBuf::from(path));
+    }
+    Ok(codegen::generate_query(
+        tree,
+        &filter_spec(args)?,
+        args.is_present("details"),
+    ))
+}
+
+/// The stem aliases of the CAN pins (e.g. "FDCAN" to "CAN"), from the config
+/// file and `--stem-alias`.
+fn stem_aliases(
+    args: &ArgMatches,
+    config: &config::Config,
+) -> Result<HashMap<String, String>, String> {
+    let mut aliases = config.codegen.stem_aliases.clone();
+    for definition in args.values_of("stem_alias").into_iter().flatten() {
+        let (stem, alias) = definition
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid stem alias {:?}, expected STEM=ALIAS", definition))?;
+        aliases.insert(stem.to_string(), alias.to_string());
     }
+    Ok(aliases)
+}
 
-    let mut pin_map = pin_map
+/// Generate the DMA request mappings of the DMA IP versions of the selected
+/// MCUs.
+fn generate_dma_requests(
+    family: &FamilyData,
+    db_dir: &Path,
+    names: &features::FeatureNames,
+) -> Result<codegen::GeneratedArtifact, String> {
+    let groups = ip_versions(&family.mcu_ip_map, dma::DMA_IPS)
         .into_iter()
-        .map(|(k, mut v)| {
-            #[allow(clippy::redundant_closure)]
-            v.sort_by(|a, b| compare_str(a, b));
-            (k, v)
-        })
+        .map(|(ip, version, mcus)| dma::DmaGroup::load(db_dir, ip, version, mcus.to_vec()))
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(codegen::generate_dma_requests(
+        &groups,
+        &family.mcu_die_map,
+        names,
+    ))
+}
+
+/// Generate the clock metadata of the RCC IP versions of the selected MCUs.
+fn generate_clocks(
+    family: &FamilyData,
+    db_dir: &Path,
+    names: &features::FeatureNames,
+) -> Result<codegen::GeneratedArtifact, String> {
+    let groups = ip_versions(&family.mcu_ip_map, &["RCC"])
+        .into_iter()
+        .map(|(_, version, mcus)| rcc::RccGroup::load(db_dir, version, mcus.to_vec()))
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(codegen::generate_clocks(
+        &groups,
+        &family.mcu_die_map,
+        names,
+    ))
+}
+
+/// Generate the interrupts of the NVIC IP versions of the selected MCUs.
+fn generate_interrupts(
+    family: &FamilyData,
+    db_dir: &Path,
+    names: &features::FeatureNames,
+) -> Result<codegen::GeneratedArtifact, String> {
+    let groups = ip_versions(&family.mcu_ip_map, nvic::NVIC_IPS)
+        .into_iter()
+        .map(|(ip, version, mcus)| nvic::NvicGroup::load(db_dir, ip, version, mcus.to_vec()))
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(codegen::generate_interrupts(
+        &groups,
+        &family.mcu_die_map,
+        names,
+    ))
+}
+
+/// Warn about requested packages that no MCU of the family comes in, listing
+/// the packages that are available.
+fn check_packages(family: &family::Family, packages: &[String], summary: &mut summary::Summary) {
+    let mut available = family
+        .mcus()
+        .map(|mcu| mcu.package_name.as_str())
+        .filter(|package| !package.is_empty())
         .collect::<Vec<_>>();
+    available.sort_by(|a, b| compare_str(a, b));
+    available.dedup();
+    if available.is_empty() {
+        summary.warn(format!(
+            "The database has no package data for family {}",
+            family.name
+        ));
+        return;
+    }
+    for package in packages {
+        if !available
+            .iter()
+            .any(|p| family::package_matches(p, package))
+        {
+            summary.warn(format!(
+                "No MCU of family {} comes in package {}, available packages: {}",
+                family.name,
+                package,
+                available.join(", ")
+            ));
+        }
+    }
+}
 
-    pin_map.sort_by(|a, b| compare_str(&a.0, &b.0));
+/// Read a list of names (e.g. MCU ref names), one per line. Empty lines and
+/// lines starting with `#` are ignored.
+fn read_list(path: &Path, what: &str) -> Result<Vec<String>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read {} {}: {}", what, path.display(), e))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect())
+}
 
-    println!("pins! {{");
-    for (n, af) in pin_map {
-        if af.is_empty() {
-            continue;
-        } else if af.len() == 1 {
-            println!("    {} => {{{}}},", n, af[0]);
+/// Return the versions of some IPs (e.g. "DMA") used by the selected MCUs with
+/// the MCUs using each version, sorted by IP and version.
+fn ip_versions<'a>(
+    mcu_ip_map: &'a HashMap<(String, String), Vec<String>>,
+    ips: &[&str],
+) -> Vec<(&'a str, &'a str, &'a [String])> {
+    let mut versions = mcu_ip_map
+        .iter()
+        .filter(|((ip, _), _)| ips.contains(&ip.as_str()))
+        .map(|((ip, version), mcus)| (ip.as_str(), version.as_str(), mcus.as_slice()))
+        .collect::<Vec<_>>();
+    versions.sort_by(|(ip_a, a, _), (ip_b, b, _)| ip_a.cmp(ip_b).then_with(|| compare_str(a, b)));
+    versions
+}
+
+/// Build the AF tree and list the stems matched by each wildcard or regex stem
+/// pattern.
+fn build_tree(
+    tree_builder: af_tree::AfTreeBuilder,
+    stem_patterns: &[stems::StemPattern],
+    explain: bool,
+    summary: &mut summary::Summary,
+) -> Result<af_tree::AfTree, String> {
+    let af_tree = tree_builder.build()?;
+    if explain {
+        for step in af_tree.filter_steps() {
+            info!(
+                "  {}: {} pins, {} signals",
+                step.filter, step.pins, step.signals
+            );
+        }
+    }
+    let mut stems = af_tree
+        .groups()
+        .iter()
+        .flat_map(|group| group.stems())
+        .collect::<Vec<_>>();
+    stems.sort_by(|a, b| compare_str(a, b));
+    stems.dedup();
+    for pattern in stem_patterns.iter().filter(|p| p.is_pattern()) {
+        let matched = stems
+            .iter()
+            .filter(|stem| pattern.matches(stem))
+            .cloned()
+            .collect::<Vec<_>>();
+        if matched.is_empty() {
+            summary.warn(format!(
+                "Stem pattern {} matches no peripheral (see the list-stems subcommand)",
+                pattern
+            ));
         } else {
-            println!("    {} => {{", n);
-            for a in af {
-                println!("        {},", a);
-            }
-            println!("    }},");
+            info!("Stem pattern {} matches {}", pattern, matched.join(", "));
         }
     }
-    println!("}}");
+    Ok(af_tree)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Parse an alternate function given as "AF7" or "7".
+fn parse_af(af: &str) -> Result<String, String> {
+    // The kinds of the analog signals and other additional functions
+    let kinds = [
+        additional::AdditionalKind::Analog,
+        additional::AdditionalKind::Wakeup,
+        additional::AdditionalKind::Tamper,
+        additional::AdditionalKind::Other,
+    ];
+    if let Some(kind) = kinds.iter().find(|k| k.name().eq_ignore_ascii_case(af)) {
+        return Ok(kind.name().to_string());
+    }
+    let number = af.strip_prefix("AF").unwrap_or(af);
+    number
+        .parse::<u8>()
+        .map(|n| format!("AF{}", n))
+        .map_err(|_| format!("Invalid alternate function {:?}, expected e.g. AF7", af))
+}
 
-    #[test]
-    fn test_gpio_version_to_feature() {
-        // Success
-        assert_eq!(
-            gpio_version_to_feature("STM32L152x8_gpio_v1_0").unwrap(),
-            "io-STM32L152x8"
-        );
-        assert_eq!(
-            gpio_version_to_feature("STM32F333_gpio_v1_0").unwrap(),
-            "io-STM32F333"
-        );
+/// Read the hand-maintained module of a sub-family.
+fn read_override(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| {
+        format!(
+            "Could not read sub-family override {}: {}",
+            path.display(),
+            e
+        )
+    })
+}
 
-        // Error parsing, unsupported version
-        assert!(gpio_version_to_feature("STM32F333_gpio_v1_1").is_err());
+/// Build the filter of the `query` subcommand.
+fn filter_spec(args: &ArgMatches) -> Result<af_tree::FilterSpec, String> {
+    let mut filter = af_tree::FilterSpec::new();
+    if let Some(devices) = args.values_of("device") {
+        filter = filter.devices(devices);
+    }
+    if let Some(afs) = args.values_of("af") {
+        filter = filter.afs(afs.map(parse_af).collect::<Result<Vec<_>, _>>()?);
+    }
+    if let Some(ports) = args.values_of("port") {
+        filter = filter.ports(ports);
+    }
+    if let Some(gpio_versions) = args.values_of("gpio-version") {
+        filter = filter.gpio_versions(gpio_versions);
+    }
+    Ok(filter)
+}
 
-        // Error parsing, wrong pattern
-        assert!(gpio_version_to_feature("STM32F333_qqio_v1_0").is_err());
+/// Run the `query` subcommand against trees cached with `--dump-ir` instead of
+/// the database, or compare two of them.
+fn query_irs(paths: &[&str], args: &ArgMatches) -> Result<codegen::GeneratedArtifact, String> {
+    let trees = paths
+        .iter()
+        .map(|path| {
+            let json = fs::read_to_string(path)
+                .map_err(|e| format!("Could not read IR {}: {}", path, e))?;
+            af_tree::AfTree::from_json(&json)
+                .map_err(|e| format!("Could not parse IR {}: {}", path, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let filter = filter_spec(args)?;
+    match (args.is_present("compare"), trees.as_slice()) {
+        (false, [tree]) => Ok(codegen::generate_query(
+            tree,
+            &filter,
+            args.is_present("details"),
+        )),
+        (true, [old, new]) => Ok(codegen::generate_query_diff(old, new, &filter)),
+        (false, _) => Err("Multiple IRs can only be queried with --compare".into()),
+        (true, _) => Err("--compare requires exactly two IRs".into()),
+    }
+}
+
+/// List the labelled pins of a board with the alternate functions of its MCU.
+fn generate_board_pins(
+    families: &family::Families,
+    db_dir: &Path,
+    board: &str,
+    strict: bool,
+) -> Result<codegen::GeneratedArtifact, String> {
+    let board = board::Board::load(db_dir, board)?;
+    let tree = af_tree::AfTree::builder(db_dir)
+        .mcu_gpio_map(single_mcu_gpio_map(families, db_dir, &board.mcu)?)
+        .strict(strict)
+        .build()?;
+    Ok(codegen::generate_board_pins(&board, tree.groups().first()))
+}
 
-        // Error parsing, too many underscores
-        assert!(gpio_version_to_feature("STM32_STM32F333_gpio_v1_0").is_err());
+/// Generate the pin mappings of the signals assigned in a CubeMX project.
+fn generate_ioc_pins(
+    families: &family::Families,
+    db_dir: &Path,
+    path: &Path,
+    strict: bool,
+    config: &config::Config,
+) -> Result<codegen::GeneratedArtifact, String> {
+    let project = ioc::Project::load(path)?;
+    let tree = af_tree::AfTree::builder(db_dir)
+        .mcu_gpio_map(single_mcu_gpio_map(families, db_dir, &project.mcu)?)
+        .assignments(project.assignments())
+        .strict(strict)
+        .serial_handshake(config.codegen.serial_handshake)
+        .build()?;
+    codegen::generate_pin_mappings(
+        &tree,
+        &codegen::PinMappingOptions {
+            af_defines: config.codegen.af_defines,
+            alternatives: false,
+            debug_docs: config.codegen.debug_pin_docs,
+            sealed_traits: config.codegen.sealed_traits,
+            package_pins: None,
+            mcu_docs: !config.codegen.no_mcu_docs,
+            cfg_style: match &config.codegen.cfg_style {
+                Some(cfg_style) => cfg_style.parse()?,
+                None => codegen::CfgStyle::Io,
+            },
+            cfg_prefix: config.codegen.cfg_prefix.as_deref().unwrap_or_default(),
+            merge_serial_stems: config.codegen.merge_serial_stems,
+        },
+        &config.features,
+        &load_templates(config.codegen.template_dir.clone())?,
+    )
+}
+
+/// Load the code templates, overridden by the ones in `dir` if given.
+fn load_templates(dir: Option<PathBuf>) -> Result<templates::Templates, String> {
+    match dir {
+        Some(dir) => {
+            info!("Loading templates from {}", dir.display());
+            templates::Templates::load(dir)
+        }
+        None => Ok(templates::Templates::new()),
+    }
+}
+
+/// Map the GPIO version of a single MCU (e.g. "STM32L031K6Tx") to its ref
+/// name.
+fn single_mcu_gpio_map(
+    families: &family::Families,
+    db_dir: &Path,
+    ref_name: &str,
+) -> Result<HashMap<String, Vec<String>>, String> {
+    let mcu = families
+        .into_iter()
+        .flat_map(|family| family.mcus())
+        .find(|mcu| mcu.ref_name == ref_name)
+        .ok_or_else(|| format!("MCU {} not found", ref_name))?;
+    let mcu_dat = mcu::Mcu::load(db_dir, &mcu.name)
+        .map_err(|e| format!("Could not load MCU data of {}: {}", mcu.ref_name, e))?;
+    let gpio_version = mcu_dat
+        .get_ip("GPIO")
+        .ok_or_else(|| format!("MCU {} has no GPIO IP", mcu.ref_name))?
+        .get_version()
+        .to_string();
+    let mut mcu_gpio_map = HashMap::new();
+    mcu_gpio_map.insert(gpio_version, vec![mcu.ref_name.clone()]);
+    Ok(mcu_gpio_map)
+}
+
+/// Generate a report of the MCUs in all families that can provide all of the
+/// specified signals (on any pin).
+fn generate_portability(
+    families: &family::Families,
+    db_dir: &Path,
+    signals: &[&str],
+    strict: bool,
+    summary: &mut summary::Summary,
+) -> Result<codegen::GeneratedArtifact, String> {
+    let mut out = String::new();
+    writeln!(out, "# Portability of {}\n", signals.join(", ")).unwrap();
+    for family in families {
+        let af_tree = match af_tree::AfTree::builder(db_dir)
+            .family(&family.name)
+            .strict(strict)
+            .build()
+        {
+            Ok(af_tree) => af_tree,
+            Err(e) if strict => return Err(format!("Family {}: {}", family.name, e)),
+            Err(e) => {
+                summary.error(format!("Skipping family {}: {}", family.name, e));
+                continue;
+            }
+        };
+        let groups = af_tree
+            .groups()
+            .iter()
+            .filter(|group| signals.iter().all(|s| group.has_signal(s)))
+            .collect::<Vec<_>>();
+
+        writeln!(out, "## {}\n", family.name).unwrap();
+        if groups.is_empty() {
+            writeln!(out, "(none)\n").unwrap();
+            continue;
+        }
+        for group in groups {
+            writeln!(out, "- {}: {}", group.version(), group.mcus().join(", ")).unwrap();
+        }
+        writeln!(out).unwrap();
     }
+    Ok(codegen::GeneratedArtifact::new(
+        "portability.md",
+        codegen::ArtifactKind::Report,
+        out,
+    ))
 }
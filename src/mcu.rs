@@ -1,14 +1,66 @@
 use std::error::Error;
 use std::path::Path;
+use std::str::FromStr;
 
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Serialize, Serializer};
 use serde_derive::Deserialize;
 
+use crate::nvic;
 use crate::utils::load_file;
 
+lazy_static! {
+    static ref GPIO_NAME: Regex = Regex::new(r"^(P[A-Z]\d+(?:_C$)?)(?:\D|$)").unwrap();
+    /// The analog-only dual pads of the STM32H7, e.g. "PA0_C".
+    static ref DUAL_PAD: Regex = Regex::new(r"^(P[A-Z])(\d+)_C$").unwrap();
+    static ref VARIANTS: Regex = Regex::new(r"^(.*)\((.*)\)(.*)$").unwrap();
+}
+
+/// The MCU data from a MCU XML file (e.g. `STM32L071K(B-Z)Tx.xml`).
+///
+/// Note that a single MCU file may describe several MCUs that only differ in
+/// flash size. In that case, `flash` contains one entry per variant.
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct Mcu {
+    /// The ref name, possibly with a variant pattern (e.g.
+    /// "STM32L071K(B-Z)Tx"), empty if the file does not specify it.
+    #[serde(default)]
+    pub ref_name: String,
+    /// The family (e.g. "STM32L0"), empty if the file does not specify it.
+    #[serde(default)]
+    pub family: String,
+    /// The line (e.g. "STM32L0x1").
+    #[serde(default)]
+    pub line: String,
+    /// The package (e.g. "LQFP32"), empty if the file does not specify it.
+    #[serde(default)]
+    pub package: String,
+    /// The CPU core(s) (e.g. "Arm Cortex-M0+"). Dual-core MCUs have two
+    /// entries, the STM32MP1 MPUs list their Cortex-A7 cores and the
+    /// Cortex-M4.
+    #[serde(rename = "Core", default)]
+    pub cores: Vec<String>,
+    /// The silicon die (e.g. "DIE447"). MCUs sharing a die only differ in
+    /// their package, flash size and enabled peripherals.
+    #[serde(default)]
+    pub die: Option<String>,
+    /// The maximum frequency, one entry per core type.
+    #[serde(rename = "Frequency", default)]
+    frequency: Vec<String>,
+    #[serde(rename = "Ram", default)]
+    ram: Vec<String>,
+    #[serde(rename = "Flash", default)]
+    flash: Vec<String>,
+    #[serde(rename = "IONb", default)]
+    io_count: Option<String>,
+    #[serde(rename = "E2prom", default)]
+    eeprom: Option<String>,
     #[serde(rename = "IP", default)]
     ip: Vec<IP>,
+    #[serde(rename = "Pin", default)]
+    pin: Vec<Pin>,
 }
 
 impl Mcu {
@@ -19,8 +71,182 @@ impl Mcu {
     pub fn get_ip(&self, name: &str) -> Option<&IP> {
         self.ip.iter().find(|v| v.name == name)
     }
+
+    /// Return the Cortex-M cores, skipping unknown ones. Dual-core MCUs have
+    /// two entries (e.g. Cortex-M7 and Cortex-M4). On MPUs, only the
+    /// Cortex-M coprocessor is returned.
+    pub fn get_cores(&self) -> Vec<Core> {
+        self.cores.iter().filter_map(|c| c.parse().ok()).collect()
+    }
+
+    /// Whether this is a MPU with Cortex-A cores (e.g. STM32MP1), of which
+    /// only the Cortex-M side is supported.
+    pub fn is_mpu(&self) -> bool {
+        self.cores.iter().any(|c| c.contains("Cortex-A"))
+    }
+
+    /// Return the maximum frequency in MHz, one per core type (e.g. 650 for
+    /// the Cortex-A7 and 209 for the Cortex-M4 on the STM32MP1).
+    pub fn get_frequency(&self) -> Vec<u32> {
+        parse_numbers(&self.frequency)
+    }
+
+    /// Return the RAM size(s) in KiB.
+    pub fn get_ram(&self) -> Vec<u32> {
+        parse_numbers(&self.ram)
+    }
+
+    /// Return the flash size(s) in KiB, one per variant.
+    pub fn get_flash(&self) -> Vec<u32> {
+        parse_numbers(&self.flash)
+    }
+
+    /// Return the flash size in KiB of one of the MCUs described by this file
+    /// (e.g. 192 for "STM32L071KZTx" in `STM32L071K(B-Z)Tx`). MPUs without
+    /// internal flash (listed as 0) have none.
+    pub fn get_variant_flash(&self, ref_name: &str) -> Option<u32> {
+        let flash = self.get_flash();
+        let size = if flash.len() == 1 {
+            flash[0]
+        } else {
+            let captures = VARIANTS.captures(&self.ref_name)?;
+            let index = captures[2].split('-').position(|variant| {
+                format!("{}{}{}", &captures[1], variant, &captures[3]) == ref_name
+            })?;
+            *flash.get(index)?
+        };
+        Some(size).filter(|size| *size > 0)
+    }
+
+    /// Return the number of IOs.
+    pub fn get_io_count(&self) -> Option<u32> {
+        self.io_count.as_ref().and_then(|v| v.trim().parse().ok())
+    }
+
+    /// Return the data EEPROM size in bytes, on the families that have one
+    /// (e.g. STM32L0 and STM32L1).
+    pub fn get_eeprom(&self) -> Option<u32> {
+        self.eeprom
+            .as_ref()
+            .and_then(|v| v.trim().parse().ok())
+            .filter(|size| *size > 0)
+    }
+
+    /// Return all internal peripherals of this MCU.
+    pub fn get_ips(&self) -> &[IP] {
+        &self.ip
+    }
+
+    /// Return all pins of this MCU, including the power and reset pins.
+    pub fn get_pins(&self) -> &[Pin] {
+        &self.pin
+    }
+
+    /// Load the device interrupts of this MCU (of the first core on dual-core
+    /// MCUs) from the Modes file of its NVIC.
+    pub fn load_interrupts<P: AsRef<Path>>(
+        &self,
+        db_dir: P,
+    ) -> Result<Vec<nvic::Interrupt>, String> {
+        let ip = self
+            .ip
+            .iter()
+            .find(|ip| nvic::NVIC_IPS.contains(&ip.name.as_str()))
+            .ok_or_else(|| format!("MCU {} has no NVIC IP", self.ref_name))?;
+        nvic::IpNvic::load(db_dir, &ip.name, &ip.version)
+            .map(|nvic| nvic.interrupts())
+            .map_err(|e| {
+                format!(
+                    "Could not load {}: {}",
+                    nvic::IpNvic::path(&ip.name, &ip.version),
+                    e
+                )
+            })
+    }
+}
+
+/// A Cortex-M CPU core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Core {
+    CortexM0,
+    CortexM0Plus,
+    CortexM3,
+    CortexM4,
+    CortexM7,
+    CortexM23,
+    CortexM33,
+    CortexM55,
+}
+
+impl Core {
+    /// The name of the core feature (e.g. "cortex-m0plus").
+    pub fn feature_name(self) -> &'static str {
+        match self {
+            Core::CortexM0 => "cortex-m0",
+            Core::CortexM0Plus => "cortex-m0plus",
+            Core::CortexM3 => "cortex-m3",
+            Core::CortexM4 => "cortex-m4",
+            Core::CortexM7 => "cortex-m7",
+            Core::CortexM23 => "cortex-m23",
+            Core::CortexM33 => "cortex-m33",
+            Core::CortexM55 => "cortex-m55",
+        }
+    }
+
+    /// The short name of the core (e.g. "cm4"), used to select the target
+    /// core of dual-core MCUs.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Core::CortexM0 => "cm0",
+            Core::CortexM0Plus => "cm0plus",
+            Core::CortexM3 => "cm3",
+            Core::CortexM4 => "cm4",
+            Core::CortexM7 => "cm7",
+            Core::CortexM23 => "cm23",
+            Core::CortexM33 => "cm33",
+            Core::CortexM55 => "cm55",
+        }
+    }
+}
+
+impl Serialize for Core {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.feature_name())
+    }
+}
+
+impl FromStr for Core {
+    type Err = String;
+
+    /// Parse a core as listed in the MCU files (e.g. "Arm Cortex-M0+").
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s
+            .trim()
+            .trim_start_matches("Arm ")
+            .trim_start_matches("ARM ");
+        Ok(match name {
+            "Cortex-M0" => Core::CortexM0,
+            "Cortex-M0+" => Core::CortexM0Plus,
+            "Cortex-M3" => Core::CortexM3,
+            "Cortex-M4" => Core::CortexM4,
+            "Cortex-M7" => Core::CortexM7,
+            "Cortex-M23" => Core::CortexM23,
+            "Cortex-M33" => Core::CortexM33,
+            "Cortex-M55" => Core::CortexM55,
+            _ => return Err(format!("Unknown core {:?}", s)),
+        })
+    }
 }
 
+/// Parse a list of numbers, skipping invalid entries.
+fn parse_numbers(values: &[String]) -> Vec<u32> {
+    values
+        .iter()
+        .filter_map(|v| v.trim().parse().ok())
+        .collect()
+}
+
+/// An internal peripheral (e.g. `<IP InstanceName="USART1" Name="USART" Version="sci3_v1_1_Cube"/>`).
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct IP {
@@ -30,7 +256,206 @@ pub struct IP {
 }
 
 impl IP {
+    pub fn get_instance_name(&self) -> &str {
+        &self.instance_name
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
     pub fn get_version(&self) -> &str {
         &self.version
     }
 }
+
+/// A pin of the package (e.g. `<Pin Name="PA0" Position="6" Type="I/O">`)
+/// with the signals it can provide, including the ones that are not alternate
+/// functions (e.g. "ADC_IN0").
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Pin {
+    name: String,
+    #[serde(default)]
+    position: String,
+    #[serde(rename = "Type", default)]
+    pin_type: String,
+    #[serde(rename = "Signal", default)]
+    signal: Vec<Signal>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Signal {
+    name: String,
+}
+
+impl Pin {
+    /// Return the name, possibly with a suffix (e.g. "PC14-OSC32_IN").
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return the position in the package: the pin number (e.g. "6"), or the
+    /// ball (e.g. "A1") of BGA and WLCSP packages.
+    pub fn get_position(&self) -> &str {
+        &self.position
+    }
+
+    /// Return the pin type (e.g. "I/O", "Power" or "Reset").
+    pub fn get_type(&self) -> &str {
+        &self.pin_type
+    }
+
+    /// Return the GPIO name without any suffix (e.g. "PC14"), or `None` for
+    /// pins that are not GPIOs (e.g. "VDD"). The dual pads of the STM32H7 keep
+    /// their suffix (e.g. "PA0_C"), since they are separate pins.
+    pub fn get_gpio_name(&self) -> Option<&str> {
+        GPIO_NAME
+            .captures(&self.name)
+            .map(|c| c.get(1).unwrap().as_str())
+    }
+
+    /// Return the names of the signals of this pin (e.g. "USART2_TX").
+    pub fn get_signals(&self) -> impl Iterator<Item = &str> {
+        self.signal.iter().map(|s| s.name.as_str())
+    }
+}
+
+/// Parse the name of an analog-only dual pad of the STM32H7 (e.g. "PA0_C")
+/// into the port and number of the GPIO sharing its number (e.g. "PA" and
+/// "0"). Other pins return `None`.
+pub fn parse_dual_pad(name: &str) -> Option<(&str, &str)> {
+    let captures = DUAL_PAD.captures(name)?;
+    Some((
+        captures.get(1).unwrap().as_str(),
+        captures.get(2).unwrap().as_str(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_mcu() {
+        let xml = r#"
+            <Mcu ClockTree="STM32L0" DBVersion="V3.0" Family="STM32L0" HasPowerPad="false"
+                    IOType="" Line="STM32L0x1" Package="LQFP32" RefName="STM32L071K(B-Z)Tx"
+                    xmlns="http://mcd.rou.st.com/modules.php?name=mcu">
+                <Core>Arm Cortex-M0+</Core>
+                <Frequency>32</Frequency>
+                <E2prom>6144</E2prom>
+                <Ram>20</Ram>
+                <IONb>25</IONb>
+                <Die>DIE447</Die>
+                <Flash>128</Flash>
+                <Flash>192</Flash>
+                <Voltage Max="3.6" Min="1.65"/>
+                <IP InstanceName="USART1" Name="USART" Version="sci3_v1_1_Cube"/>
+                <IP ConfigFile="GPIO-STM32L0xx" InstanceName="GPIO" Name="GPIO" Version="STM32L071_gpio_v1_0"/>
+                <Pin Name="VDD" Position="1" Type="Power"/>
+                <Pin Name="PC14-OSC32_IN" Position="2" Type="I/O">
+                    <Signal Name="RCC_OSC32_IN"/>
+                </Pin>
+                <Pin Name="PA0" Position="6" Type="I/O">
+                    <Signal Name="ADC_IN0"/>
+                    <Signal Name="USART2_CTS"/>
+                </Pin>
+            </Mcu>
+        "#;
+        let mcu: Mcu = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        assert_eq!(mcu.ref_name, "STM32L071K(B-Z)Tx");
+        assert_eq!(mcu.family, "STM32L0");
+        assert_eq!(mcu.line, "STM32L0x1");
+        assert_eq!(mcu.package, "LQFP32");
+        assert_eq!(mcu.cores, vec!["Arm Cortex-M0+"]);
+        assert_eq!(mcu.get_cores(), vec![Core::CortexM0Plus]);
+        assert_eq!("Arm Cortex-M7".parse(), Ok(Core::CortexM7));
+        assert!("Arm Cortex-A7".parse::<Core>().is_err());
+        assert_eq!(mcu.get_frequency(), vec![32]);
+        assert_eq!(mcu.get_ram(), vec![20]);
+        assert_eq!(mcu.get_flash(), vec![128, 192]);
+        assert_eq!(mcu.get_io_count(), Some(25));
+        assert_eq!(mcu.get_eeprom(), Some(6144));
+        assert_eq!(mcu.get_variant_flash("STM32L071KZTx"), Some(192));
+        assert_eq!(mcu.get_variant_flash("STM32L071KCTx"), None);
+        assert_eq!(mcu.get_ips().len(), 2);
+        assert_eq!(
+            mcu.get_ip("GPIO").unwrap().get_version(),
+            "STM32L071_gpio_v1_0"
+        );
+        assert_eq!(mcu.get_ips()[0].get_instance_name(), "USART1");
+        let pins = mcu.get_pins();
+        assert_eq!(pins.len(), 3);
+        assert_eq!(pins[0].get_gpio_name(), None);
+        assert_eq!(pins[1].get_gpio_name(), Some("PC14"));
+        assert_eq!(pins[1].get_position(), "2");
+        assert_eq!(pins[1].get_type(), "I/O");
+        assert_eq!(
+            pins[2].get_signals().collect::<Vec<_>>(),
+            ["ADC_IN0", "USART2_CTS"]
+        );
+        assert!(!mcu.is_mpu());
+    }
+
+    #[test]
+    fn test_deserialize_mpu() {
+        let xml = r#"
+            <Mcu ClockTree="STM32MP1" DBVersion="V3.0" Family="STM32MP1" HasPowerPad="false"
+                    IOType="" Line="STM32MP157" Package="TFBGA361" RefName="STM32MP157AACx"
+                    xmlns="http://mcd.rou.st.com/modules.php?name=mcu">
+                <Core>Arm Cortex-A7</Core>
+                <Core>Arm Cortex-A7</Core>
+                <Core>Arm Cortex-M4</Core>
+                <Frequency>650</Frequency>
+                <Frequency>209</Frequency>
+                <Ram>708</Ram>
+                <IONb>148</IONb>
+                <Flash>0</Flash>
+                <IP ConfigFile="GPIO-STM32MP15xx" InstanceName="GPIO" Name="GPIO" Version="STM32MP157_gpio_v1_0"/>
+                <Pin Name="PZ0" Position="A1" Type="I/O"/>
+                <Pin Name="DDR_DQ0" Position="B2" Type="MonoIO"/>
+            </Mcu>
+        "#;
+        let mcu: Mcu = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        assert!(mcu.is_mpu());
+        assert_eq!(mcu.get_cores(), vec![Core::CortexM4]);
+        assert_eq!(mcu.get_frequency(), vec![650, 209]);
+        assert_eq!(mcu.get_variant_flash("STM32MP157AACx"), None);
+        assert_eq!(mcu.get_pins()[0].get_gpio_name(), Some("PZ0"));
+        assert_eq!(mcu.get_pins()[1].get_gpio_name(), None);
+    }
+
+    #[test]
+    fn test_deserialize_minimal_mcu() {
+        let xml = r#"
+            <Mcu>
+                <IP InstanceName="GPIO" Name="GPIO" Version="STM32L071_gpio_v1_0"/>
+            </Mcu>
+        "#;
+        let mcu: Mcu = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        assert_eq!(mcu.ref_name, "");
+        assert_eq!(mcu.family, "");
+        assert_eq!(mcu.package, "");
+        assert_eq!(
+            mcu.get_ip("GPIO").unwrap().get_version(),
+            "STM32L071_gpio_v1_0"
+        );
+    }
+
+    #[test]
+    fn test_dual_pad() {
+        let pin = |name: &str| Pin {
+            name: name.to_string(),
+            position: String::new(),
+            pin_type: String::new(),
+            signal: vec![],
+        };
+        assert_eq!(pin("PA0_C").get_gpio_name(), Some("PA0_C"));
+        assert_eq!(pin("PA0").get_gpio_name(), Some("PA0"));
+        assert_eq!(parse_dual_pad("PC3_C"), Some(("PC", "3")));
+        assert_eq!(parse_dual_pad("PC3"), None);
+        assert_eq!(Core::CortexM7.short_name(), "cm7");
+    }
+}
@@ -0,0 +1,320 @@
+//! Candidate pin sets for external memories.
+//!
+//! External memories (QSPI/OCTOSPI flash, SDRAM and NAND flash via FMC) need a
+//! whole bundle of signals to be routed before they can be used. For every
+//! GPIO group, this module collects the pins that can provide each required
+//! signal, so that a complete pin set can be picked without going through the
+//! signals one by one.
+
+use std::str::FromStr;
+
+use crate::{
+    af_tree::GpioGroup,
+    fmc::{fmc_bundles, parse_fmc_signal, FmcBundle},
+    qspi::{parse_qspi_signal, QspiBus},
+};
+
+/// The supported external memory types.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemoryType {
+    /// Quad-SPI NOR flash on bank 1 of the QUADSPI peripheral.
+    Qspi,
+    /// Quad-SPI NOR flash on port 1 of the OCTOSPI IO manager.
+    Ospi,
+    /// 16 bit SDRAM on the FMC.
+    Sdram,
+    /// 8 bit NAND flash on the FMC.
+    Nand,
+}
+
+impl FromStr for MemoryType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "qspi" => Ok(MemoryType::Qspi),
+            "ospi" => Ok(MemoryType::Ospi),
+            "sdram" => Ok(MemoryType::Sdram),
+            "nand" => Ok(MemoryType::Nand),
+            _ => Err(format!("Unknown memory type {:?}", s)),
+        }
+    }
+}
+
+/// The number of data lines of the supported SDRAM and NAND memories.
+const SDRAM_DATA_WIDTH: usize = 16;
+const NAND_DATA_WIDTH: usize = 8;
+
+impl MemoryType {
+    /// The signals required by this memory type, each with the functions that
+    /// can provide it: the IO traits of the QUADSPI and OCTOSPI signals (see
+    /// `QspiSignal::io_trait`), the functions of the FMC signals (see
+    /// `FmcSignal`).
+    ///
+    /// Every signal must be provided by at least one pin for the pin set to be
+    /// complete. The SDRAM can use either bank, as long as the clock enable and
+    /// chip select are of the same bank, so its bank signals depend on the
+    /// pins of the group (see `sdram_bank`).
+    fn required_signals(&self, group: &GpioGroup) -> Vec<Vec<String>> {
+        let single = |functions: &[String]| {
+            functions
+                .iter()
+                .map(|function| vec![function.clone()])
+                .collect::<Vec<_>>()
+        };
+        match self {
+            MemoryType::Qspi => single(&bus_traits(QspiBus::new("QUADSPI", 1))),
+            MemoryType::Ospi => single(&bus_traits(QspiBus::new("OCTOSPIM", 1))),
+            MemoryType::Sdram => {
+                let mut signals = vec![];
+                for (name, width) in &[
+                    ("SdramControlPins", 4),
+                    ("AddressPins", 13),
+                    ("DataPins", SDRAM_DATA_WIDTH),
+                    ("ByteLanePins", 2),
+                    ("SdramBankAddressPins", 2),
+                ] {
+                    signals.extend(single(&fmc_bundle(name, *width).functions));
+                }
+                signals.extend(single(&sdram_bank(group).functions));
+                signals
+            }
+            MemoryType::Nand => {
+                let alternatives = |functions: &[&str]| {
+                    functions.iter().map(|f| f.to_string()).collect::<Vec<_>>()
+                };
+                let mut signals = vec![
+                    alternatives(&["NCE", "NCE2", "NCE3"]),
+                    alternatives(&["NOE"]),
+                    alternatives(&["NWE"]),
+                    alternatives(&["NWAIT"]),
+                    alternatives(&["ALE", "A17"]),
+                    alternatives(&["CLE", "A16"]),
+                ];
+                signals.extend(single(&fmc_bundle("DataPins", NAND_DATA_WIDTH).functions));
+                signals
+            }
+        }
+    }
+
+    /// The functions provided by a signal of a pin (e.g. `Bk1Io0Pin` for
+    /// "QUADSPI_BK1_IO0", `A17` and `ALE` for "FMC_A17_ALE").
+    fn signal_functions(&self, signal: &str) -> Vec<String> {
+        match self {
+            MemoryType::Qspi | MemoryType::Ospi => parse_qspi_signal(signal)
+                .map(|signal| signal.io_trait())
+                .into_iter()
+                .collect(),
+            MemoryType::Sdram | MemoryType::Nand => parse_fmc_signal(signal)
+                .into_iter()
+                .map(|signal| signal.function)
+                .collect(),
+        }
+    }
+}
+
+/// The IO traits of the pins of a QUADSPI or OCTOSPI bus.
+fn bus_traits(bus: QspiBus) -> Vec<String> {
+    bus.pins.into_iter().map(|(_, io)| io).collect()
+}
+
+/// The FMC bundle with the given name and number of pins.
+fn fmc_bundle(name: &str, width: usize) -> FmcBundle {
+    fmc_bundles()
+        .into_iter()
+        .find(|bundle| bundle.name == name && bundle.functions.len() == width)
+        .unwrap()
+}
+
+/// The SDRAM bank used by the memory: the first bank whose clock enable and
+/// chip select (e.g. "FMC_SDCKE1" and "FMC_SDNE1" for bank 2) are both
+/// available on the pins of the group, or bank 1 if neither bank is.
+fn sdram_bank(group: &GpioGroup) -> FmcBundle {
+    let functions = group
+        .pins()
+        .iter()
+        .flat_map(|pin| pin.raw_signals())
+        .flat_map(|signal| parse_fmc_signal(signal.name()))
+        .map(|signal| signal.function)
+        .collect::<Vec<_>>();
+    let banks = fmc_bundles()
+        .into_iter()
+        .filter(|bundle| {
+            bundle.name.starts_with("SdramBank") && bundle.name != "SdramBankAddressPins"
+        })
+        .collect::<Vec<_>>();
+    banks
+        .iter()
+        .find(|bank| bank.functions.iter().all(|f| functions.contains(f)))
+        .unwrap_or(&banks[0])
+        .clone()
+}
+
+/// A pin that can provide a memory signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// The pin name (e.g. "PB2").
+    pub pin: String,
+    /// The signal name as listed in the GPIO IP file (e.g. "QUADSPI_CLK").
+    pub signal: String,
+    /// The alternate function (e.g. "AF9").
+    pub af: String,
+}
+
+/// The candidate pins of one required memory signal.
+#[derive(Debug)]
+pub struct MemorySignal {
+    /// The functions that can provide the signal (e.g. `["SDNE0"]` or
+    /// `["ALE", "A17"]`).
+    pub functions: Vec<String>,
+    pub candidates: Vec<Candidate>,
+}
+
+impl MemorySignal {
+    /// The name of the signal (e.g. "SDNE0" or "ALE|A17").
+    pub fn name(&self) -> String {
+        self.functions.join("|")
+    }
+}
+
+/// Collect the candidate pins for every signal required by `memory`.
+pub fn candidate_pins(group: &GpioGroup, memory: MemoryType) -> Vec<MemorySignal> {
+    memory
+        .required_signals(group)
+        .into_iter()
+        .map(|functions| {
+            let candidates = group
+                .pins()
+                .iter()
+                .flat_map(|pin| {
+                    pin.raw_signals()
+                        .iter()
+                        .filter(|sig| {
+                            memory
+                                .signal_functions(sig.name())
+                                .iter()
+                                .any(|f| functions.contains(f))
+                        })
+                        .map(move |sig| Candidate {
+                            pin: pin.name(),
+                            signal: sig.name().to_string(),
+                            af: sig.af().to_string(),
+                        })
+                })
+                .collect();
+            MemorySignal {
+                functions,
+                candidates,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::af_tree::AfTree;
+
+    /// A tree with one GPIO group whose pins have the given signals, all on
+    /// AF12.
+    fn fixture(signals: &[(&str, &str)]) -> AfTree {
+        let pins = signals
+            .iter()
+            .map(|(pin, signal)| {
+                format!(
+                    r#"{{"port": "{}", "number": "{}", "original_name": null,
+                        "raw_signals": [{{"name": "{}", "af": "AF12", "define": null}}],
+                        "signals": []}}"#,
+                    &pin[..2],
+                    &pin[2..],
+                    signal
+                )
+            })
+            .collect::<Vec<_>>();
+        AfTree::from_json(&format!(
+            r#"{{"groups": [{{"version": "STM32F429_gpio_v1_0", "mcus": [], "pins": [{}]}}]}}"#,
+            pins.join(",")
+        ))
+        .unwrap()
+    }
+
+    /// The names of the signals without candidates.
+    fn missing(group: &GpioGroup, memory: MemoryType) -> Vec<String> {
+        candidate_pins(group, memory)
+            .iter()
+            .filter(|s| s.candidates.is_empty())
+            .map(|s| s.name())
+            .collect()
+    }
+
+    #[test]
+    fn test_sdram_bank() {
+        // The clock enable of bank 1 and the chip select of bank 2 do not
+        // make a bank.
+        let tree = fixture(&[("PC3", "FMC_SDCKE0"), ("PH7", "FMC_SDNE1")]);
+        let group = &tree.groups()[0];
+        assert_eq!(sdram_bank(group).name, "SdramBank1Pins");
+        let missing = missing(group, MemoryType::Sdram);
+        assert!(missing.contains(&"SDNE0".to_string()));
+        assert!(!missing.contains(&"SDCKE0".to_string()));
+        assert!(!missing
+            .iter()
+            .any(|s| s.ends_with('1') && s.starts_with("SD")));
+
+        let tree = fixture(&[
+            ("PC3", "FMC_SDCKE0"),
+            ("PH6", "FMC_SDCKE1"),
+            ("PH7", "FMC_SDNE1"),
+        ]);
+        let group = &tree.groups()[0];
+        assert_eq!(sdram_bank(group).name, "SdramBank2Pins");
+        let signals = candidate_pins(group, MemoryType::Sdram);
+        let bank = signals
+            .iter()
+            .filter(|s| s.name().starts_with("SDCKE") || s.name().starts_with("SDNE"))
+            .map(|s| (s.name(), s.candidates[0].pin.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bank,
+            vec![
+                ("SDCKE1".to_string(), "PH6".to_string()),
+                ("SDNE1".to_string(), "PH7".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_candidate_pins() {
+        let tree = fixture(&[
+            ("PB2", "QUADSPI_CLK"),
+            ("PB6", "QUADSPI_BK1_NCS"),
+            ("PD11", "QUADSPI_BK1_IO0"),
+            ("PD12", "QUADSPI_BK1_IO1"),
+            ("PE2", "QUADSPI_BK1_IO2"),
+            ("PD13", "QUADSPI_BK1_IO3"),
+            ("PE7", "QUADSPI_BK2_IO0"),
+            ("PD7", "FMC_NCE2"),
+            ("PD12", "FMC_A17_ALE"),
+        ]);
+        let group = &tree.groups()[0];
+        assert!(missing(group, MemoryType::Qspi).is_empty());
+        assert_eq!(
+            missing(group, MemoryType::Ospi),
+            vec!["P1ClkPin", "P1NcsPin", "P1Io0Pin", "P1Io1Pin", "P1Io2Pin", "P1Io3Pin"]
+        );
+
+        let signals = candidate_pins(group, MemoryType::Nand);
+        let ale = signals.iter().find(|s| s.name() == "ALE|A17").unwrap();
+        assert_eq!(
+            ale.candidates,
+            vec![Candidate {
+                pin: "PD12".to_string(),
+                signal: "FMC_A17_ALE".to_string(),
+                af: "AF12".to_string(),
+            }]
+        );
+        assert_eq!(signals[0].name(), "NCE|NCE2|NCE3");
+        assert_eq!(signals[0].candidates[0].pin, "PD7");
+        assert!(missing(group, MemoryType::Nand).contains(&"D0".to_string()));
+    }
+}
@@ -0,0 +1,160 @@
+//! Interrupts from the NVIC IP Modes files.
+//!
+//! The NVIC Modes files (e.g. `IP/NVIC-STM32L0xx_Modes.xml`) list the
+//! interrupt table of the MCUs using them in the `IRQn` setting, e.g.
+//! `<PossibleValue Comment="Window watchdog interrupt" Value="WWDG_IRQn:..."/>`.
+//! Dual-core MCUs have one NVIC per core ("NVIC1" and "NVIC2").
+//!
+//! Note that the database only contains the names and the order of the
+//! interrupts, not their vector numbers.
+
+use std::error::Error;
+use std::path::Path;
+
+use serde_derive::Deserialize;
+
+use crate::rcc::RefParameter;
+use crate::utils::load_file;
+
+/// The Cortex-M core exceptions, which are listed in the interrupt table as
+/// well.
+const CORE_EXCEPTIONS: &[&str] = &[
+    "NonMaskableInt",
+    "HardFault",
+    "MemoryManagement",
+    "BusFault",
+    "UsageFault",
+    "SVCall",
+    "SVC",
+    "DebugMonitor",
+    "PendSV",
+    "SysTick",
+];
+
+/// The names of the NVIC IPs in the MCU files.
+pub const NVIC_IPS: &[&str] = &["NVIC", "NVIC1", "NVIC2"];
+
+/// A NVIC IP Modes file.
+#[derive(Debug, Deserialize)]
+#[serde(rename = "IP")]
+pub struct IpNvic {
+    #[serde(rename = "RefParameter", default)]
+    ref_parameter: Vec<RefParameter>,
+}
+
+impl IpNvic {
+    /// Load the Modes file of a NVIC IP (e.g. "NVIC") version.
+    pub fn load<P: AsRef<Path>>(
+        db_dir: P,
+        ip: &str,
+        version: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        load_file(db_dir, Self::path(ip, version))
+    }
+
+    /// The path of the Modes file of a NVIC IP version, relative to the
+    /// database directory (e.g. "IP/NVIC-STM32L0xx_Modes.xml").
+    pub fn path(ip: &str, version: &str) -> String {
+        format!("IP/{}-{}_Modes.xml", ip, version)
+    }
+
+    /// Return the device interrupts in the order of the interrupt table,
+    /// without the core exceptions.
+    pub fn interrupts(&self) -> Vec<Interrupt> {
+        let mut interrupts = vec![];
+        for p in self.ref_parameter.iter().filter(|p| p.name == "IRQn") {
+            for value in &p.possible_value {
+                let name = value.value.split(':').next().unwrap_or_default();
+                let name = name.trim_end_matches("_IRQn");
+                if name.is_empty()
+                    || CORE_EXCEPTIONS.contains(&name)
+                    || interrupts.iter().any(|i: &Interrupt| i.name == name)
+                {
+                    continue;
+                }
+                interrupts.push(Interrupt {
+                    name: name.to_string(),
+                    description: value.comment.clone().unwrap_or_default(),
+                });
+            }
+        }
+        interrupts
+    }
+}
+
+/// A device interrupt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interrupt {
+    /// The name, without the `_IRQn` suffix (e.g. "WWDG").
+    pub name: String,
+    /// The description (e.g. "Window watchdog interrupt").
+    pub description: String,
+}
+
+/// The interrupts of a NVIC IP version and the MCUs using it.
+#[derive(Debug)]
+pub struct NvicGroup {
+    /// The NVIC IP (e.g. "NVIC", or "NVIC1" for the first core).
+    pub ip: String,
+    /// The IP version (e.g. "STM32L0xx").
+    pub version: String,
+    /// The ref names of the MCUs using this version.
+    pub mcus: Vec<String>,
+    pub interrupts: Vec<Interrupt>,
+}
+
+impl NvicGroup {
+    pub fn load<P: AsRef<Path>>(
+        db_dir: P,
+        ip: &str,
+        version: &str,
+        mcus: Vec<String>,
+    ) -> Result<Self, String> {
+        let interrupts = IpNvic::load(db_dir, ip, version)
+            .map_err(|e| format!("Could not load {}: {}", IpNvic::path(ip, version), e))?
+            .interrupts();
+        Ok(Self {
+            ip: ip.to_string(),
+            version: version.to_string(),
+            mcus,
+            interrupts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupts() {
+        let xml = r#"
+            <IP DBVersion="V4.0" IPType="service" Name="NVIC" Version="STM32L0xx">
+                <RefParameter Comment="Priority Group" Name="PriorityGroup" Type="list">
+                    <PossibleValue Comment="0 bits" Value="NVIC_PRIORITYGROUP_0"/>
+                </RefParameter>
+                <RefParameter Comment="Interrupt Table" Name="IRQn" Type="list">
+                    <PossibleValue Comment="Non maskable interrupt" Value="NonMaskableInt_IRQn:N,IF_HAL::HAL_RCC_NMI_IRQHandler:"/>
+                    <PossibleValue Comment="System tick timer" Value="SysTick_IRQn:N,IF_HAL::HAL_IncTick:"/>
+                    <PossibleValue Comment="Window watchdog interrupt" Value="WWDG_IRQn:WWDG:WWDG:HAL_WWDG_IRQHandler:"/>
+                    <PossibleValue Comment="USART2 global interrupt" Value="USART2_IRQn:USART:USART2:HAL_UART_IRQHandler:"/>
+                </RefParameter>
+            </IP>
+        "#;
+        let ip: IpNvic = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let interrupts = ip.interrupts();
+        assert_eq!(
+            interrupts,
+            vec![
+                Interrupt {
+                    name: "WWDG".into(),
+                    description: "Window watchdog interrupt".into()
+                },
+                Interrupt {
+                    name: "USART2".into(),
+                    description: "USART2 global interrupt".into()
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! Oscillator pins.
+//!
+//! The pins of the external oscillators carry the oscillator function in
+//! their name (e.g. "PC14-OSC32_IN" or "PH0-OSC_IN"). They are GPIOs, but may
+//! be unavailable as such while the oscillator is in use.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+lazy_static! {
+    /// Oscillator pin names, e.g. "PC14-OSC32_IN", "PH0-OSC_IN" or
+    /// "PC15-OSC32_OUT (PC15)".
+    static ref OSCILLATOR_PIN: Regex =
+        Regex::new(r"^(P[A-Z]\d+)\b.*?\bOSC(32)?_(IN|OUT)\b").unwrap();
+}
+
+/// The oscillator function of a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OscillatorFunction {
+    /// The input of the high speed external oscillator ("OSC_IN").
+    OscIn,
+    /// The output of the high speed external oscillator ("OSC_OUT").
+    OscOut,
+    /// The input of the low speed external oscillator ("OSC32_IN").
+    Osc32In,
+    /// The output of the low speed external oscillator ("OSC32_OUT").
+    Osc32Out,
+}
+
+impl OscillatorFunction {
+    /// The function as in the pin names (e.g. "OSC32_IN").
+    pub fn name(self) -> &'static str {
+        match self {
+            OscillatorFunction::OscIn => "OSC_IN",
+            OscillatorFunction::OscOut => "OSC_OUT",
+            OscillatorFunction::Osc32In => "OSC32_IN",
+            OscillatorFunction::Osc32Out => "OSC32_OUT",
+        }
+    }
+
+    /// Whether this is a pin of the low speed (32 kHz) oscillator.
+    pub fn is_low_speed(self) -> bool {
+        matches!(
+            self,
+            OscillatorFunction::Osc32In | OscillatorFunction::Osc32Out
+        )
+    }
+}
+
+/// Parse a pin name (e.g. "PC14-OSC32_IN") into the GPIO name (e.g. "PC14"),
+/// which is a clean Rust identifier, and its oscillator function. Other pins
+/// (e.g. "PA0-WKUP") return `None`.
+pub fn parse_oscillator_pin(name: &str) -> Option<(&str, OscillatorFunction)> {
+    let captures = OSCILLATOR_PIN.captures(name)?;
+    let function = match (captures.get(2).is_some(), &captures[3]) {
+        (false, "IN") => OscillatorFunction::OscIn,
+        (false, _) => OscillatorFunction::OscOut,
+        (true, "IN") => OscillatorFunction::Osc32In,
+        (true, _) => OscillatorFunction::Osc32Out,
+    };
+    Some((captures.get(1).unwrap().as_str(), function))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_oscillator_pin() {
+        assert_eq!(
+            parse_oscillator_pin("PC14-OSC32_IN"),
+            Some(("PC14", OscillatorFunction::Osc32In))
+        );
+        assert_eq!(
+            parse_oscillator_pin("PC15-OSC32_OUT (PC15)"),
+            Some(("PC15", OscillatorFunction::Osc32Out))
+        );
+        assert_eq!(
+            parse_oscillator_pin("PH0-OSC_IN"),
+            Some(("PH0", OscillatorFunction::OscIn))
+        );
+        assert_eq!(
+            parse_oscillator_pin("PF1-OSC_OUT"),
+            Some(("PF1", OscillatorFunction::OscOut))
+        );
+        assert_eq!(parse_oscillator_pin("PA0-WKUP"), None);
+        assert_eq!(parse_oscillator_pin("PA2"), None);
+        assert!(OscillatorFunction::Osc32In.is_low_speed());
+        assert_eq!(OscillatorFunction::OscOut.name(), "OSC_OUT");
+    }
+}
@@ -0,0 +1,183 @@
+//! Pin placement in the packages.
+//!
+//! The MCU files list the position of every pin in the package: the pin
+//! number for packages with leads (e.g. "6" on LQFP32), or the ball for BGA and
+//! WLCSP packages (e.g. "A1"). Since the pin functions differ between MCUs
+//! sharing a package, the placement is per MCU.
+//!
+//! Besides the GPIOs, the pinout includes the system pins (e.g. "NRST",
+//! "BOOT0", "VBAT", "VDD" and "VSS") with their type, as needed for pinout
+//! diagrams and schematic symbols. The RF pins of the wireless families (e.g.
+//! "RF1") are system pins of their own category.
+
+use alphanumeric_sort::compare_str;
+
+use crate::{mcu::Mcu, rf};
+
+/// The type of a pin, as listed in the MCU file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PinType {
+    /// A GPIO ("I/O").
+    Io,
+    /// An input or output that is not a GPIO ("MonoIO", e.g. "PDR_ON").
+    MonoIo,
+    /// A supply or ground pin ("Power", e.g. "VDD", "VSS" or "VBAT").
+    Power,
+    /// The reset pin ("Reset", "NRST").
+    Reset,
+    /// A dedicated boot mode pin ("Boot", "BOOT0").
+    Boot,
+    /// Any other type.
+    Other(String),
+}
+
+impl PinType {
+    /// Parse the type of a pin (e.g. "I/O" or "Power").
+    pub fn parse(pin_type: &str) -> Self {
+        match pin_type {
+            "I/O" => PinType::Io,
+            "MonoIO" => PinType::MonoIo,
+            "Power" => PinType::Power,
+            "Reset" => PinType::Reset,
+            "Boot" => PinType::Boot,
+            other => PinType::Other(other.to_string()),
+        }
+    }
+
+    /// The type as listed in the MCU file.
+    pub fn as_str(&self) -> &str {
+        match self {
+            PinType::Io => "I/O",
+            PinType::MonoIo => "MonoIO",
+            PinType::Power => "Power",
+            PinType::Reset => "Reset",
+            PinType::Boot => "Boot",
+            PinType::Other(other) => other,
+        }
+    }
+}
+
+/// A pin at a position in the package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackagePin {
+    /// The pin number or ball (e.g. "6" or "A1").
+    pub position: String,
+    /// The pin name as listed in the MCU file (e.g. "PC14-OSC32_IN" or
+    /// "VDD").
+    pub name: String,
+    /// The GPIO name (e.g. "PC14"), `None` for system pins.
+    pub gpio: Option<String>,
+    pub pin_type: PinType,
+}
+
+impl PackagePin {
+    /// Whether this is a system pin (e.g. "NRST" or "VDD") rather than a
+    /// GPIO.
+    pub fn is_system(&self) -> bool {
+        self.gpio.is_none()
+    }
+
+    /// Whether this is a dedicated RF pin of the wireless families (e.g.
+    /// "RF1" or "RFO_HP").
+    pub fn is_rf(&self) -> bool {
+        self.is_system() && rf::is_rf_pin(&self.name)
+    }
+}
+
+/// The pin placement of a MCU in its package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pinout {
+    /// The ref name of the MCU (e.g. "STM32L071KBTx").
+    pub mcu: String,
+    /// The package (e.g. "LQFP32").
+    pub package: String,
+    /// The GPIO and system pins, sorted by position.
+    pub pins: Vec<PackagePin>,
+}
+
+impl Pinout {
+    /// Collect the placement of the pins of a MCU. Pins without a position
+    /// are skipped.
+    pub fn new(ref_name: &str, mcu: &Mcu) -> Self {
+        let mut pins = mcu
+            .get_pins()
+            .iter()
+            .filter(|pin| !pin.get_position().is_empty())
+            .map(|pin| PackagePin {
+                position: pin.get_position().to_string(),
+                name: pin.get_name().to_string(),
+                gpio: pin.get_gpio_name().map(String::from),
+                pin_type: PinType::parse(pin.get_type()),
+            })
+            .collect::<Vec<_>>();
+        pins.sort_by(|a, b| compare_str(&a.position, &b.position));
+        Self {
+            mcu: ref_name.to_string(),
+            package: mcu.package.clone(),
+            pins,
+        }
+    }
+
+    /// Return the GPIO pins.
+    pub fn gpio_pins(&self) -> impl Iterator<Item = &PackagePin> {
+        self.pins.iter().filter(|pin| !pin.is_system())
+    }
+
+    /// Return the system pins (e.g. "NRST", "BOOT0", "VDD").
+    pub fn system_pins(&self) -> impl Iterator<Item = &PackagePin> {
+        self.pins.iter().filter(|pin| pin.is_system())
+    }
+
+    /// Return the position of a GPIO pin (e.g. "PA0"), if it is bonded out.
+    pub fn position_of(&self, gpio: &str) -> Option<&str> {
+        self.pins
+            .iter()
+            .find(|pin| pin.gpio.as_deref() == Some(gpio))
+            .map(|pin| pin.position.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pinout() {
+        let xml = r#"
+            <Mcu Family="STM32L0" Package="UFBGA64" RefName="STM32L071R(B-Z)Hx">
+                <Pin Name="PA0" Position="C2" Type="I/O"/>
+                <Pin Name="VDD" Position="A1" Type="Power"/>
+                <Pin Name="PC14-OSC32_IN" Position="B1" Type="I/O"/>
+                <Pin Name="PA10" Position="A10" Type="I/O"/>
+                <Pin Name="NRST" Position="D1" Type="Reset"/>
+                <Pin Name="BOOT0" Position="B5" Type="Boot"/>
+                <Pin Name="RF1" Position="C1" Type="MonoIO"/>
+            </Mcu>
+        "#;
+        let mcu: Mcu = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        let pinout = Pinout::new("STM32L071RBHx", &mcu);
+        assert_eq!(pinout.package, "UFBGA64");
+        let positions = pinout
+            .gpio_pins()
+            .map(|p| (p.position.as_str(), p.gpio.as_deref().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, [("A10", "PA10"), ("B1", "PC14"), ("C2", "PA0")]);
+        let system = pinout
+            .system_pins()
+            .map(|p| (p.position.as_str(), p.name.as_str(), &p.pin_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            system,
+            [
+                ("A1", "VDD", &PinType::Power),
+                ("B5", "BOOT0", &PinType::Boot),
+                ("C1", "RF1", &PinType::MonoIo),
+                ("D1", "NRST", &PinType::Reset),
+            ]
+        );
+        assert!(pinout.pins.iter().any(|p| p.name == "RF1" && p.is_rf()));
+        assert!(!pinout.pins.iter().any(|p| p.name == "NRST" && p.is_rf()));
+        assert_eq!(pinout.position_of("PA0"), Some("C2"));
+        assert_eq!(pinout.position_of("PB0"), None);
+    }
+}
@@ -0,0 +1,202 @@
+//! The pins of the peripheral instances of a GPIO group.
+//!
+//! HALs take the pins of a peripheral as a tuple (e.g. `(TX, RX)` for a
+//! serial port), accepted by an impl of their `Pins` trait. The pins of an
+//! instance are derived from the IO traits of the signals of the group: the
+//! pins any instance of the stem provides in the group are required (e.g.
+//! `SckPin`, `MisoPin` and `MosiPin` of an SPI), the serial flow control,
+//! clock and driver enable pins are optional. The driver enable is the RTS
+//! pin used for RS-485 (e.g. "USART2_RTS_DE"), so the two are alternatives.
+
+use std::collections::HashMap;
+
+use alphanumeric_sort::compare_str;
+
+use crate::{
+    af_tree::{device_stem, GpioGroup},
+    internal_peripheral::{io_trait_names, SERIAL_HANDSHAKE_TRAITS},
+};
+
+/// The optional IO traits of a single pin, which a tuple can only have one of.
+const ALTERNATIVE_TRAITS: [&str; 2] = ["RtsPin", "DePin"];
+
+/// The IO traits of the pins of a peripheral instance (e.g. "USART2"), in
+/// the conventional order of the pins (see `io_trait_names`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeripheralPins {
+    pub device: String,
+    /// The IO traits of the pins the peripheral needs (e.g. "TxPin" and
+    /// "RxPin").
+    pub required: Vec<&'static str>,
+    /// The IO traits of the optional pins the instance provides (e.g.
+    /// "CtsPin").
+    pub optional: Vec<&'static str>,
+    /// The required IO traits no pin of the group provides for this instance.
+    pub missing: Vec<&'static str>,
+}
+
+impl PeripheralPins {
+    /// Whether the group provides all required pins of the instance.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// The sets of optional IO traits the instance accepts together, in tuple
+    /// order. An instance with both the RTS and the driver enable pin gets a
+    /// set for each (e.g. `["CtsPin", "RtsPin"]` and `["CtsPin", "DePin"]`),
+    /// an instance without optional pins none.
+    pub fn optional_sets(&self) -> Vec<Vec<&'static str>> {
+        if self.optional.is_empty() {
+            return vec![];
+        }
+        if !ALTERNATIVE_TRAITS
+            .iter()
+            .all(|io| self.optional.contains(io))
+        {
+            return vec![self.optional.clone()];
+        }
+        ALTERNATIVE_TRAITS
+            .iter()
+            .map(|kept| {
+                self.optional
+                    .iter()
+                    .copied()
+                    .filter(|io| io == kept || !ALTERNATIVE_TRAITS.contains(io))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Return the pins of the peripheral instances with IO traits in a GPIO
+/// group, sorted by instance.
+///
+/// The required IO traits of an instance are those of its stem that some
+/// instance of the stem in the group provides (e.g. `MisoPin` is required for
+/// SPI2 if SPI1 has a MISO pin), so an instance lacking one of them is
+/// incomplete (see `PeripheralPins::missing`).
+pub fn peripheral_pins(group: &GpioGroup) -> Vec<PeripheralPins> {
+    let mut device_traits: HashMap<&str, Vec<&str>> = HashMap::new();
+    for signal in group.pins().iter().flat_map(|pin| pin.signals()) {
+        device_traits
+            .entry(signal.device())
+            .or_default()
+            .push(signal.io());
+    }
+    let stem_traits = |stem: &str| {
+        device_traits
+            .iter()
+            .filter(|(device, _)| device_stem(device) == stem)
+            .flat_map(|(_, traits)| traits.iter().copied())
+            .collect::<Vec<_>>()
+    };
+
+    let mut pins = device_traits
+        .iter()
+        .map(|(device, traits)| {
+            let stem_traits = stem_traits(device_stem(device));
+            let optional = |io: &str| SERIAL_HANDSHAKE_TRAITS.contains(&io);
+            let required = io_trait_names()
+                .into_iter()
+                .filter(|io| !optional(io) && stem_traits.contains(io))
+                .collect::<Vec<_>>();
+            PeripheralPins {
+                device: device.to_string(),
+                missing: required
+                    .iter()
+                    .copied()
+                    .filter(|io| !traits.contains(io))
+                    .collect(),
+                required,
+                optional: io_trait_names()
+                    .into_iter()
+                    .filter(|io| optional(io) && traits.contains(io))
+                    .collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    pins.sort_by(|a, b| compare_str(&a.device, &b.device));
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::af_tree::AfTree;
+
+    #[test]
+    fn test_peripheral_pins() {
+        let tree = AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L071_gpio_v1_0", "mcus": [], "pins": [
+                {"port": "PA", "number": "0", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "CtsPin", "device": "USART2", "define": null}]},
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]},
+                {"port": "PA", "number": "3", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "RxPin", "device": "USART2", "define": null}]},
+                {"port": "PA", "number": "5", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF0", "io": "SckPin", "device": "SPI1", "define": null}]},
+                {"port": "PA", "number": "6", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF0", "io": "MisoPin", "device": "SPI1", "define": null}]},
+                {"port": "PA", "number": "7", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF0", "io": "MosiPin", "device": "SPI1", "define": null}]},
+                {"port": "PB", "number": "13", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF0", "io": "SckPin", "device": "SPI2", "define": null}]},
+                {"port": "PB", "number": "15", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF0", "io": "MosiPin", "device": "SPI2", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let pins = peripheral_pins(&tree.groups()[0]);
+        assert_eq!(
+            pins,
+            vec![
+                PeripheralPins {
+                    device: "SPI1".into(),
+                    required: vec!["SckPin", "MisoPin", "MosiPin"],
+                    optional: vec![],
+                    missing: vec![],
+                },
+                PeripheralPins {
+                    device: "SPI2".into(),
+                    required: vec!["SckPin", "MisoPin", "MosiPin"],
+                    optional: vec![],
+                    missing: vec!["MisoPin"],
+                },
+                PeripheralPins {
+                    device: "USART2".into(),
+                    required: vec!["TxPin", "RxPin"],
+                    optional: vec!["CtsPin"],
+                    missing: vec![],
+                },
+            ]
+        );
+        assert!(pins[0].is_complete());
+        assert!(!pins[1].is_complete());
+        assert_eq!(pins[0].optional_sets(), Vec::<Vec<&str>>::new());
+        assert_eq!(pins[2].optional_sets(), vec![vec!["CtsPin"]]);
+    }
+
+    #[test]
+    fn test_optional_sets() {
+        // A combined RTS/DE signal gives one pin both traits
+        let tree = AfTree::from_json(
+            r#"{"groups": [{"version": "STM32L071_gpio_v1_0", "mcus": [], "pins": [
+                {"port": "PA", "number": "0", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "CtsPin", "device": "USART2", "define": null}]},
+                {"port": "PA", "number": "1", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "RtsPin", "device": "USART2", "define": null},
+                             {"af": "AF4", "io": "DePin", "device": "USART2", "define": null}]},
+                {"port": "PA", "number": "2", "original_name": null, "raw_signals": [],
+                 "signals": [{"af": "AF4", "io": "TxPin", "device": "USART2", "define": null}]}
+            ]}]}"#,
+        )
+        .unwrap();
+        let pins = peripheral_pins(&tree.groups()[0]);
+        assert_eq!(pins[0].optional, vec!["CtsPin", "RtsPin", "DePin"]);
+        assert_eq!(
+            pins[0].optional_sets(),
+            vec![vec!["CtsPin", "RtsPin"], vec!["CtsPin", "DePin"]]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fmt, str::FromStr};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref PIN_SPEC: Regex =
+        Regex::new(r"^(P[A-Z])(?:(\d+)(?:-(?:(P[A-Z]))?(\d+))?|(\*))$").unwrap();
+}
+
+/// A selection of GPIO pins: a single pin (e.g. "PA0"), a range of pins of
+/// the same port (e.g. "PB3-PB7") or all pins of a port (e.g. "PC*").
+#[derive(Debug, Clone, PartialEq)]
+pub enum PinSpec {
+    Pin(String, u32),
+    Range(String, u32, u32),
+    Port(String),
+}
+
+impl PinSpec {
+    /// Return whether the pin (e.g. port "PA" and number "2") is selected.
+    pub fn matches(&self, port: &str, number: &str) -> bool {
+        let number = match number.parse::<u32>() {
+            Ok(number) => number,
+            Err(_) => return false,
+        };
+        match self {
+            PinSpec::Pin(p, n) => p == port && *n == number,
+            PinSpec::Range(p, from, to) => p == port && (*from..=*to).contains(&number),
+            PinSpec::Port(p) => p == port,
+        }
+    }
+}
+
+impl FromStr for PinSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid pin {:?}, expected e.g. PA0, PB3-PB7 or PC*", s);
+        let captures = PIN_SPEC.captures(s).ok_or_else(invalid)?;
+        let port = captures[1].to_string();
+        if captures.get(5).is_some() {
+            return Ok(PinSpec::Port(port));
+        }
+        let from = captures[2].parse().map_err(|_| invalid())?;
+        match captures.get(4) {
+            None => Ok(PinSpec::Pin(port, from)),
+            Some(to) => {
+                let to = to.as_str().parse().map_err(|_| invalid())?;
+                let same_port = captures.get(3).is_none_or(|p| p.as_str() == port);
+                if !same_port || to < from {
+                    return Err(invalid());
+                }
+                Ok(PinSpec::Range(port, from, to))
+            }
+        }
+    }
+}
+
+impl fmt::Display for PinSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PinSpec::Pin(port, n) => write!(f, "{}{}", port, n),
+            PinSpec::Range(port, from, to) => write!(f, "{}{}-{}{}", port, from, port, to),
+            PinSpec::Port(port) => write!(f, "{}*", port),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_spec() {
+        let spec = |s: &str| s.parse::<PinSpec>().unwrap();
+
+        assert_eq!(spec("PA0"), PinSpec::Pin("PA".into(), 0));
+        assert_eq!(spec("PB3-PB7"), PinSpec::Range("PB".into(), 3, 7));
+        assert_eq!(spec("PB3-7"), PinSpec::Range("PB".into(), 3, 7));
+        assert_eq!(spec("PC*"), PinSpec::Port("PC".into()));
+        assert_eq!(spec("PB3-PB7").to_string(), "PB3-PB7");
+
+        assert!(spec("PA0").matches("PA", "0"));
+        assert!(!spec("PA0").matches("PA", "10"));
+        assert!(spec("PB3-PB7").matches("PB", "7"));
+        assert!(!spec("PB3-PB7").matches("PB", "8"));
+        assert!(!spec("PB3-PB7").matches("PA", "5"));
+        assert!(spec("PC*").matches("PC", "14"));
+
+        assert!("PA".parse::<PinSpec>().is_err());
+        assert!("PA3-PB7".parse::<PinSpec>().is_err());
+        assert!("PA7-PA3".parse::<PinSpec>().is_err());
+        assert!("A0".parse::<PinSpec>().is_err());
+    }
+}
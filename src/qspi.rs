@@ -0,0 +1,284 @@
+//! QUADSPI and OCTOSPI signals.
+//!
+//! The QUADSPI interface has a common clock and two banks of four IOs with a
+//! chip select each (e.g. "QUADSPI_BK1_IO0", "QUADSPI_BK2_NCS"). The OCTOSPI
+//! IO manager routes the OCTOSPI instances to two ports of eight IOs (e.g.
+//! "OCTOSPIM_P1_IO7"), each with its own clock, chip select and data strobe.
+//! A memory only works with all IOs of its bank or port, so besides the pins
+//! the generated code bundles them into a bus (see `QspiBus`).
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// QUADSPI signals, e.g. "QUADSPI_CLK", "QUADSPI_BK1_IO0" or
+    /// "QUADSPI_BK2_NCS". The signals without bank belong to bank 1, except
+    /// for the common clock.
+    static ref QUADSPI_SIGNAL: Regex =
+        Regex::new(r"^(QUADSPI)_(?:BK([12])_)?(CLK|NCS|IO([0-3]))$").unwrap();
+    /// OCTOSPI IO manager signals, e.g. "OCTOSPIM_P1_IO7" or
+    /// "OCTOSPIM_P2_DQS".
+    static ref OCTOSPI_SIGNAL: Regex =
+        Regex::new(r"^(OCTOSPIM)_P([12])_(CLK|NCLK|NCS|DQS|IO([0-7]))$").unwrap();
+}
+
+/// The number of IOs of a quad SPI memory, which `QspiBus` requires.
+const QUAD_IOS: u8 = 4;
+
+/// The function of a QUADSPI or OCTOSPI pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum QspiFunction {
+    /// The clock ("CLK").
+    Clk,
+    /// The inverted clock of differential clock memories ("NCLK").
+    Nclk,
+    /// The chip select ("NCS").
+    Ncs,
+    /// The data strobe ("DQS").
+    Dqs,
+    /// A data line (e.g. 0 for "IO0").
+    Io(u8),
+}
+
+impl QspiFunction {
+    fn name(self) -> String {
+        match self {
+            QspiFunction::Clk => "Clk".to_string(),
+            QspiFunction::Nclk => "Nclk".to_string(),
+            QspiFunction::Ncs => "Ncs".to_string(),
+            QspiFunction::Dqs => "Dqs".to_string(),
+            QspiFunction::Io(n) => format!("Io{}", n),
+        }
+    }
+}
+
+/// A QUADSPI or OCTOSPI signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QspiSignal {
+    /// The peripheral ("QUADSPI" or "OCTOSPIM").
+    pub device: String,
+    /// The QUADSPI bank or OCTOSPI port, `None` for the common QUADSPI clock.
+    pub bank: Option<u8>,
+    pub function: QspiFunction,
+}
+
+impl QspiSignal {
+    /// The name of the IO trait of the pin (e.g. "ClkPin", "Bk1Io0Pin" or
+    /// "P2DqsPin").
+    pub fn io_trait(&self) -> String {
+        format!(
+            "{}{}Pin",
+            bank_prefix(&self.device, self.bank),
+            self.function.name()
+        )
+    }
+}
+
+/// The prefix of the IO traits of a bank (e.g. "Bk1" for QUADSPI bank 1, "P1"
+/// for OCTOSPI port 1).
+fn bank_prefix(device: &str, bank: Option<u8>) -> String {
+    match bank {
+        None => String::new(),
+        Some(n) if device == "OCTOSPIM" => format!("P{}", n),
+        Some(n) => format!("Bk{}", n),
+    }
+}
+
+/// Parse a QUADSPI or OCTOSPI signal (e.g. "QUADSPI_BK1_IO2" into `QUADSPI`,
+/// bank 1 and `Io(2)`).
+pub fn parse_qspi_signal(signal: &str) -> Option<QspiSignal> {
+    let captures = QUADSPI_SIGNAL
+        .captures(signal)
+        .or_else(|| OCTOSPI_SIGNAL.captures(signal))?;
+    let function = match (&captures[3], captures.get(4)) {
+        (_, Some(n)) => QspiFunction::Io(n.as_str().parse().ok()?),
+        ("CLK", _) => QspiFunction::Clk,
+        ("NCLK", _) => QspiFunction::Nclk,
+        ("NCS", _) => QspiFunction::Ncs,
+        _ => QspiFunction::Dqs,
+    };
+    let device = captures[1].to_string();
+    let bank = match captures.get(2) {
+        Some(bank) => Some(bank.as_str().parse().ok()?),
+        None if device == "QUADSPI" && function == QspiFunction::Clk => None,
+        None => Some(1),
+    };
+    Some(QspiSignal {
+        device,
+        bank,
+        function,
+    })
+}
+
+/// A QUADSPI or OCTOSPI signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct QspiPin {
+    /// The pin (e.g. "PB6").
+    pub pin: String,
+    /// The alternate function (e.g. "AF10").
+    pub af: String,
+    pub signal: QspiSignal,
+}
+
+/// Return the QUADSPI and OCTOSPI signals of the pins of a GPIO group, sorted
+/// by peripheral, bank, function and pin.
+pub fn qspi_pins(group: &GpioGroup) -> Vec<QspiPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            if let Some(signal) = parse_qspi_signal(raw.name()) {
+                pins.push(QspiPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.sort_by(|a, b| {
+        compare_str(&a.signal.device, &b.signal.device)
+            .then_with(|| a.signal.bank.cmp(&b.signal.bank))
+            .then_with(|| a.signal.function.cmp(&b.signal.function))
+            .then_with(|| compare_str(&a.pin, &b.pin))
+    });
+    pins.dedup();
+    pins
+}
+
+/// The pins a quad SPI memory needs on a QUADSPI bank or OCTOSPI port: the
+/// clock, the chip select and four IOs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QspiBus {
+    /// The name of the bus type (e.g. "QspiBank1Pins" or "OspiPort1Pins").
+    pub name: String,
+    /// The peripheral ("QUADSPI" or "OCTOSPIM").
+    pub device: String,
+    /// The QUADSPI bank or OCTOSPI port.
+    pub bank: u8,
+    /// The field names and IO traits of the pins (e.g. `("io0",
+    /// "Bk1Io0Pin")`).
+    pub pins: Vec<(String, String)>,
+}
+
+impl QspiBus {
+    /// The bus of a QUADSPI bank or OCTOSPI port.
+    pub fn new(device: &str, bank: u8) -> Self {
+        let (name, clock_bank) = match device {
+            "OCTOSPIM" => (format!("OspiPort{}Pins", bank), Some(bank)),
+            _ => (format!("QspiBank{}Pins", bank), None),
+        };
+        let signal = |bank, function| QspiSignal {
+            device: device.to_string(),
+            bank,
+            function,
+        };
+        let mut pins = vec![
+            ("clk".to_string(), signal(clock_bank, QspiFunction::Clk)),
+            ("ncs".to_string(), signal(Some(bank), QspiFunction::Ncs)),
+        ];
+        for n in 0..QUAD_IOS {
+            pins.push((format!("io{}", n), signal(Some(bank), QspiFunction::Io(n))));
+        }
+        Self {
+            name,
+            device: device.to_string(),
+            bank,
+            pins: pins
+                .into_iter()
+                .map(|(field, signal)| (field, signal.io_trait()))
+                .collect(),
+        }
+    }
+}
+
+/// Return the buses whose pins are all available on some pin of the group
+/// (e.g. `QspiBank1Pins` if the group has the QUADSPI clock, bank 1 chip
+/// select and bank 1 IOs 0 to 3).
+pub fn qspi_buses(pins: &[QspiPin]) -> Vec<QspiBus> {
+    let mut banks = pins
+        .iter()
+        .filter_map(|p| Some((p.signal.device.as_str(), p.signal.bank?)))
+        .collect::<Vec<_>>();
+    banks.sort_unstable();
+    banks.dedup();
+    banks
+        .into_iter()
+        .map(|(device, bank)| QspiBus::new(device, bank))
+        .filter(|bus| {
+            bus.pins.iter().all(|(_, io)| {
+                pins.iter()
+                    .any(|p| p.signal.device == bus.device && p.signal.io_trait() == *io)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_qspi_signal() {
+        let signal = parse_qspi_signal("QUADSPI_BK1_IO2").unwrap();
+        assert_eq!(signal.device, "QUADSPI");
+        assert_eq!(signal.bank, Some(1));
+        assert_eq!(signal.function, QspiFunction::Io(2));
+        assert_eq!(signal.io_trait(), "Bk1Io2Pin");
+
+        let signal = parse_qspi_signal("QUADSPI_CLK").unwrap();
+        assert_eq!(signal.bank, None);
+        assert_eq!(signal.io_trait(), "ClkPin");
+
+        assert_eq!(
+            parse_qspi_signal("QUADSPI_NCS").map(|s| s.io_trait()),
+            Some("Bk1NcsPin".into())
+        );
+        assert_eq!(
+            parse_qspi_signal("QUADSPI_BK2_NCS").map(|s| s.io_trait()),
+            Some("Bk2NcsPin".into())
+        );
+        assert_eq!(
+            parse_qspi_signal("OCTOSPIM_P2_DQS").map(|s| s.io_trait()),
+            Some("P2DqsPin".into())
+        );
+        assert_eq!(
+            parse_qspi_signal("OCTOSPIM_P1_IO7").map(|s| s.function),
+            Some(QspiFunction::Io(7))
+        );
+        assert!(parse_qspi_signal("QUADSPI_BK1_IO4").is_none());
+        assert!(parse_qspi_signal("SPI1_SCK").is_none());
+    }
+
+    #[test]
+    fn test_qspi_buses() {
+        let pin = |name: &str, signal: &str| QspiPin {
+            pin: name.to_string(),
+            af: "AF10".to_string(),
+            signal: parse_qspi_signal(signal).unwrap(),
+        };
+        let mut pins = vec![
+            pin("PB2", "QUADSPI_CLK"),
+            pin("PB6", "QUADSPI_BK1_NCS"),
+            pin("PD11", "QUADSPI_BK1_IO0"),
+            pin("PD12", "QUADSPI_BK1_IO1"),
+            pin("PE2", "QUADSPI_BK1_IO2"),
+            pin("PE7", "QUADSPI_BK2_IO0"),
+        ];
+        assert!(qspi_buses(&pins).is_empty());
+
+        pins.push(pin("PD13", "QUADSPI_BK1_IO3"));
+        let buses = qspi_buses(&pins);
+        assert_eq!(buses.len(), 1);
+        assert_eq!(buses[0].name, "QspiBank1Pins");
+        assert_eq!(
+            buses[0].pins[..2],
+            [
+                ("clk".to_string(), "ClkPin".to_string()),
+                ("ncs".to_string(), "Bk1NcsPin".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Clock metadata from the RCC IP Modes files.
+//!
+//! The RCC Modes files (e.g. `IP/RCC-STM32L051_rcc_v1_0_Modes.xml`) describe
+//! the settings of the clock configuration in CubeMX. Among them are the
+//! maximum frequency of every clock (e.g. `SYSCLKFreq_Value`) and the possible
+//! values of every prescaler (e.g. `AHBCLKDivider`).
+
+use std::error::Error;
+use std::path::Path;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::utils::load_file;
+
+lazy_static! {
+    static ref FREQUENCY: Regex = Regex::new(r"(?i)^(\w+?)_?Freq_Value$").unwrap();
+    static ref PRESCALER: Regex = Regex::new(r"^(\w+?)_?CLKDivider$").unwrap();
+    static ref DIVIDER: Regex = Regex::new(r"_DIV(\d+)$").unwrap();
+}
+
+/// A possible value of a `RefParameter` (e.g. `<PossibleValue Comment="2"
+/// Value="RCC_SYSCLK_DIV2"/>`).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ListValue {
+    pub(crate) value: String,
+    #[serde(default)]
+    pub(crate) comment: Option<String>,
+}
+
+/// A setting of an IP Modes file.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct RefParameter {
+    pub(crate) name: String,
+    #[serde(default)]
+    max: Option<String>,
+    #[serde(default)]
+    pub(crate) possible_value: Vec<ListValue>,
+}
+
+/// A RCC IP Modes file.
+#[derive(Debug, Deserialize)]
+#[serde(rename = "IP")]
+pub struct IpRcc {
+    #[serde(rename = "RefParameter", default)]
+    ref_parameter: Vec<RefParameter>,
+}
+
+impl IpRcc {
+    pub fn load<P: AsRef<Path>>(db_dir: P, version: &str) -> Result<Self, Box<dyn Error>> {
+        load_file(db_dir, Self::path(version))
+    }
+
+    /// The path of the Modes file of a RCC IP version, relative to the
+    /// database directory (e.g. "IP/RCC-STM32L051_rcc_v1_0_Modes.xml").
+    pub fn path(version: &str) -> String {
+        format!("IP/RCC-{}_Modes.xml", version)
+    }
+
+    /// Return the maximum frequency in Hz of every clock (e.g. "SYSCLK"), in
+    /// the order of the file. Limits that are not a plain number (e.g. ones
+    /// that depend on the voltage range) are skipped.
+    pub fn max_frequencies(&self) -> Vec<(String, u32)> {
+        self.ref_parameter
+            .iter()
+            .filter_map(|p| {
+                let clock = FREQUENCY.captures(&p.name)?[1].to_string();
+                let max = p.max.as_ref()?.trim().parse::<f64>().ok()?;
+                Some((clock, max as u32))
+            })
+            .collect()
+    }
+
+    /// Return the possible division factors of every prescaler (e.g. "AHB"),
+    /// in the order of the file.
+    pub fn prescalers(&self) -> Vec<(String, Vec<u32>)> {
+        self.ref_parameter
+            .iter()
+            .filter_map(|p| {
+                let prescaler = PRESCALER.captures(&p.name)?[1].to_string();
+                let mut factors = p
+                    .possible_value
+                    .iter()
+                    .filter_map(|v| DIVIDER.captures(&v.value)?[1].parse().ok())
+                    .collect::<Vec<u32>>();
+                factors.sort_unstable();
+                factors.dedup();
+                if factors.is_empty() {
+                    None
+                } else {
+                    Some((prescaler, factors))
+                }
+            })
+            .collect()
+    }
+}
+
+/// The clock metadata of a RCC IP version and the MCUs using it.
+#[derive(Debug)]
+pub struct RccGroup {
+    /// The IP version (e.g. "STM32L051_rcc_v1_0").
+    pub version: String,
+    /// The ref names of the MCUs using this version.
+    pub mcus: Vec<String>,
+    /// The maximum frequency in Hz of every clock (e.g. "SYSCLK").
+    pub max_frequencies: Vec<(String, u32)>,
+    /// The possible division factors of every prescaler (e.g. "AHB").
+    pub prescalers: Vec<(String, Vec<u32>)>,
+}
+
+impl RccGroup {
+    pub fn load<P: AsRef<Path>>(
+        db_dir: P,
+        version: &str,
+        mcus: Vec<String>,
+    ) -> Result<Self, String> {
+        let ip = IpRcc::load(db_dir, version)
+            .map_err(|e| format!("Could not load {}: {}", IpRcc::path(version), e))?;
+        Ok(Self {
+            version: version.to_string(),
+            mcus,
+            max_frequencies: ip.max_frequencies(),
+            prescalers: ip.prescalers(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_metadata() {
+        let xml = r#"
+            <IP DBVersion="V4.0" IPType="service" Name="RCC" Version="STM32L051_rcc_v1_0">
+                <RefParameter Comment="SYSCLK (Hz)" Max="32000000" Min="0" Name="SYSCLKFreq_VALUE" Type="integer"/>
+                <RefParameter Comment="APB1 (Hz)" Max="3.2E7" Min="0" Name="APB1Freq_Value" Type="integer"/>
+                <RefParameter Comment="HSE (Hz)" Max="${HSE_MAX}" Name="HSE_VALUE" Type="integer"/>
+                <RefParameter Comment="AHB Prescaler" Name="AHBCLKDivider" Type="list">
+                    <PossibleValue Comment="1" Value="RCC_SYSCLK_DIV1"/>
+                    <PossibleValue Comment="4" Value="RCC_SYSCLK_DIV4"/>
+                    <PossibleValue Comment="2" Value="RCC_SYSCLK_DIV2"/>
+                </RefParameter>
+                <RefParameter Comment="MCO" Name="RCC_MCODiv" Type="list">
+                    <PossibleValue Comment="1" Value="RCC_MCODIV_1"/>
+                </RefParameter>
+            </IP>
+        "#;
+        let ip: IpRcc = serde_xml_rs::deserialize(xml.as_bytes()).unwrap();
+        assert_eq!(
+            ip.max_frequencies(),
+            vec![("SYSCLK".into(), 32_000_000), ("APB1".into(), 32_000_000)]
+        );
+        assert_eq!(ip.prescalers(), vec![("AHB".into(), vec![1, 2, 4])]);
+    }
+}
@@ -0,0 +1,189 @@
+//! AFIO remaps of the STM32F1.
+//!
+//! The STM32F1 has no alternate function numbers per pin. Instead, the pins of
+//! a peripheral are selected for all its signals at once by an AFIO remap
+//! configuration: no remap, a partial remap or a full remap. The GPIO IP files
+//! list the remap blocks routing a signal to a pin, with the C HAL define
+//! selecting them (e.g. `__HAL_AFIO_REMAP_USART1_ENABLE` for USART1_TX on PB6).
+
+use std::fmt;
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// Remap defines, e.g. "__HAL_AFIO_REMAP_USART1_ENABLE",
+    /// "__HAL_AFIO_REMAP_TIM2_PARTIAL_1" or "__HAL_AFIO_REMAP_CAN1_2".
+    static ref REMAP_DEFINE: Regex = Regex::new(
+        r"^__HAL_AFIO_REMAP_(\w+?)_(?:(DISABLE)|(ENABLE)|PARTIAL(?:_(\d))?|(\d))$"
+    )
+    .unwrap();
+    /// Remap block names, e.g. "USART1_REMAP1".
+    static ref REMAP_BLOCK: Regex = Regex::new(r"^(\w+?)_REMAP\d+$").unwrap();
+}
+
+/// A remap configuration of a peripheral.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RemapMode {
+    /// The default pins.
+    None,
+    /// A partial remap (e.g. 1 for `PARTIAL` or `PARTIAL_1`, 2 for
+    /// `PARTIAL_2`).
+    Partial(u8),
+    /// The full remap.
+    Full,
+}
+
+impl fmt::Display for RemapMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemapMode::None => write!(f, "None"),
+            RemapMode::Partial(n) => write!(f, "Partial{}", n),
+            RemapMode::Full => write!(f, "Full"),
+        }
+    }
+}
+
+/// The remap of a peripheral routing a signal to a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Remap {
+    /// The remapped peripheral (e.g. "USART1").
+    pub peripheral: String,
+    pub mode: RemapMode,
+}
+
+impl Remap {
+    /// The pseudo alternate function of the remapped signals in the AF tree
+    /// (e.g. "REMAP_FULL"), shown by the query output.
+    pub fn af(&self) -> String {
+        format!("REMAP_{}", self.mode.to_string().to_uppercase())
+    }
+}
+
+/// Parse a remap define (e.g. "__HAL_AFIO_REMAP_USART1_ENABLE").
+///
+/// The numbered remaps of the CAN peripherals (e.g.
+/// "__HAL_AFIO_REMAP_CAN1_2") map to no remap (1), a partial remap (2) and
+/// the full remap (3).
+pub fn parse_remap_define(define: &str) -> Option<Remap> {
+    let captures = REMAP_DEFINE.captures(define)?;
+    let mode = if captures.get(2).is_some() {
+        RemapMode::None
+    } else if captures.get(3).is_some() {
+        RemapMode::Full
+    } else if let Some(number) = captures.get(5) {
+        match number.as_str() {
+            "1" => RemapMode::None,
+            "2" => RemapMode::Partial(1),
+            "3" => RemapMode::Full,
+            _ => return None,
+        }
+    } else {
+        RemapMode::Partial(
+            captures
+                .get(4)
+                .and_then(|n| n.as_str().parse().ok())
+                .unwrap_or(1),
+        )
+    };
+    Some(Remap {
+        peripheral: captures[1].to_string(),
+        mode,
+    })
+}
+
+/// Determine the remap of a remap block from its define, or for a default
+/// block without define from its name (e.g. "I2C1_REMAP1" without remap).
+pub fn parse_remap_block(name: &str, default: bool, define: Option<&str>) -> Option<Remap> {
+    match define {
+        Some(define) => parse_remap_define(define),
+        None if default => Some(Remap {
+            peripheral: REMAP_BLOCK.captures(name)?[1].to_string(),
+            mode: RemapMode::None,
+        }),
+        None => None,
+    }
+}
+
+/// A remapped signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemapPin {
+    /// The pin (e.g. "PB6").
+    pub pin: String,
+    /// The signal (e.g. "USART1_TX").
+    pub signal: String,
+    pub remap: Remap,
+}
+
+/// Return the remapped signals of the pins of a GPIO group, sorted by
+/// peripheral, remap and pin.
+pub fn remap_pins(group: &GpioGroup) -> Vec<RemapPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            if let Some(remap) = raw.remap() {
+                pins.push(RemapPin {
+                    pin: pin.name(),
+                    signal: raw.name().to_string(),
+                    remap: remap.clone(),
+                });
+            }
+        }
+    }
+    pins.sort_by(|a, b| {
+        compare_str(&a.remap.peripheral, &b.remap.peripheral)
+            .then_with(|| a.remap.mode.cmp(&b.remap.mode))
+            .then_with(|| compare_str(&a.pin, &b.pin))
+    });
+    pins.dedup();
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remap_define() {
+        let remap = |define| parse_remap_define(define).map(|r| (r.peripheral, r.mode));
+        assert_eq!(
+            remap("__HAL_AFIO_REMAP_USART1_ENABLE"),
+            Some(("USART1".into(), RemapMode::Full))
+        );
+        assert_eq!(
+            remap("__HAL_AFIO_REMAP_USART1_DISABLE"),
+            Some(("USART1".into(), RemapMode::None))
+        );
+        assert_eq!(
+            remap("__HAL_AFIO_REMAP_TIM3_PARTIAL"),
+            Some(("TIM3".into(), RemapMode::Partial(1)))
+        );
+        assert_eq!(
+            remap("__HAL_AFIO_REMAP_TIM2_PARTIAL_2"),
+            Some(("TIM2".into(), RemapMode::Partial(2)))
+        );
+        assert_eq!(
+            remap("__HAL_AFIO_REMAP_CAN1_3"),
+            Some(("CAN1".into(), RemapMode::Full))
+        );
+        assert_eq!(remap("GPIO_AF4_USART2"), None);
+
+        assert_eq!(
+            parse_remap_block("I2C1_REMAP1", true, None).map(|r| r.peripheral),
+            Some("I2C1".into())
+        );
+        assert_eq!(parse_remap_block("I2C1_REMAP2", false, None), None);
+        assert_eq!(
+            Remap {
+                peripheral: "TIM2".into(),
+                mode: RemapMode::Partial(2)
+            }
+            .af(),
+            "REMAP_PARTIAL2"
+        );
+    }
+}
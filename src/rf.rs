@@ -0,0 +1,82 @@
+//! RF pins and signals of the wireless families (STM32WB and STM32WL).
+//!
+//! The radio has dedicated pins (e.g. "RF1" on the STM32WB, "RFI_P" or
+//! "RFO_HP" on the STM32WL), which are not GPIOs. Some GPIOs provide radio
+//! control and debug signals as alternate functions (e.g. "RF_TX_MOD_EXT_PA",
+//! "DEBUG_RF_HSE32RDY" or "DEBUG_SUBGHZSPI-NSSOUT"). Their names do not start
+//! with a peripheral instance, so they are grouped under the `RF` device and
+//! never map to an IO trait.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// The device of the RF signals, e.g. for the stem and device filters.
+pub const RF: &str = "RF";
+
+lazy_static! {
+    /// RF signals, e.g. "RF_TX_MOD_EXT_PA", "RF_IRQ0", "DEBUG_RF_DTB1" or
+    /// "DEBUG_SUBGHZSPI-NSSOUT".
+    static ref RF_SIGNAL: Regex =
+        Regex::new(r"^(DEBUG_)?(?:RF_|(SUBGHZSPI)[_-])([A-Za-z0-9][\w-]*)$").unwrap();
+    /// RF pins, e.g. "RF1", "RFI_N" or "RFO_LP".
+    static ref RF_PIN: Regex = Regex::new(r"^RF(\d+|[IO]_[A-Z]+|_[A-Z]+\w*)$").unwrap();
+}
+
+/// An RF signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RfSignal {
+    /// The signal without the RF prefix (e.g. "TX_MOD_EXT_PA", or
+    /// "SUBGHZSPI-NSSOUT" for "DEBUG_SUBGHZSPI-NSSOUT").
+    pub function: String,
+    /// Whether this is a debug signal of the radio (e.g. "DEBUG_RF_DTB1").
+    pub debug: bool,
+}
+
+/// Parse an RF signal (e.g. "DEBUG_RF_HSE32RDY" into the debug function
+/// "HSE32RDY"). Other signals return `None`.
+pub fn parse_rf_signal(signal: &str) -> Option<RfSignal> {
+    let captures = RF_SIGNAL.captures(signal)?;
+    let function = match captures.get(2) {
+        Some(spi) => format!("{}{}", spi.as_str(), &signal[spi.end()..]),
+        None => captures[3].to_string(),
+    };
+    Some(RfSignal {
+        function,
+        debug: captures.get(1).is_some(),
+    })
+}
+
+/// Whether a pin name as listed in the MCU file (e.g. "RF1") is a dedicated
+/// RF pin.
+pub fn is_rf_pin(name: &str) -> bool {
+    RF_PIN.is_match(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rf_signal() {
+        let signal = parse_rf_signal("RF_TX_MOD_EXT_PA").unwrap();
+        assert_eq!(signal.function, "TX_MOD_EXT_PA");
+        assert!(!signal.debug);
+
+        let signal = parse_rf_signal("DEBUG_RF_HSE32RDY").unwrap();
+        assert_eq!(signal.function, "HSE32RDY");
+        assert!(signal.debug);
+
+        assert_eq!(
+            parse_rf_signal("DEBUG_SUBGHZSPI-NSSOUT").map(|s| s.function),
+            Some("SUBGHZSPI-NSSOUT".into())
+        );
+        assert!(parse_rf_signal("SPI1_MOSI").is_none());
+        assert!(parse_rf_signal("RFU").is_none());
+
+        assert!(is_rf_pin("RF1"));
+        assert!(is_rf_pin("RFI_N"));
+        assert!(is_rf_pin("RFO_HP"));
+        assert!(!is_rf_pin("PA0"));
+        assert!(!is_rf_pin("VDDRF"));
+    }
+}
@@ -0,0 +1,121 @@
+//! SPI signals.
+//!
+//! An SPI instance has a clock ("SPI1_SCK"), two data lines ("SPI1_MISO" and
+//! "SPI1_MOSI") and a hardware slave select ("SPI1_NSS"). Not every
+//! application uses all of them: the slave select is often a GPIO driven by
+//! software, and a transmit-only or bidirectional 3-wire bus has no MISO pin.
+//! The I2S signals of the SPI instances are parsed by `i2s`.
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// SPI signals, e.g. "SPI1_SCK" or "SPI2_NSS".
+    static ref SPI_SIGNAL: Regex = Regex::new(r"^(SPI\d+)_(SCK|MISO|MOSI|NSS)$").unwrap();
+}
+
+/// The function of an SPI pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpiFunction {
+    /// The clock ("SCK").
+    Sck,
+    /// The master input, slave output data line ("MISO").
+    Miso,
+    /// The master output, slave input data line ("MOSI").
+    Mosi,
+    /// The hardware slave select ("NSS").
+    Nss,
+}
+
+/// An SPI signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SpiSignal {
+    /// The SPI instance (e.g. "SPI1").
+    pub device: String,
+    pub function: SpiFunction,
+}
+
+impl SpiSignal {
+    /// The name of the IO trait of the pin (e.g. "MosiPin").
+    pub fn io_trait(&self) -> &'static str {
+        match self.function {
+            SpiFunction::Sck => "SckPin",
+            SpiFunction::Miso => "MisoPin",
+            SpiFunction::Mosi => "MosiPin",
+            SpiFunction::Nss => "NssPin",
+        }
+    }
+}
+
+/// Parse an SPI signal (e.g. "SPI1_MOSI" into `SPI1` and `Mosi`).
+pub fn parse_spi_signal(signal: &str) -> Option<SpiSignal> {
+    let captures = SPI_SIGNAL.captures(signal)?;
+    let function = match &captures[2] {
+        "SCK" => SpiFunction::Sck,
+        "MISO" => SpiFunction::Miso,
+        "MOSI" => SpiFunction::Mosi,
+        _ => SpiFunction::Nss,
+    };
+    Some(SpiSignal {
+        device: captures[1].to_string(),
+        function,
+    })
+}
+
+/// An SPI signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SpiPin {
+    /// The pin (e.g. "PA7").
+    pub pin: String,
+    /// The alternate function (e.g. "AF5").
+    pub af: String,
+    pub signal: SpiSignal,
+}
+
+/// Return the SPI signals of the pins of a GPIO group, sorted by instance,
+/// function and pin.
+pub fn spi_pins(group: &GpioGroup) -> Vec<SpiPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            if let Some(signal) = parse_spi_signal(raw.name()) {
+                pins.push(SpiPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.sort_by(|a, b| {
+        compare_str(&a.signal.device, &b.signal.device)
+            .then_with(|| a.signal.function.cmp(&b.signal.function))
+            .then_with(|| compare_str(&a.pin, &b.pin))
+    });
+    pins.dedup();
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spi_signal() {
+        let signal = parse_spi_signal("SPI1_MOSI").unwrap();
+        assert_eq!(signal.device, "SPI1");
+        assert_eq!(signal.function, SpiFunction::Mosi);
+        assert_eq!(signal.io_trait(), "MosiPin");
+
+        assert_eq!(
+            parse_spi_signal("SPI2_NSS").map(|s| s.io_trait()),
+            Some("NssPin")
+        );
+        assert!(parse_spi_signal("QUADSPI_CLK").is_none());
+        assert!(parse_spi_signal("I2S2_CK").is_none());
+        assert!(parse_spi_signal("USART2_TX").is_none());
+    }
+}
@@ -0,0 +1,196 @@
+//! Peripheral stems and user-defined composite stems.
+//!
+//! A stem is a peripheral instance name without the instance number (e.g.
+//! "USART" for "USART2"). A composite stem groups several stems under a new
+//! name (e.g. `SERIAL = USART|UART|LPUART`), so that they can be selected
+//! together.
+//!
+//! Stems can also be selected with a wildcard pattern (e.g. `TIM*`) or a
+//! regex enclosed in slashes (e.g. `/^U(S)?ART/`), see `StemPattern`.
+//!
+//! The serial peripherals have three stems ("USART", "UART" and "LPUART")
+//! sharing the same IO traits, which some outputs merge, see `serial_stem`.
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use regex::Regex;
+
+use crate::utils::wildcard_regex;
+
+/// The stems of the serial peripherals.
+pub const SERIAL_STEMS: &[&str] = &["USART", "UART", "LPUART"];
+
+/// Return "USART" for the stems of the serial peripherals (e.g. "LPUART"),
+/// and other stems unchanged.
+pub fn serial_stem(stem: &str) -> &str {
+    if SERIAL_STEMS.contains(&stem) {
+        SERIAL_STEMS[0]
+    } else {
+        stem
+    }
+}
+
+/// A pattern selecting peripheral stems.
+#[derive(Debug, Clone)]
+pub enum StemPattern {
+    /// A stem name (e.g. "USART").
+    Exact(String),
+    /// A wildcard pattern, where `*` matches any number of characters and `?`
+    /// matches a single character (e.g. "TIM*").
+    Wildcard(String, Regex),
+    /// A regex enclosed in slashes (e.g. "/^U(S)?ART/").
+    Regex(Regex),
+}
+
+impl StemPattern {
+    pub fn matches(&self, stem: &str) -> bool {
+        match self {
+            StemPattern::Exact(name) => name == stem,
+            StemPattern::Wildcard(_, re) | StemPattern::Regex(re) => re.is_match(stem),
+        }
+    }
+
+    /// Return whether this pattern can match more than one stem.
+    pub fn is_pattern(&self) -> bool {
+        !matches!(self, StemPattern::Exact(_))
+    }
+}
+
+impl FromStr for StemPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+            let re = Regex::new(&s[1..s.len() - 1])
+                .map_err(|e| format!("Invalid stem regex {:?}: {}", s, e))?;
+            Ok(StemPattern::Regex(re))
+        } else if s.contains(['*', '?']) {
+            let re = wildcard_regex(s, false);
+            Ok(StemPattern::Wildcard(s.to_string(), re))
+        } else {
+            Ok(StemPattern::Exact(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for StemPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StemPattern::Exact(name) | StemPattern::Wildcard(name, _) => write!(f, "{}", name),
+            StemPattern::Regex(re) => write!(f, "/{}/", re),
+        }
+    }
+}
+
+/// A set of composite stem definitions.
+#[derive(Debug, Clone, Default)]
+pub struct CompositeStems {
+    definitions: HashMap<String, Vec<String>>,
+}
+
+impl CompositeStems {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse and add a definition of the form `NAME=STEM1|STEM2|...`.
+    pub fn add_definition(&mut self, definition: &str) -> Result<(), String> {
+        let mut parts = definition.splitn(2, '=');
+        let name = parts.next().unwrap().trim();
+        let stems = parts
+            .next()
+            .ok_or_else(|| format!("Invalid composite stem {:?}, expected NAME=A|B", definition))?
+            .split('|')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        if name.is_empty() || stems.is_empty() {
+            return Err(format!(
+                "Invalid composite stem {:?}, expected NAME=A|B",
+                definition
+            ));
+        }
+        self.definitions.insert(name.to_string(), stems);
+        Ok(())
+    }
+
+    /// Return the member stems of a composite stem.
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.definitions.get(name).map(Vec::as_slice)
+    }
+
+    /// Expand composite stems in the list to their members. Other stems are
+    /// kept as they are. Duplicates are removed.
+    pub fn expand<S: AsRef<str>>(&self, stems: &[S]) -> Vec<String> {
+        let mut expanded: Vec<String> = vec![];
+        for stem in stems {
+            let stem = stem.as_ref();
+            let members = match self.definitions.get(stem) {
+                Some(members) => members.clone(),
+                None => vec![stem.to_string()],
+            };
+            for member in members {
+                if !expanded.contains(&member) {
+                    expanded.push(member);
+                }
+            }
+        }
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stem_pattern() {
+        let pattern = |s: &str| s.parse::<StemPattern>().unwrap();
+
+        assert!(pattern("TIM").matches("TIM"));
+        assert!(!pattern("TIM").matches("LPTIM"));
+        assert!(!pattern("TIM").is_pattern());
+
+        assert!(pattern("TIM*").matches("TIM"));
+        assert!(!pattern("TIM*").matches("LPTIM"));
+        assert!(pattern("*TIM").matches("LPTIM"));
+        assert!(pattern("I2?").matches("I2C"));
+        assert!(!pattern("I2?").matches("I2"));
+        assert!(pattern("I2?").is_pattern());
+
+        assert!(pattern("/^U(S)?ART/").matches("USART"));
+        assert!(pattern("/^U(S)?ART/").matches("UART"));
+        assert!(!pattern("/^U(S)?ART/").matches("LPUART"));
+        assert_eq!(pattern("/^U(S)?ART/").to_string(), "/^U(S)?ART/");
+
+        assert!("/(/".parse::<StemPattern>().is_err());
+    }
+
+    #[test]
+    fn test_composite_stems() {
+        let mut composite = CompositeStems::new();
+        composite
+            .add_definition("SERIAL = USART|UART|LPUART")
+            .unwrap();
+        assert_eq!(
+            composite.get("SERIAL").unwrap(),
+            &["USART", "UART", "LPUART"]
+        );
+        assert_eq!(
+            composite.expand(&["SERIAL", "I2C", "USART"]),
+            vec!["USART", "UART", "LPUART", "I2C"]
+        );
+
+        assert!(composite.add_definition("SERIAL").is_err());
+        assert!(composite.add_definition("=USART").is_err());
+        assert!(composite.add_definition("SERIAL=").is_err());
+    }
+
+    #[test]
+    fn test_serial_stem() {
+        assert_eq!(serial_stem("LPUART"), "USART");
+        assert_eq!(serial_stem("UART"), "USART");
+        assert_eq!(serial_stem("USART"), "USART");
+        assert_eq!(serial_stem("SPI"), "SPI");
+    }
+}
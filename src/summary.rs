@@ -0,0 +1,59 @@
+use std::{error::Error, fs::File, path::Path, path::PathBuf, time::Instant};
+
+use log::{error, warn};
+use serde_derive::Serialize;
+
+/// A machine-readable summary of a single run, meant to be parsed by CI jobs.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    /// The MCU family that was processed.
+    pub family: String,
+    /// The release of the CubeMX database, if it could be determined.
+    pub db_release: Option<String>,
+    /// The targets that were generated (e.g. "features").
+    pub targets: Vec<String>,
+    /// Paths of all files written to the output directory.
+    pub files_written: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+    pub errors: Vec<String>,
+    /// Wall-clock duration of the run in seconds.
+    pub duration_secs: f64,
+    #[serde(skip)]
+    start: Instant,
+}
+
+impl Summary {
+    pub fn new() -> Self {
+        Self {
+            family: String::new(),
+            db_release: None,
+            targets: vec![],
+            files_written: vec![],
+            warnings: vec![],
+            errors: vec![],
+            duration_secs: 0.0,
+            start: Instant::now(),
+        }
+    }
+
+    /// Log a warning and record it in the summary.
+    pub fn warn(&mut self, msg: String) {
+        warn!("{}", msg);
+        self.warnings.push(msg);
+    }
+
+    /// Log an error that did not abort the run (e.g. a MCU that could not be
+    /// loaded and was skipped) and record it in the summary.
+    pub fn error(&mut self, msg: String) {
+        error!("{}", msg);
+        self.errors.push(msg);
+    }
+
+    /// Stop the timer and write the summary as JSON to the specified file.
+    pub fn write<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<dyn Error>> {
+        self.duration_secs = self.start.elapsed().as_secs_f64();
+        let fout = File::create(path)?;
+        serde_json::to_writer_pretty(fout, self)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+//! Serial wire and JTAG debug port (SWJ-DP) pins.
+//!
+//! After reset, the debug pins (e.g. PA13 and PA14 for SWDIO and SWCLK) are
+//! in their debug alternate function. Reconfiguring them as GPIO or for
+//! another peripheral disconnects the debugger, and if the firmware does so
+//! early, the MCU can only be reflashed by connecting under reset.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+
+lazy_static! {
+    /// Debug port signals, e.g. "SYS_JTMS-SWDIO", "SYS_SWCLK", "SYS_JTDO-SWO",
+    /// "SYS_NJTRST" or "DEBUG_JTCK-SWCLK".
+    static ref SWJ_SIGNAL: Regex = Regex::new(
+        r"^(?:SYS|DEBUG)_(?:(?:JTMS-)?(SWDIO)|(?:JTCK-)?(SWCLK)|JTDO(?:-(?:TRACE)?SWO)?|(JTDI)|N?JTRST)$"
+    )
+    .unwrap();
+}
+
+/// The debug function of a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SwjFunction {
+    /// The SWD data line, shared with the JTAG mode select ("JTMS-SWDIO").
+    Swdio,
+    /// The SWD clock, shared with the JTAG clock ("JTCK-SWCLK").
+    Swclk,
+    /// The JTAG data output, shared with the trace output ("JTDO-SWO").
+    Jtdo,
+    /// The JTAG data input ("JTDI").
+    Jtdi,
+    /// The JTAG reset ("NJTRST").
+    Njtrst,
+}
+
+impl SwjFunction {
+    /// The name of the function (e.g. "SWDIO").
+    pub fn name(self) -> &'static str {
+        match self {
+            SwjFunction::Swdio => "SWDIO",
+            SwjFunction::Swclk => "SWCLK",
+            SwjFunction::Jtdo => "JTDO",
+            SwjFunction::Jtdi => "JTDI",
+            SwjFunction::Njtrst => "NJTRST",
+        }
+    }
+
+    /// Whether the pin is needed for serial wire debugging, not only JTAG.
+    pub fn is_swd(self) -> bool {
+        matches!(self, SwjFunction::Swdio | SwjFunction::Swclk)
+    }
+}
+
+/// Parse a debug port signal (e.g. "SYS_JTMS-SWDIO"). Other signals return
+/// `None`.
+pub fn parse_swj_signal(signal: &str) -> Option<SwjFunction> {
+    let captures = SWJ_SIGNAL.captures(signal)?;
+    Some(if captures.get(1).is_some() {
+        SwjFunction::Swdio
+    } else if captures.get(2).is_some() {
+        SwjFunction::Swclk
+    } else if captures.get(3).is_some() {
+        SwjFunction::Jtdi
+    } else if signal.contains("JTDO") {
+        SwjFunction::Jtdo
+    } else {
+        SwjFunction::Njtrst
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_swj_signal() {
+        assert_eq!(parse_swj_signal("SYS_JTMS-SWDIO"), Some(SwjFunction::Swdio));
+        assert_eq!(parse_swj_signal("SYS_SWCLK"), Some(SwjFunction::Swclk));
+        assert_eq!(
+            parse_swj_signal("DEBUG_JTCK-SWCLK"),
+            Some(SwjFunction::Swclk)
+        );
+        assert_eq!(
+            parse_swj_signal("SYS_JTDO-TRACESWO"),
+            Some(SwjFunction::Jtdo)
+        );
+        assert_eq!(parse_swj_signal("SYS_JTDI"), Some(SwjFunction::Jtdi));
+        assert_eq!(parse_swj_signal("SYS_NJTRST"), Some(SwjFunction::Njtrst));
+        assert_eq!(parse_swj_signal("SYS_JTRST"), Some(SwjFunction::Njtrst));
+        assert_eq!(parse_swj_signal("SYS_WKUP1"), None);
+        assert_eq!(parse_swj_signal("USART2_TX"), None);
+        assert!(SwjFunction::Swdio.is_swd());
+        assert!(!SwjFunction::Jtdi.is_swd());
+    }
+}
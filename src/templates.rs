@@ -0,0 +1,96 @@
+//! Handlebars templates of the generated code.
+//!
+//! The built-in templates generate the `pins!` blocks expected by the HALs
+//! cube-parse was written for. HALs with other macro names or layouts can
+//! replace any of them with a file of the same name (e.g.
+//! `pin_group.rs.hbs`) in a template directory, see `Templates::load`. The
+//! built-in templates in the `templates` directory of the repository are a
+//! starting point for this.
+
+use std::{fs, path::Path};
+
+use handlebars::Handlebars;
+use log::warn;
+use serde::Serialize;
+
+/// The template of the pin mappings of one GPIO version, rendered with a
+/// `codegen::PinGroupContext`.
+pub const PIN_GROUP: &str = "pin_group";
+
+/// The names and sources of the built-in templates.
+const BUILT_IN: &[(&str, &str)] = &[(PIN_GROUP, include_str!("../templates/pin_group.rs.hbs"))];
+
+/// The file extension of the templates in a template directory.
+const EXTENSION: &str = ".rs.hbs";
+
+/// The templates used by the code generation.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    registry: Handlebars<'static>,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        let mut registry = Handlebars::new();
+        // The generated code is Rust, not HTML
+        registry.register_escape_fn(handlebars::no_escape);
+        registry.set_strict_mode(true);
+        for (name, source) in BUILT_IN {
+            registry
+                .register_template_string(name, source)
+                .expect("Invalid built-in template");
+        }
+        Self { registry }
+    }
+}
+
+impl Templates {
+    /// The built-in templates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in templates, overridden by the templates in `dir` (e.g.
+    /// `dir/pin_group.rs.hbs` replaces `PIN_GROUP`). Files that do not
+    /// replace a built-in template are ignored with a warning.
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self, String> {
+        let dir = dir.as_ref();
+        let mut templates = Self::new();
+        let entries = fs::read_dir(dir)
+            .map_err(|e| format!("Could not read template dir {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry
+                .map_err(|e| format!("Could not read template dir {}: {}", dir.display(), e))?
+                .path();
+            let file_name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            let name = match file_name.strip_suffix(EXTENSION) {
+                Some(name) if BUILT_IN.iter().any(|(n, _)| *n == name) => name,
+                _ => {
+                    warn!("Unknown template {} (ignoring)", path.display());
+                    continue;
+                }
+            };
+            let source = fs::read_to_string(&path)
+                .map_err(|e| format!("Could not read template {}: {}", path.display(), e))?;
+            templates.register(name, &source)?;
+        }
+        Ok(templates)
+    }
+
+    /// Replace the template `name` with `source`.
+    pub fn register(&mut self, name: &str, source: &str) -> Result<(), String> {
+        self.registry
+            .register_template_string(name, source)
+            .map_err(|e| format!("Invalid template {}: {}", name, e))
+    }
+
+    /// Render the template `name` with `data`.
+    pub fn render<T: Serialize>(&self, name: &str, data: &T) -> Result<String, String> {
+        self.registry
+            .render(name, data)
+            .map_err(|e| format!("Could not render template {}: {}", name, e))
+    }
+}
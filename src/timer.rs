@@ -0,0 +1,206 @@
+//! Timer signals.
+//!
+//! Timer signals (e.g. "TIM1_CH2N") are parsed into the timer instance and the
+//! function of the pin, so that PWM and capture pins can be generated with
+//! their channel index instead of matching the signal names downstream.
+
+use alphanumeric_sort::compare_str;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// Timer signals, e.g. "TIM2_CH1", "TIM1_CH2N", "TIM2_ETR", "TIM1_BKIN2",
+    /// or "TIM2_CH1_ETR" on parts sharing the channel and trigger pins.
+    static ref TIMER_SIGNAL: Regex =
+        Regex::new(r"^(TIM\d+)_(?:CH(\d+)(N?)(_ETR)?|(ETR)|BKIN(\d*))$").unwrap();
+}
+
+/// The function of a timer pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimerFunction {
+    /// A capture/compare channel (e.g. 1 for "CH1").
+    Channel(u8),
+    /// A complementary output channel (e.g. 1 for "CH1N").
+    ComplementaryChannel(u8),
+    /// The external trigger input ("ETR").
+    ExternalTrigger,
+    /// A break input (1 for "BKIN", 2 for "BKIN2").
+    BreakInput(u8),
+}
+
+/// A timer signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TimerSignal {
+    /// The timer instance (e.g. "TIM1").
+    pub device: String,
+    pub function: TimerFunction,
+}
+
+impl TimerSignal {
+    /// The channel index of a (complementary) channel.
+    pub fn channel(&self) -> Option<u8> {
+        match self.function {
+            TimerFunction::Channel(n) | TimerFunction::ComplementaryChannel(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a complementary output channel.
+    pub fn is_complementary(&self) -> bool {
+        matches!(self.function, TimerFunction::ComplementaryChannel(_))
+    }
+}
+
+/// Parse a signal into its timer functions (e.g. "TIM1_CH2N" into `TIM1`
+/// and `ComplementaryChannel(2)`). A shared channel and trigger signal (e.g.
+/// "TIM2_CH1_ETR") has both functions. Other signals have none.
+pub fn parse_timer_signal(signal: &str) -> Vec<TimerSignal> {
+    let captures = match TIMER_SIGNAL.captures(signal) {
+        Some(captures) => captures,
+        None => return vec![],
+    };
+    let device = captures[1].to_string();
+    let mut functions = vec![];
+    if let Some(channel) = captures.get(2).and_then(|c| c.as_str().parse().ok()) {
+        if &captures[3] == "N" {
+            functions.push(TimerFunction::ComplementaryChannel(channel));
+        } else {
+            functions.push(TimerFunction::Channel(channel));
+        }
+    }
+    if captures.get(4).is_some() || captures.get(5).is_some() {
+        functions.push(TimerFunction::ExternalTrigger);
+    }
+    if let Some(input) = captures.get(6) {
+        functions.push(TimerFunction::BreakInput(
+            input.as_str().parse().unwrap_or(1),
+        ));
+    }
+    functions
+        .into_iter()
+        .map(|function| TimerSignal {
+            device: device.clone(),
+            function,
+        })
+        .collect()
+}
+
+/// A timer signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TimerPin {
+    /// The pin (e.g. "PA8").
+    pub pin: String,
+    /// The alternate function (e.g. "AF2").
+    pub af: String,
+    pub signal: TimerSignal,
+}
+
+/// Return the timer signals of the pins of a GPIO group, sorted by timer,
+/// function and pin.
+pub fn timer_pins(group: &GpioGroup) -> Vec<TimerPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            for signal in parse_timer_signal(raw.name()) {
+                pins.push(TimerPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.sort_by(|a, b| {
+        compare_str(&a.signal.device, &b.signal.device)
+            .then_with(|| sort_key(a.signal.function).cmp(&sort_key(b.signal.function)))
+            .then_with(|| compare_str(&a.pin, &b.pin))
+    });
+    pins.dedup();
+    pins
+}
+
+/// Order the channels first, each followed by its complementary channel.
+fn sort_key(function: TimerFunction) -> (u8, u8, bool) {
+    match function {
+        TimerFunction::Channel(n) => (0, n, false),
+        TimerFunction::ComplementaryChannel(n) => (0, n, true),
+        TimerFunction::ExternalTrigger => (1, 0, false),
+        TimerFunction::BreakInput(n) => (2, n, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timer_signal() {
+        let parse = |signal| {
+            parse_timer_signal(signal)
+                .into_iter()
+                .map(|s| (s.device, s.function))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            parse("TIM2_CH3"),
+            vec![("TIM2".into(), TimerFunction::Channel(3))]
+        );
+        assert_eq!(
+            parse("TIM1_CH2N"),
+            vec![("TIM1".into(), TimerFunction::ComplementaryChannel(2))]
+        );
+        assert_eq!(
+            parse("TIM2_CH1_ETR"),
+            vec![
+                ("TIM2".into(), TimerFunction::Channel(1)),
+                ("TIM2".into(), TimerFunction::ExternalTrigger),
+            ]
+        );
+        assert_eq!(
+            parse("TIM1_BKIN"),
+            vec![("TIM1".into(), TimerFunction::BreakInput(1))]
+        );
+        assert_eq!(
+            parse("TIM1_BKIN2"),
+            vec![("TIM1".into(), TimerFunction::BreakInput(2))]
+        );
+        assert!(parse("LPTIM1_IN1").is_empty());
+        assert!(parse("USART2_TX").is_empty());
+
+        let signal = &parse_timer_signal("TIM1_CH2N")[0];
+        assert_eq!(signal.channel(), Some(2));
+        assert!(signal.is_complementary());
+    }
+
+    #[test]
+    fn test_timer_pins() {
+        let tree = crate::af_tree::AfTree::from_json(
+            r#"{"groups": [
+                {"version": "STM32L071_gpio_v1_0", "mcus": [],
+                 "pins": [{"port": "PA", "number": "7", "signals": [],
+                           "raw_signals": [{"name": "TIM1_CH1N", "af": "AF1"}]},
+                          {"port": "PA", "number": "8", "signals": [],
+                           "raw_signals": [{"name": "TIM1_CH1", "af": "AF1"},
+                                           {"name": "USART1_CK", "af": "AF7"}]}]}
+            ]}"#,
+        )
+        .unwrap();
+        let pins = timer_pins(&tree.groups()[0])
+            .into_iter()
+            .map(|p| (p.pin, p.af, p.signal.function))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            pins,
+            vec![
+                ("PA8".into(), "AF1".into(), TimerFunction::Channel(1)),
+                (
+                    "PA7".into(),
+                    "AF1".into(),
+                    TimerFunction::ComplementaryChannel(1)
+                ),
+            ]
+        );
+    }
+}
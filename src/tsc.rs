@@ -0,0 +1,85 @@
+//! Touch sensing controller (TSC) signals.
+//!
+//! The TSC IOs are organized in groups of up to four IOs (e.g. "TSC_G2_IO3" is
+//! IO 3 of group 2). Within a group, one IO is connected to the sampling
+//! capacitor and the others to electrodes, so HALs need the group and IO
+//! numbers of each pin rather than the signal name.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// TSC signals, e.g. "TSC_G1_IO2" or the synchronization input "TSC_SYNC".
+    static ref TSC_SIGNAL: Regex = Regex::new(r"^TSC_(?:G(\d+)_IO(\d+)|(SYNC))$").unwrap();
+}
+
+/// A TSC signal of a pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TscSignal {
+    /// An IO of a group (e.g. group 1, IO 2 for "TSC_G1_IO2").
+    Io { group: u8, io: u8 },
+    /// The synchronization input ("TSC_SYNC").
+    Sync,
+}
+
+/// Parse a TSC signal (e.g. "TSC_G1_IO2").
+pub fn parse_tsc_signal(signal: &str) -> Option<TscSignal> {
+    let captures = TSC_SIGNAL.captures(signal)?;
+    if captures.get(3).is_some() {
+        return Some(TscSignal::Sync);
+    }
+    Some(TscSignal::Io {
+        group: captures[1].parse().ok()?,
+        io: captures[2].parse().ok()?,
+    })
+}
+
+/// A TSC signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TscPin {
+    /// The pin (e.g. "PA0").
+    pub pin: String,
+    /// The alternate function (e.g. "AF3").
+    pub af: String,
+    pub signal: TscSignal,
+}
+
+/// Return the TSC signals of the pins of a GPIO group, sorted by pin.
+pub fn tsc_pins(group: &GpioGroup) -> Vec<TscPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            if let Some(signal) = parse_tsc_signal(raw.name()) {
+                pins.push(TscPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                });
+            }
+        }
+    }
+    pins.dedup();
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tsc_signal() {
+        assert_eq!(
+            parse_tsc_signal("TSC_G1_IO2"),
+            Some(TscSignal::Io { group: 1, io: 2 })
+        );
+        assert_eq!(
+            parse_tsc_signal("TSC_G8_IO4"),
+            Some(TscSignal::Io { group: 8, io: 4 })
+        );
+        assert_eq!(parse_tsc_signal("TSC_SYNC"), Some(TscSignal::Sync));
+        assert_eq!(parse_tsc_signal("TSC_G1"), None);
+        assert_eq!(parse_tsc_signal("TIM2_CH1"), None);
+    }
+}
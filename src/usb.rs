@@ -0,0 +1,183 @@
+//! USB signals.
+//!
+//! The USB peripherals are named "USB", "USB_DRD_FS", "USB_OTG_FS" or
+//! "USB_OTG_HS", so their signals (e.g. "USB_OTG_FS_DP") do not split into an
+//! instance and a function at the first underscore like other signals. The
+//! high speed instances additionally connect an external PHY through the
+//! ULPI bus (e.g. "USB_OTG_HS_ULPI_D0"), which HALs configure as a whole.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::af_tree::GpioGroup;
+
+lazy_static! {
+    /// USB signals, e.g. "USB_DP", "USB_OTG_FS_ID", "USB_OTG_HS_SOF" or
+    /// "USB_OTG_HS_ULPI_D7".
+    static ref USB_SIGNAL: Regex = Regex::new(
+        r"^(USB(?:_OTG_[FH]S|_DRD_FS)?)_(?:ULPI_(CK|DIR|NXT|STP|D([0-7]))|(DP|DM|ID|SOF|VBUS|NOE))$"
+    )
+    .unwrap();
+}
+
+/// The function of a USB pin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UsbFunction {
+    /// The D+ data line ("DP").
+    Dp,
+    /// The D- data line ("DM").
+    Dm,
+    /// The OTG ID input ("ID").
+    Id,
+    /// The start of frame output ("SOF").
+    Sof,
+    /// The VBUS sensing input ("VBUS").
+    Vbus,
+    /// The output enable of an external transceiver ("NOE").
+    Noe,
+    /// The ULPI clock ("ULPI_CK").
+    UlpiCk,
+    /// The ULPI direction ("ULPI_DIR").
+    UlpiDir,
+    /// The ULPI next data ("ULPI_NXT").
+    UlpiNxt,
+    /// The ULPI stop ("ULPI_STP").
+    UlpiStp,
+    /// A ULPI data line (e.g. 0 for "ULPI_D0").
+    UlpiData(u8),
+}
+
+impl UsbFunction {
+    /// Whether this is a signal of the ULPI bus.
+    pub fn is_ulpi(self) -> bool {
+        matches!(
+            self,
+            UsbFunction::UlpiCk
+                | UsbFunction::UlpiDir
+                | UsbFunction::UlpiNxt
+                | UsbFunction::UlpiStp
+                | UsbFunction::UlpiData(_)
+        )
+    }
+}
+
+/// A USB signal of a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsbSignal {
+    /// The USB instance (e.g. "USB_OTG_FS").
+    pub instance: String,
+    pub function: UsbFunction,
+}
+
+impl UsbSignal {
+    /// The name of the IO trait of the pin (e.g. "DpPin", or "UlpiD0Pin" for
+    /// the first ULPI data line).
+    pub fn io_trait(&self) -> String {
+        match self.function {
+            UsbFunction::Dp => "DpPin".to_string(),
+            UsbFunction::Dm => "DmPin".to_string(),
+            UsbFunction::Id => "IdPin".to_string(),
+            UsbFunction::Sof => "SofPin".to_string(),
+            UsbFunction::Vbus => "VbusPin".to_string(),
+            UsbFunction::Noe => "NoePin".to_string(),
+            UsbFunction::UlpiCk => "UlpiClkPin".to_string(),
+            UsbFunction::UlpiDir => "UlpiDirPin".to_string(),
+            UsbFunction::UlpiNxt => "UlpiNxtPin".to_string(),
+            UsbFunction::UlpiStp => "UlpiStpPin".to_string(),
+            UsbFunction::UlpiData(n) => format!("UlpiD{}Pin", n),
+        }
+    }
+}
+
+/// Parse a USB signal (e.g. "USB_OTG_HS_ULPI_D3" into `USB_OTG_HS` and
+/// `UlpiData(3)`).
+pub fn parse_usb_signal(signal: &str) -> Option<UsbSignal> {
+    let captures = USB_SIGNAL.captures(signal)?;
+    let function = match (captures.get(2), captures.get(3), captures.get(4)) {
+        (_, Some(n), _) => UsbFunction::UlpiData(n.as_str().parse().ok()?),
+        (Some(ulpi), _, _) => match ulpi.as_str() {
+            "CK" => UsbFunction::UlpiCk,
+            "DIR" => UsbFunction::UlpiDir,
+            "NXT" => UsbFunction::UlpiNxt,
+            _ => UsbFunction::UlpiStp,
+        },
+        (_, _, Some(function)) => match function.as_str() {
+            "DP" => UsbFunction::Dp,
+            "DM" => UsbFunction::Dm,
+            "ID" => UsbFunction::Id,
+            "SOF" => UsbFunction::Sof,
+            "VBUS" => UsbFunction::Vbus,
+            _ => UsbFunction::Noe,
+        },
+        _ => return None,
+    };
+    Some(UsbSignal {
+        instance: captures[1].to_string(),
+        function,
+    })
+}
+
+/// A USB signal on a pin.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsbPin {
+    /// The pin (e.g. "PA12").
+    pub pin: String,
+    /// The alternate function (e.g. "AF10").
+    pub af: String,
+    pub signal: UsbSignal,
+}
+
+/// Return the USB signals of the pins of a GPIO group, sorted by pin. The
+/// ULPI signals are only included with `ulpi`, the others only without.
+pub fn usb_pins(group: &GpioGroup, ulpi: bool) -> Vec<UsbPin> {
+    let mut pins = vec![];
+    for pin in group.pins() {
+        for raw in pin.raw_signals() {
+            match parse_usb_signal(raw.name()) {
+                Some(signal) if signal.function.is_ulpi() == ulpi => pins.push(UsbPin {
+                    pin: pin.name(),
+                    af: raw.af().to_string(),
+                    signal,
+                }),
+                _ => {}
+            }
+        }
+    }
+    pins.dedup();
+    pins
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_usb_signal() {
+        let signal = parse_usb_signal("USB_OTG_FS_DP").unwrap();
+        assert_eq!(signal.instance, "USB_OTG_FS");
+        assert_eq!(signal.function, UsbFunction::Dp);
+        assert_eq!(signal.io_trait(), "DpPin");
+
+        let signal = parse_usb_signal("USB_DM").unwrap();
+        assert_eq!(signal.instance, "USB");
+        assert_eq!(signal.io_trait(), "DmPin");
+
+        let signal = parse_usb_signal("USB_OTG_HS_ULPI_D3").unwrap();
+        assert_eq!(signal.instance, "USB_OTG_HS");
+        assert_eq!(signal.function, UsbFunction::UlpiData(3));
+        assert!(signal.function.is_ulpi());
+        assert_eq!(signal.io_trait(), "UlpiD3Pin");
+
+        assert_eq!(
+            parse_usb_signal("USB_OTG_HS_ULPI_CK").map(|s| s.io_trait()),
+            Some("UlpiClkPin".into())
+        );
+        assert_eq!(
+            parse_usb_signal("USB_OTG_HS_SOF").map(|s| s.function),
+            Some(UsbFunction::Sof)
+        );
+        assert!(parse_usb_signal("USB_CRS_SYNC").is_none());
+        assert!(parse_usb_signal("USB_OTG_FS_ULPI_DP").is_none());
+        assert!(parse_usb_signal("USART2_TX").is_none());
+    }
+}
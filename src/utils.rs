@@ -1,13 +1,123 @@
-use std::{error::Error, fs::File, io::BufReader, path::Path};
+use std::{error::Error, fs, path::Path, sync::Mutex};
 
+use lazy_static::lazy_static;
+use log::warn;
+use regex::{Regex, RegexBuilder};
 use serde::Deserialize;
 
+lazy_static! {
+    /// The files that could only be decoded with workarounds, see `decode`.
+    static ref DECODING_ISSUES: Mutex<Vec<String>> = Mutex::new(vec![]);
+}
+
 pub fn load_file<'a, P: AsRef<Path>, Q: AsRef<Path>, R: Deserialize<'a>>(
     db_dir: P,
     file_path: Q,
 ) -> Result<R, Box<dyn Error>> {
-    let db_dir = db_dir.as_ref();
-    let mut fin = BufReader::new(File::open(&db_dir.join(file_path.as_ref()))?);
+    let path = db_dir.as_ref().join(file_path.as_ref());
+    let (contents, issue) = decode(&fs::read(&path)?);
+    if let Some(issue) = issue {
+        let issue = format!("{}: {}", path.display(), issue);
+        warn!("{}", issue);
+        DECODING_ISSUES.lock().unwrap().push(issue);
+    }
+
+    Ok(serde_xml_rs::deserialize(contents.as_bytes())?)
+}
+
+/// Return (and forget) the database files that were loaded despite encoding
+/// quirks, with a description of the quirk.
+pub fn take_decoding_issues() -> Vec<String> {
+    DECODING_ISSUES.lock().unwrap().drain(..).collect()
+}
+
+/// Decode the contents of a database file, tolerating a byte order mark,
+/// UTF-16 and invalid UTF-8 (undecodable bytes are replaced).
+///
+/// Returns the decoded contents and a description of the quirk, if any.
+fn decode(bytes: &[u8]) -> (String, Option<&'static str>) {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|c| from_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xef, 0xbb, 0xbf, rest @ ..] => {
+            let (contents, issue) = decode(rest);
+            (contents, issue.or(Some("UTF-8 byte order mark")))
+        }
+        [0xff, 0xfe, rest @ ..] => (utf16(rest, u16::from_le_bytes), Some("UTF-16 encoded")),
+        [0xfe, 0xff, rest @ ..] => (utf16(rest, u16::from_be_bytes), Some("UTF-16 encoded")),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(contents) => (contents.to_string(), None),
+            Err(_) => (
+                String::from_utf8_lossy(bytes).into_owned(),
+                Some("invalid UTF-8, undecodable bytes were replaced"),
+            ),
+        },
+    }
+}
+
+/// Convert a wildcard pattern, where `*` matches any number of characters and
+/// `?` matches a single character, to an anchored regex.
+pub(crate) fn wildcard_regex(pattern: &str, case_insensitive: bool) -> Regex {
+    let re = pattern
+        .split('*')
+        .map(|part| {
+            part.split('?')
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+        .collect::<Vec<_>>()
+        .join(".*");
+    RegexBuilder::new(&format!("^{}$", re))
+        .case_insensitive(case_insensitive)
+        .build()
+        .unwrap()
+}
+
+/// Return the Levenshtein distance between two strings, i.e. the number of
+/// single character insertions, deletions and substitutions needed to turn
+/// one into the other.
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev + usize::from(ca != *cb);
+            prev = row[j + 1];
+            row[j + 1] = substitution.min(prev + 1).min(row[j] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    Ok(serde_xml_rs::deserialize(&mut fin)?)
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(b"<a/>"), ("<a/>".to_string(), None));
+        assert_eq!(
+            decode(b"\xef\xbb\xbf<a/>"),
+            ("<a/>".to_string(), Some("UTF-8 byte order mark"))
+        );
+        assert_eq!(
+            decode(b"\xff\xfe<\0a\0/\0>\0"),
+            ("<a/>".to_string(), Some("UTF-16 encoded"))
+        );
+        assert_eq!(
+            decode(b"<a>\xe9</a>"),
+            (
+                "<a>\u{fffd}</a>".to_string(),
+                Some("invalid UTF-8, undecodable bytes were replaced")
+            )
+        );
+    }
 }
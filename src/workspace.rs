@@ -0,0 +1,286 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::error;
+
+/// Name of the staging directory, relative to the output directory.
+const STAGING_DIR: &str = ".cube-parse-staging";
+
+/// Name of the directory the replaced files are moved to while committing,
+/// relative to the output directory.
+const BACKUP_DIR: &str = ".cube-parse-backup";
+
+/// A staging area for generated files.
+///
+/// All files are first written to a staging directory inside the output
+/// directory. Only when `commit` is called are they moved into place. If the
+/// workspace is dropped without being committed (e.g. because generation
+/// failed half-way through), the staging directory is removed again and the
+/// output directory is left untouched.
+///
+/// Committing moves the files one by one, so the files it replaces are first
+/// moved to a backup directory. If a move fails, the files already moved are
+/// put back, and the output directory is left as it was before the commit.
+///
+/// The staging directory has a fixed name, so a stale one left behind by a
+/// killed run is cleaned up by the next run. The backup directory is only left
+/// behind if a run was killed while committing or some files could not be
+/// restored. It then holds the only copies of the replaced files, so no
+/// workspace is created until it has been dealt with.
+pub struct Workspace {
+    output_dir: PathBuf,
+    staging_dir: PathBuf,
+    backup_dir: PathBuf,
+    files: Vec<PathBuf>,
+    committed: bool,
+}
+
+impl Workspace {
+    pub fn new<P: AsRef<Path>>(output_dir: P) -> io::Result<Self> {
+        let output_dir = output_dir.as_ref().to_path_buf();
+        let staging_dir = output_dir.join(STAGING_DIR);
+        let backup_dir = output_dir.join(BACKUP_DIR);
+        if backup_dir.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "{} holds the files replaced by an earlier run that failed, \
+                     restore them and remove it",
+                    backup_dir.display()
+                ),
+            ));
+        }
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir)?;
+        }
+        fs::create_dir_all(&staging_dir)?;
+        Ok(Self {
+            output_dir,
+            staging_dir,
+            backup_dir,
+            files: vec![],
+            committed: false,
+        })
+    }
+
+    /// Stage a file. The path is relative to the output directory, and every
+    /// path can only be staged once.
+    pub fn write<P: AsRef<Path>>(&mut self, file_path: P, contents: &str) -> io::Result<()> {
+        let file_path = file_path.as_ref();
+        if self.files.iter().any(|f| f == file_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is already staged", file_path.display()),
+            ));
+        }
+        let staged = self.staging_dir.join(file_path);
+        if let Some(parent) = staged.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&staged, contents)?;
+        self.files.push(file_path.to_path_buf());
+        Ok(())
+    }
+
+    /// Move all staged files into the output directory.
+    ///
+    /// Returns the paths of the files that were written. On error, the output
+    /// directory is restored to its state before the commit. If some replaced
+    /// files cannot be restored, the backup directory is kept and named in the
+    /// error.
+    pub fn commit(mut self) -> io::Result<Vec<PathBuf>> {
+        let mut moved = vec![];
+        if let Err(e) = self.move_files(&mut moved) {
+            if !self.rollback(&moved) {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!(
+                        "{} (the files that could not be restored are in {})",
+                        e,
+                        self.backup_dir.display()
+                    ),
+                ));
+            }
+            let _ = fs::remove_dir_all(&self.backup_dir);
+            return Err(e);
+        }
+        self.committed = true;
+        fs::remove_dir_all(&self.staging_dir)?;
+        if self.backup_dir.exists() {
+            fs::remove_dir_all(&self.backup_dir)?;
+        }
+        Ok(moved.into_iter().map(|(target, _)| target).collect())
+    }
+
+    /// Move the staged files into the output directory, backing up the files
+    /// they replace. Every target is recorded in `moved` (with its backup, if
+    /// any) before it is touched, so that `rollback` can undo a partial move.
+    fn move_files(&self, moved: &mut Vec<(PathBuf, Option<PathBuf>)>) -> io::Result<()> {
+        for file_path in &self.files {
+            let target = self.output_dir.join(file_path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let backup = if target.exists() {
+                let backup = self.backup_dir.join(file_path);
+                if let Some(parent) = backup.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::rename(&target, &backup)?;
+                Some(backup)
+            } else {
+                None
+            };
+            moved.push((target.clone(), backup));
+            fs::rename(self.staging_dir.join(file_path), &target)?;
+        }
+        Ok(())
+    }
+
+    /// Undo a partial `move_files`: remove the new files and restore the
+    /// files they replaced, in reverse order. Returns whether all replaced
+    /// files were restored.
+    fn rollback(&self, moved: &[(PathBuf, Option<PathBuf>)]) -> bool {
+        let mut restored = true;
+        for (target, backup) in moved.iter().rev() {
+            let _ = fs::remove_file(target);
+            if let Some(backup) = backup {
+                if let Err(e) = fs::rename(backup, target) {
+                    error!(
+                        "Could not restore {} from {}: {}",
+                        target.display(),
+                        backup.display(),
+                        e
+                    );
+                    restored = false;
+                }
+            }
+        }
+        restored
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_dir_all(&self.staging_dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create an empty output directory for a test.
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cube-parse-workspace-{}", name));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_commit() {
+        let dir = output_dir("commit");
+        fs::write(dir.join("a.rs"), "old").unwrap();
+        fs::write(dir.join("other.rs"), "other").unwrap();
+
+        let mut workspace = Workspace::new(&dir).unwrap();
+        workspace.write("a.rs", "new").unwrap();
+        workspace.write("b/c.rs", "c").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.rs")).unwrap(), "old");
+        let written = workspace.commit().unwrap();
+
+        assert_eq!(written, vec![dir.join("a.rs"), dir.join("b/c.rs")]);
+        assert_eq!(fs::read_to_string(dir.join("a.rs")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dir.join("b/c.rs")).unwrap(), "c");
+        assert_eq!(fs::read_to_string(dir.join("other.rs")).unwrap(), "other");
+        assert!(!dir.join(STAGING_DIR).exists());
+        assert!(!dir.join(BACKUP_DIR).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_commit_failure() {
+        let dir = output_dir("commit-failure");
+        fs::write(dir.join("a.rs"), "old").unwrap();
+        // A file where the second staged file needs a directory, so moving it
+        // fails after "a.rs" was replaced.
+        fs::write(dir.join("b"), "not a directory").unwrap();
+
+        let mut workspace = Workspace::new(&dir).unwrap();
+        workspace.write("a.rs", "new").unwrap();
+        workspace.write("new.rs", "new").unwrap();
+        workspace.write("b/c.rs", "c").unwrap();
+        assert!(workspace.commit().is_err());
+
+        assert_eq!(fs::read_to_string(dir.join("a.rs")).unwrap(), "old");
+        assert!(!dir.join("new.rs").exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("b")).unwrap(),
+            "not a directory"
+        );
+        assert!(!dir.join(STAGING_DIR).exists());
+        assert!(!dir.join(BACKUP_DIR).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rollback_failure() {
+        let dir = output_dir("rollback-failure");
+        fs::write(dir.join("a.rs"), "new").unwrap();
+        let workspace = Workspace::new(&dir).unwrap();
+        // A backup that is gone can't be restored
+        let moved = vec![(dir.join("a.rs"), Some(dir.join(BACKUP_DIR).join("a.rs")))];
+        assert!(!workspace.rollback(&moved));
+        assert!(workspace.rollback(&[(dir.join("b.rs"), None)]));
+        drop(workspace);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_write() {
+        let dir = output_dir("duplicate-write");
+        let mut workspace = Workspace::new(&dir).unwrap();
+        workspace.write("a.rs", "first").unwrap();
+        assert!(workspace.write("a.rs", "second").is_err());
+        workspace.commit().unwrap();
+        assert_eq!(fs::read_to_string(dir.join("a.rs")).unwrap(), "first");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cleanup() {
+        let dir = output_dir("cleanup");
+        fs::create_dir_all(dir.join(STAGING_DIR).join("stale")).unwrap();
+
+        let mut workspace = Workspace::new(&dir).unwrap();
+        assert!(!dir.join(STAGING_DIR).join("stale").exists());
+        workspace.write("a.rs", "new").unwrap();
+        drop(workspace);
+
+        assert!(!dir.join("a.rs").exists());
+        assert!(!dir.join(STAGING_DIR).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stale_backup() {
+        let dir = output_dir("stale-backup");
+        fs::create_dir_all(dir.join(BACKUP_DIR)).unwrap();
+        fs::write(dir.join(BACKUP_DIR).join("a.rs"), "old").unwrap();
+
+        let err = Workspace::new(&dir).err().unwrap();
+        assert!(err.to_string().contains(BACKUP_DIR));
+        assert_eq!(
+            fs::read_to_string(dir.join(BACKUP_DIR).join("a.rs")).unwrap(),
+            "old"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
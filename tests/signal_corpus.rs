@@ -0,0 +1,134 @@
+//! Check the signal parsing and classification against a corpus of signal
+//! names taken from the CubeMX database.
+//!
+//! Set `CUBE_PARSE_DB_DIR` to also check every signal of the GPIO IP files in
+//! that database, and additionally set `CUBE_PARSE_UPDATE_CORPUS=1` to
+//! replace `tests/data/signal_corpus.txt` with the signals found there.
+
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use cube_parse::af_tree::device_stem;
+use cube_parse::internal_peripheral::{io_traits, parse_af_define, IpGPIO};
+use cube_parse::remap::parse_remap_define;
+
+lazy_static! {
+    static ref SIGNAL_NAME: Regex = Regex::new("^[A-Za-z0-9]+([_-][A-Za-z0-9]+)*$").unwrap();
+}
+
+fn data_file(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join(name)
+}
+
+fn read_lines(name: &str) -> Vec<String> {
+    fs::read_to_string(data_file(name))
+        .unwrap()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+fn read_corpus() -> BTreeSet<(String, String)> {
+    read_lines("signal_corpus.txt")
+        .iter()
+        .map(|l| {
+            let mut parts = l.split_whitespace();
+            let name = parts.next().unwrap().to_string();
+            let define = parts.next().unwrap_or("").to_string();
+            (name, define)
+        })
+        .collect()
+}
+
+/// Collect all signals of all GPIO IP files in the database.
+fn harvest(db_dir: &Path) -> BTreeSet<(String, String)> {
+    let mut signals = BTreeSet::new();
+    for entry in fs::read_dir(db_dir.join("IP")).unwrap() {
+        let file_name = entry.unwrap().file_name().into_string().unwrap();
+        if !file_name.starts_with("GPIO-") || !file_name.ends_with("_Modes.xml") {
+            continue;
+        }
+        let version = &file_name["GPIO-".len()..file_name.len() - "_Modes.xml".len()];
+        let gpio = IpGPIO::load(db_dir, version).unwrap();
+        for (name, define) in gpio.signals() {
+            signals.insert((name.to_string(), define.to_string()));
+        }
+    }
+    signals
+}
+
+/// Return the broken invariants of a signal.
+fn check_signal(name: &str, define: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    if !SIGNAL_NAME.is_match(name) {
+        errors.push("malformed signal name".to_string());
+    }
+    if parse_af_define(define).is_none() && parse_remap_define(define).is_none() {
+        errors.push(format!("unparseable AF define {:?}", define));
+    }
+    let peripheral = name.split('_').next().unwrap();
+    if device_stem(peripheral).is_empty() {
+        errors.push("empty peripheral stem".to_string());
+    }
+    for io in io_traits(name) {
+        let stems: &[&str] = match io {
+            "RxPin" | "TxPin" | "CtsPin" | "RtsPin" | "CkPin" | "DePin" => {
+                &["USART", "UART", "LPUART"]
+            }
+            "MosiPin" | "MisoPin" | "SckPin" => &["SPI", "QUADSPI"],
+            _ => &["I2C"],
+        };
+        if !stems.contains(&device_stem(peripheral)) {
+            errors.push(format!(
+                "classified as {} but peripheral is {}",
+                io, peripheral
+            ));
+        }
+    }
+    errors
+}
+
+#[test]
+fn test_signal_corpus() {
+    let mut corpus = read_corpus();
+    if let Ok(db_dir) = env::var("CUBE_PARSE_DB_DIR") {
+        let harvested = harvest(Path::new(&db_dir));
+        if env::var("CUBE_PARSE_UPDATE_CORPUS").as_deref() == Ok("1") {
+            let mut contents = fs::read_to_string(data_file("signal_corpus.txt"))
+                .unwrap()
+                .lines()
+                .take_while(|l| l.starts_with('#'))
+                .map(|l| format!("{}\n", l))
+                .collect::<String>();
+            for (name, define) in &harvested {
+                contents.push_str(&format!("{} {}\n", name, define));
+            }
+            fs::write(data_file("signal_corpus.txt"), contents).unwrap();
+        }
+        corpus.extend(harvested);
+    }
+
+    let ignored = read_lines("signal_ignore.txt")
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let failures = corpus
+        .iter()
+        .filter(|(name, _)| !ignored.contains(name))
+        .flat_map(|(name, define)| {
+            check_signal(name, define)
+                .into_iter()
+                .map(move |e| format!("{}: {}", name, e))
+        })
+        .collect::<Vec<_>>();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}